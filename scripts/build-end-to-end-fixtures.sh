@@ -0,0 +1,72 @@
+#!/usr/bin/env bash
+# Builds the fixtures directory of tests/end_to_end.rs: a mango v3 group created with the
+# create-group subcommand on a throwaway solana-test-validator, dumped with its market makers.
+#
+# usage: scripts/build-end-to-end-fixtures.sh <fixtures dir> <mango.so> <serum_dex.so>
+#
+# solana, solana-keygen, solana-test-validator, curl and jq have to be in the PATH.
+set -euo pipefail
+
+if [ $# -ne 3 ]; then
+    echo "usage: $0 <fixtures dir> <mango.so> <serum_dex.so>" >&2
+    exit 1
+fi
+FIXTURES=$1
+MANGO_SO=$2
+SERUM_SO=$3
+# the end to end test reads the default group name of the run
+GROUP_NAME=testnet.0
+MARKET_MAKERS=2
+PERP_MARKETS=2
+RPC_PORT=18999
+RPC_URL=http://127.0.0.1:$RPC_PORT
+TOKEN_PROGRAM=TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+
+mkdir -p "$FIXTURES/accounts"
+rm -f "$FIXTURES"/accounts/*.json "$FIXTURES/ids.json"
+WORK_DIR=$(mktemp -d)
+
+solana-keygen new --no-bip39-passphrase --silent --force -o "$FIXTURES/authority.json"
+solana-keygen new --no-bip39-passphrase --silent -o "$WORK_DIR/mango-program.json"
+solana-keygen new --no-bip39-passphrase --silent -o "$WORK_DIR/serum-program.json"
+MANGO_PROGRAM=$(solana-keygen pubkey "$WORK_DIR/mango-program.json")
+SERUM_PROGRAM=$(solana-keygen pubkey "$WORK_DIR/serum-program.json")
+AUTHORITY=$(solana-keygen pubkey "$FIXTURES/authority.json")
+
+solana-test-validator --reset --quiet --ledger "$WORK_DIR/ledger" --rpc-port $RPC_PORT \
+    --bpf-program "$MANGO_PROGRAM" "$MANGO_SO" \
+    --bpf-program "$SERUM_PROGRAM" "$SERUM_SO" > /dev/null &
+VALIDATOR_PID=$!
+trap 'kill $VALIDATOR_PID; wait $VALIDATOR_PID 2> /dev/null; rm -rf "$WORK_DIR"' EXIT
+
+until solana -u $RPC_URL cluster-version > /dev/null 2>&1; do
+    sleep 1
+done
+solana -u $RPC_URL airdrop 1000 "$AUTHORITY" > /dev/null
+
+cargo run --release --bin mango-simulation -- create-group -u $RPC_URL \
+    --identity "$FIXTURES/authority.json" \
+    --mango-program "$MANGO_PROGRAM" --serum-program "$SERUM_PROGRAM" \
+    --perp-markets $PERP_MARKETS --mango-cluster $GROUP_NAME --output "$FIXTURES/ids.json" \
+    --market-makers $MARKET_MAKERS --accounts-output "$FIXTURES/accounts.json"
+
+# every account of the group belongs to the mango, serum or token program, the market makers and
+# the authority hold the lamports paying the fees
+program_accounts() {
+    curl -s $RPC_URL -H 'Content-Type: application/json' -d "{
+        \"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"getProgramAccounts\",
+        \"params\": [\"$1\", {\"encoding\": \"base64\", \"dataSlice\": {\"offset\": 0, \"length\": 0}}]
+    }" | jq -r '.result[].pubkey'
+}
+{
+    program_accounts "$MANGO_PROGRAM"
+    program_accounts "$SERUM_PROGRAM"
+    program_accounts $TOKEN_PROGRAM
+    echo "$AUTHORITY"
+    jq -r '.[].publicKey' "$FIXTURES/accounts.json"
+} | while read -r pubkey; do
+    solana -u $RPC_URL account "$pubkey" --output json --output-file "$FIXTURES/accounts/$pubkey.json" > /dev/null
+done
+
+cp "$MANGO_SO" "$FIXTURES/mango.so"
+echo "fixtures written to $FIXTURES"
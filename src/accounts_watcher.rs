@@ -0,0 +1,106 @@
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use log::{info, warn};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_metrics::datapoint_info;
+use solana_sdk::hash::Hash;
+use tokio::{
+    sync::{Notify, RwLock},
+    task::JoinHandle,
+};
+
+use crate::{
+    config_loader::load_config,
+    mango::AccountKeys,
+    mango_program::MangoProgram,
+    market_markers::{cancel_all_orders, MarketMakerRoster, QuotedMarket},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// remote accounts files have no modification time, they are only reloaded on request
+fn modified_at(accounts_file: &str) -> Option<SystemTime> {
+    fs::metadata(accounts_file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+async fn read_account_keys(accounts_file: &str) -> anyhow::Result<Vec<AccountKeys>> {
+    let account_keys_json = load_config(accounts_file).await?;
+    serde_json::from_str(&account_keys_json)
+        .map_err(|e| anyhow::anyhow!("{accounts_file} is not a valid accounts file : {e}"))
+}
+
+/// Reloads the accounts file when it changes, or when the control server asks for it, then
+/// starts the market makers of the added mango accounts and retires the removed ones, whose
+/// orders are cancelled. Churn experiments can then change the number of participants during the
+/// run. Once the run is over and every market maker it started is joined, returns the markets
+/// quoted by the market makers still running
+pub fn start_accounts_watcher(
+    accounts_file: String,
+    mut roster: MarketMakerRoster,
+    mango_program: Arc<dyn MangoProgram>,
+    rpc_client: Arc<RpcClient>,
+    blockhash: Arc<RwLock<Hash>>,
+    reload: Arc<Notify>,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<Vec<QuotedMarket>> {
+    tokio::spawn(async move {
+        info!("watching {accounts_file} for market makers to add or retire");
+        let mut last_modified = modified_at(&accounts_file);
+        let mut tasks = vec![];
+        let mut cancelling = vec![];
+        while !exit_signal.load(Ordering::Relaxed) && !roster.has_ended() {
+            let requested = tokio::select! {
+                _ = reload.notified() => true,
+                _ = tokio::time::sleep(POLL_INTERVAL) => false,
+            };
+            let modified = modified_at(&accounts_file);
+            if !requested && modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            let account_keys = match read_account_keys(&accounts_file).await {
+                Ok(account_keys) => account_keys,
+                Err(e) => {
+                    warn!("keeping the current market makers, could not reload the accounts : {e}");
+                    continue;
+                }
+            };
+            let update = roster.update(&account_keys);
+            info!(
+                "accounts reloaded : {} market makers started, {} retired, {} running",
+                update.started,
+                update.nb_retired,
+                roster.len()
+            );
+            datapoint_info!(
+                "bencher-market-makers",
+                ("started", update.started, i64),
+                ("retired", update.nb_retired, i64),
+                ("running", roster.len(), i64),
+            );
+            tasks.extend(update.tasks);
+            if !update.retired.is_empty() {
+                cancelling.push(tokio::spawn(cancel_all_orders(
+                    mango_program.clone(),
+                    rpc_client.clone(),
+                    update.retired,
+                    blockhash.clone(),
+                )));
+            }
+        }
+
+        futures::future::join_all(tasks).await;
+        futures::future::join_all(cancelling).await;
+        roster.quoted_markets()
+    })
+}
@@ -0,0 +1,99 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use core_affinity::CoreId;
+use log::{info, warn};
+
+/// Cores the threads of the bencher are pinned to, in turn. The market makers and the
+/// confirmation run as tasks on the runtime workers, pinned workers keep the scheduler from
+/// moving them across cores and sockets during the run
+#[derive(Clone, Debug)]
+pub struct ThreadPinning {
+    cores: Arc<Vec<usize>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl FromStr for ThreadPinning {
+    type Err = String;
+
+    /// comma separated cores and ranges of cores, e.g. `0-7,16-23`, every core when empty
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cores = vec![];
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let parse = |core: &str| {
+                core.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid core {core}"))
+            };
+            match entry.split_once('-') {
+                Some((first, last)) => cores.extend(parse(first)?..=parse(last)?),
+                None => cores.push(parse(entry)?),
+            }
+        }
+        if cores.is_empty() {
+            cores = core_affinity::get_core_ids()
+                .ok_or("the cores of the host are unknown")?
+                .into_iter()
+                .map(|core| core.id)
+                .collect();
+        }
+        Ok(Self {
+            cores: Arc::new(cores),
+            next: Arc::default(),
+        })
+    }
+}
+
+impl ThreadPinning {
+    /// pins the calling thread to the next core of the list
+    pub fn pin_current_thread(&self) {
+        self.pin_to(self.next.fetch_add(1, Ordering::Relaxed));
+    }
+
+    /// pins the calling runtime worker to the core of its index in the list, the threads pinned
+    /// with `pin_current_thread` afterwards continue on the cores after the ones of the workers
+    pub fn pin_worker(&self, index: usize) {
+        self.next.fetch_max(index + 1, Ordering::Relaxed);
+        self.pin_to(index);
+    }
+
+    fn pin_to(&self, index: usize) {
+        if self.cores.is_empty() {
+            warn!("no core to pin the thread to");
+            return;
+        }
+        let core = self.cores[index % self.cores.len()];
+        core_affinity::set_for_current(CoreId { id: core });
+        info!(
+            "thread {:?} pinned to core {core}",
+            std::thread::current().name().unwrap_or_default()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_cores_and_ranges() {
+        let pinning: ThreadPinning = "0-3, 8,16-17".parse().unwrap();
+        assert_eq!(*pinning.cores, vec![0, 1, 2, 3, 8, 16, 17]);
+
+        assert!("0-x".parse::<ThreadPinning>().is_err());
+        assert!("a".parse::<ThreadPinning>().is_err());
+    }
+
+    #[test]
+    fn threads_pinned_after_the_workers_take_the_next_cores() {
+        let pinning: ThreadPinning = "0-3".parse().unwrap();
+        pinning.pin_worker(1);
+        pinning.pin_worker(0);
+        assert_eq!(pinning.next.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use serde::Serialize;
+use solana_metrics::datapoint_error;
+use tokio::task::JoinHandle;
+
+use crate::{
+    result_writer::RowTags,
+    stats::{MangoSimulationStats, WindowedRates},
+};
+
+const ALERT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_ALERT_WINDOW: Duration = Duration::from_secs(60);
+// transactions of the window below which a rule is not checked, the value of a handful of
+// transactions would make the rule flap
+const MIN_ALERT_SAMPLES: u64 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertMetric {
+    // successful transactions in percent of the transactions sent in the window
+    ConfirmationRate,
+    P95LatencyMs,
+}
+
+impl AlertMetric {
+    fn name(&self) -> &'static str {
+        match self {
+            AlertMetric::ConfirmationRate => "confirmation_rate",
+            AlertMetric::P95LatencyMs => "p95_latency_ms",
+        }
+    }
+}
+
+/// A metric compared with a threshold over the transactions of the last window, written as
+/// `confirmation_rate<80:60s` or `p95_latency_ms>10000:30s`, the window is 60s by default
+#[derive(Clone, Copy, Debug)]
+pub struct AlertRule {
+    pub metric: AlertMetric,
+    // breached above the threshold, otherwise below it
+    pub above: bool,
+    pub threshold: f64,
+    pub window: Duration,
+}
+
+impl FromStr for AlertRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (condition, window) = match s.split_once(':') {
+            Some((condition, window)) => {
+                let secs = window
+                    .trim()
+                    .trim_end_matches('s')
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid window {window} in alert {s}"))?;
+                (condition, Duration::from_secs(secs))
+            }
+            None => (s, DEFAULT_ALERT_WINDOW),
+        };
+        let (metric, above, threshold) = match condition.split_once('<') {
+            Some((metric, threshold)) => (metric, false, threshold),
+            None => match condition.split_once('>') {
+                Some((metric, threshold)) => (metric, true, threshold),
+                None => return Err(format!("alert {s} has no < or > comparison")),
+            },
+        };
+        let metric = match metric.trim() {
+            "confirmation_rate" => AlertMetric::ConfirmationRate,
+            "p95_latency_ms" => AlertMetric::P95LatencyMs,
+            other => {
+                return Err(format!(
+                    "unknown alert metric {other}, expected confirmation_rate or p95_latency_ms"
+                ))
+            }
+        };
+        let threshold = threshold
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid threshold {threshold} in alert {s}"))?;
+        Ok(Self {
+            metric,
+            above,
+            threshold,
+            window,
+        })
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}:{}s",
+            self.metric.name(),
+            if self.above { ">" } else { "<" },
+            self.threshold,
+            self.window.as_secs()
+        )
+    }
+}
+
+impl AlertRule {
+    fn breached(&self, value: f64) -> bool {
+        if self.above {
+            value > self.threshold
+        } else {
+            value < self.threshold
+        }
+    }
+}
+
+/// value of the metric over the rates of the window, none with too few transactions to compute it
+fn evaluate(metric: AlertMetric, rates: &WindowedRates) -> Option<f64> {
+    match metric {
+        AlertMetric::ConfirmationRate => (rates.sent >= MIN_ALERT_SAMPLES)
+            .then(|| rates.successful_per_second * 100.0 / rates.sent_per_second),
+        AlertMetric::P95LatencyMs => rates
+            .p95_latency_ms
+            .filter(|_| rates.confirmed >= MIN_ALERT_SAMPLES)
+            .map(|p95| p95 as f64),
+    }
+}
+
+#[derive(Serialize)]
+struct AlertNotification<'a> {
+    run_id: &'a str,
+    cluster: Option<&'a str>,
+    shard: Option<&'a str>,
+    rule: String,
+    value: f64,
+    // firing or resolved
+    state: &'static str,
+}
+
+/// Checks the alert rules every second over the rates of their window, read from the per second
+/// counts of the stats. A breached rule emits an error-level `bencher-alert` datapoint and calls
+/// the webhook once when it starts firing and once when it is resolved
+pub fn start_alerts(
+    rules: Vec<AlertRule>,
+    webhook_url: Option<String>,
+    stats: MangoSimulationStats,
+    tags: RowTags,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    if let Some(longest_window) = rules.iter().map(|r| r.window).max() {
+        stats.keep_rates_for(longest_window);
+    }
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut firing = vec![false; rules.len()];
+        let mut interval = tokio::time::interval(ALERT_CHECK_INTERVAL);
+        while !exit_signal.load(Ordering::Relaxed) {
+            interval.tick().await;
+            for (rule, firing) in rules.iter().zip(firing.iter_mut()) {
+                let Some(value) = evaluate(rule.metric, &stats.rates_over(rule.window)) else {
+                    continue;
+                };
+                let breached = rule.breached(value);
+                if breached == *firing {
+                    continue;
+                }
+                *firing = breached;
+                let state = if breached { "firing" } else { "resolved" };
+                if breached {
+                    error!("alert {rule} is firing, the value is {value:.2}");
+                    datapoint_error!(
+                        "bencher-alert",
+                        ("rule", rule.to_string(), String),
+                        ("value", value, f64),
+                        ("run_id", tags.run_id.clone(), String),
+                    );
+                } else {
+                    info!("alert {rule} is resolved, the value is {value:.2}");
+                }
+                if let Some(webhook_url) = &webhook_url {
+                    let notification = AlertNotification {
+                        run_id: &tags.run_id,
+                        cluster: tags.cluster.as_deref(),
+                        shard: tags.shard.as_deref(),
+                        rule: rule.to_string(),
+                        value,
+                        state,
+                    };
+                    if let Err(e) = client
+                        .post(webhook_url)
+                        .timeout(Duration::from_secs(10))
+                        .json(&notification)
+                        .send()
+                        .await
+                        .and_then(|response| response.error_for_status())
+                    {
+                        warn!("could not call the alert webhook : {e}");
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_alert_rules() {
+        let rule: AlertRule = "confirmation_rate < 80:30s".parse().unwrap();
+        assert_eq!(rule.metric, AlertMetric::ConfirmationRate);
+        assert!(!rule.above);
+        assert_eq!(rule.threshold, 80.0);
+        assert_eq!(rule.window, Duration::from_secs(30));
+        assert_eq!(rule.to_string(), "confirmation_rate<80:30s");
+
+        let rule: AlertRule = "p95_latency_ms>10000".parse().unwrap();
+        assert!(rule.above);
+        assert_eq!(rule.window, DEFAULT_ALERT_WINDOW);
+        assert!(rule.breached(10_001.0));
+        assert!(!rule.breached(10_000.0));
+
+        assert!("confirmation_rate=80".parse::<AlertRule>().is_err());
+        assert!("error_rate>5".parse::<AlertRule>().is_err());
+        assert!("confirmation_rate<x".parse::<AlertRule>().is_err());
+        assert!("confirmation_rate<80:1m".parse::<AlertRule>().is_err());
+    }
+
+    #[test]
+    fn rules_need_enough_transactions_in_the_window() {
+        let rates = WindowedRates {
+            window_secs: 10,
+            sent_per_second: 4.0,
+            successful_per_second: 3.0,
+            sent: 40,
+            confirmed: 10,
+            p95_latency_ms: Some(1_500),
+            ..WindowedRates::default()
+        };
+        assert_eq!(evaluate(AlertMetric::ConfirmationRate, &rates), Some(75.0));
+        assert_eq!(evaluate(AlertMetric::P95LatencyMs, &rates), None);
+
+        let rates = WindowedRates {
+            sent: 10,
+            confirmed: 20,
+            ..rates
+        };
+        assert_eq!(evaluate(AlertMetric::ConfirmationRate, &rates), None);
+        assert_eq!(evaluate(AlertMetric::P95LatencyMs, &rates), Some(1_500.0));
+    }
+}
@@ -0,0 +1,696 @@
+use std::collections::{BTreeMap, HashMap};
+
+use iter_tools::Itertools;
+use serde::Deserialize;
+use solana_sdk::slot_history::Slot;
+
+use crate::{
+    baseline::BASELINE_PHASE,
+    cli::AnalyzeConfig,
+    csv_schema::{read_versioned_csv, BLOCK_SCHEMA_VERSION, TRANSACTION_SCHEMA_VERSION},
+    helpers::{block_cu_utilization, parse_datetime, percentile},
+};
+
+/// Columns of the transaction csv needed for the analysis, other columns are ignored
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransactionRow {
+    pub signature: String,
+    pub sent_slot: Slot,
+    pub sent_at: String,
+    // microsecond timestamps, missing in older files
+    #[serde(default)]
+    pub sent_at_us: Option<i64>,
+    pub confirmed_slot: Option<Slot>,
+    pub confirmed_at: Option<String>,
+    #[serde(default)]
+    pub confirmed_at_us: Option<i64>,
+    pub successful: bool,
+    pub error: Option<String>,
+    // missing in older files
+    #[serde(default)]
+    pub mango_error_code: Option<u32>,
+    pub market: Option<String>,
+    #[serde(default)]
+    pub market_maker: Option<String>,
+    pub keeper_instruction: Option<String>,
+    // mm, keeper or crank, missing in older files
+    #[serde(default)]
+    pub tx_type: Option<String>,
+    pub timed_out: bool,
+    // missing in older files, their expired transactions are timed out
+    #[serde(default)]
+    pub expired: bool,
+    pub priority_fees: u64,
+    #[serde(default)]
+    pub blockhash_age_slots: Option<u64>,
+    // kind of the intentionally failing transactions sent with --chaos
+    #[serde(default)]
+    pub chaos: Option<String>,
+    // order type of the quotes, missing in older files
+    #[serde(default)]
+    pub order_type: Option<String>,
+    #[serde(default)]
+    pub reduce_only: bool,
+    // only written with --send-delay-ms-distribution
+    #[serde(default)]
+    pub send_delay_ms: Option<u64>,
+    // missing in older files
+    #[serde(default)]
+    pub tx_size_bytes: Option<u64>,
+    #[serde(default)]
+    pub num_instructions: Option<u64>,
+    // only written with --track-all-commitments
+    #[serde(default)]
+    pub commitment_processed_at: Option<String>,
+    #[serde(default)]
+    pub commitment_confirmed_at: Option<String>,
+    #[serde(default)]
+    pub commitment_finalized_at: Option<String>,
+    // missing in older files
+    #[serde(default)]
+    pub inclusion_latency_ms: Option<i64>,
+    #[serde(default)]
+    pub corrected_inclusion_latency_ms: Option<i64>,
+}
+
+impl TransactionRow {
+    /// time between sending and confirmation in milliseconds
+    pub fn latency_ms(&self) -> Option<i64> {
+        if let (Some(sent_at_us), Some(confirmed_at_us)) = (self.sent_at_us, self.confirmed_at_us) {
+            return Some((confirmed_at_us - sent_at_us) / 1000);
+        }
+        let sent_at = parse_datetime(&self.sent_at)?;
+        let confirmed_at = parse_datetime(self.confirmed_at.as_ref()?)?;
+        Some((confirmed_at - sent_at).num_milliseconds())
+    }
+
+    fn latency_to_ms(&self, reached_at: &Option<String>) -> Option<i64> {
+        let sent_at = parse_datetime(&self.sent_at)?;
+        let reached_at = parse_datetime(reached_at.as_ref()?)?;
+        Some((reached_at - sent_at).num_milliseconds())
+    }
+
+    pub fn latency_slots(&self) -> Option<u64> {
+        self.confirmed_slot
+            .map(|slot| slot.saturating_sub(self.sent_slot))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlockRow {
+    pub block_slot: Slot,
+    pub block_leader: String,
+    pub total_transactions: u64,
+    pub number_of_mango_simulation_txs: u64,
+    pub cu_consumed: u64,
+    pub cu_consumed_by_mango_simulations: u64,
+    // not written by older versions
+    #[serde(default)]
+    pub inclusion_delay_max: Option<u64>,
+    #[serde(default)]
+    pub missed_transactions: u64,
+    pub block_time: u64,
+    #[serde(default)]
+    pub phase: Option<String>,
+}
+
+/// rows of a transaction csv of any schema version
+pub async fn read_transactions(path: &str) -> anyhow::Result<(u32, Vec<TransactionRow>)> {
+    read_versioned_csv(path, TRANSACTION_SCHEMA_VERSION).await
+}
+
+fn rate(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+fn print_transaction_summary(name: &str, rows: &[&TransactionRow]) {
+    let confirmed = rows.iter().filter(|r| r.confirmed_slot.is_some()).count();
+    let successful = rows.iter().filter(|r| r.successful).count();
+    let timed_out = rows.iter().filter(|r| r.timed_out).count();
+    let expired = rows.iter().filter(|r| r.expired).count();
+    let errors = rows
+        .iter()
+        .filter(|r| r.error.is_some() && !r.timed_out && !r.expired)
+        .count();
+    println!(
+        "{name} : Sent({}) Confirmed({}, {:.2}%) Successful({}, {:.2}%) Errors({}, {:.2}%) Expired({}, {:.2}%) Timed out({}, {:.2}%)",
+        rows.len(),
+        confirmed,
+        rate(confirmed, rows.len()),
+        successful,
+        rate(successful, rows.len()),
+        errors,
+        rate(errors, rows.len()),
+        expired,
+        rate(expired, rows.len()),
+        timed_out,
+        rate(timed_out, rows.len()),
+    );
+}
+
+fn print_latency_summary(rows: &[TransactionRow]) {
+    let latencies_ms = rows
+        .iter()
+        .filter_map(|r| r.latency_ms())
+        .sorted()
+        .collect_vec();
+    let latencies_slots = rows
+        .iter()
+        .filter_map(|r| r.latency_slots())
+        .sorted()
+        .collect_vec();
+    for p in [50.0, 90.0, 95.0, 99.0] {
+        println!(
+            "Latency p{p} : {} ms, {} slots",
+            percentile(&latencies_ms, p).unwrap_or_default(),
+            percentile(&latencies_slots, p).unwrap_or_default(),
+        );
+    }
+}
+
+/// latency between the send and the block time, as measured and corrected by the clock offset
+fn print_inclusion_latency_summary(rows: &[TransactionRow]) {
+    let latencies = |latency: fn(&TransactionRow) -> Option<i64>| {
+        rows.iter().filter_map(latency).sorted().collect_vec()
+    };
+    let raw = latencies(|r| r.inclusion_latency_ms);
+    let corrected = latencies(|r| r.corrected_inclusion_latency_ms);
+    if raw.is_empty() {
+        return;
+    }
+    for p in [50.0, 90.0, 99.0] {
+        println!(
+            "Inclusion latency p{p} : {} ms, corrected for the clock skew {} ms",
+            percentile(&raw, p).unwrap_or_default(),
+            percentile(&corrected, p).unwrap_or_default(),
+        );
+    }
+}
+
+/// latency to reach each commitment level, to quantify the cost of finality
+fn print_commitment_summary(rows: &[TransactionRow]) {
+    let latencies = |reached_at: fn(&TransactionRow) -> &Option<String>| {
+        rows.iter()
+            .filter_map(|r| r.latency_to_ms(reached_at(r)))
+            .sorted()
+            .collect_vec()
+    };
+    let levels = [
+        ("processed", latencies(|r| &r.commitment_processed_at)),
+        ("confirmed", latencies(|r| &r.commitment_confirmed_at)),
+        ("finalized", latencies(|r| &r.commitment_finalized_at)),
+    ];
+    if levels.iter().all(|(_, latencies)| latencies.is_empty()) {
+        return;
+    }
+    for (level, latencies) in levels {
+        println!(
+            "Latency to {level} : {} transactions p50({} ms) p90({} ms) p99({} ms)",
+            latencies.len(),
+            percentile(&latencies, 50.0).unwrap_or_default(),
+            percentile(&latencies, 90.0).unwrap_or_default(),
+            percentile(&latencies, 99.0).unwrap_or_default(),
+        );
+    }
+}
+
+// upper bounds (inclusive) of the priority fee buckets in micro lamports per CU
+const PRIORITY_FEE_BUCKETS: [u64; 6] = [0, 250, 500, 750, 1000, u64::MAX];
+
+/// landing probability and latency of transactions depending on their priority fees
+fn print_priority_fee_summary(rows: &[TransactionRow]) {
+    let by_bucket = rows
+        .iter()
+        .map(|r| {
+            let bucket = PRIORITY_FEE_BUCKETS
+                .iter()
+                .position(|max| r.priority_fees <= *max)
+                .unwrap();
+            (bucket, r)
+        })
+        .into_group_map();
+    for (bucket, rows) in by_bucket.iter().sorted_by_key(|x| x.0) {
+        let name = match *bucket {
+            0 => "0".to_string(),
+            b if PRIORITY_FEE_BUCKETS[b] == u64::MAX => {
+                format!(">{}", PRIORITY_FEE_BUCKETS[b - 1])
+            }
+            b => format!(
+                "{}-{}",
+                PRIORITY_FEE_BUCKETS[b - 1] + 1,
+                PRIORITY_FEE_BUCKETS[b]
+            ),
+        };
+        let landed = rows.iter().filter(|r| r.confirmed_slot.is_some()).count();
+        let timed_out = rows.iter().filter(|r| r.timed_out).count();
+        let latencies_ms = rows
+            .iter()
+            .filter_map(|r| r.latency_ms())
+            .sorted()
+            .collect_vec();
+        println!(
+            "Priority fees {name} : Sent({}) Landed({}, {:.2}%) Timed out({}, {:.2}%) Latency p50({} ms) p90({} ms)",
+            rows.len(),
+            landed,
+            rate(landed, rows.len()),
+            timed_out,
+            rate(timed_out, rows.len()),
+            percentile(&latencies_ms, 50.0).unwrap_or_default(),
+            percentile(&latencies_ms, 90.0).unwrap_or_default(),
+        );
+    }
+}
+
+// upper bounds of the blockhash age buckets in slots, blockhashes expire after 150 blocks
+const BLOCKHASH_AGE_BUCKETS: [u64; 5] = [10, 30, 60, 150, u64::MAX];
+
+/// Expiries of transactions signed with old blockhashes point to stale blockhashes, timeouts to
+/// transactions dropped by the network
+fn print_blockhash_age_summary(rows: &[TransactionRow]) {
+    let by_bucket = rows
+        .iter()
+        .filter_map(|r| {
+            let age = r.blockhash_age_slots?;
+            let bucket = BLOCKHASH_AGE_BUCKETS.iter().position(|max| age <= *max)?;
+            Some((bucket, r))
+        })
+        .into_group_map();
+    for (bucket, rows) in by_bucket.iter().sorted_by_key(|x| x.0) {
+        let name = match *bucket {
+            0 => format!("0-{}", BLOCKHASH_AGE_BUCKETS[0]),
+            b if BLOCKHASH_AGE_BUCKETS[b] == u64::MAX => {
+                format!(">{}", BLOCKHASH_AGE_BUCKETS[b - 1])
+            }
+            b => format!(
+                "{}-{}",
+                BLOCKHASH_AGE_BUCKETS[b - 1] + 1,
+                BLOCKHASH_AGE_BUCKETS[b]
+            ),
+        };
+        let landed = rows.iter().filter(|r| r.confirmed_slot.is_some()).count();
+        let expired = rows.iter().filter(|r| r.expired).count();
+        let timed_out = rows.iter().filter(|r| r.timed_out).count();
+        println!(
+            "Blockhash age {name} slots : Sent({}) Landed({}, {:.2}%) Expired({}, {:.2}%) Timed out({}, {:.2}%)",
+            rows.len(),
+            landed,
+            rate(landed, rows.len()),
+            expired,
+            rate(expired, rows.len()),
+            timed_out,
+            rate(timed_out, rows.len()),
+        );
+    }
+}
+
+/// Packing of the transactions, the number of quotes fitting in a block depends on their size
+fn print_size_summary(rows: &[TransactionRow]) {
+    let sized = rows
+        .iter()
+        .filter_map(|r| Some((r.tx_size_bytes?, r.num_instructions.unwrap_or_default(), r)))
+        .collect_vec();
+    if sized.is_empty() {
+        return;
+    }
+    let bytes = sized.iter().map(|(size, _, _)| *size).sum::<u64>();
+    let instructions = sized.iter().map(|(_, nb, _)| *nb).sum::<u64>();
+    let sizes = sized
+        .iter()
+        .map(|(size, _, _)| *size)
+        .sorted()
+        .collect_vec();
+    let sent_at = sized
+        .iter()
+        .filter_map(|(_, _, r)| parse_datetime(&r.sent_at))
+        .minmax()
+        .into_option();
+    let bytes_per_second = sent_at
+        .map(|(first, last)| (last - first).num_milliseconds())
+        .filter(|ms| *ms > 0)
+        .map(|ms| bytes as f64 * 1000.0 / ms as f64)
+        .unwrap_or_default();
+    println!(
+        "Transaction size : Bytes({bytes}, {bytes_per_second:.0}/s) Average({:.0} bytes, {:.2} instructions) p50({:?}) max({:?})",
+        bytes as f64 / sized.len() as f64,
+        instructions as f64 / sized.len() as f64,
+        percentile(&sizes, 50.0),
+        sizes.last(),
+    );
+}
+
+// upper bounds of the send delay buckets in milliseconds, a slot lasts about 400ms
+const SEND_DELAY_BUCKETS: [u64; 6] = [10, 50, 200, 400, 1000, u64::MAX];
+
+/// Landing rate of the transactions by the artificial delay waited between signing and sending
+fn print_send_delay_summary(rows: &[TransactionRow]) {
+    let by_bucket = rows
+        .iter()
+        .filter_map(|r| {
+            let delay = r.send_delay_ms?;
+            let bucket = SEND_DELAY_BUCKETS.iter().position(|max| delay <= *max)?;
+            Some((bucket, r))
+        })
+        .into_group_map();
+    for (bucket, rows) in by_bucket.iter().sorted_by_key(|x| x.0) {
+        let name = match *bucket {
+            0 => format!("0-{}", SEND_DELAY_BUCKETS[0]),
+            b if SEND_DELAY_BUCKETS[b] == u64::MAX => format!(">{}", SEND_DELAY_BUCKETS[b - 1]),
+            b => format!(
+                "{}-{}",
+                SEND_DELAY_BUCKETS[b - 1] + 1,
+                SEND_DELAY_BUCKETS[b]
+            ),
+        };
+        let landed = rows.iter().filter(|r| r.confirmed_slot.is_some()).count();
+        let timed_out = rows.iter().filter(|r| r.timed_out).count();
+        println!(
+            "Send delay {name}ms : Sent({}) Landed({}, {:.2}%) Timed out({}, {:.2}%)",
+            rows.len(),
+            landed,
+            rate(landed, rows.len()),
+            timed_out,
+            rate(timed_out, rows.len()),
+        );
+    }
+}
+
+// lower bounds of the write lock contention buckets, in transactions per slot on the same account
+const CONTENTION_BUCKETS: [usize; 5] = [1, 2, 5, 10, 20];
+
+/// Transactions sent in the same slot which write lock the same account compete for it in the
+/// leader, every transaction on a market writes its bids, asks and event queue and every market
+/// maker transaction writes the mango accounts of its signer
+fn print_write_lock_contention_summary(rows: &[TransactionRow]) {
+    let accounts: [(&str, fn(&TransactionRow) -> Option<&String>); 2] = [
+        ("perp market, bids, asks and event queue", |r| {
+            r.market.as_ref()
+        }),
+        ("market maker mango account", |r| r.market_maker.as_ref()),
+    ];
+    for (kind, account) in accounts {
+        let contention = rows
+            .iter()
+            .filter_map(|r| account(r).map(|a| ((r.sent_slot, a), r)))
+            .into_group_map();
+        if contention.is_empty() {
+            continue;
+        }
+        println!("Write lock contention on {kind}");
+
+        let by_bucket = contention
+            .values()
+            .flat_map(|txs| {
+                let bucket = CONTENTION_BUCKETS
+                    .iter()
+                    .rposition(|min| txs.len() >= *min)
+                    .unwrap();
+                txs.iter().map(move |r| (bucket, *r))
+            })
+            .into_group_map();
+        for (bucket, txs) in by_bucket.iter().sorted_by_key(|x| x.0) {
+            let name = match CONTENTION_BUCKETS.get(bucket + 1) {
+                Some(next) if next - 1 == CONTENTION_BUCKETS[*bucket] => {
+                    CONTENTION_BUCKETS[*bucket].to_string()
+                }
+                Some(next) => format!("{}-{}", CONTENTION_BUCKETS[*bucket], next - 1),
+                None => format!("{}+", CONTENTION_BUCKETS[*bucket]),
+            };
+            let failed = txs.iter().filter(|r| !r.successful).count();
+            println!(
+                "  {name} transactions per slot : Sent({}) Failed({}, {:.2}%)",
+                txs.len(),
+                failed,
+                rate(failed, txs.len()),
+            );
+        }
+
+        let most_contended = contention
+            .iter()
+            .sorted_by(|x, y| y.1.len().cmp(&x.1.len()).then(x.0.cmp(y.0)))
+            .take(5);
+        for ((slot, account), txs) in most_contended {
+            println!(
+                "  Most contended : {account} at slot {slot} with {} transactions",
+                txs.len()
+            );
+        }
+    }
+}
+
+const FULLNESS_BUCKETS: [(f64, f64); 5] = [
+    (0.0, 25.0),
+    (25.0, 50.0),
+    (50.0, 75.0),
+    (75.0, 90.0),
+    (90.0, f64::MAX),
+];
+
+/// landing probability of transactions depending on how full the first block produced
+/// after they were sent was
+fn print_block_fullness_summary(transactions: &[TransactionRow], blocks: &[BlockRow]) {
+    let fullness_by_slot: BTreeMap<Slot, f64> = blocks
+        .iter()
+        .map(|b| (b.block_slot, block_cu_utilization(b.cu_consumed)))
+        .collect();
+    let average = fullness_by_slot.values().sum::<f64>() / fullness_by_slot.len().max(1) as f64;
+    println!("Average block CU utilization : {average:.2}%");
+
+    let mut buckets = [(0usize, 0usize, 0usize); FULLNESS_BUCKETS.len()];
+    for (blocks, (min, max)) in buckets.iter_mut().map(|b| &mut b.0).zip(FULLNESS_BUCKETS) {
+        *blocks = fullness_by_slot
+            .values()
+            .filter(|f| **f >= min && **f < max)
+            .count();
+    }
+    for tx in transactions {
+        let fullness = match fullness_by_slot.range(tx.sent_slot..).next() {
+            Some((_, fullness)) => *fullness,
+            None => continue,
+        };
+        if let Some(bucket) = FULLNESS_BUCKETS
+            .iter()
+            .position(|(min, max)| fullness >= *min && fullness < *max)
+        {
+            buckets[bucket].1 += 1;
+            if tx.confirmed_slot.is_some() {
+                buckets[bucket].2 += 1;
+            }
+        }
+    }
+    for ((min, max), (nb_blocks, sent, landed)) in FULLNESS_BUCKETS.iter().zip(buckets) {
+        let max = if *max == f64::MAX { 100.0 } else { *max };
+        println!(
+            "Block fullness {min}-{max}% : blocks({nb_blocks}) sent({sent}) landed({landed}) landing probability({:.2}%)",
+            rate(landed, sent)
+        );
+    }
+}
+
+/// fill rate, compute units and slot time of a set of blocks, to compare the run with the idle
+/// cluster
+fn print_block_phase_summary(name: &str, blocks: &[BlockRow]) {
+    let nb_blocks = blocks.len().max(1) as u64;
+    let (Some(first), Some(last)) = (
+        blocks.iter().min_by_key(|b| b.block_slot),
+        blocks.iter().max_by_key(|b| b.block_slot),
+    ) else {
+        return;
+    };
+    // block times are rounded to the second, the slot time is only meaningful over many slots
+    let slot_time_ms = (last.block_time.saturating_sub(first.block_time) * 1000)
+        .checked_div(last.block_slot - first.block_slot);
+    println!(
+        "{name} : blocks({}) average transactions({}) average CU({}) CU utilization({:.2}%) slot time({})",
+        blocks.len(),
+        blocks.iter().map(|b| b.total_transactions).sum::<u64>() / nb_blocks,
+        blocks.iter().map(|b| b.cu_consumed).sum::<u64>() / nb_blocks,
+        blocks
+            .iter()
+            .map(|b| block_cu_utilization(b.cu_consumed))
+            .sum::<f64>()
+            / nb_blocks as f64,
+        slot_time_ms.map_or("n/a".to_string(), |ms| format!("{ms}ms")),
+    );
+}
+
+/// landing of our transactions for each leader of the run
+fn print_leader_summary(blocks: &[BlockRow]) {
+    let by_leader = blocks
+        .iter()
+        .map(|b| (b.block_leader.as_str(), b))
+        .into_group_map();
+    println!("Leaders : {}", by_leader.len());
+    for (leader, blocks) in by_leader
+        .iter()
+        .sorted_by(|x, y| y.1.len().cmp(&x.1.len()).then(x.0.cmp(y.0)))
+    {
+        let nb_blocks = blocks.len() as u64;
+        let landed = blocks
+            .iter()
+            .map(|b| b.number_of_mango_simulation_txs)
+            .sum::<u64>();
+        println!(
+            "Leader {leader} : blocks({nb_blocks}) simulation transactions({landed}, {} per block) average transactions({}) average CU({})",
+            landed / nb_blocks,
+            blocks.iter().map(|b| b.total_transactions).sum::<u64>() / nb_blocks,
+            blocks.iter().map(|b| b.cu_consumed).sum::<u64>() / nb_blocks,
+        );
+    }
+}
+
+pub async fn analyze(config: AnalyzeConfig) -> anyhow::Result<()> {
+    let (schema_version, transactions) = read_transactions(&config.transaction_save_file).await?;
+    // chaos transactions are expected to fail and would skew every other summary
+    let (chaos, transactions): (Vec<_>, Vec<_>) =
+        transactions.into_iter().partition(|r| r.chaos.is_some());
+
+    println!(
+        "\n\nAnalysis of {} (schema version {schema_version})",
+        config.transaction_save_file
+    );
+    let by_chaos = chaos
+        .iter()
+        .filter_map(|r| r.chaos.clone().map(|c| (c, r)))
+        .into_group_map();
+    for (kind, rows) in by_chaos.iter().sorted_by_key(|x| x.0) {
+        print_transaction_summary(&format!("Chaos {kind}"), rows);
+    }
+    print_transaction_summary("All transactions", &transactions.iter().collect_vec());
+    let market_making = transactions
+        .iter()
+        .filter(|r| r.keeper_instruction.is_none())
+        .collect_vec();
+    print_transaction_summary("Market maker transactions", &market_making);
+
+    let by_tx_type = transactions
+        .iter()
+        .filter_map(|r| r.tx_type.clone().map(|t| (t, r)))
+        .into_group_map();
+    for (tx_type, rows) in by_tx_type.iter().sorted_by_key(|x| x.0) {
+        print_transaction_summary(&format!("Type {tx_type}"), rows);
+    }
+
+    let by_keeper_instruction = transactions
+        .iter()
+        .filter_map(|r| r.keeper_instruction.clone().map(|k| (k, r)))
+        .into_group_map();
+    for (keeper_instruction, rows) in by_keeper_instruction.iter().sorted_by_key(|x| x.0) {
+        print_transaction_summary(&format!("Keeper {keeper_instruction}"), rows);
+    }
+
+    let by_market = market_making
+        .iter()
+        .filter_map(|r| r.market.clone().map(|m| (m, *r)))
+        .into_group_map();
+    for (market, rows) in by_market.iter().sorted_by_key(|x| x.0) {
+        print_transaction_summary(&format!("Market {market}"), rows);
+    }
+
+    // the order types take different paths through mango, only broken down for a mix
+    let by_order_type = market_making
+        .iter()
+        .filter_map(|r| {
+            r.order_type.clone().map(|order_type| {
+                let reduce_only = if r.reduce_only { " reduce-only" } else { "" };
+                (format!("{order_type}{reduce_only}"), *r)
+            })
+        })
+        .into_group_map();
+    if by_order_type.len() > 1 {
+        for (order_type, rows) in by_order_type.iter().sorted_by_key(|x| x.0) {
+            print_transaction_summary(&format!("Order type {order_type}"), rows);
+        }
+    }
+
+    print_latency_summary(&transactions);
+    print_inclusion_latency_summary(&transactions);
+    print_commitment_summary(&transactions);
+    print_priority_fee_summary(&transactions);
+    print_blockhash_age_summary(&transactions);
+    print_send_delay_summary(&transactions);
+    print_size_summary(&transactions);
+    print_write_lock_contention_summary(&transactions);
+
+    let mut errors: HashMap<&str, u64> = HashMap::new();
+    for error in transactions.iter().filter_map(|r| r.error.as_deref()) {
+        *errors.entry(error).or_default() += 1;
+    }
+    for (idx, (error, count)) in errors
+        .iter()
+        .sorted_by(|x, y| y.1.cmp(x.1))
+        .take(5)
+        .enumerate()
+    {
+        println!("Error #{idx} : {error} ({count})");
+    }
+    let mango_errors = transactions
+        .iter()
+        .filter_map(|r| r.mango_error_code)
+        .counts();
+    for (code, count) in mango_errors.iter().sorted_by(|x, y| y.1.cmp(x.1)).take(5) {
+        println!("Mango error code {code} ({count})");
+    }
+
+    if let Some(block_data_save_file) = &config.block_data_save_file {
+        let (schema_version, blocks): (_, Vec<BlockRow>) =
+            read_versioned_csv(block_data_save_file, BLOCK_SCHEMA_VERSION).await?;
+        // the blocks of the idle cluster are only compared with the blocks of the run
+        let (baseline, blocks): (Vec<_>, Vec<_>) = blocks
+            .into_iter()
+            .partition(|b| b.phase.as_deref() == Some(BASELINE_PHASE));
+        let nb_blocks = blocks.len().max(1) as u64;
+        println!("\nAnalysis of {block_data_save_file} (schema version {schema_version})");
+        println!(
+            "Blocks : {} from slot {} to {}",
+            blocks.len(),
+            blocks
+                .iter()
+                .map(|b| b.block_slot)
+                .min()
+                .unwrap_or_default(),
+            blocks
+                .iter()
+                .map(|b| b.block_slot)
+                .max()
+                .unwrap_or_default(),
+        );
+        println!(
+            "Average per block : transactions({}) simulation transactions({}) CU({}) simulation CU({})",
+            blocks.iter().map(|b| b.total_transactions).sum::<u64>() / nb_blocks,
+            blocks
+                .iter()
+                .map(|b| b.number_of_mango_simulation_txs)
+                .sum::<u64>()
+                / nb_blocks,
+            blocks.iter().map(|b| b.cu_consumed).sum::<u64>() / nb_blocks,
+            blocks
+                .iter()
+                .map(|b| b.cu_consumed_by_mango_simulations)
+                .sum::<u64>()
+                / nb_blocks,
+        );
+        let max_delays = blocks
+            .iter()
+            .filter_map(|b| b.inclusion_delay_max)
+            .sorted()
+            .collect_vec();
+        println!(
+            "Inclusion delay (max per block) : p50({} slots) p90({} slots), missed transactions per block({:.2})",
+            percentile(&max_delays, 50.0).unwrap_or_default(),
+            percentile(&max_delays, 90.0).unwrap_or_default(),
+            blocks.iter().map(|b| b.missed_transactions).sum::<u64>() as f64 / nb_blocks as f64,
+        );
+        print_block_fullness_summary(&transactions, &blocks);
+        print_leader_summary(&blocks);
+        if !baseline.is_empty() {
+            print_block_phase_summary("Baseline", &baseline);
+            print_block_phase_summary("Run", &blocks);
+        }
+    }
+    println!("\n");
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use std::{sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use futures::StreamExt;
+use log::{info, warn};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_transaction_status::{TransactionDetails, UiTransactionEncoding};
+use tokio::sync::broadcast;
+
+use crate::{
+    clock_skew::ClockSkew,
+    confirmation_strategies::{get_block_with_retry, get_blocks_with_retry, process_blocks},
+    states::BlockData,
+};
+
+/// Phase of the blocks observed before the run
+pub const BASELINE_PHASE: &str = "baseline";
+
+const BASELINE_FETCH_CONCURRENCY: usize = 8;
+
+/// Observes the blocks of the cluster for the given duration before any transaction of the
+/// benchmark is sent, so the fill rate, compute units and slot times of the idle cluster can be
+/// compared with the ones of the run
+pub async fn observe_baseline(client: Arc<RpcClient>, duration: Duration) -> Vec<BlockData> {
+    let commitment_confirmation = CommitmentConfig {
+        commitment: CommitmentLevel::Confirmed,
+    };
+    let from_slot = match client
+        .get_slot_with_commitment(commitment_confirmation)
+        .await
+    {
+        Ok(slot) => slot,
+        Err(e) => {
+            warn!("Failed to get the slot, skipping the baseline : {e}");
+            return vec![];
+        }
+    };
+    info!("observing the idle cluster for {duration:?} from slot {from_slot}");
+    tokio::time::sleep(duration).await;
+
+    let Ok(block_slots) =
+        get_blocks_with_retry(client.clone(), from_slot, commitment_confirmation).await
+    else {
+        warn!("Failed to get the blocks of the baseline");
+        return vec![];
+    };
+    let block_config = RpcBlockConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        transaction_details: Some(TransactionDetails::Full),
+        rewards: Some(true),
+        commitment: Some(commitment_confirmation),
+        max_supported_transaction_version: Some(0),
+    };
+    // the blocks are decoded like the ones of the run, without any transaction to match
+    let (tx_confirm_records, _) = broadcast::channel(1);
+    let (tx_block_data, mut block_data) = broadcast::channel(block_slots.len().max(1));
+    let transaction_map = Arc::new(DashMap::new());
+    let clock_skew = ClockSkew::default();
+    let mut blocks = futures::stream::iter(block_slots)
+        .map(|slot| {
+            let client = client.clone();
+            async move {
+                (
+                    slot,
+                    get_block_with_retry(&client, slot, block_config).await,
+                )
+            }
+        })
+        .buffered(BASELINE_FETCH_CONCURRENCY);
+    while let Some((slot, block)) = blocks.next().await {
+        let Some(block) = block else {
+            warn!("Gap at slot {slot} in the baseline");
+            continue;
+        };
+        process_blocks(
+            &block,
+            tx_confirm_records.clone(),
+            tx_block_data.clone(),
+            transaction_map.clone(),
+            slot,
+            CommitmentLevel::Confirmed,
+            None,
+            &clock_skew,
+        )
+        .await;
+    }
+    drop(tx_block_data);
+
+    let mut baseline = vec![];
+    while let Ok(mut block) = block_data.recv().await {
+        block.phase = Some(BASELINE_PHASE.to_string());
+        baseline.push(block);
+    }
+    info!("{} blocks observed during the baseline", baseline.len());
+    baseline
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_std::fs::File;
+use chrono::Utc;
+use log::warn;
+use mango::matching::BookSide;
+use mango_common::Loadable;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{commitment_config::CommitmentConfig, slot_history::Slot};
+use tokio::task::JoinHandle;
+
+use crate::{helpers::to_sdk_pk, market_markers::QuotedMarket, result_writer::RowTags};
+
+pub const DEFAULT_BOOK_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
+pub const DEFAULT_BOOK_SNAPSHOT_DEPTH: usize = 10;
+
+/// A price level of one side of a book at a snapshot, level 0 is the best price
+#[derive(Clone, Debug, Serialize)]
+pub struct BookLevel {
+    pub snapshot_at: String,
+    pub slot: Slot,
+    pub market: String,
+    pub side: &'static str,
+    pub level: usize,
+    pub price_lots: i64,
+    pub quantity_lots: i64,
+    pub orders: u64,
+    // set by the writer, the cluster only when running against several clusters
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+}
+
+// accounts read by a single getMultipleAccounts request
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+// (price, quantity, orders) of the best levels of a side, None if the account could not be read.
+// The expired orders still in the tree cannot be matched anymore and are left out
+fn levels(data: &[u8], bids: bool, depth: usize, now_ts: u64) -> Option<Vec<(i64, i64, u64)>> {
+    let book_side = BookSide::load_from_bytes(data).ok()?;
+    let mut levels: BTreeMap<i64, (i64, u64)> = BTreeMap::new();
+    for leaf in book_side.iter_valid(now_ts) {
+        let level = levels.entry(leaf.price()).or_default();
+        level.0 += leaf.quantity;
+        level.1 += 1;
+    }
+    let levels = levels
+        .into_iter()
+        .map(|(price, (quantity, orders))| (price, quantity, orders));
+    Some(if bids {
+        levels.rev().take(depth).collect()
+    } else {
+        levels.take(depth).collect()
+    })
+}
+
+/// Snapshots the bids and asks of every quoted market at each interval and writes their top
+/// `depth` levels, to check the spread and depth produced by the market makers after the run
+/// (mango v3 only)
+pub fn start_book_recorder(
+    rpc_client: Arc<RpcClient>,
+    quoted_markets: &[QuotedMarket],
+    book_snapshot_file: String,
+    interval: Duration,
+    depth: usize,
+    tags: RowTags,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    // (market, side, is bids) of every quoted market
+    let book_sides = quoted_markets
+        .iter()
+        .map(|(_, _, cache)| (cache.perp_market_pk, cache.bids, cache.asks))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .flat_map(|(market, bids, asks)| [(market, bids, true), (market, asks, false)])
+        .collect::<Vec<(Pubkey, Pubkey, bool)>>();
+
+    tokio::spawn(async move {
+        let mut writer = csv_async::AsyncSerializer::from_writer(
+            File::create(book_snapshot_file).await.unwrap(),
+        );
+        let sdk_pks = book_sides
+            .iter()
+            .map(|(_, side, _)| to_sdk_pk(side))
+            .collect::<Vec<_>>();
+        while !exit_signal.load(Ordering::Relaxed) {
+            tokio::time::sleep(interval).await;
+            // slot and account of every side, read by chunks of the request limit
+            let mut accounts = Vec::with_capacity(sdk_pks.len());
+            for chunk in sdk_pks.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+                match rpc_client
+                    .get_multiple_accounts_with_commitment(chunk, CommitmentConfig::confirmed())
+                    .await
+                {
+                    Ok(snapshot) => accounts.extend(
+                        snapshot
+                            .value
+                            .into_iter()
+                            .map(|account| (snapshot.context.slot, account)),
+                    ),
+                    Err(e) => {
+                        warn!("book recorder : could not get the books {e}");
+                        break;
+                    }
+                }
+            }
+            if accounts.len() < sdk_pks.len() {
+                continue;
+            }
+            let now = Utc::now();
+            let snapshot_at = now.to_string();
+            let now_ts = now.timestamp() as u64;
+            for ((market, _, bids), (slot, account)) in book_sides.iter().zip(accounts) {
+                let Some(levels) =
+                    account.and_then(|account| levels(&account.data, *bids, depth, now_ts))
+                else {
+                    warn!("book recorder : could not decode a side of the book of {market}");
+                    continue;
+                };
+                for (level, (price_lots, quantity_lots, orders)) in levels.into_iter().enumerate() {
+                    let row = BookLevel {
+                        snapshot_at: snapshot_at.clone(),
+                        slot,
+                        market: market.to_string(),
+                        side: if *bids { "bid" } else { "ask" },
+                        level,
+                        price_lots,
+                        quantity_lots,
+                        orders,
+                        run_id: tags.run_id.clone(),
+                        cluster: tags.cluster.clone(),
+                        shard: tags.shard.clone(),
+                    };
+                    writer.serialize(row).await.unwrap();
+                }
+            }
+            // the snapshots are written as they are taken, an interrupted run keeps them
+            writer.flush().await.unwrap();
+        }
+    })
+}
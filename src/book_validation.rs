@@ -0,0 +1,222 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use log::{info, warn};
+use mango::matching::BookSide;
+use mango_common::Loadable;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_metrics::datapoint_info;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{commitment_config::CommitmentConfig, signer::Signer};
+use tokio::{
+    sync::broadcast::{error::TryRecvError, Receiver},
+    task::JoinHandle,
+};
+
+use crate::{
+    helpers::{to_sdk_pk, to_sp_pk},
+    market_markers::QuotedMarket,
+    states::{TransactionConfirmRecord, TransactionType},
+    stats::DroppedRecords,
+};
+
+// the last wait for the next snapshot ends this long after the run stops at most
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Orders of the market makers found on the books at one snapshot
+#[derive(Clone, Copy, Debug, Default)]
+struct BookCheck {
+    // quoting accounts with a successful quote confirmed before the snapshot
+    expected: u64,
+    // quoting accounts with at least one order on the side
+    resting_bid: u64,
+    resting_ask: u64,
+    // orders of the quoting accounts on the side
+    bid_orders: u64,
+    ask_orders: u64,
+}
+
+// accounts read by a single getMultipleAccounts request
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+// number of orders of each owner on a side of a book, None if the account could not be read.
+// The expired orders still in the tree do not rest anymore and are left out
+fn orders_by_owner(data: &[u8], now_ts: u64) -> Option<HashMap<Pubkey, u64>> {
+    let book_side = BookSide::load_from_bytes(data).ok()?;
+    let mut orders = HashMap::new();
+    for leaf in book_side.iter_valid(now_ts) {
+        *orders.entry(leaf.owner).or_default() += 1;
+    }
+    Some(orders)
+}
+
+// waits for the next snapshot, false once the run stopped
+async fn wait_next_snapshot(interval: Duration, exit_signal: &AtomicBool) -> bool {
+    let next_snapshot = Instant::now() + interval;
+    while !exit_signal.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now >= next_snapshot {
+            return true;
+        }
+        tokio::time::sleep((next_snapshot - now).min(EXIT_CHECK_INTERVAL)).await;
+    }
+    false
+}
+
+/// Periodically snapshots the bids and asks of every quoted market and reports how many of the
+/// confirmed quotes actually rest on the book, quotes can confirm while mango rejected the orders.
+/// Every quote cancels the previous orders of the account, so an account which had a quote
+/// confirmed before a snapshot is expected to have a bid and an ask on the book (mango v3 only).
+/// The task ends soon after the exit signal and logs the totals of the run
+pub fn start_book_validation(
+    rpc_client: Arc<RpcClient>,
+    quoted_markets: &[QuotedMarket],
+    mut tx_status_rx: Receiver<TransactionConfirmRecord>,
+    interval: Duration,
+    dropped_records: DroppedRecords,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    // (market maker, market) of the confirm records to (mango accounts, bids, asks), a signer
+    // with several accounts counts as resting when any of them has an order on the side
+    let mut quoting_accounts: HashMap<(String, String), (Vec<Pubkey>, Pubkey, Pubkey)> =
+        HashMap::new();
+    for (signers, mango_account_pk, cache) in quoted_markets {
+        quoting_accounts
+            .entry((
+                to_sp_pk(&signers.owner.pubkey()).to_string(),
+                cache.perp_market_pk.to_string(),
+            ))
+            .or_insert_with(|| (vec![], cache.bids, cache.asks))
+            .0
+            .push(*mango_account_pk);
+    }
+    let book_sides = quoted_markets
+        .iter()
+        .flat_map(|(_, _, cache)| [cache.bids, cache.asks])
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    tokio::spawn(async move {
+        // slot of the last successful quote of each quoting account
+        let mut last_quote_slots: HashMap<(String, String), u64> = HashMap::new();
+        let mut total = BookCheck::default();
+        while wait_next_snapshot(interval, &exit_signal).await {
+            loop {
+                match tx_status_rx.try_recv() {
+                    Ok(record) => {
+                        if record.tx_type != TransactionType::MarketMaker
+                            || record.spoofing
+                            || record.error.is_some()
+                        {
+                            continue;
+                        }
+                        let (Some(market_maker), Some(market), Some(slot)) =
+                            (record.market_maker, record.market, record.confirmed_slot)
+                        else {
+                            continue;
+                        };
+                        last_quote_slots.insert((market_maker, market), slot);
+                    }
+                    Err(TryRecvError::Lagged(n)) => {
+                        warn!("book validation lagged behind, {n} confirmations were skipped");
+                        dropped_records.add(n);
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            let sdk_pks = book_sides.iter().map(to_sdk_pk).collect::<Vec<_>>();
+            // read by chunks of the request limit, the snapshot is as old as its oldest chunk
+            let mut accounts = Vec::with_capacity(sdk_pks.len());
+            let mut snapshot_slot = u64::MAX;
+            for chunk in sdk_pks.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+                match rpc_client
+                    .get_multiple_accounts_with_commitment(chunk, CommitmentConfig::confirmed())
+                    .await
+                {
+                    Ok(snapshot) => {
+                        snapshot_slot = snapshot_slot.min(snapshot.context.slot);
+                        accounts.extend(snapshot.value);
+                    }
+                    Err(e) => {
+                        warn!("book validation : could not get the books {e}");
+                        break;
+                    }
+                }
+            }
+            if accounts.len() < sdk_pks.len() {
+                continue;
+            }
+            let now_ts = Utc::now().timestamp() as u64;
+            let orders: HashMap<Pubkey, HashMap<Pubkey, u64>> = book_sides
+                .iter()
+                .zip(accounts)
+                .filter_map(|(pk, account)| Some((*pk, orders_by_owner(&account?.data, now_ts)?)))
+                .collect();
+
+            let mut check = BookCheck::default();
+            for (key, slot) in &last_quote_slots {
+                if *slot > snapshot_slot {
+                    continue;
+                }
+                let Some((mango_account_pks, bids, asks)) = quoting_accounts.get(key) else {
+                    continue;
+                };
+                let resting_orders = |side: &Pubkey| {
+                    orders.get(side).map_or(0, |orders| {
+                        mango_account_pks
+                            .iter()
+                            .filter_map(|pk| orders.get(pk))
+                            .sum::<u64>()
+                    })
+                };
+                let (bid_orders, ask_orders) = (resting_orders(bids), resting_orders(asks));
+                check.expected += 1;
+                check.resting_bid += (bid_orders > 0) as u64;
+                check.resting_ask += (ask_orders > 0) as u64;
+                check.bid_orders += bid_orders;
+                check.ask_orders += ask_orders;
+            }
+            info!(
+                "book validation at slot {} : {} quoting accounts, {} with a resting bid, {} with a resting ask, {} bids and {} asks resting",
+                snapshot_slot,
+                check.expected,
+                check.resting_bid,
+                check.resting_ask,
+                check.bid_orders,
+                check.ask_orders
+            );
+            datapoint_info!(
+                "bencher-book-validation",
+                ("slot", snapshot_slot, i64),
+                ("expected", check.expected, i64),
+                ("resting_bid", check.resting_bid, i64),
+                ("resting_ask", check.resting_ask, i64),
+                ("bid_orders", check.bid_orders, i64),
+                ("ask_orders", check.ask_orders, i64),
+            );
+            total.expected += check.expected;
+            total.resting_bid += check.resting_bid;
+            total.resting_ask += check.resting_ask;
+            total.bid_orders += check.bid_orders;
+            total.ask_orders += check.ask_orders;
+        }
+        if total.expected > 0 {
+            info!(
+                "book validation : {:.2}% of the expected bids and {:.2}% of the expected asks were on the books, {:.2} bids and {:.2} asks per quoting account",
+                total.resting_bid as f64 * 100.0 / total.expected as f64,
+                total.resting_ask as f64 * 100.0 / total.expected as f64,
+                total.bid_orders as f64 / total.expected as f64,
+                total.ask_orders as f64 / total.expected as f64,
+            );
+        }
+    })
+}
@@ -0,0 +1,138 @@
+use anyhow::Context;
+use log::{info, warn};
+use mango::state::{MangoCache, MangoGroup, QUOTE_INDEX};
+use mango_common::Loadable;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    clock::Clock, signature::Keypair, signer::Signer, sysvar, transaction::Transaction,
+};
+
+use crate::{helpers::to_sdk_pk, keeper::create_keeper_instructions, states::PerpMarketCache};
+
+/// An entry of the mango cache older than the valid interval of the group, orders fail with
+/// InvalidCache while an entry used by the mango accounts is stale
+#[derive(Clone, Debug)]
+pub struct StaleCacheEntry {
+    pub kind: &'static str,
+    pub index: usize,
+    pub age_secs: u64,
+}
+
+/// Compares the price, root bank and perp market caches of the quoted markets with the cluster
+/// clock (mango v3 only)
+pub async fn check_cache_freshness(
+    rpc_client: &RpcClient,
+    perp_markets: &[PerpMarketCache],
+) -> anyhow::Result<Vec<StaleCacheEntry>> {
+    let Some(first) = perp_markets.first() else {
+        return Ok(vec![]);
+    };
+    let accounts = rpc_client
+        .get_multiple_accounts(&[
+            to_sdk_pk(&first.mango_group_pk),
+            to_sdk_pk(&first.mango_cache_pk),
+            sysvar::clock::id(),
+        ])
+        .await?;
+    let [group, cache, clock] = [0, 1, 2].map(|i| accounts[i].as_ref());
+    let group = MangoGroup::load_from_bytes(&group.context("mango group not found")?.data)
+        .map_err(|e| anyhow::anyhow!("could not load the mango group : {e:?}"))?;
+    let cache = MangoCache::load_from_bytes(&cache.context("mango cache not found")?.data)
+        .map_err(|e| anyhow::anyhow!("could not load the mango cache : {e:?}"))?;
+    let clock: Clock = bincode::deserialize(&clock.context("clock sysvar not found")?.data)?;
+    let now = clock.unix_timestamp as u64;
+
+    let mut entries = vec![];
+    for market in perp_markets {
+        if let Some(i) = group.oracles.iter().position(|o| *o == market.price_oracle) {
+            entries.push(("price", i, cache.price_cache[i].last_update));
+        }
+        if let Some(i) = group
+            .tokens
+            .iter()
+            .position(|t| t.root_bank == market.root_bank)
+        {
+            entries.push(("root bank", i, cache.root_bank_cache[i].last_update));
+        }
+        if let Some(i) = group
+            .perp_markets
+            .iter()
+            .position(|p| p.perp_market == market.perp_market_pk)
+        {
+            entries.push(("perp market", i, cache.perp_market_cache[i].last_update));
+        }
+    }
+    // every order is checked against the quote bank
+    entries.push((
+        "root bank",
+        QUOTE_INDEX,
+        cache.root_bank_cache[QUOTE_INDEX].last_update,
+    ));
+    entries.sort();
+    entries.dedup();
+
+    Ok(entries
+        .into_iter()
+        .filter(|(_, _, last_update)| now > last_update + group.valid_interval)
+        .map(|(kind, index, last_update)| StaleCacheEntry {
+            kind,
+            index,
+            age_secs: now.saturating_sub(last_update),
+        })
+        .collect())
+}
+
+/// Makes sure that the mango cache is fresh before the market makers start, stale entries are
+/// refreshed once by sending the keeper instructions through the rpc (mango v3 only)
+pub async fn ensure_fresh_cache(
+    rpc_client: &RpcClient,
+    perp_markets: &[PerpMarketCache],
+    quote_root_bank: Pubkey,
+    quote_node_banks: Vec<Pubkey>,
+    payer: &Keypair,
+) -> anyhow::Result<()> {
+    let stale = check_cache_freshness(rpc_client, perp_markets).await?;
+    if stale.is_empty() {
+        info!("mango cache is fresh");
+        return Ok(());
+    }
+    for entry in &stale {
+        warn!(
+            "{} cache {} is {}s old, refreshing it",
+            entry.kind, entry.index, entry.age_secs
+        );
+    }
+
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+    let refreshes = create_keeper_instructions(perp_markets, quote_root_bank, quote_node_banks)
+        .into_iter()
+        .map(|(keeper_instruction, ixs)| {
+            let tx = Transaction::new_signed_with_payer(
+                &ixs,
+                Some(&payer.pubkey()),
+                &[payer],
+                blockhash,
+            );
+            async move {
+                if let Err(e) = rpc_client.send_and_confirm_transaction(&tx).await {
+                    warn!("could not refresh the cache with {keeper_instruction} : {e}");
+                }
+            }
+        });
+    futures::future::join_all(refreshes).await;
+
+    let stale = check_cache_freshness(rpc_client, perp_markets).await?;
+    if let Some(entry) = stale.first() {
+        anyhow::bail!(
+            "{} cache entries are still stale after the refresh, e.g. {} cache {} is {}s old, \
+            orders would fail with InvalidCache",
+            stale.len(),
+            entry.kind,
+            entry.index,
+            entry.age_secs
+        );
+    }
+    info!("mango cache refreshed");
+    Ok(())
+}
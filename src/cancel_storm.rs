@@ -0,0 +1,138 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use iter_tools::Itertools;
+use log::{info, warn};
+use solana_sdk::{hash::Hash, signer::Signer};
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::{
+    clock,
+    helpers::to_sp_pk,
+    mango_program::MangoProgram,
+    market_markers::{create_cancel_all_orders, QuotedMarket, CANCEL_ALL_PRIORITY_FEES},
+    states::{LiveParameters, TransactionSendRecord, TransactionType},
+    tpu_manager::TpuManager,
+};
+
+/// offset from the start of market making, in seconds with an optional `s` suffix, e.g. `120s`
+pub fn parse_storm_offset(s: &str) -> Result<Duration, String> {
+    s.trim()
+        .trim_end_matches('s')
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|_| format!("invalid cancel storm offset {s}"))
+}
+
+/// Sends a cancel-all transaction for every quoted market at once, like the cancellation waves
+/// of the market makers when the prices move fast. The storms happen at the given offsets and
+/// whenever the scenario asks for one, the market makers keep quoting in between
+#[allow(clippy::too_many_arguments)]
+pub fn start_cancel_storms(
+    mango_program: Arc<dyn MangoProgram>,
+    quoted_markets: Vec<QuotedMarket>,
+    tpu_manager: TpuManager,
+    blockhash: Arc<RwLock<Hash>>,
+    current_slot: Arc<AtomicU64>,
+    live_parameters: Arc<LiveParameters>,
+    mut offsets: Vec<Duration>,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    offsets.sort();
+    tokio::spawn(async move {
+        let start = Instant::now();
+        let mut offsets = offsets.into_iter().peekable();
+        let mut storm = 0;
+        loop {
+            if exit_signal.load(Ordering::Relaxed) {
+                return;
+            }
+            // checks the exit signal at least every second
+            let wait = offsets
+                .peek()
+                .map_or(Duration::from_secs(1), |at| {
+                    at.saturating_sub(start.elapsed())
+                })
+                .min(Duration::from_secs(1));
+            tokio::select! {
+                _ = live_parameters.cancel_storm.notified() => {},
+                _ = tokio::time::sleep(wait) => {
+                    if offsets.peek().map_or(true, |at| start.elapsed() < *at) {
+                        continue;
+                    }
+                    offsets.next();
+                },
+            }
+            if exit_signal.load(Ordering::Relaxed) {
+                return;
+            }
+            storm += 1;
+            info!(
+                "cancel storm {storm} : cancelling the orders of {} quoted markets",
+                quoted_markets.len()
+            );
+
+            let recent_blockhash = *blockhash.read().await;
+            let sent_slot = current_slot.load(Ordering::Acquire);
+            let storm_transactions = quoted_markets
+                .iter()
+                .map(|(signers, mango_account_pk, perp_market)| {
+                    let mut tx = create_cancel_all_orders(
+                        mango_program.as_ref(),
+                        perp_market,
+                        *mango_account_pk,
+                        signers,
+                    );
+                    if let Some(memo) = tpu_manager.memo() {
+                        memo.tag(&mut tx);
+                    }
+                    tx.sign(&signers.signers(), recent_blockhash);
+                    let sent_at = clock::now();
+                    let record = TransactionSendRecord {
+                        signature: tx.signatures[0],
+                        sent_at,
+                        sent_at_us: sent_at.timestamp_micros(),
+                        sent_slot,
+                        market_maker: Some(to_sp_pk(&signers.owner.pubkey())),
+                        market: Some(perp_market.perp_market_pk),
+                        priority_fees: CANCEL_ALL_PRIORITY_FEES,
+                        keeper_instruction: None,
+                        tx_type: TransactionType::CancelStorm,
+                        spoofing: false,
+                        phase: None,
+                        blockhash_slot: None,
+                        blockhash_age_slots: None,
+                        last_valid_block_height: None,
+                        chaos: None,
+                        send_delay_ms: None,
+                        tx_size_bytes: 0,
+                        num_instructions: 0,
+                        order_type: None,
+                        reduce_only: false,
+                        client_order_id: None,
+                        route: None,
+                    };
+                    (tx, record)
+                })
+                .collect_vec();
+            // every cancellation is handed over at the same time
+            let sends = storm_transactions.into_iter().map(|transaction| {
+                let tpu_manager = tpu_manager.clone();
+                async move { tpu_manager.send_transaction_batch(vec![transaction]).await }
+            });
+            let failed = futures::future::join_all(sends)
+                .await
+                .into_iter()
+                .filter(|sent| !sent)
+                .count();
+            if failed > 0 {
+                warn!("cancel storm {storm} : {failed} cancellations could not be sent");
+            }
+        }
+    })
+}
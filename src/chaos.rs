@@ -0,0 +1,150 @@
+use std::{str::FromStr, sync::Arc};
+
+use rand::Rng;
+use serde::Serialize;
+use solana_sdk::{packet::PACKET_DATA_SIZE, signature::Keypair, transaction::Transaction};
+
+use crate::{
+    helpers::{append_instruction_without_accounts, weighted_pick},
+    noop,
+    signing::MarketMakerSigners,
+};
+
+/// Intentionally failing transaction mixed into the market maker flow, chaos transactions are
+/// tagged in the records and excluded from the success metrics
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChaosKind {
+    // orders placed on the mango account by a key which does not own it
+    WrongOwner,
+    // larger than a packet, cannot be sent to a leader
+    Oversized,
+    // signed with a blockhash no validator knows
+    ExpiredBlockhash,
+    // the previous transaction sent a second time
+    Duplicate,
+}
+
+/// Percentages of the market maker transactions turned into each kind of chaos transaction
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChaosConfig {
+    pub wrong_owner_pct: u8,
+    pub oversized_pct: u8,
+    pub expired_blockhash_pct: u8,
+    pub duplicate_pct: u8,
+}
+
+impl FromStr for ChaosConfig {
+    type Err = String;
+
+    /// comma separated `kind=percent`, e.g. `wrong-owner=5,duplicate=2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = ChaosConfig::default();
+        for entry in s.split(',').filter(|e| !e.is_empty()) {
+            let (kind, pct) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected kind=percent, got {entry}"))?;
+            let pct: u8 = pct
+                .parse()
+                .map_err(|_| format!("invalid chaos percentage {pct}"))?;
+            match kind.trim() {
+                "wrong-owner" => config.wrong_owner_pct = pct,
+                "oversized" => config.oversized_pct = pct,
+                "expired-blockhash" => config.expired_blockhash_pct = pct,
+                "duplicate" => config.duplicate_pct = pct,
+                _ => return Err(format!("unknown chaos kind {kind}")),
+            }
+        }
+        if config.total_pct() > 100 {
+            return Err("chaos percentages add up to more than 100".to_string());
+        }
+        Ok(config)
+    }
+}
+
+impl ChaosConfig {
+    fn total_pct(&self) -> u32 {
+        self.wrong_owner_pct as u32
+            + self.oversized_pct as u32
+            + self.expired_blockhash_pct as u32
+            + self.duplicate_pct as u32
+    }
+
+    /// kind of chaos transaction the next transaction is turned into, if any
+    pub fn pick(&self, rng: &mut impl Rng) -> Option<ChaosKind> {
+        if self.total_pct() == 0 {
+            return None;
+        }
+        weighted_pick(
+            rng,
+            &[
+                (ChaosKind::WrongOwner, self.wrong_owner_pct as u32),
+                (ChaosKind::Oversized, self.oversized_pct as u32),
+                (
+                    ChaosKind::ExpiredBlockhash,
+                    self.expired_blockhash_pct as u32,
+                ),
+                (ChaosKind::Duplicate, self.duplicate_pct as u32),
+            ],
+            100,
+        )
+    }
+}
+
+/// signers of a wrong owner transaction, the market maker still pays the fees so that the
+/// transaction is executed and rejected by mango
+pub fn wrong_owner_signers(signers: &MarketMakerSigners) -> MarketMakerSigners {
+    MarketMakerSigners {
+        owner: Arc::new(Keypair::new()),
+        fee_payer: Some(signers.fee_payer.clone().unwrap_or(signers.owner.clone())),
+    }
+}
+
+/// appends a noop instruction with a packet worth of data to an unsigned transaction
+pub fn make_oversized(tx: &mut Transaction) {
+    let noop_program = noop::instruction(vec![]).program_id;
+    append_instruction_without_accounts(tx, &noop_program, vec![0; PACKET_DATA_SIZE]);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn parses_the_chaos_percentages() {
+        let config: ChaosConfig = "wrong-owner=5, oversized=2,duplicate=1".parse().unwrap();
+        assert_eq!(config.wrong_owner_pct, 5);
+        assert_eq!(config.oversized_pct, 2);
+        assert_eq!(config.expired_blockhash_pct, 0);
+        assert_eq!(config.duplicate_pct, 1);
+        assert_eq!("".parse::<ChaosConfig>().unwrap().total_pct(), 0);
+
+        assert!("wrong-owner".parse::<ChaosConfig>().is_err());
+        assert!("wrong-owner=x".parse::<ChaosConfig>().is_err());
+        assert!("unknown=1".parse::<ChaosConfig>().is_err());
+        assert!("wrong-owner=60,duplicate=41"
+            .parse::<ChaosConfig>()
+            .is_err());
+    }
+
+    #[test]
+    fn picks_the_kinds_at_their_percentages() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(ChaosConfig::default().pick(&mut rng), None);
+
+        let config: ChaosConfig = "oversized=100".parse().unwrap();
+        assert!((0..100).all(|_| config.pick(&mut rng) == Some(ChaosKind::Oversized)));
+
+        let config: ChaosConfig = "wrong-owner=20,duplicate=30".parse().unwrap();
+        let picks = (0..10_000)
+            .map(|_| config.pick(&mut rng))
+            .collect::<Vec<_>>();
+        let count = |kind| picks.iter().filter(|pick| **pick == kind).count();
+        assert!((1_800..2_200).contains(&count(Some(ChaosKind::WrongOwner))));
+        assert!((2_800..3_200).contains(&count(Some(ChaosKind::Duplicate))));
+        assert!((4_800..5_200).contains(&count(None)));
+        assert_eq!(count(Some(ChaosKind::Oversized)), 0);
+    }
+}
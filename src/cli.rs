@@ -1,11 +1,57 @@
 use {
-    clap::{crate_description, crate_name, App, Arg, ArgMatches},
+    crate::{
+        affinity::ThreadPinning,
+        alerts::AlertRule,
+        book_recorder::{DEFAULT_BOOK_SNAPSHOT_DEPTH, DEFAULT_BOOK_SNAPSHOT_INTERVAL},
+        cancel_storm::parse_storm_offset,
+        chaos::ChaosConfig,
+        compression::Compression,
+        confirmation_strategies::ConfirmationSource,
+        impairment::NetworkImpairment,
+        mango_program::ProgramVersion,
+        mango_v3_perp_crank_sink::CrankBatchConfig,
+        market_maker_failures::DEFAULT_MAX_MARKET_MAKER_RESTARTS,
+        market_markers::MarketWeights,
+        oracle::OracleFeederConfig,
+        partitioned_output::OutputPartitioning,
+        periphery::{PeripheryConfig, DEFAULT_PERIPHERY_INTERVAL, DEFAULT_PERIPHERY_QUANTITY},
+        rate_limiter::Pacing,
+        run_manifest::new_run_id,
+        scenario::Scenario,
+        send_delay::SendDelay,
+        shard::Shard,
+        states::QuoteConfig,
+        summary::Thresholds,
+        top_up::TopUpConfig,
+        transaction_sender::SenderKind,
+        watchdog::DEFAULT_STALL_TIMEOUT,
+    },
+    clap::{crate_description, crate_name, App, AppSettings, Arg, ArgMatches, SubCommand},
+    mango::matching::ExpiryType,
     solana_clap_utils::input_validators::{is_url, is_url_or_moniker, is_valid_percentage},
     solana_cli_config::{ConfigInput, CONFIG_FILE},
-    solana_sdk::signature::{read_keypair_file, Keypair},
-    std::{net::SocketAddr, process::exit, time::Duration},
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{
+        commitment_config::CommitmentLevel,
+        native_token::sol_to_lamports,
+        signature::{read_keypair_file, Keypair},
+    },
+    std::{
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        path::Path,
+        process::exit,
+        str::FromStr,
+        time::Duration,
+    },
 };
 
+pub const DEFAULT_QUIC_CONNECTION_POOL_SIZE: usize = 10;
+// records buffered for the slowest of the writers and aggregators before they are dropped
+pub const DEFAULT_RECORD_BUFFER: usize = 1_000_000;
+pub const DEFAULT_JITO_URL: &str = "https://mainnet.block-engine.jito.wtf";
+pub const DEFAULT_BLOCK_FETCH_CONCURRENCY: usize = 16;
+pub const DEFAULT_FUNDING_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Holds the configuration for a single run of the benchmark
 pub struct Config {
     pub entrypoint_addr: SocketAddr,
@@ -13,17 +59,144 @@ pub struct Config {
     pub websocket_url: String,
     pub identity: Keypair,
     pub duration: Duration,
+    // the market makers stop once the cluster produced this many slots, whichever of the two
+    // durations ends first
+    pub duration_slots: Option<u64>,
     pub quotes_per_second: u64,
     pub account_keys: String,
     pub mango_keys: String,
     pub transaction_save_file: String,
     pub block_data_save_file: String,
+    // the transaction and block files are split in several files listed in a manifest
+    pub partition_output: Option<OutputPartitioning>,
+    // the transaction and block files are compressed as the rows are written
+    pub compress: Option<Compression>,
     pub mango_cluster: String,
     pub txs_batch_size: Option<usize>,
     pub priority_fees_proba: u8,
     pub keeper_prioritization: u64,
+    // also crank the serum event queues of the spot markets of the group
+    pub crank_spot_markets: bool,
+    pub crank_batch: CrankBatchConfig,
     pub keeper_authority: Option<Keypair>,
+    // the fee payers of the market makers are funded from this keypair during the run
+    pub top_up: Option<TopUpConfig>,
     pub number_of_markers_per_mm: u8,
+    pub market_weights: MarketWeights,
+    pub spoofing_levels: Option<u8>,
+    // every quote of a market maker on a market gets a client order id above the previous one
+    pub unique_client_order_ids: bool,
+    // (market maker, market, tick, side) of each order encoded in its client order id
+    pub encoded_client_order_ids: bool,
+    pub program_version: ProgramVersion,
+    pub db_url: Option<String>,
+    pub slot_coverage_file: String,
+    // sent and confirmed transactions per second
+    pub timeseries_file: String,
+    // funding of the perp markets sampled at the funding interval
+    pub funding_file: String,
+    // fills of the market makers decoded from the event queues
+    pub fills_file: String,
+    pub funding_interval: Duration,
+    pub dry_run: bool,
+    pub control_port: Option<u16>,
+    // the control server steers the run without authentication, it only listens locally by default
+    pub control_bind: IpAddr,
+    // http server answering the liveness and readiness probes
+    pub health_port: Option<u16>,
+    pub health_bind: IpAddr,
+    // websocket server publishing the confirmation records as they are produced
+    pub results_stream_port: Option<u16>,
+    pub results_stream_bind: IpAddr,
+    pub warmup_probes: usize,
+    pub warmup_max_failure_pct: u8,
+    // blocks are observed without sending anything for this long before the run
+    pub baseline_secs: u64,
+    pub quote_config: QuoteConfig,
+    // the compute unit limit of the quotes is estimated by simulating them
+    pub estimate_compute_units: bool,
+    pub summary_file: String,
+    pub thresholds: Thresholds,
+    // checked during the run over the last confirmations, unlike the thresholds
+    pub alerts: Vec<AlertRule>,
+    pub alert_webhook: Option<String>,
+    pub accounts_per_signer: usize,
+    // start and retire market makers when the accounts file changes during the run
+    pub watch_accounts: bool,
+    pub quic_connection_pool_size: usize,
+    pub tpu_max_parallel_streams: Option<usize>,
+    pub num_quic_endpoints: usize,
+    pub send_errors_file: String,
+    pub scenario: Option<Scenario>,
+    // offsets from the start of market making at which every market maker cancels its orders
+    pub cancel_storm_at: Vec<Duration>,
+    // redeem-mngo, deposit and withdraw instructions sent besides the orders
+    pub periphery: Option<PeripheryConfig>,
+    pub oracle_feeder: Option<OracleFeederConfig>,
+    pub commitment: CommitmentLevel,
+    pub track_all_commitments: bool,
+    // blocks fetched in parallel when confirming by polling the blocks of the RPC node
+    pub block_fetch_concurrency: usize,
+    // how the transactions not notified by lite-rpc are confirmed
+    pub confirmation_source: ConfirmationSource,
+    pub stall_timeout: Duration,
+    pub abort_on_stall: bool,
+    // restarts of a failed market maker thread before it is abandoned
+    pub max_market_maker_restarts: u32,
+    pub seed: Option<u64>,
+    pub sender: SenderKind,
+    pub jito_url: String,
+    // a share of the transactions is sent through this relayer instead of the sender
+    pub relayer_url: Option<String>,
+    pub relayer_pct: u8,
+    // rpc nodes whose confirmed blocks are compared, empty to disable the cross check
+    pub confirm_rpc_urls: Vec<String>,
+    pub rpc_sightings_file: String,
+    pub book_validation_interval: Option<Duration>,
+    // top levels of the books of the quoted markets written at each interval
+    pub book_snapshot_file: String,
+    pub book_snapshot_interval: Duration,
+    pub book_snapshot_depth: usize,
+    pub record_buffer: usize,
+    pub chaos: ChaosConfig,
+    // group whose markets, banks and oracles are read on-chain instead of from the mango keys
+    pub discover_markets: Option<Pubkey>,
+    // quotes are throttled while the confirmations are further behind the cluster
+    pub max_slot_lag: Option<u64>,
+    // corpus file the transactions are written to instead of being sent
+    pub generate_only: Option<String>,
+    // QUIC client of --sender quic
+    pub bind_address: IpAddr,
+    pub client_node_id: Option<Keypair>,
+    // tpu addresses every transaction is sent to by the QUIC client instead of the leaders
+    pub tpu_addrs: Vec<SocketAddr>,
+    // upcoming leaders the QUIC client connects to ahead of time, 0 to disable
+    pub preconnect_leaders: usize,
+    // artificial delay between signing and sending the transactions
+    pub send_delay: Option<SendDelay>,
+    // transactions dropped, duplicated or delayed before they reach the sender
+    pub network_impairment: Option<NetworkImpairment>,
+    // memo with the run id and a sequence number in every transaction
+    pub memo: bool,
+    // tag of the cluster in the outputs, only set when running against several clusters
+    pub cluster: Option<String>,
+    // written in every row of the outputs, generated when not given
+    pub run_id: Option<String>,
+    pub run_manifest_file: String,
+    // progress of the run, read back with --resume
+    pub run_state_file: String,
+    // continue the run of the run state, appending to its transaction and block outputs
+    pub resume: bool,
+    // overwrite the outputs of a previous run
+    pub force: bool,
+    // part of the accounts quoted by this instance of a fleet
+    pub shard: Option<Shard>,
+    // burst size and pacing of the quotes of each market
+    pub pacing: Pacing,
+    // threads building and signing the quotes ahead of the sends, built inline when not set
+    pub build_threads: Option<usize>,
+    // cores the runtime workers and the quote builders are pinned to
+    pub pin_threads: Option<ThreadPinning>,
 }
 
 impl Default for Config {
@@ -34,22 +207,105 @@ impl Default for Config {
             websocket_url: ConfigInput::default().websocket_url,
             identity: Keypair::new(),
             duration: Duration::new(std::u64::MAX, 0),
+            duration_slots: None,
             quotes_per_second: 1,
             account_keys: String::new(),
             mango_keys: String::new(),
             transaction_save_file: String::new(),
             block_data_save_file: String::new(),
+            partition_output: None,
+            compress: None,
             mango_cluster: "testnet.0".to_string(),
             txs_batch_size: None,
             priority_fees_proba: 0,
             keeper_authority: None,
+            top_up: None,
             number_of_markers_per_mm: 5,
+            market_weights: MarketWeights::Config,
             keeper_prioritization: 1000,
+            crank_spot_markets: false,
+            crank_batch: CrankBatchConfig::default(),
+            spoofing_levels: None,
+            unique_client_order_ids: false,
+            encoded_client_order_ids: false,
+            program_version: ProgramVersion::V3,
+            db_url: None,
+            slot_coverage_file: String::new(),
+            timeseries_file: "timeseries.csv".to_string(),
+            funding_file: String::new(),
+            fills_file: String::new(),
+            funding_interval: DEFAULT_FUNDING_INTERVAL,
+            dry_run: false,
+            control_port: None,
+            control_bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            health_port: None,
+            health_bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            results_stream_port: None,
+            results_stream_bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            warmup_probes: 1,
+            warmup_max_failure_pct: 10,
+            baseline_secs: 0,
+            quote_config: QuoteConfig::default(),
+            estimate_compute_units: false,
+            summary_file: String::new(),
+            thresholds: Thresholds::default(),
+            alerts: vec![],
+            alert_webhook: None,
+            accounts_per_signer: 1,
+            watch_accounts: false,
+            quic_connection_pool_size: DEFAULT_QUIC_CONNECTION_POOL_SIZE,
+            tpu_max_parallel_streams: None,
+            num_quic_endpoints: 1,
+            send_errors_file: String::new(),
+            scenario: None,
+            cancel_storm_at: vec![],
+            periphery: None,
+            oracle_feeder: None,
+            commitment: CommitmentLevel::Finalized,
+            track_all_commitments: false,
+            block_fetch_concurrency: DEFAULT_BLOCK_FETCH_CONCURRENCY,
+            confirmation_source: ConfirmationSource::Blocks,
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+            abort_on_stall: false,
+            max_market_maker_restarts: DEFAULT_MAX_MARKET_MAKER_RESTARTS,
+            seed: None,
+            sender: SenderKind::Tpu,
+            jito_url: DEFAULT_JITO_URL.to_string(),
+            relayer_url: None,
+            relayer_pct: 50,
+            confirm_rpc_urls: vec![],
+            rpc_sightings_file: String::new(),
+            book_validation_interval: None,
+            book_snapshot_file: String::new(),
+            book_snapshot_interval: DEFAULT_BOOK_SNAPSHOT_INTERVAL,
+            book_snapshot_depth: DEFAULT_BOOK_SNAPSHOT_DEPTH,
+            record_buffer: DEFAULT_RECORD_BUFFER,
+            chaos: ChaosConfig::default(),
+            discover_markets: None,
+            max_slot_lag: None,
+            generate_only: None,
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            client_node_id: None,
+            tpu_addrs: vec![],
+            preconnect_leaders: 4,
+            send_delay: None,
+            network_impairment: None,
+            memo: false,
+            cluster: None,
+            run_id: None,
+            run_manifest_file: "run_manifest.json".to_string(),
+            run_state_file: "run_state.json".to_string(),
+            resume: false,
+            force: false,
+            shard: None,
+            pacing: Pacing::default(),
+            build_threads: None,
+            pin_threads: None,
         }
     }
 }
 
-/// Defines and builds the CLI args for a run of the benchmark
+/// Defines and builds the CLI args and subcommands of the benchmark
 pub fn build_args(version: &str) -> App<'_, '_> {
     App::new(crate_name!())
         .about(crate_description!())
@@ -74,11 +330,14 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                 .long("url")
                 .value_name("URL_OR_MONIKER")
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
                 .global(true)
                 .validator(is_url_or_moniker)
                 .help(
                     "URL for Solana's JSON RPC or moniker (or their first letter): \
-                     [mainnet-beta, testnet, devnet, localhost]",
+                     [mainnet-beta, testnet, devnet, localhost], \
+                     the run subcommand sends the same load to every cluster given",
                 ),
         )
         .arg(
@@ -86,10 +345,27 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                 .long("ws")
                 .value_name("URL")
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
                 .global(true)
-                .validator(is_url)
-                .help("WebSocket URL for the solana cluster"),
+                .validator(is_url_list)
+                .help("WebSocket URL for the solana cluster, once per --url when given several clusters. \
+                A comma separated list of urls fails over to the next url when the current one dies"),
         )
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(run_subcommand())
+        .subcommand(analyze_subcommand())
+        .subcommand(compare_subcommand())
+        .subcommand(crank_subcommand())
+        .subcommand(replay_subcommand())
+        .subcommand(validate_config_subcommand())
+        .subcommand(inspect_account_subcommand())
+        .subcommand(create_group_subcommand())
+}
+
+fn run_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("run")
+        .about("Run the simulation against a cluster")
         .arg(
             Arg::with_name("entrypoint")
                 .short("n")
@@ -117,6 +393,15 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                 .takes_value(true)
                 .help("Seconds to run benchmark, then exit; default is forever"),
         )
+        .arg(
+            Arg::with_name("duration-slots")
+                .long("duration-slots")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .required(false)
+                .help("Slots produced by the cluster after which the benchmark stops, so that runs on slow \
+                and fast clusters cover a comparable amount of block space"),
+        )
         .arg(
             Arg::with_name("quotes-per-second")
                 .short("q")
@@ -125,6 +410,42 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                 .takes_value(true)
                 .help("Number of quotes per second"),
         )
+        .arg(
+            Arg::with_name("burst-size")
+                .long("burst-size")
+                .value_name("QUOTES")
+                .takes_value(true)
+                .required(false)
+                .help("Most quotes a market maker sends at once on a market, quotes not sent while a market maker \
+                is inactive accumulate up to this size, defaults to the quotes of a second"),
+        )
+        .arg(
+            Arg::with_name("pacing-interval-ms")
+                .long("pacing-interval-ms")
+                .value_name("MILLIS")
+                .takes_value(true)
+                .required(false)
+                .help("Quotes of a market are released at most this often, defaults to 1000 which sends the quotes \
+                of a second at once, shorter intervals spread them over the second"),
+        )
+        .arg(
+            Arg::with_name("build-threads")
+                .long("build-threads")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Builds and signs the quotes on a pool of this many threads, one tick ahead of the sends"),
+        )
+        .arg(
+            Arg::with_name("pin-threads")
+                .long("pin-threads")
+                .value_name("CORES")
+                .takes_value(true)
+                .min_values(0)
+                .required(false)
+                .help("Pins the runtime threads and the quote builders to the given cores in turn, e.g. 0-7,16-23, \
+                or to every core when no core is given"),
+        )
         .arg(
             Arg::with_name("account-keys")
                 .short("a")
@@ -132,16 +453,18 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                 .value_name("FILENAME")
                 .required(true)
                 .takes_value(true)
-                .help("Read account keys from JSON file generated with mango-client-v3"),
+                .help("Read account keys from JSON file generated with mango-client-v3, \
+                or fetch them from an http(s):// or gs:// uri"),
         )
         .arg(
             Arg::with_name("mango-keys")
                 .short("m")
                 .long("mango")
                 .value_name("FILENAME")
-                .required(true)
+                .required_unless("discover-markets")
                 .takes_value(true)
-                .help("Read mango keys from JSON file generated with mango-client-v3"),
+                .help("Read mango keys from JSON file generated with mango-client-v3, \
+                or fetch them from an http(s):// or gs:// uri"),
         )
         .arg(
             Arg::with_name("transaction-save-file")
@@ -161,6 +484,24 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                 .required(false)
                 .help("To save details of all block containing mm transactions"),
         )
+        .arg(
+            Arg::with_name("partition-output")
+                .long("partition-output")
+                .value_name("PARTITIONING")
+                .takes_value(true)
+                .required(false)
+                .possible_values(&["by-hour", "by-mm"])
+                .help("Splits the transaction and block files in one file per hour or per market maker, listed in <file>.manifest.json"),
+        )
+        .arg(
+            Arg::with_name("compress")
+                .long("compress")
+                .value_name("COMPRESSION")
+                .takes_value(true)
+                .required(false)
+                .possible_values(&["gzip", "zstd"])
+                .help("Compresses the transaction and block files as they are written, .gz or .zst is appended to their names"),
+        )
         .arg(
             Arg::with_name("mango-cluster")
                 .short("c")
@@ -198,6 +539,39 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                     "If specified, authority keypair would be used to pay for keeper transactions",
                 ),
         )
+        .arg(
+            Arg::with_name("top-up-funder")
+                .long("top-up-funder")
+                .value_name("FILEPATH")
+                .takes_value(true)
+                .required(false)
+                .help("If specified, the fee payers of the market makers below the top up threshold are funded \
+                from this keypair during the run"),
+        )
+        .arg(
+            Arg::with_name("top-up-threshold-sol")
+                .long("top-up-threshold-sol")
+                .value_name("SOL")
+                .takes_value(true)
+                .required(false)
+                .help("Balance under which a fee payer is topped up, default 0.5 SOL"),
+        )
+        .arg(
+            Arg::with_name("top-up-amount-sol")
+                .long("top-up-amount-sol")
+                .value_name("SOL")
+                .takes_value(true)
+                .required(false)
+                .help("Amount transferred to a fee payer under the threshold, default 1 SOL"),
+        )
+        .arg(
+            Arg::with_name("top-up-interval")
+                .long("top-up-interval")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .required(false)
+                .help("Interval between two checks of the fee payer balances, default 60s"),
+        )
         .arg(
             Arg::with_name("markets-per-mm")
                 .long("markets-per-mm")
@@ -206,6 +580,16 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                 .required(false)
                 .help("Number of markets a market maker will trade on at a time"),
         )
+        .arg(
+            Arg::with_name("market-weights")
+                .long("market-weights")
+                .value_name("config|open-interest")
+                .takes_value(true)
+                .required(false)
+                .help("Markets are assigned to the market makers in proportion to the weight of each market in \
+                the group config (config, by default uniform), or to their on-chain open interest (open-interest, \
+                mango v3 only)"),
+        )
         .arg(
             Arg::with_name("keeper-prioritization-fees")
                 .long("keeper-prioritization-fees")
@@ -215,105 +599,2095 @@ pub fn build_args(version: &str) -> App<'_, '_> {
                 .required(false)
                 .help("Prioritization fees set for all keeper instructions (1000 by default)")
         )
-}
-
-/// Parses a clap `ArgMatches` structure into a `Config`
-/// # Arguments
-/// * `matches` - command line arguments parsed by clap
-/// # Panics
-/// Panics if there is trouble parsing any of the arguments
-pub fn extract_args(matches: &ArgMatches) -> Config {
-    let mut args = Config::default();
-
-    let config = if let Some(config_file) = matches.value_of("config-file") {
-        solana_cli_config::Config::load(config_file).unwrap_or_default()
-    } else {
-        solana_cli_config::Config::default()
-    };
-    let (_, json_rpc_url) = ConfigInput::compute_json_rpc_url_setting(
-        matches.value_of("json-rpc-url").unwrap_or(""),
-        &config.json_rpc_url,
-    );
-    args.json_rpc_url = json_rpc_url;
-
-    let (_, websocket_url) = ConfigInput::compute_websocket_url_setting(
-        matches.value_of("websocket-url").unwrap_or(""),
-        &config.websocket_url,
-        matches.value_of("json-rpc-url").unwrap_or(""),
-        &config.json_rpc_url,
-    );
-    args.websocket_url = websocket_url;
-
-    let (_, id_path) = ConfigInput::compute_keypair_path_setting(
-        matches.value_of("identity").unwrap_or(""),
-        &config.keypair_path,
-    );
-    if let Ok(id) = read_keypair_file(id_path) {
-        args.identity = id;
-    } else if matches.is_present("identity") {
-        panic!("could not parse identity path");
-    }
-
-    if let Some(addr) = matches.value_of("entrypoint") {
-        args.entrypoint_addr = solana_net_utils::parse_host_port(addr).unwrap_or_else(|e| {
-            eprintln!("failed to parse entrypoint address: {}", e);
-            exit(1)
-        });
-    }
-
-    if let Some(duration) = matches.value_of("duration") {
-        args.duration = Duration::new(
-            duration.to_string().parse().expect("can't parse duration"),
-            0,
-        );
-    }
-
-    if let Some(qps) = matches.value_of("quotes-per-second") {
-        args.quotes_per_second = qps.parse().expect("can't parse quotes-per-second");
-    }
-
-    args.account_keys = matches.value_of("account-keys").unwrap().to_string();
-    args.mango_keys = matches.value_of("mango-keys").unwrap().to_string();
-    args.transaction_save_file = match matches.value_of("transaction-save-file") {
-        Some(x) => x.to_string(),
-        None => String::new(),
-    };
-    args.block_data_save_file = match matches.value_of("block-data-save-file") {
-        Some(x) => x.to_string(),
-        None => String::new(),
-    };
-
-    args.mango_cluster = match matches.value_of("mango-cluster") {
-        Some(x) => x.to_string(),
-        None => "testnet.0".to_string(),
-    };
-    args.txs_batch_size = matches
-        .value_of("batch-size")
-        .map(|batch_size_str| batch_size_str.parse().expect("can't parse batch-size"));
-
-    args.priority_fees_proba = match matches.value_of("prioritization-fees") {
-        Some(x) => x
-            .parse()
-            .expect("Percentage of transactions having prioritization fees"),
-        None => 0,
-    };
-    let (_, kp_auth_path) = ConfigInput::compute_keypair_path_setting(
-        matches.value_of("keeper-authority").unwrap_or(""),
-        &config.keypair_path,
-    );
-
-    args.keeper_authority = read_keypair_file(kp_auth_path).ok();
-
-    args.number_of_markers_per_mm = match matches.value_of("markets-per-mm") {
-        Some(x) => x
-            .parse()
-            .expect("can't parse number of markets per market maker"),
-        None => 5,
-    };
-
-    args.keeper_prioritization = match matches.value_of("keeper-prioritization-fees") {
-        Some(x) => x.parse().expect("can't parse keeper prioritization fees"),
-        None => 1000,
+        .arg(
+            Arg::with_name("crank-spot-markets")
+                .long("crank-spot-markets")
+                .takes_value(false)
+                .required(false)
+                .help("Also consume the events of the serum spot markets of the group, so that spot fills settle"),
+        )
+        .args(&crank_batch_args())
+        .arg(
+            Arg::with_name("spoofing-levels")
+                .long("spoofing-levels")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("If specified, market makers run the adversarial spoofing preset: tiny orders \
+                are placed on UINT price levels per side and cancelled in the same transaction (max 6)"),
+        )
+        .arg(
+            Arg::with_name("unique-client-order-ids")
+                .long("unique-client-order-ids")
+                .takes_value(false)
+                .required(false)
+                .help("Guarantee that two quotes of a market maker never share a client order id, so that they \
+                never build identical transactions rejected as duplicates, and count the duplicates prevented"),
+        )
+        .arg(
+            Arg::with_name("encoded-client-order-ids")
+                .long("encoded-client-order-ids")
+                .takes_value(false)
+                .required(false)
+                .help("Encode the market maker, the market, the quote number and the side of every order in its \
+                client order id, so that the fills and the orders on the books can be traced back to their quote"),
+        )
+        .arg(
+            Arg::with_name("program-version")
+                .long("program-version")
+                .value_name("VERSION")
+                .takes_value(true)
+                .possible_values(&["v3", "v4"])
+                .default_value("v3")
+                .help("Version of the mango program deployed for the group, runs are only supported for v3 until the \
+                v4 perp markets are loaded"),
+        )
+        .arg(
+            Arg::with_name("db-url")
+                .long("db-url")
+                .value_name("URL")
+                .takes_value(true)
+                .required(false)
+                .help("If specified, transaction and block records are streamed to this database while the \
+                run is in progress, sqlite://<path> or postgres://<user>:<password>@<host>/<db>"),
+        )
+        .arg(
+            Arg::with_name("slot-coverage-file")
+                .long("slot-coverage-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save, for every slot of the run, if transactions were sent during it and if any landed in it \
+                (run length encoded csv)"),
+        )
+        .arg(
+            Arg::with_name("timeseries-file")
+                .long("timeseries-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save the sent, confirmed, errored and timed out transactions of every second of the run, \
+                defaults to timeseries.csv, empty to disable"),
+        )
+        .arg(
+            Arg::with_name("funding-file")
+                .long("funding-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save the long and short funding of the perp markets and the age of their last update, \
+                sampled during the run, mango v3 only"),
+        )
+        .arg(
+            Arg::with_name("fills-file")
+                .long("fills-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save the fills of the market makers read from the event queues, with the quotes \
+                of their orders decoded with --encoded-client-order-ids, mango v3 only"),
+        )
+        .arg(
+            Arg::with_name("funding-interval")
+                .long("funding-interval")
+                .value_name("SECS")
+                .takes_value(true)
+                .required(false)
+                .help("Interval between two samples of the funding file [default: 10]"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .takes_value(false)
+                .required(false)
+                .help("Build and simulate every keeper and crank instruction of the group once, \
+                report success and CU consumed for each of them and exit without sending anything"),
+        )
+        .arg(
+            Arg::with_name("control-port")
+                .long("control-port")
+                .value_name("PORT")
+                .takes_value(true)
+                .required(false)
+                .help("If specified, starts an http control server on this port to follow and steer the run, \
+                see the documentation of start_control_server for the endpoints"),
+        )
+        .arg(
+            Arg::with_name("control-bind")
+                .long("control-bind")
+                .value_name("IP")
+                .takes_value(true)
+                .required(false)
+                .help("Address the control server listens on, defaults to 127.0.0.1. The control server has no \
+                authentication, anyone who can reach it can steer the run"),
+        )
+        .arg(
+            Arg::with_name("health-port")
+                .long("health-port")
+                .value_name("PORT")
+                .takes_value(true)
+                .required(false)
+                .help("If specified, starts an http server on this port answering GET /healthz and GET /ready, \
+                for the liveness and readiness probes of container orchestrators"),
+        )
+        .arg(
+            Arg::with_name("health-bind")
+                .long("health-bind")
+                .value_name("IP")
+                .takes_value(true)
+                .required(false)
+                .help("Address the health server listens on, defaults to 127.0.0.1. Probes from outside \
+                the host or the container need 0.0.0.0"),
+        )
+        .arg(
+            Arg::with_name("results-stream-port")
+                .long("results-stream-port")
+                .value_name("PORT")
+                .takes_value(true)
+                .required(false)
+                .help("If specified, starts a websocket server on this port publishing the confirmation records \
+                as they are produced and their counts every second, for live dashboards"),
+        )
+        .arg(
+            Arg::with_name("results-stream-bind")
+                .long("results-stream-bind")
+                .value_name("IP")
+                .takes_value(true)
+                .required(false)
+                .help("Address the results stream listens on, defaults to 127.0.0.1"),
+        )
+        .arg(
+            Arg::with_name("warmup-probes")
+                .long("warmup-probes")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Number of probe quotes sent and confirmed for each market maker before the run \
+                to verify that its mango account can place orders, 0 disables the probes [default: 1]"),
+        )
+        .arg(
+            Arg::with_name("warmup-max-failure")
+                .long("warmup-max-failure")
+                .value_name("PERCENT")
+                .validator(is_valid_percentage)
+                .takes_value(true)
+                .required(false)
+                .help("Abort the run if more than this percentage of the warmup probes fail [default: 10]"),
+        )
+        .arg(
+            Arg::with_name("baseline-secs")
+                .long("baseline-secs")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .required(false)
+                .help("Observes the blocks of the idle cluster for this many seconds before sending anything, \
+                they are written to the block file with the baseline phase"),
+        )
+        .arg(
+            Arg::with_name("order-size-lots")
+                .long("order-size-lots")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Size of market maker orders in base lots, defaults to one unit of the base token. \
+                Can be overridden per market with orderSizeLots in the mango group config"),
+        )
+        .arg(
+            Arg::with_name("size-jitter-pct")
+                .long("size-jitter-pct")
+                .value_name("PERCENT")
+                .validator(is_valid_percentage)
+                .takes_value(true)
+                .required(false)
+                .help("Randomize each order size by up to this percentage [default: 0]. \
+                Can be overridden per market with sizeJitterPct in the mango group config"),
+        )
+        .arg(
+            Arg::with_name("max-spread-bps")
+                .long("max-spread-bps")
+                .value_name("BPS")
+                .takes_value(true)
+                .required(false)
+                .help("Maximum spread between the bid and the ask, the spread is random below it [default: 1000]. \
+                Can be overridden per market with maxSpreadBps in the mango group config"),
+        )
+        .arg(
+            Arg::with_name("max-price-offset-lots")
+                .long("max-price-offset-lots")
+                .value_name("LOTS")
+                .takes_value(true)
+                .required(false)
+                .help("Both quotes are moved by a random offset below this many price lots, 0 quotes around the \
+                quote price only [default: 20]. Can be overridden per market with maxPriceOffsetLots"),
+        )
+        .arg(
+            Arg::with_name("inventory-skew-bps")
+                .long("inventory-skew-bps")
+                .value_name("BPS")
+                .takes_value(true)
+                .required(false)
+                .help("Tracks the fills of the market makers and moves both quotes against their inventory by this \
+                much of the price per order size held (mango v3 only). Can be overridden per market with inventorySkewBps"),
+        )
+        .arg(
+            Arg::with_name("order-expiry-secs")
+                .long("order-expiry-secs")
+                .value_name("SECS")
+                .takes_value(true)
+                .required(false)
+                .help("Quotes expire this many seconds after they are built instead of resting until cancelled. \
+                Can be overridden per market with orderExpirySecs"),
+        )
+        .arg(
+            Arg::with_name("order-expiry-type")
+                .long("order-expiry-type")
+                .value_name("TYPE")
+                .takes_value(true)
+                .possible_values(&["absolute", "relative"])
+                .required(false)
+                .help("Sends the expiry of the quotes as an absolute timestamp or as seconds relative to the \
+                on-chain clock, defaults to absolute"),
+        )
+        .arg(
+            Arg::with_name("order-types")
+                .long("order-types")
+                .value_name("TYPE=WEIGHT,...")
+                .takes_value(true)
+                .required(false)
+                .help("Relative weights of the order types of the quotes, among limit, post-only, post-only-slide, \
+                ioc and market, e.g. limit=70,post-only=20,ioc=10. Only limit orders when not set. \
+                Can be overridden per market with orderTypes"),
+        )
+        .arg(
+            Arg::with_name("reduce-only-pct")
+                .long("reduce-only-pct")
+                .value_name("PERCENT")
+                .validator(is_valid_percentage)
+                .takes_value(true)
+                .required(false)
+                .help("Percentage of the quotes sent reduce only, defaults to 0. \
+                Can be overridden per market with reduceOnlyPct"),
+        )
+        .arg(
+            Arg::with_name("order-limit")
+                .long("order-limit")
+                .value_name("N")
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of orders a quote matches or removes when expired, defaults to 64. \
+                Can be overridden per market with orderLimit"),
+        )
+        .arg(
+            Arg::with_name("max-quote-lots")
+                .long("max-quote-lots")
+                .value_name("LOTS")
+                .takes_value(true)
+                .required(false)
+                .help("Maximum quote quantity of each order in quote lots, unlimited by default"),
+        )
+        .arg(
+            Arg::with_name("compute-unit-limit")
+                .long("compute-unit-limit")
+                .value_name("UNITS|auto")
+                .takes_value(true)
+                .required(false)
+                .help("Compute units requested by each quote, auto simulates the quote of every market once to \
+                request what it consumes. The runtime default of 200k units per instruction when not set. \
+                Can be overridden per market with computeUnitLimit"),
+        )
+        .arg(
+            Arg::with_name("summary-file")
+                .long("summary-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save the summary of the run with its parameters as json, not saved by default"),
+        )
+        .arg(
+            Arg::with_name("min-confirmation-rate")
+                .long("min-confirmation-rate")
+                .value_name("PERCENT")
+                .takes_value(true)
+                .required(false)
+                .help("Exit with an error if less than this percentage of the sent transactions were confirmed"),
+        )
+        .arg(
+            Arg::with_name("max-p95-latency-ms")
+                .long("max-p95-latency-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .required(false)
+                .help("Exit with an error if the p95 confirmation latency is above this value"),
+        )
+        .arg(
+            Arg::with_name("alert")
+                .long("alert")
+                .value_name("RULE")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false)
+                .help("Alert during the run when a metric over the last window breaches a threshold, e.g. \
+                confirmation_rate<80:60s or p95_latency_ms>10000:60s. Can be given several times"),
+        )
+        .arg(
+            Arg::with_name("alert-webhook")
+                .long("alert-webhook")
+                .value_name("URL")
+                .takes_value(true)
+                .required(false)
+                .validator(is_url)
+                .help("Url which receives a json POST when an alert starts firing and when it is resolved"),
+        )
+        .arg(
+            Arg::with_name("accounts-per-signer")
+                .long("accounts-per-signer")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of mango accounts of each keypair quoting in parallel, \
+                every account quotes on its own markets [default: 1]"),
+        )
+        .arg(
+            Arg::with_name("watch-accounts")
+                .long("watch-accounts")
+                .takes_value(false)
+                .required(false)
+                .help("Reload the accounts file when it changes during the run, market makers are started \
+                for the added mango accounts and retired for the removed ones"),
+        )
+        .arg(
+            Arg::with_name("quic-connection-pool-size")
+                .long("quic-connection-pool-size")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Number of QUIC connections opened to each leader [default: 10]"),
+        )
+        .arg(
+            Arg::with_name("tpu-max-parallel-streams")
+                .long("tpu-max-parallel-streams")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Maximum number of transactions sent in parallel, with --sender quic each of them \
+                is sent on its own QUIC stream, unbounded by default"),
+        )
+        .arg(
+            Arg::with_name("num-quic-endpoints")
+                .long("num-quic-endpoints")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Number of TPU services, each with its own QUIC endpoint bound to a distinct local port, \
+                transactions are sent to them in round robin [default: 1]"),
+        )
+        .arg(
+            Arg::with_name("send-errors-file")
+                .long("send-errors-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save the transactions which could not be sent to the TPU with their error"),
+        )
+        .arg(
+            Arg::with_name("scenario")
+                .long("scenario")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("Json file describing phases changing the quotes per second, priority fees and active market makers during the run"),
+        )
+        .arg(
+            Arg::with_name("cancel-storm-at")
+                .long("cancel-storm-at")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false)
+                .help("Offset from the start of market making, e.g. 120s, at which every market maker sends \
+                a cancel-all transaction for each of its markets at the same time. Can be given several times"),
+        )
+        .arg(
+            Arg::with_name("periphery-mix")
+                .long("periphery-mix")
+                .value_name("INSTRUCTION=WEIGHT,...")
+                .takes_value(true)
+                .required(false)
+                .help("Relative weights of the redeem-mngo, deposit and withdraw instructions sent by random market makers \
+                besides their orders, e.g. redeem-mngo=1,deposit=2,withdraw=2 (mango v3 only)"),
+        )
+        .arg(
+            Arg::with_name("periphery-interval-ms")
+                .long("periphery-interval-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .required(false)
+                .requires("periphery-mix")
+                .help("Interval between two periphery instructions, defaults to 1000"),
+        )
+        .arg(
+            Arg::with_name("periphery-quantity")
+                .long("periphery-quantity")
+                .value_name("NATIVE")
+                .takes_value(true)
+                .required(false)
+                .requires("periphery-mix")
+                .help("Native units of the quote token of each deposit and withdraw, defaults to 1000"),
+        )
+        .arg(
+            Arg::with_name("feed-oracles")
+                .long("feed-oracles")
+                .takes_value(false)
+                .required(false)
+                .help("Push a random walk price to the stub oracle of every perp market, for localnet groups without pyth prices, the keeper authority (or identity) has to be the group admin"),
+        )
+        .arg(
+            Arg::with_name("oracle-start-price")
+                .long("oracle-start-price")
+                .value_name("PRICE")
+                .takes_value(true)
+                .required(false)
+                .help("Price the oracle random walk starts from, default 100"),
+        )
+        .arg(
+            Arg::with_name("oracle-volatility-bps")
+                .long("oracle-volatility-bps")
+                .value_name("BPS")
+                .takes_value(true)
+                .required(false)
+                .help("Maximum move of the oracle price at each update in basis points, default 20"),
+        )
+        .arg(
+            Arg::with_name("oracle-interval-ms")
+                .long("oracle-interval-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .required(false)
+                .help("Interval between two oracle price updates, default 1000ms"),
+        )
+        .arg(
+            Arg::with_name("commitment")
+                .long("commitment")
+                .value_name("COMMITMENT")
+                .takes_value(true)
+                .possible_values(&["processed", "confirmed", "finalized"])
+                .default_value("finalized")
+                .help("Commitment level at which transactions are considered confirmed, processed transactions are only seen once confirmed"),
+        )
+        .arg(
+            Arg::with_name("track-all-commitments")
+                .long("track-all-commitments")
+                .takes_value(false)
+                .required(false)
+                .help("Wait for transactions to be finalized and record the time each commitment level was reached"),
+        )
+        .arg(
+            Arg::with_name("block-fetch-concurrency")
+                .long("block-fetch-concurrency")
+                .value_name("BLOCKS")
+                .takes_value(true)
+                .required(false)
+                .help("Number of blocks fetched in parallel when the transactions are confirmed by polling the blocks \
+                of the RPC node, defaults to 16"),
+        )
+        .arg(
+            Arg::with_name("confirmation-source")
+                .long("confirmation-source")
+                .value_name("SOURCE")
+                .takes_value(true)
+                .possible_values(&["blocks", "signature-statuses"])
+                .default_value("blocks")
+                .help("How the transactions which are not notified by lite-rpc are confirmed, by polling the blocks \
+                of the RPC node or the statuses of the pending signatures with getSignatureStatuses"),
+        )
+        .arg(
+            Arg::with_name("stall-timeout")
+                .long("stall-timeout")
+                .value_name("SECS")
+                .takes_value(true)
+                .required(false)
+                .help("Seconds without heartbeat after which a thread is reported as stalled, default 60"),
+        )
+        .arg(
+            Arg::with_name("abort-on-stall")
+                .long("abort-on-stall")
+                .takes_value(false)
+                .required(false)
+                .help("Abort the run when a thread stalls instead of only reporting it"),
+        )
+        .arg(
+            Arg::with_name("max-market-maker-restarts")
+                .long("max-market-maker-restarts")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Restarts of a market maker thread which panics or keeps failing to send before it is \
+                abandoned, default 3"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Seed of the random generators of the market makers, two runs with the same seed send the same order flow"),
+        )
+        .arg(
+            Arg::with_name("sender")
+                .long("sender")
+                .value_name("SENDER")
+                .takes_value(true)
+                .possible_values(&["tpu", "rpc", "jito", "quic", "mock"])
+                .default_value("tpu")
+                .help("How transactions are sent, transactions sent through an rpc, jito or the quic client of solana are confirmed by polling blocks, \
+                mock keeps them in memory to measure the sending path without a cluster"),
+        )
+        .arg(
+            Arg::with_name("jito-url")
+                .long("jito-url")
+                .value_name("URL")
+                .takes_value(true)
+                .required(false)
+                .validator(is_url)
+                .help("Block engine used with --sender jito"),
+        )
+        .arg(
+            Arg::with_name("relayer-url")
+                .long("relayer-url")
+                .value_name("URL")
+                .takes_value(true)
+                .required(false)
+                .validator(is_http_url)
+                .help("Http url of a relayer whose json rpc sendTransaction method receives a share of the \
+                transactions instead of the sender"),
+        )
+        .arg(
+            Arg::with_name("relayer-pct")
+                .long("relayer-pct")
+                .value_name("PERCENT")
+                .takes_value(true)
+                .required(false)
+                .requires("relayer-url")
+                .help("Percentage of the transactions sent through --relayer-url, 50 by default"),
+        )
+        .arg(
+            Arg::with_name("confirm-rpc-urls")
+                .long("confirm-rpc-urls")
+                .value_name("URL,URL,...")
+                .takes_value(true)
+                .required(false)
+                .help("Comma separated rpc nodes whose confirmed blocks are polled in parallel to record when each of them \
+                first returned the sent transactions, the skew between the nodes is logged at the end of the run"),
+        )
+        .arg(
+            Arg::with_name("rpc-sightings-file")
+                .long("rpc-sightings-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save the time at which each node of --confirm-rpc-urls first returned each transaction"),
+        )
+        .arg(
+            Arg::with_name("validate-book-interval")
+                .long("validate-book-interval")
+                .value_name("SECS")
+                .takes_value(true)
+                .required(false)
+                .help("If specified, snapshots the books of the quoted markets at this interval and reports how many \
+                confirmed quotes actually rest on them, mango v3 only"),
+        )
+        .arg(
+            Arg::with_name("book-snapshot-file")
+                .long("book-snapshot-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save the top levels of the books of the quoted markets at each --book-snapshot-interval, mango v3 only"),
+        )
+        .arg(
+            Arg::with_name("book-snapshot-interval")
+                .long("book-snapshot-interval")
+                .value_name("SECS")
+                .takes_value(true)
+                .required(false)
+                .help("Interval between two book snapshots (10 by default)"),
+        )
+        .arg(
+            Arg::with_name("book-snapshot-depth")
+                .long("book-snapshot-depth")
+                .value_name("LEVELS")
+                .takes_value(true)
+                .required(false)
+                .help("Price levels of each side of the books written at each snapshot (10 by default)"),
+        )
+        .arg(
+            Arg::with_name("record-buffer")
+                .long("record-buffer")
+                .value_name("RECORDS")
+                .takes_value(true)
+                .required(false)
+                .help("Number of confirmation and block records buffered for the writers and aggregators, \
+                a consumer falling further behind loses records, counted in the summary, defaults to 1000000"),
+        )
+        .arg(
+            Arg::with_name("chaos")
+                .long("chaos")
+                .value_name("KIND=PCT,...")
+                .takes_value(true)
+                .required(false)
+                .help("Turns a percentage of the market maker transactions into failing ones, kinds are wrong-owner, \
+                oversized, expired-blockhash and duplicate, e.g. wrong-owner=5,duplicate=2. \
+                Chaos transactions are tagged in the records and excluded from the success metrics"),
+        )
+        .arg(
+            Arg::with_name("discover-markets")
+                .long("discover-markets")
+                .value_name("GROUP_PUBKEY")
+                .takes_value(true)
+                .required(false)
+                .help("Reads the perp markets, root banks and oracles from the on-chain mango group account \
+                instead of the mango keys file (mango v3 only)"),
+        )
+        .arg(
+            Arg::with_name("max-slot-lag")
+                .long("max-slot-lag")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .required(false)
+                .help("Backs off quoting while the latest block seen by the confirmations is more than \
+                this many slots behind the current slot, the rate recovers once the lag is back under half of it"),
+        )
+        .arg(
+            Arg::with_name("generate-only")
+                .long("generate-only")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("Builds and signs the transactions but writes them with their timing to this corpus file \
+                instead of sending them, the corpus is sent with the replay subcommand"),
+        )
+        .arg(
+            Arg::with_name("bind-address")
+                .long("bind-address")
+                .value_name("IP")
+                .takes_value(true)
+                .required(false)
+                .help("Address the QUIC client binds to with --sender quic, defaults to 0.0.0.0"),
+        )
+        .arg(
+            Arg::with_name("client-node-id")
+                .long("client-node-id")
+                .value_name("FILEPATH")
+                .takes_value(true)
+                .required(false)
+                .help("Identity of a staked node the QUIC client registers with when sending with --sender quic, \
+                its connections then get the stake of the node, they are unstaked without it"),
+        )
+        .arg(
+            Arg::with_name("tpu-addr")
+                .long("tpu-addr")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false)
+                .help("QUIC tpu address every transaction is sent to with --sender quic instead of the \
+                upcoming leaders, for a single validator or a tpu proxy. Can be given several times"),
+        )
+        .arg(
+            Arg::with_name("preconnect-leaders")
+                .long("preconnect-leaders")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Number of upcoming leaders the QUIC client of --sender quic connects to ahead of time, \
+                defaults to 4, 0 to disable"),
+        )
+        .arg(
+            Arg::with_name("send-delay-ms-distribution")
+                .long("send-delay-ms-distribution")
+                .value_name("DISTRIBUTION")
+                .takes_value(true)
+                .required(false)
+                .help("Artificial delay in milliseconds between signing and sending each transaction, \
+                constant:<ms>, uniform:<min>-<max> or exponential:<mean>"),
+        )
+        .arg(
+            Arg::with_name("network-impairment")
+                .long("network-impairment")
+                .value_name("IMPAIRMENTS")
+                .takes_value(true)
+                .required(false)
+                .help("Percentages of the transactions dropped, duplicated or delayed before they reach the \
+                sender, e.g. drop=5,duplicate=2,delay=10,delay-ms=500"),
+        )
+        .arg(
+            Arg::with_name("memo")
+                .long("memo")
+                .takes_value(false)
+                .required(false)
+                .help("Add a memo with the run id and a sequence number to every transaction, so that the \
+                transactions of the run can be told apart in the blocks"),
+        )
+        .arg(
+            Arg::with_name("run-id")
+                .long("run-id")
+                .value_name("ID")
+                .takes_value(true)
+                .required(false)
+                .help("Identifier written in every row of the csv outputs and in the run manifest, \
+                generated from the start time when not given"),
+        )
+        .arg(
+            Arg::with_name("run-manifest-file")
+                .long("run-manifest-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("File the metadata of the run is written to, defaults to run_manifest.json"),
+        )
+        .arg(
+            Arg::with_name("run-state-file")
+                .long("run-state-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("File the progress of the run is written to every few seconds, read back \
+                by --resume, defaults to run_state.json"),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .takes_value(false)
+                .required(false)
+                .conflicts_with("force")
+                .help("Continue the crashed run of the run state file for its remaining duration, \
+                with the same run id, appending to its transaction and block outputs"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .takes_value(false)
+                .required(false)
+                .help("Overwrite the outputs of a previous run, the run refuses to start when \
+                one of its csv outputs already exists otherwise"),
+        )
+        .arg(
+            Arg::with_name("shard")
+                .long("shard")
+                .value_name("I/N")
+                .takes_value(true)
+                .required(false)
+                .help("Quotes only the accounts of shard i out of n, starting at 0, so that the same accounts file \
+                can be shared by a fleet of benchers. Accounts are assigned in round robin by their position in the file"),
+        )
+}
+
+fn analyze_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("analyze")
+        .about("Compute summary statistics from the csv outputs of a previous run")
+        .arg(
+            Arg::with_name("transaction-save-file")
+                .short("t")
+                .long("transaction-save-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(true)
+                .help("Transaction csv file written by a previous run"),
+        )
+        .arg(
+            Arg::with_name("block-data-save-file")
+                .short("b")
+                .long("block-data-save-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("Block data csv file written by a previous run"),
+        )
+}
+
+fn compare_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("compare")
+        .about("Compare a run against a baseline from their transaction csv or summary json files")
+        .arg(
+            Arg::with_name("baseline")
+                .value_name("BASELINE")
+                .index(1)
+                .required(true)
+                .help("Transaction csv or summary json file of the reference run"),
+        )
+        .arg(
+            Arg::with_name("candidate")
+                .value_name("CANDIDATE")
+                .index(2)
+                .required(true)
+                .help("Transaction csv or summary json file of the run to validate"),
+        )
+        .arg(
+            Arg::with_name("max-confirmation-rate-drop")
+                .long("max-confirmation-rate-drop")
+                .value_name("POINTS")
+                .takes_value(true)
+                .required(false)
+                .help("Significant drop of the confirmation rate, in percentage points, tolerated before \
+                reporting a regression, defaults to 1"),
+        )
+        .arg(
+            Arg::with_name("max-latency-increase")
+                .long("max-latency-increase")
+                .value_name("PCT")
+                .takes_value(true)
+                .required(false)
+                .help("Significant increase of a latency percentile, in percent, tolerated before \
+                reporting a regression, defaults to 10"),
+        )
+}
+
+fn crank_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("crank")
+        .about(
+            "Run the mango v3 perp crank continuously against a group, without any market making",
+        )
+        .arg(
+            Arg::with_name("identity")
+                .short("i")
+                .long("identity")
+                .value_name("FILEPATH")
+                .takes_value(true)
+                .help("Identity used in the QUIC connection"),
+        )
+        .arg(
+            Arg::with_name("keeper-authority")
+                .long("keeper-authority")
+                .short("ka")
+                .value_name("FILEPATH")
+                .takes_value(true)
+                .required(false)
+                .help("Keypair paying for the crank transactions, defaults to the identity"),
+        )
+        .arg(
+            Arg::with_name("mango-keys")
+                .short("m")
+                .long("mango")
+                .value_name("FILENAME")
+                .required(true)
+                .takes_value(true)
+                .help("Read mango keys from JSON file generated with mango-client-v3"),
+        )
+        .arg(
+            Arg::with_name("mango-cluster")
+                .short("c")
+                .long("mango-cluster")
+                .value_name("STR")
+                .takes_value(true)
+                .help("Name of mango cluster from ids.json"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .short("d")
+                .long("duration")
+                .value_name("SECS")
+                .takes_value(true)
+                .help("Seconds to run the crank, then exit; default is until interrupted"),
+        )
+        .arg(
+            Arg::with_name("keeper-prioritization-fees")
+                .long("keeper-prioritization-fees")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Prioritization fees set for all crank transactions (1000 by default)"),
+        )
+        .arg(
+            Arg::with_name("crank-spot-markets")
+                .long("crank-spot-markets")
+                .takes_value(false)
+                .required(false)
+                .help("Also consume the events of the serum spot markets of the group, so that spot fills settle"),
+        )
+        .args(&crank_batch_args())
+        .arg(
+            Arg::with_name("transaction-save-file")
+                .long("transaction-save-file")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("To save details of all crank transactions"),
+        )
+}
+
+fn replay_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("replay")
+        .about("Send a corpus of transactions generated with --generate-only, to measure the raw sending throughput")
+        .arg(
+            Arg::with_name("corpus")
+                .value_name("FILENAME")
+                .index(1)
+                .required(true)
+                .help("Corpus file written by a run with --generate-only"),
+        )
+        .arg(
+            Arg::with_name("identity")
+                .short("i")
+                .long("identity")
+                .value_name("FILEPATH")
+                .takes_value(true)
+                .help("Identity used in the QUIC connection"),
+        )
+        .arg(
+            Arg::with_name("sender")
+                .long("sender")
+                .value_name("SENDER")
+                .takes_value(true)
+                .possible_values(&["tpu", "rpc", "jito", "quic", "mock"])
+                .default_value("tpu")
+                .help("How transactions are sent"),
+        )
+        .arg(
+            Arg::with_name("jito-url")
+                .long("jito-url")
+                .value_name("URL")
+                .takes_value(true)
+                .required(false)
+                .help("Block engine the transactions are sent to with --sender jito"),
+        )
+        .arg(
+            Arg::with_name("bind-address")
+                .long("bind-address")
+                .value_name("IP")
+                .takes_value(true)
+                .required(false)
+                .help("Address the QUIC client binds to with --sender quic, defaults to 0.0.0.0"),
+        )
+        .arg(
+            Arg::with_name("client-node-id")
+                .long("client-node-id")
+                .value_name("FILEPATH")
+                .takes_value(true)
+                .required(false)
+                .help("Identity of a staked node the QUIC client registers with when sending with --sender quic, \
+                its connections then get the stake of the node, they are unstaked without it"),
+        )
+        .arg(
+            Arg::with_name("tpu-addr")
+                .long("tpu-addr")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(false)
+                .help("QUIC tpu address every transaction is sent to with --sender quic instead of the \
+                upcoming leaders, for a single validator or a tpu proxy. Can be given several times"),
+        )
+        .arg(
+            Arg::with_name("speed")
+                .long("speed")
+                .value_name("FACTOR")
+                .takes_value(true)
+                .required(false)
+                .help("Replays the corpus this many times faster than it was generated, \
+                0 sends as fast as possible, defaults to 1"),
+        )
+}
+
+// how the crank splits the events of the perp markets in transactions, for the run and the crank
+fn crank_batch_args() -> [Arg<'static, 'static>; 4] {
+    [
+        Arg::with_name("crank-events-per-tx")
+            .long("crank-events-per-tx")
+            .value_name("UINT")
+            .takes_value(true)
+            .required(false)
+            .help("Events consumed by each crank transaction, the limit of its consume events instruction (50 by default)"),
+        Arg::with_name("crank-accounts-per-tx")
+            .long("crank-accounts-per-tx")
+            .value_name("UINT")
+            .takes_value(true)
+            .required(false)
+            .help("Mango accounts passed to each crank transaction (24 by default)"),
+        Arg::with_name("crank-cu-per-event")
+            .long("crank-cu-per-event")
+            .value_name("UINT")
+            .takes_value(true)
+            .required(false)
+            .help("Compute units requested for each event of a crank transaction, on top of 40000 for the instruction (25000 by default)"),
+    ]
+}
+
+pub fn extract_crank_batch(matches: &ArgMatches) -> CrankBatchConfig {
+    let mut batch = CrankBatchConfig::default();
+    if let Some(x) = matches.value_of("crank-events-per-tx") {
+        batch.events_per_tx = x.parse().expect("can't parse crank events per tx");
+    }
+    if let Some(x) = matches.value_of("crank-accounts-per-tx") {
+        batch.accounts_per_tx = x.parse().expect("can't parse crank accounts per tx");
+    }
+    if let Some(x) = matches.value_of("crank-cu-per-event") {
+        batch.compute_units_per_event = x.parse().expect("can't parse crank cu per event");
+    }
+    batch
+}
+
+fn validate_config_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("validate-config")
+        .about("Check the mango keys and accounts files against the cluster before a run (mango v3 only)")
+        .arg(
+            Arg::with_name("mango-keys")
+                .short("m")
+                .long("mango")
+                .value_name("FILENAME")
+                .required(true)
+                .takes_value(true)
+                .help("Mango keys JSON file generated with mango-client-v3"),
+        )
+        .arg(
+            Arg::with_name("mango-cluster")
+                .short("c")
+                .long("mango-cluster")
+                .value_name("STR")
+                .takes_value(true)
+                .help("Name of mango cluster from ids.json"),
+        )
+        .arg(
+            Arg::with_name("account-keys")
+                .short("a")
+                .long("accounts")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("Accounts JSON file generated with mango-client-v3, its mango accounts are checked as well"),
+        )
+}
+
+fn inspect_account_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("inspect-account")
+        .about("Print the deposits, perp positions, open orders and health of a mango account (mango v3 only)")
+        .arg(
+            Arg::with_name("mango-account")
+                .value_name("MANGO_ACCOUNT")
+                .required(true)
+                .takes_value(true)
+                .help("Mango account to inspect"),
+        )
+        .arg(
+            Arg::with_name("mango-keys")
+                .short("m")
+                .long("mango")
+                .value_name("FILENAME")
+                .required(true)
+                .takes_value(true)
+                .help("Mango keys JSON file generated with mango-client-v3"),
+        )
+        .arg(
+            Arg::with_name("mango-cluster")
+                .short("c")
+                .long("mango-cluster")
+                .value_name("STR")
+                .takes_value(true)
+                .help("Name of mango cluster from ids.json"),
+        )
+}
+
+fn create_group_subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("create-group")
+        .about("Create a mango v3 group with perp markets on a private cluster and write its mango keys file")
+        .arg(
+            Arg::with_name("identity")
+                .short("i")
+                .long("identity")
+                .value_name("FILEPATH")
+                .takes_value(true)
+                .help("Admin of the group, pays for all the accounts and is the mint authority of the tokens"),
+        )
+        .arg(
+            Arg::with_name("mango-program")
+                .long("mango-program")
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .required(true)
+                .help("Mango v3 program deployed on the cluster"),
+        )
+        .arg(
+            Arg::with_name("serum-program")
+                .long("serum-program")
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .required(true)
+                .help("Serum dex v3 program deployed on the cluster, the spot markets of the tokens are listed on it"),
+        )
+        .arg(
+            Arg::with_name("perp-markets")
+                .long("perp-markets")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Number of perp markets to list, at most 15 (3 by default)"),
+        )
+        .arg(
+            Arg::with_name("base-lot-size")
+                .long("base-lot-size")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Base lot size of the markets in native units (100 by default)"),
+        )
+        .arg(
+            Arg::with_name("quote-lot-size")
+                .long("quote-lot-size")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Quote lot size of the markets in native units (10 by default)"),
+        )
+        .arg(
+            Arg::with_name("maint-leverage")
+                .long("maint-leverage")
+                .value_name("FLOAT")
+                .takes_value(true)
+                .required(false)
+                .help("Maintenance leverage of the markets (20 by default)"),
+        )
+        .arg(
+            Arg::with_name("init-leverage")
+                .long("init-leverage")
+                .value_name("FLOAT")
+                .takes_value(true)
+                .required(false)
+                .help("Initial leverage of the markets, below the maintenance leverage (10 by default)"),
+        )
+        .arg(
+            Arg::with_name("price")
+                .long("price")
+                .value_name("FLOAT")
+                .takes_value(true)
+                .required(false)
+                .help("Price set on the stub oracles of the markets (100 by default)"),
+        )
+        .arg(
+            Arg::with_name("mango-cluster")
+                .short("c")
+                .long("mango-cluster")
+                .value_name("STR")
+                .takes_value(true)
+                .help("Name of the group in the mango keys file (testnet.0 by default)"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("Mango keys file the group is written to, a group of the same name is replaced (mango_keys.json by default)"),
+        )
+        .arg(
+            Arg::with_name("market-makers")
+                .long("market-makers")
+                .value_name("UINT")
+                .takes_value(true)
+                .required(false)
+                .help("Number of market makers created with a mango account funded with quote tokens (0 by default)"),
+        )
+        .arg(
+            Arg::with_name("accounts-output")
+                .long("accounts-output")
+                .value_name("FILENAME")
+                .takes_value(true)
+                .required(false)
+                .help("Accounts file the market makers are written to, it is overwritten (accounts.json by default)"),
+        )
+}
+
+/// Configuration of the `analyze` subcommand
+pub struct AnalyzeConfig {
+    pub transaction_save_file: String,
+    pub block_data_save_file: Option<String>,
+}
+
+pub fn extract_analyze_args(matches: &ArgMatches) -> AnalyzeConfig {
+    AnalyzeConfig {
+        transaction_save_file: matches
+            .value_of("transaction-save-file")
+            .unwrap()
+            .to_string(),
+        block_data_save_file: matches
+            .value_of("block-data-save-file")
+            .map(|x| x.to_string()),
+    }
+}
+
+/// Configuration of the `compare` subcommand
+pub struct CompareConfig {
+    pub baseline: String,
+    pub candidate: String,
+    // in percentage points
+    pub max_confirmation_rate_drop: f64,
+    pub max_latency_increase_pct: f64,
+}
+
+pub fn extract_compare_args(matches: &ArgMatches) -> CompareConfig {
+    CompareConfig {
+        baseline: matches.value_of("baseline").unwrap().to_string(),
+        candidate: matches.value_of("candidate").unwrap().to_string(),
+        max_confirmation_rate_drop: matches
+            .value_of("max-confirmation-rate-drop")
+            .map(|x| x.parse().expect("can't parse max confirmation rate drop"))
+            .unwrap_or(1.0),
+        max_latency_increase_pct: matches
+            .value_of("max-latency-increase")
+            .map(|x| x.parse().expect("can't parse max latency increase"))
+            .unwrap_or(10.0),
+    }
+}
+
+/// Configuration of the `replay` subcommand
+pub struct ReplayConfig {
+    pub json_rpc_url: String,
+    pub websocket_url: String,
+    pub identity: Keypair,
+    pub corpus_file: String,
+    pub sender: SenderKind,
+    pub jito_url: String,
+    pub bind_address: IpAddr,
+    pub client_node_id: Option<Keypair>,
+    pub tpu_addrs: Vec<SocketAddr>,
+    pub speed: f64,
+}
+
+/// Configuration of the `crank` subcommand
+pub struct CrankConfig {
+    pub json_rpc_url: String,
+    pub websocket_url: String,
+    pub identity: Keypair,
+    pub authority: Option<Keypair>,
+    pub mango_keys: String,
+    pub mango_cluster: String,
+    pub duration: Option<Duration>,
+    pub prioritization_fee: u64,
+    pub transaction_save_file: String,
+    pub crank_spot_markets: bool,
+    pub crank_batch: CrankBatchConfig,
+}
+
+/// Configuration of the `validate-config` subcommand
+pub struct ValidateConfig {
+    pub json_rpc_url: String,
+    pub mango_keys: String,
+    pub mango_cluster: String,
+    pub account_keys: Option<String>,
+}
+
+/// Configuration of the `inspect-account` subcommand
+pub struct InspectAccountConfig {
+    pub json_rpc_url: String,
+    pub mango_account: Pubkey,
+    pub mango_keys: String,
+    pub mango_cluster: String,
+}
+
+/// Configuration of the `create-group` subcommand
+pub struct CreateGroupConfig {
+    pub json_rpc_url: String,
+    pub admin: Keypair,
+    pub mango_program_id: Pubkey,
+    pub serum_program_id: Pubkey,
+    pub nb_markets: usize,
+    pub base_lot_size: i64,
+    pub quote_lot_size: i64,
+    pub maint_leverage: f64,
+    pub init_leverage: f64,
+    pub price: f64,
+    pub group_name: String,
+    pub output: String,
+    pub nb_market_makers: usize,
+    pub accounts_output: String,
+}
+
+fn load_cli_config(matches: &ArgMatches) -> solana_cli_config::Config {
+    if let Some(config_file) = matches.value_of("config-file") {
+        solana_cli_config::Config::load(config_file).unwrap_or_default()
+    } else {
+        solana_cli_config::Config::default()
+    }
+}
+
+// comma separated list of urls
+fn is_url_list(urls: String) -> Result<(), String> {
+    urls.split(',').try_for_each(|url| is_url(url.trim()))
+}
+
+// the relayers are called with json rpc over http, there is no gRPC client
+fn is_http_url(url: String) -> Result<(), String> {
+    is_url(&url)?;
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(format!(
+            "{url} is not an http(s) url, only json rpc relayers are supported"
+        ))
+    }
+}
+
+// json rpc and websocket urls from the args, falling back to the solana cli config
+fn compute_urls(matches: &ArgMatches, config: &solana_cli_config::Config) -> (String, String) {
+    let (_, json_rpc_url) = ConfigInput::compute_json_rpc_url_setting(
+        matches.value_of("json-rpc-url").unwrap_or(""),
+        &config.json_rpc_url,
+    );
+    let (_, websocket_url) = ConfigInput::compute_websocket_url_setting(
+        matches.value_of("websocket-url").unwrap_or(""),
+        &config.websocket_url,
+        matches.value_of("json-rpc-url").unwrap_or(""),
+        &config.json_rpc_url,
+    );
+    (json_rpc_url, websocket_url)
+}
+
+pub fn extract_crank_args(matches: &ArgMatches) -> CrankConfig {
+    let config = load_cli_config(matches);
+    let (json_rpc_url, websocket_url) = compute_urls(matches, &config);
+
+    let (_, id_path) = ConfigInput::compute_keypair_path_setting(
+        matches.value_of("identity").unwrap_or(""),
+        &config.keypair_path,
+    );
+    let identity = read_keypair_file(id_path).expect("could not parse identity path");
+    let authority = matches
+        .value_of("keeper-authority")
+        .map(|path| read_keypair_file(path).expect("could not parse keeper authority path"));
+
+    CrankConfig {
+        json_rpc_url,
+        websocket_url,
+        identity,
+        authority,
+        mango_keys: matches.value_of("mango-keys").unwrap().to_string(),
+        mango_cluster: matches
+            .value_of("mango-cluster")
+            .unwrap_or("testnet.0")
+            .to_string(),
+        duration: matches
+            .value_of("duration")
+            .map(|x| Duration::from_secs(x.parse().expect("can't parse duration"))),
+        prioritization_fee: matches
+            .value_of("keeper-prioritization-fees")
+            .map(|x| x.parse().expect("can't parse keeper prioritization fees"))
+            .unwrap_or(1000),
+        transaction_save_file: matches
+            .value_of("transaction-save-file")
+            .unwrap_or_default()
+            .to_string(),
+        crank_spot_markets: matches.is_present("crank-spot-markets"),
+        crank_batch: extract_crank_batch(matches),
+    }
+}
+
+pub fn extract_validate_config_args(matches: &ArgMatches) -> ValidateConfig {
+    let config = load_cli_config(matches);
+    let (json_rpc_url, _) = compute_urls(matches, &config);
+    ValidateConfig {
+        json_rpc_url,
+        mango_keys: matches.value_of("mango-keys").unwrap().to_string(),
+        mango_cluster: matches
+            .value_of("mango-cluster")
+            .unwrap_or("testnet.0")
+            .to_string(),
+        account_keys: matches.value_of("account-keys").map(|x| x.to_string()),
+    }
+}
+
+pub fn extract_inspect_account_args(matches: &ArgMatches) -> InspectAccountConfig {
+    let config = load_cli_config(matches);
+    let (json_rpc_url, _) = compute_urls(matches, &config);
+    InspectAccountConfig {
+        json_rpc_url,
+        mango_account: Pubkey::from_str(matches.value_of("mango-account").unwrap())
+            .expect("can't parse mango account"),
+        mango_keys: matches.value_of("mango-keys").unwrap().to_string(),
+        mango_cluster: matches
+            .value_of("mango-cluster")
+            .unwrap_or("testnet.0")
+            .to_string(),
+    }
+}
+
+pub fn extract_create_group_args(matches: &ArgMatches) -> CreateGroupConfig {
+    let config = load_cli_config(matches);
+    let (json_rpc_url, _) = compute_urls(matches, &config);
+    let (_, id_path) = ConfigInput::compute_keypair_path_setting(
+        matches.value_of("identity").unwrap_or(""),
+        &config.keypair_path,
+    );
+    CreateGroupConfig {
+        json_rpc_url,
+        admin: read_keypair_file(id_path).expect("could not parse identity path"),
+        mango_program_id: Pubkey::from_str(matches.value_of("mango-program").unwrap())
+            .expect("can't parse mango program"),
+        serum_program_id: Pubkey::from_str(matches.value_of("serum-program").unwrap())
+            .expect("can't parse serum program"),
+        nb_markets: matches
+            .value_of("perp-markets")
+            .map_or(3, |x| x.parse().expect("can't parse perp markets")),
+        base_lot_size: matches
+            .value_of("base-lot-size")
+            .map_or(100, |x| x.parse().expect("can't parse base lot size")),
+        quote_lot_size: matches
+            .value_of("quote-lot-size")
+            .map_or(10, |x| x.parse().expect("can't parse quote lot size")),
+        maint_leverage: matches
+            .value_of("maint-leverage")
+            .map_or(20.0, |x| x.parse().expect("can't parse maint leverage")),
+        init_leverage: matches
+            .value_of("init-leverage")
+            .map_or(10.0, |x| x.parse().expect("can't parse init leverage")),
+        price: matches
+            .value_of("price")
+            .map_or(100.0, |x| x.parse().expect("can't parse price")),
+        group_name: matches
+            .value_of("mango-cluster")
+            .unwrap_or("testnet.0")
+            .to_string(),
+        output: matches
+            .value_of("output")
+            .unwrap_or("mango_keys.json")
+            .to_string(),
+        nb_market_makers: matches
+            .value_of("market-makers")
+            .map_or(0, |x| x.parse().expect("can't parse market makers")),
+        accounts_output: matches
+            .value_of("accounts-output")
+            .unwrap_or("accounts.json")
+            .to_string(),
+    }
+}
+
+fn parse_bind_address(matches: &ArgMatches) -> IpAddr {
+    matches
+        .value_of("bind-address")
+        .map(|x| x.parse().expect("can't parse bind address"))
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+// the http and websocket servers of the run only listen locally unless told otherwise
+fn parse_server_bind(matches: &ArgMatches, name: &str) -> IpAddr {
+    matches
+        .value_of(name)
+        .map(|x| x.parse().unwrap_or_else(|_| panic!("can't parse {name}")))
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+fn parse_client_node_id(matches: &ArgMatches) -> Option<Keypair> {
+    matches
+        .value_of("client-node-id")
+        .map(|path| read_keypair_file(path).expect("could not parse client node id path"))
+}
+
+fn parse_tpu_addrs(matches: &ArgMatches, sender: SenderKind) -> Vec<SocketAddr> {
+    let tpu_addrs: Vec<SocketAddr> = matches
+        .values_of("tpu-addr")
+        .map(|values| {
+            values
+                .map(|x| x.parse().expect("can't parse tpu address"))
+                .collect()
+        })
+        .unwrap_or_default();
+    assert!(
+        tpu_addrs.is_empty() || sender == SenderKind::Quic,
+        "--tpu-addr is only used by --sender quic"
+    );
+    tpu_addrs
+}
+
+pub fn extract_replay_args(matches: &ArgMatches) -> ReplayConfig {
+    let config = load_cli_config(matches);
+    let (json_rpc_url, websocket_url) = compute_urls(matches, &config);
+    let (_, id_path) = ConfigInput::compute_keypair_path_setting(
+        matches.value_of("identity").unwrap_or(""),
+        &config.keypair_path,
+    );
+
+    let sender = matches
+        .value_of("sender")
+        .unwrap()
+        .parse()
+        .expect("can't parse transaction sender");
+    ReplayConfig {
+        json_rpc_url,
+        websocket_url,
+        identity: read_keypair_file(id_path).expect("could not parse identity path"),
+        corpus_file: matches.value_of("corpus").unwrap().to_string(),
+        sender,
+        jito_url: matches
+            .value_of("jito-url")
+            .unwrap_or(DEFAULT_JITO_URL)
+            .to_string(),
+        bind_address: parse_bind_address(matches),
+        client_node_id: parse_client_node_id(matches),
+        tpu_addrs: parse_tpu_addrs(matches, sender),
+        speed: matches
+            .value_of("speed")
+            .map(|x| x.parse().expect("can't parse replay speed"))
+            .unwrap_or(1.0),
+    }
+}
+
+// host and port of the rpc url, used as the cluster tag
+fn cluster_tag(json_rpc_url: &str) -> String {
+    let host = json_rpc_url
+        .split_once("://")
+        .map_or(json_rpc_url, |(_, rest)| rest);
+    let host = host.split('/').next().unwrap_or(host);
+    host.replace(':', "_")
+}
+
+// output file of a cluster, the tag is inserted before the extension
+fn cluster_file(file: &str, tag: &str) -> String {
+    if file.is_empty() {
+        return String::new();
+    }
+    let path = Path::new(file);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(file);
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{stem}-{tag}.{extension}"),
+        None => format!("{stem}-{tag}"),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+/// Parses the `run` subcommand into one `Config` per cluster, `--url` can be given several times
+/// to send the same load to several clusters at once. The outputs of each cluster are written to
+/// their own files, tagged with the cluster
+/// read before the runtime is built, its workers are pinned as they start
+pub fn extract_thread_pinning(matches: &ArgMatches) -> Option<ThreadPinning> {
+    matches.is_present("pin-threads").then(|| {
+        matches
+            .value_of("pin-threads")
+            .unwrap_or_default()
+            .parse()
+            .expect("can't parse pinned cores")
+    })
+}
+
+pub fn extract_cluster_args(matches: &ArgMatches) -> Vec<Config> {
+    let json_rpc_urls: Vec<&str> = matches
+        .values_of("json-rpc-url")
+        .map(|urls| urls.collect())
+        .unwrap_or_default();
+    if json_rpc_urls.len() <= 1 {
+        return vec![extract_args(matches)];
+    }
+    let websocket_urls: Vec<&str> = matches
+        .values_of("websocket-url")
+        .map(|urls| urls.collect())
+        .unwrap_or_default();
+    assert!(
+        websocket_urls.is_empty() || websocket_urls.len() == json_rpc_urls.len(),
+        "--ws should be given once per --url"
+    );
+
+    // the runs against every cluster share the same id
+    let run_id = matches
+        .value_of("run-id")
+        .map(|x| x.to_string())
+        .unwrap_or_else(new_run_id);
+    json_rpc_urls
+        .iter()
+        .enumerate()
+        .map(|(i, json_rpc_url)| {
+            let mut args = extract_args(matches);
+            args.run_id = Some(run_id.clone());
+            let websocket_url = websocket_urls.get(i).copied().unwrap_or("");
+            let (_, websocket_url) =
+                ConfigInput::compute_websocket_url_setting(websocket_url, "", json_rpc_url, "");
+            let (_, json_rpc_url) =
+                ConfigInput::compute_json_rpc_url_setting(json_rpc_url, &args.json_rpc_url);
+            let tag = cluster_tag(&json_rpc_url);
+            args.transaction_save_file = cluster_file(&args.transaction_save_file, &tag);
+            args.block_data_save_file = cluster_file(&args.block_data_save_file, &tag);
+            args.slot_coverage_file = cluster_file(&args.slot_coverage_file, &tag);
+            args.book_snapshot_file = cluster_file(&args.book_snapshot_file, &tag);
+            args.timeseries_file = cluster_file(&args.timeseries_file, &tag);
+            args.funding_file = cluster_file(&args.funding_file, &tag);
+            args.fills_file = cluster_file(&args.fills_file, &tag);
+            args.summary_file = cluster_file(&args.summary_file, &tag);
+            args.send_errors_file = cluster_file(&args.send_errors_file, &tag);
+            args.rpc_sightings_file = cluster_file(&args.rpc_sightings_file, &tag);
+            args.run_manifest_file = cluster_file(&args.run_manifest_file, &tag);
+            args.run_state_file = cluster_file(&args.run_state_file, &tag);
+            args.generate_only = args.generate_only.map(|file| cluster_file(&file, &tag));
+            // a single control server and results stream can be bound on the ports
+            if i > 0 {
+                args.control_port = None;
+                args.health_port = None;
+                args.results_stream_port = None;
+            }
+            args.json_rpc_url = json_rpc_url;
+            args.websocket_url = websocket_url;
+            args.cluster = Some(tag);
+            args
+        })
+        .collect()
+}
+
+/// Parses a clap `ArgMatches` structure of the `run` subcommand into a `Config`
+/// # Arguments
+/// * `matches` - command line arguments parsed by clap
+/// # Panics
+/// Panics if there is trouble parsing any of the arguments
+pub fn extract_args(matches: &ArgMatches) -> Config {
+    let mut args = Config::default();
+
+    let config = load_cli_config(matches);
+    let (json_rpc_url, websocket_url) = compute_urls(matches, &config);
+    args.json_rpc_url = json_rpc_url;
+    args.websocket_url = websocket_url;
+
+    let (_, id_path) = ConfigInput::compute_keypair_path_setting(
+        matches.value_of("identity").unwrap_or(""),
+        &config.keypair_path,
+    );
+    if let Ok(id) = read_keypair_file(id_path) {
+        args.identity = id;
+    } else if matches.is_present("identity") {
+        panic!("could not parse identity path");
+    }
+
+    if let Some(addr) = matches.value_of("entrypoint") {
+        args.entrypoint_addr = solana_net_utils::parse_host_port(addr).unwrap_or_else(|e| {
+            eprintln!("failed to parse entrypoint address: {}", e);
+            exit(1)
+        });
+    }
+
+    if let Some(duration) = matches.value_of("duration") {
+        args.duration = Duration::new(
+            duration.to_string().parse().expect("can't parse duration"),
+            0,
+        );
+    }
+    args.duration_slots = matches
+        .value_of("duration-slots")
+        .map(|x| x.parse().expect("can't parse duration slots"));
+
+    if let Some(qps) = matches.value_of("quotes-per-second") {
+        args.quotes_per_second = qps.parse().expect("can't parse quotes-per-second");
+    }
+
+    args.account_keys = matches.value_of("account-keys").unwrap().to_string();
+    args.mango_keys = matches
+        .value_of("mango-keys")
+        .unwrap_or_default()
+        .to_string();
+    args.transaction_save_file = match matches.value_of("transaction-save-file") {
+        Some(x) => x.to_string(),
+        None => String::new(),
+    };
+    args.block_data_save_file = match matches.value_of("block-data-save-file") {
+        Some(x) => x.to_string(),
+        None => String::new(),
+    };
+    args.partition_output = matches
+        .value_of("partition-output")
+        .map(|x| x.parse().expect("can't parse output partitioning"));
+    args.compress = matches
+        .value_of("compress")
+        .map(|x| x.parse().expect("can't parse compression"));
+
+    args.mango_cluster = match matches.value_of("mango-cluster") {
+        Some(x) => x.to_string(),
+        None => "testnet.0".to_string(),
+    };
+    args.txs_batch_size = matches
+        .value_of("batch-size")
+        .map(|batch_size_str| batch_size_str.parse().expect("can't parse batch-size"));
+
+    args.priority_fees_proba = match matches.value_of("prioritization-fees") {
+        Some(x) => x
+            .parse()
+            .expect("Percentage of transactions having prioritization fees"),
+        None => 0,
+    };
+    let (_, kp_auth_path) = ConfigInput::compute_keypair_path_setting(
+        matches.value_of("keeper-authority").unwrap_or(""),
+        &config.keypair_path,
+    );
+
+    args.keeper_authority = read_keypair_file(kp_auth_path).ok();
+    args.top_up = matches.value_of("top-up-funder").map(|path| {
+        let default = TopUpConfig::new(
+            read_keypair_file(path).expect("could not read the top up funder keypair"),
+        );
+        TopUpConfig {
+            threshold_lamports: matches
+                .value_of("top-up-threshold-sol")
+                .map(|x| sol_to_lamports(x.parse().expect("can't parse top up threshold")))
+                .unwrap_or(default.threshold_lamports),
+            amount_lamports: matches
+                .value_of("top-up-amount-sol")
+                .map(|x| sol_to_lamports(x.parse().expect("can't parse top up amount")))
+                .unwrap_or(default.amount_lamports),
+            interval: matches
+                .value_of("top-up-interval")
+                .map(|x| Duration::from_secs(x.parse().expect("can't parse top up interval")))
+                .unwrap_or(default.interval),
+            ..default
+        }
+    });
+
+    args.number_of_markers_per_mm = match matches.value_of("markets-per-mm") {
+        Some(x) => x
+            .parse()
+            .expect("can't parse number of markets per market maker"),
+        None => 5,
+    };
+    args.market_weights = matches
+        .value_of("market-weights")
+        .map_or(MarketWeights::Config, |x| {
+            x.parse().expect("can't parse market weights")
+        });
+
+    args.keeper_prioritization = match matches.value_of("keeper-prioritization-fees") {
+        Some(x) => x.parse().expect("can't parse keeper prioritization fees"),
+        None => 1000,
+    };
+    args.crank_spot_markets = matches.is_present("crank-spot-markets");
+    args.crank_batch = extract_crank_batch(matches);
+
+    args.spoofing_levels = matches
+        .value_of("spoofing-levels")
+        .map(|x| x.parse().expect("can't parse spoofing levels"));
+    args.unique_client_order_ids = matches.is_present("unique-client-order-ids");
+    args.encoded_client_order_ids = matches.is_present("encoded-client-order-ids");
+
+    args.program_version = matches
+        .value_of("program-version")
+        .unwrap()
+        .parse()
+        .expect("can't parse program version");
+
+    args.db_url = matches.value_of("db-url").map(|x| x.to_string());
+    args.slot_coverage_file = matches
+        .value_of("slot-coverage-file")
+        .unwrap_or_default()
+        .to_string();
+    args.timeseries_file = matches
+        .value_of("timeseries-file")
+        .unwrap_or("timeseries.csv")
+        .to_string();
+    args.funding_file = matches
+        .value_of("funding-file")
+        .unwrap_or_default()
+        .to_string();
+    args.fills_file = matches
+        .value_of("fills-file")
+        .unwrap_or_default()
+        .to_string();
+    args.funding_interval = matches
+        .value_of("funding-interval")
+        .map(|x| Duration::from_secs(x.parse().expect("can't parse funding interval")))
+        .unwrap_or(DEFAULT_FUNDING_INTERVAL);
+    args.dry_run = matches.is_present("dry-run");
+    args.control_port = matches
+        .value_of("control-port")
+        .map(|x| x.parse().expect("can't parse control port"));
+    args.control_bind = parse_server_bind(matches, "control-bind");
+    args.health_port = matches
+        .value_of("health-port")
+        .map(|x| x.parse().expect("can't parse health port"));
+    args.health_bind = parse_server_bind(matches, "health-bind");
+    args.results_stream_port = matches
+        .value_of("results-stream-port")
+        .map(|x| x.parse().expect("can't parse results stream port"));
+    args.results_stream_bind = parse_server_bind(matches, "results-stream-bind");
+    args.warmup_probes = match matches.value_of("warmup-probes") {
+        Some(x) => x.parse().expect("can't parse warmup probes"),
+        None => 1,
+    };
+    args.warmup_max_failure_pct = match matches.value_of("warmup-max-failure") {
+        Some(x) => x.parse().expect("can't parse warmup max failure"),
+        None => 10,
+    };
+    args.baseline_secs = matches
+        .value_of("baseline-secs")
+        .map_or(0, |x| x.parse().expect("can't parse baseline seconds"));
+    args.quote_config = QuoteConfig {
+        order_size_lots: matches
+            .value_of("order-size-lots")
+            .map(|x| x.parse().expect("can't parse order size lots")),
+        size_jitter_pct: matches
+            .value_of("size-jitter-pct")
+            .map(|x| x.parse().expect("can't parse size jitter"))
+            .unwrap_or(0),
+        max_spread_bps: matches
+            .value_of("max-spread-bps")
+            .map(|x| x.parse().expect("can't parse max spread"))
+            .unwrap_or(1000),
+        max_price_offset_lots: matches
+            .value_of("max-price-offset-lots")
+            .map(|x| x.parse().expect("can't parse max price offset"))
+            .unwrap_or(20),
+        quotes_per_second: None,
+        batch_size: args.txs_batch_size,
+        inventory_skew_bps: matches
+            .value_of("inventory-skew-bps")
+            .map(|x| x.parse().expect("can't parse inventory skew"))
+            .unwrap_or(0),
+        order_expiry_secs: matches
+            .value_of("order-expiry-secs")
+            .map(|x| x.parse().expect("can't parse order expiry")),
+        expiry_type: match matches.value_of("order-expiry-type") {
+            Some("relative") => ExpiryType::Relative,
+            _ => ExpiryType::Absolute,
+        },
+        order_limit: matches
+            .value_of("order-limit")
+            .map(|x| x.parse().expect("can't parse order limit"))
+            .unwrap_or(64),
+        max_quote_lots: matches
+            .value_of("max-quote-lots")
+            .map(|x| x.parse().expect("can't parse max quote lots"))
+            .unwrap_or(i64::MAX),
+        compute_unit_limit: matches
+            .value_of("compute-unit-limit")
+            .filter(|x| *x != "auto")
+            .map(|x| x.parse().expect("can't parse compute unit limit")),
+        weight: 1.0,
+        order_mix: matches
+            .value_of("order-types")
+            .map(|x| x.parse().expect("can't parse order types"))
+            .unwrap_or_default(),
+        reduce_only_pct: matches
+            .value_of("reduce-only-pct")
+            .map(|x| x.parse().expect("can't parse reduce only percentage"))
+            .unwrap_or(0),
+    };
+    args.estimate_compute_units = matches.value_of("compute-unit-limit") == Some("auto");
+    args.summary_file = matches
+        .value_of("summary-file")
+        .unwrap_or_default()
+        .to_string();
+    args.thresholds = Thresholds {
+        min_confirmation_rate: matches
+            .value_of("min-confirmation-rate")
+            .map(|x| x.parse().expect("can't parse min confirmation rate")),
+        max_p95_latency_ms: matches
+            .value_of("max-p95-latency-ms")
+            .map(|x| x.parse().expect("can't parse max p95 latency")),
+    };
+    args.alerts = matches
+        .values_of("alert")
+        .map(|values| {
+            values
+                .map(|x| x.parse().expect("can't parse alert rule"))
+                .collect()
+        })
+        .unwrap_or_default();
+    args.alert_webhook = matches.value_of("alert-webhook").map(|x| x.to_string());
+    args.accounts_per_signer = match matches.value_of("accounts-per-signer") {
+        Some(x) => x.parse().expect("can't parse accounts per signer"),
+        None => 1,
+    };
+    args.watch_accounts = matches.is_present("watch-accounts");
+    args.quic_connection_pool_size = match matches.value_of("quic-connection-pool-size") {
+        Some(x) => x.parse().expect("can't parse quic connection pool size"),
+        None => DEFAULT_QUIC_CONNECTION_POOL_SIZE,
+    };
+    args.tpu_max_parallel_streams = matches
+        .value_of("tpu-max-parallel-streams")
+        .map(|x| x.parse().expect("can't parse tpu max parallel streams"));
+    args.num_quic_endpoints = match matches.value_of("num-quic-endpoints") {
+        Some(x) => x
+            .parse::<usize>()
+            .expect("can't parse number of quic endpoints")
+            .max(1),
+        None => 1,
+    };
+    args.send_errors_file = matches
+        .value_of("send-errors-file")
+        .unwrap_or_default()
+        .to_string();
+    args.scenario = matches
+        .value_of("scenario")
+        .map(|path| Scenario::load(path).expect("can't load scenario file"));
+    args.cancel_storm_at = matches
+        .values_of("cancel-storm-at")
+        .map(|values| {
+            values
+                .map(|x| parse_storm_offset(x).expect("can't parse cancel storm offset"))
+                .collect()
+        })
+        .unwrap_or_default();
+    args.periphery = matches
+        .value_of("periphery-mix")
+        .map(|mix| PeripheryConfig {
+            mix: mix.parse().expect("can't parse periphery mix"),
+            interval: matches
+                .value_of("periphery-interval-ms")
+                .map_or(DEFAULT_PERIPHERY_INTERVAL, |x| {
+                    Duration::from_millis(x.parse().expect("can't parse periphery interval"))
+                }),
+            quantity: matches
+                .value_of("periphery-quantity")
+                .map_or(DEFAULT_PERIPHERY_QUANTITY, |x| {
+                    x.parse().expect("can't parse periphery quantity")
+                }),
+        });
+    if matches.is_present("feed-oracles") {
+        let default = OracleFeederConfig::default();
+        args.oracle_feeder = Some(OracleFeederConfig {
+            start_price: matches
+                .value_of("oracle-start-price")
+                .map(|x| x.parse().expect("can't parse oracle start price"))
+                .unwrap_or(default.start_price),
+            volatility_bps: matches
+                .value_of("oracle-volatility-bps")
+                .map(|x| x.parse().expect("can't parse oracle volatility"))
+                .unwrap_or(default.volatility_bps),
+            interval: matches
+                .value_of("oracle-interval-ms")
+                .map(|x| Duration::from_millis(x.parse().expect("can't parse oracle interval")))
+                .unwrap_or(default.interval),
+        });
+    }
+    args.commitment = matches
+        .value_of("commitment")
+        .unwrap()
+        .parse()
+        .expect("can't parse commitment");
+    args.track_all_commitments = matches.is_present("track-all-commitments");
+    args.block_fetch_concurrency = matches
+        .value_of("block-fetch-concurrency")
+        .map(|x| x.parse().expect("can't parse block fetch concurrency"))
+        .unwrap_or(DEFAULT_BLOCK_FETCH_CONCURRENCY);
+    args.confirmation_source = matches
+        .value_of("confirmation-source")
+        .unwrap()
+        .parse()
+        .expect("can't parse confirmation source");
+    args.stall_timeout = matches
+        .value_of("stall-timeout")
+        .map(|x| Duration::from_secs(x.parse().expect("can't parse stall timeout")))
+        .unwrap_or(DEFAULT_STALL_TIMEOUT);
+    args.abort_on_stall = matches.is_present("abort-on-stall");
+    args.max_market_maker_restarts = matches
+        .value_of("max-market-maker-restarts")
+        .map(|x| x.parse().expect("can't parse max market maker restarts"))
+        .unwrap_or(DEFAULT_MAX_MARKET_MAKER_RESTARTS);
+    args.seed = matches
+        .value_of("seed")
+        .map(|x| x.parse().expect("can't parse seed"));
+    args.sender = matches
+        .value_of("sender")
+        .unwrap()
+        .parse()
+        .expect("can't parse transaction sender");
+    args.jito_url = matches
+        .value_of("jito-url")
+        .unwrap_or(DEFAULT_JITO_URL)
+        .to_string();
+    args.relayer_url = matches.value_of("relayer-url").map(|x| x.to_string());
+    if let Some(pct) = matches.value_of("relayer-pct") {
+        args.relayer_pct = pct.parse().expect("can't parse relayer pct");
+        assert!(args.relayer_pct <= 100, "relayer pct must be at most 100");
+    }
+    assert!(
+        args.confirmation_source == ConfirmationSource::Blocks
+            || args.sender != SenderKind::Tpu
+            || args.relayer_url.is_some(),
+        "--confirmation-source is ignored by --sender tpu without --relayer-url, lite-rpc notifies \
+        the transactions it sent"
+    );
+    args.confirm_rpc_urls = matches
+        .value_of("confirm-rpc-urls")
+        .map(|x| x.split(',').map(|url| url.trim().to_string()).collect())
+        .unwrap_or_default();
+    args.rpc_sightings_file = matches
+        .value_of("rpc-sightings-file")
+        .unwrap_or_default()
+        .to_string();
+    args.chaos = matches
+        .value_of("chaos")
+        .map(|x| x.parse().expect("can't parse chaos"))
+        .unwrap_or_default();
+    args.record_buffer = matches
+        .value_of("record-buffer")
+        .map(|x| x.parse().expect("can't parse record buffer"))
+        .unwrap_or(DEFAULT_RECORD_BUFFER);
+    args.book_validation_interval = matches
+        .value_of("validate-book-interval")
+        .map(|x| Duration::from_secs(x.parse().expect("can't parse book validation interval")));
+    args.book_snapshot_file = matches
+        .value_of("book-snapshot-file")
+        .unwrap_or_default()
+        .to_string();
+    if let Some(x) = matches.value_of("book-snapshot-interval") {
+        args.book_snapshot_interval =
+            Duration::from_secs(x.parse().expect("can't parse book snapshot interval"));
+    }
+    if let Some(x) = matches.value_of("book-snapshot-depth") {
+        args.book_snapshot_depth = x.parse().expect("can't parse book snapshot depth");
+    }
+    args.discover_markets = matches
+        .value_of("discover-markets")
+        .map(|x| Pubkey::from_str(x).expect("can't parse mango group pubkey"));
+    args.max_slot_lag = matches
+        .value_of("max-slot-lag")
+        .map(|x| x.parse().expect("can't parse max slot lag"));
+    args.generate_only = matches.value_of("generate-only").map(|x| x.to_string());
+    args.bind_address = parse_bind_address(matches);
+    args.client_node_id = parse_client_node_id(matches);
+    args.tpu_addrs = parse_tpu_addrs(matches, args.sender);
+    args.preconnect_leaders = matches
+        .value_of("preconnect-leaders")
+        .map_or(4, |x| x.parse().expect("can't parse preconnect leaders"));
+    args.send_delay = matches
+        .value_of("send-delay-ms-distribution")
+        .map(|x| x.parse().expect("can't parse send delay distribution"));
+    args.network_impairment = matches
+        .value_of("network-impairment")
+        .map(|x| x.parse().expect("can't parse network impairment"));
+    if let Some(impairment) = &args.network_impairment {
+        assert!(
+            impairment.duplicate_pct == 0 || args.chaos.duplicate_pct == 0,
+            "--network-impairment duplicate and --chaos duplicate both send transactions twice, use \
+            one of them"
+        );
+        assert!(
+            impairment.delay_pct == 0 || args.send_delay.is_none(),
+            "--network-impairment delay and --send-delay-ms-distribution both hold back \
+            transactions, use one of them"
+        );
+    }
+    args.memo = matches.is_present("memo");
+    args.run_id = matches.value_of("run-id").map(|x| x.to_string());
+    args.run_manifest_file = matches
+        .value_of("run-manifest-file")
+        .unwrap_or("run_manifest.json")
+        .to_string();
+    args.run_state_file = matches
+        .value_of("run-state-file")
+        .unwrap_or("run_state.json")
+        .to_string();
+    args.resume = matches.is_present("resume");
+    args.force = matches.is_present("force");
+    args.shard = matches
+        .value_of("shard")
+        .map(|x| x.parse().expect("can't parse shard"));
+    args.pacing = Pacing {
+        burst_size: matches
+            .value_of("burst-size")
+            .map(|x| x.parse().expect("can't parse burst size")),
+        interval: matches
+            .value_of("pacing-interval-ms")
+            .map_or(Pacing::default().interval, |x| {
+                Duration::from_millis(x.parse().expect("can't parse pacing interval"))
+            }),
     };
+    args.build_threads = matches
+        .value_of("build-threads")
+        .map(|x| x.parse().expect("can't parse build threads"));
+    args.pin_threads = extract_thread_pinning(matches);
     args
 }
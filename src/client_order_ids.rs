@@ -0,0 +1,263 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use mango::matching::Side;
+use serde::Serialize;
+
+use crate::clock;
+
+/// Guard against quotes repeating the client order id of the previous quote, shared by all the
+/// market makers to count the duplicates it prevented
+#[derive(Clone, Default)]
+pub struct DedupGuard {
+    enabled: bool,
+    prevented_duplicates: Arc<AtomicU64>,
+}
+
+impl DedupGuard {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            prevented_duplicates: Arc::default(),
+        }
+    }
+
+    pub fn prevented_duplicates(&self) -> u64 {
+        self.prevented_duplicates.load(Ordering::Relaxed)
+    }
+}
+
+// layout of an encoded id from the lowest bit : side, tick, restart, attempt, market index,
+// market maker index, and the highest bit set, which the clock ids never reach
+const SIDE_BITS: u32 = 1;
+const TICK_BITS: u32 = 28;
+const RESTART_BITS: u32 = 3;
+const ATTEMPT_BITS: u32 = 3;
+const MARKET_INDEX_BITS: u32 = 8;
+const MARKET_MAKER_INDEX_BITS: u32 = 20;
+const ENCODED_FLAG: u64 = 1 << 63;
+
+fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+/// Quote an order was placed by, encoded in its client order id so that the fills of the event
+/// queues and the orders of the books can be traced back to the quote which sent them. The
+/// ticks start over when a market maker restarts or the run is resumed, the restart and the
+/// attempt keep the ids of the quotes sent before apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ClientOrderIdKey {
+    // position of the mango account among the accounts of the file, over all the shards
+    pub market_maker_index: u32,
+    // position of the market among the markets quoted by the market maker
+    pub market_index: u8,
+    // resumes of the run and restarts of the market maker, both wrap around after 7
+    pub attempt: u8,
+    pub restart: u8,
+    // quotes sent by the market maker on the market before this one
+    pub tick: u64,
+    #[serde(serialize_with = "serialize_side")]
+    pub side: Side,
+}
+
+fn serialize_side<S: serde::Serializer>(side: &Side, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(match side {
+        Side::Bid => "bid",
+        Side::Ask => "ask",
+    })
+}
+
+impl ClientOrderIdKey {
+    pub fn encode(&self) -> u64 {
+        let mut id = self.market_maker_index as u64 & mask(MARKET_MAKER_INDEX_BITS);
+        id = (id << MARKET_INDEX_BITS) | self.market_index as u64;
+        id = (id << ATTEMPT_BITS) | (self.attempt as u64 & mask(ATTEMPT_BITS));
+        id = (id << RESTART_BITS) | (self.restart as u64 & mask(RESTART_BITS));
+        id = (id << TICK_BITS) | (self.tick & mask(TICK_BITS));
+        id = (id << SIDE_BITS) | (self.side == Side::Ask) as u64;
+        ENCODED_FLAG | id
+    }
+
+    /// None for the ids which were not encoded, like the ones read from the clock
+    pub fn decode(id: u64) -> Option<Self> {
+        if id & ENCODED_FLAG == 0 {
+            return None;
+        }
+        let side = if id & mask(SIDE_BITS) == 0 {
+            Side::Bid
+        } else {
+            Side::Ask
+        };
+        let id = id >> SIDE_BITS;
+        let tick = id & mask(TICK_BITS);
+        let id = id >> TICK_BITS;
+        let restart = (id & mask(RESTART_BITS)) as u8;
+        let id = id >> RESTART_BITS;
+        let attempt = (id & mask(ATTEMPT_BITS)) as u8;
+        let id = id >> ATTEMPT_BITS;
+        let market_index = (id & mask(MARKET_INDEX_BITS)) as u8;
+        let market_maker_index = ((id >> MARKET_INDEX_BITS) & mask(MARKET_MAKER_INDEX_BITS)) as u32;
+        Some(Self {
+            market_maker_index,
+            market_index,
+            attempt,
+            restart,
+            tick,
+            side,
+        })
+    }
+}
+
+/// Id of the order of a quote on the given side. The bid and the ask of an encoded quote only
+/// differ by their side, the ids read from the clock are shared by both sides
+pub fn side_client_order_id(client_order_id: u64, side: Side) -> u64 {
+    match ClientOrderIdKey::decode(client_order_id) {
+        Some(key) => ClientOrderIdKey { side, ..key }.encode(),
+        None => client_order_id,
+    }
+}
+
+/// Client order ids of the quotes of a market maker on a market. Ids read from the clock repeat
+/// for quotes built within the same microsecond, which gives identical transactions under the
+/// same blockhash when the random offsets match too, and the duplicate signature is rejected.
+/// With the guard enabled every id is above the previous one. Encoded ids are unique and name
+/// the quote instead, see ClientOrderIdKey
+pub struct ClientOrderIds {
+    guard: DedupGuard,
+    last: u64,
+    // clock reading of the previous id, the guarded ids move ahead of the clock
+    last_clock: u64,
+    // key of the first quote of the encoded ids, the following ones count the ticks
+    encoded: Option<ClientOrderIdKey>,
+    tick: u64,
+}
+
+impl ClientOrderIds {
+    pub fn new(guard: DedupGuard) -> Self {
+        Self {
+            guard,
+            last: 0,
+            last_clock: 0,
+            encoded: None,
+            tick: 0,
+        }
+    }
+
+    pub fn encoded(first: ClientOrderIdKey) -> Self {
+        Self {
+            tick: first.tick,
+            encoded: Some(first),
+            ..Self::new(DedupGuard::default())
+        }
+    }
+
+    /// id of the bid of the next quote
+    pub fn next(&mut self) -> u64 {
+        if let Some(first) = self.encoded {
+            let key = ClientOrderIdKey {
+                tick: self.tick,
+                side: Side::Bid,
+                ..first
+            };
+            self.tick += 1;
+            return key.encode();
+        }
+        let now = clock::now_micros() as u64;
+        if !self.guard.enabled {
+            return now;
+        }
+        self.next_guarded(now)
+    }
+
+    fn next_guarded(&mut self, now: u64) -> u64 {
+        // only the ids the clock would have repeated count, not the ones which are moved ahead
+        // because the previous id was
+        if now == self.last_clock {
+            self.guard
+                .prevented_duplicates
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        self.last_clock = now;
+        self.last = now.max(self.last + 1);
+        self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_ids_round_trip() {
+        let key = ClientOrderIdKey {
+            market_maker_index: 1_000_123,
+            market_index: 7,
+            attempt: 5,
+            restart: 3,
+            tick: (1 << TICK_BITS) - 1,
+            side: Side::Ask,
+        };
+        assert_eq!(ClientOrderIdKey::decode(key.encode()), Some(key));
+        // the clock ids are not decoded
+        assert_eq!(ClientOrderIdKey::decode(1_700_000_000_000_000), None);
+    }
+
+    #[test]
+    fn restarts_and_attempts_give_distinct_ids() {
+        let first = ClientOrderIdKey {
+            market_maker_index: 3,
+            market_index: 1,
+            attempt: 0,
+            restart: 0,
+            tick: 0,
+            side: Side::Bid,
+        };
+        let ids = [
+            first,
+            ClientOrderIdKey {
+                restart: 1,
+                ..first
+            },
+            ClientOrderIdKey {
+                attempt: 1,
+                ..first
+            },
+        ]
+        .map(|key| ClientOrderIds::encoded(key).next());
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[0], ids[2]);
+        assert_ne!(ids[1], ids[2]);
+
+        let mut ids = ClientOrderIds::encoded(first);
+        let bid = ids.next();
+        assert_eq!(
+            side_client_order_id(bid, Side::Ask),
+            ClientOrderIdKey {
+                side: Side::Ask,
+                ..first
+            }
+            .encode()
+        );
+        assert_eq!(ClientOrderIdKey::decode(ids.next()).unwrap().tick, 1);
+    }
+
+    #[test]
+    fn guard_counts_the_repeated_clock_readings() {
+        let guard = DedupGuard::new(true);
+        let mut ids = ClientOrderIds::new(guard.clone());
+        assert_eq!(ids.next_guarded(100), 100);
+        assert_eq!(ids.next_guarded(100), 101);
+        assert_eq!(ids.next_guarded(100), 102);
+        assert_eq!(guard.prevented_duplicates(), 2);
+
+        // behind the guarded ids but not a repeat of the clock
+        assert_eq!(ids.next_guarded(101), 103);
+        assert_eq!(ids.next_guarded(102), 104);
+        assert_eq!(guard.prevented_duplicates(), 2);
+
+        assert_eq!(ids.next_guarded(110), 110);
+        assert_eq!(guard.prevented_duplicates(), 2);
+    }
+}
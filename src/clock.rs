@@ -0,0 +1,19 @@
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+
+// the wall clock is read once, later times add the monotonic time elapsed since
+static ANCHOR: Lazy<(Instant, DateTime<Utc>)> = Lazy::new(|| (Instant::now(), Utc::now()));
+
+/// Current time as the wall clock anchor plus the monotonic time elapsed since. Records keep
+/// their order and their deltas when the system clock is adjusted during the run
+pub fn now() -> DateTime<Utc> {
+    let (anchor_instant, anchor_time) = *ANCHOR;
+    anchor_time + Duration::from_std(anchor_instant.elapsed()).unwrap_or_else(|_| Duration::zero())
+}
+
+/// microseconds since the unix epoch
+pub fn now_micros() -> i64 {
+    now().timestamp_micros()
+}
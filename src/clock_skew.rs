@@ -0,0 +1,94 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use solana_program::clock::DEFAULT_MS_PER_SLOT;
+use solana_sdk::slot_history::Slot;
+
+use crate::{clock, states::TransactionConfirmRecord};
+
+// about a minute of blocks
+const OFFSET_WINDOW: usize = 150;
+// block times kept to find the block of the confirmed transactions
+const BLOCK_TIMES_KEPT: usize = 512;
+
+#[derive(Default)]
+struct Samples {
+    // local receive time minus block time of the freshest blocks, in milliseconds
+    offsets_ms: VecDeque<i64>,
+    highest_slot: Slot,
+    // block time in seconds of the recent slots
+    block_times: BTreeMap<Slot, i64>,
+}
+
+/// Offset of the local clock over the cluster clock, estimated NTP style from the blocks: a block
+/// is received after its block time, so the smallest difference between the local receive time
+/// and the block time over the recent blocks is the closest to the offset. It is an upper bound,
+/// which includes the fastest delivery of a block and the rounding of the block times to the second
+#[derive(Clone, Default)]
+pub struct ClockSkew {
+    samples: Arc<Mutex<Samples>>,
+}
+
+impl ClockSkew {
+    /// records a block received now, only the blocks above the highest slot seen are fresh
+    /// enough to sample the offset
+    pub fn record_block(&self, slot: Slot, block_time: Option<i64>) {
+        let Some(block_time) = block_time.filter(|t| *t > 0) else {
+            return;
+        };
+        let received_at_ms = clock::now().timestamp_millis();
+        let mut samples = self.samples.lock().unwrap();
+        samples.block_times.insert(slot, block_time);
+        while samples.block_times.len() > BLOCK_TIMES_KEPT {
+            samples.block_times.pop_first();
+        }
+        if slot <= samples.highest_slot {
+            return;
+        }
+        samples.highest_slot = slot;
+        samples
+            .offsets_ms
+            .push_back(received_at_ms - block_time * 1000);
+        if samples.offsets_ms.len() > OFFSET_WINDOW {
+            samples.offsets_ms.pop_front();
+        }
+    }
+
+    /// local clock minus cluster clock, None before the first block
+    pub fn offset_ms(&self) -> Option<i64> {
+        self.samples
+            .lock()
+            .unwrap()
+            .offsets_ms
+            .iter()
+            .min()
+            .copied()
+    }
+
+    // block time in milliseconds of the slot, extrapolated from the closest known block before it
+    fn block_time_ms(&self, slot: Slot) -> Option<i64> {
+        let samples = self.samples.lock().unwrap();
+        let (known_slot, block_time) = samples.block_times.range(..=slot).next_back()?;
+        Some(block_time * 1000 + (slot - known_slot) as i64 * DEFAULT_MS_PER_SLOT as i64)
+    }
+
+    /// Sets the latency between the send and the block of a confirmed transaction. The send time
+    /// is read from the local clock and the block time from the cluster, so the latency is stored
+    /// as measured and corrected by the offset of the clocks
+    pub fn annotate(&self, record: &mut TransactionConfirmRecord) {
+        let Some(confirmed_slot) = record.confirmed_slot else {
+            return;
+        };
+        let Some(block_time_ms) = self.block_time_ms(confirmed_slot) else {
+            return;
+        };
+        let inclusion_latency_ms = block_time_ms - record.sent_at_us / 1000;
+        record.inclusion_latency_ms = Some(inclusion_latency_ms);
+        record.clock_offset_ms = self.offset_ms();
+        record.corrected_inclusion_latency_ms = record
+            .clock_offset_ms
+            .map(|offset_ms| inclusion_latency_ms + offset_ms);
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use iter_tools::Itertools;
+use serde::Deserialize;
+
+use crate::{
+    analyze::{read_transactions, TransactionRow},
+    cli::CompareConfig,
+    helpers::percentile,
+    stats::LatencyPercentiles,
+};
+
+// two sided 95% confidence
+const Z_95: f64 = 1.96;
+const PERCENTILES: [f64; 4] = [50.0, 90.0, 95.0, 99.0];
+
+#[derive(Deserialize)]
+struct SummaryFile {
+    results: SummaryResults,
+}
+
+#[derive(Deserialize)]
+struct SummaryResults {
+    sent: u64,
+    confirmed: u64,
+    latency_ms: LatencyPercentiles,
+}
+
+/// Results of one run, with the individual latencies when read from a transaction csv
+struct RunSample {
+    sent: u64,
+    confirmed: u64,
+    // sorted, empty for a summary file
+    latencies_ms: Vec<u64>,
+    percentiles_ms: [Option<u64>; 4],
+}
+
+impl RunSample {
+    fn from_rows(rows: &[&TransactionRow]) -> Self {
+        let latencies_ms = rows
+            .iter()
+            .filter_map(|r| r.latency_ms())
+            .map(|l| l.max(0) as u64)
+            .sorted()
+            .collect_vec();
+        Self {
+            sent: rows.len() as u64,
+            confirmed: rows.iter().filter(|r| r.confirmed_slot.is_some()).count() as u64,
+            percentiles_ms: PERCENTILES.map(|p| percentile(&latencies_ms, p)),
+            latencies_ms,
+        }
+    }
+
+    fn from_summary(summary: &SummaryResults) -> Self {
+        let l = &summary.latency_ms;
+        Self {
+            sent: summary.sent,
+            confirmed: summary.confirmed,
+            latencies_ms: vec![],
+            percentiles_ms: [l.p50, l.p90, l.p95, l.p99],
+        }
+    }
+
+    fn confirmation_rate(&self) -> f64 {
+        if self.sent == 0 {
+            0.0
+        } else {
+            self.confirmed as f64 / self.sent as f64
+        }
+    }
+
+    // distribution free confidence interval of a percentile from the ranks of the order statistics
+    fn percentile_ci(&self, p: f64) -> Option<(u64, u64)> {
+        let n = self.latencies_ms.len() as f64;
+        if n == 0.0 {
+            return None;
+        }
+        let q = p / 100.0;
+        let spread = Z_95 * (n * q * (1.0 - q)).sqrt();
+        let rank = |r: f64| (r.max(1.0).min(n) as usize) - 1;
+        Some((
+            self.latencies_ms[rank((n * q - spread).floor())],
+            self.latencies_ms[rank((n * q + spread).ceil())],
+        ))
+    }
+}
+
+/// delta of the confirmation rates in percentage points with its 95% confidence interval
+fn confirmation_rate_delta(baseline: &RunSample, candidate: &RunSample) -> (f64, f64, f64) {
+    let (p1, p2) = (baseline.confirmation_rate(), candidate.confirmation_rate());
+    let variance = |p: f64, n: u64| {
+        if n == 0 {
+            0.0
+        } else {
+            p * (1.0 - p) / n as f64
+        }
+    };
+    let half_width = Z_95 * (variance(p1, baseline.sent) + variance(p2, candidate.sent)).sqrt();
+    let delta = p2 - p1;
+    (
+        delta * 100.0,
+        (delta - half_width) * 100.0,
+        (delta + half_width) * 100.0,
+    )
+}
+
+#[derive(Debug, Default)]
+struct Verdict {
+    regressions: Vec<String>,
+    improvements: Vec<String>,
+}
+
+impl Verdict {
+    fn compare(
+        &mut self,
+        name: &str,
+        baseline: &RunSample,
+        candidate: &RunSample,
+        config: &CompareConfig,
+        percentiles: &[f64],
+    ) {
+        let (delta, low, high) = confirmation_rate_delta(baseline, candidate);
+        println!(
+            "{name} confirmation rate : {:.2}% -> {:.2}%, delta {delta:+.2} points (95% CI {low:+.2} to {high:+.2})",
+            baseline.confirmation_rate() * 100.0,
+            candidate.confirmation_rate() * 100.0,
+        );
+        if high < 0.0 && -delta > config.max_confirmation_rate_drop {
+            self.regressions.push(format!(
+                "{name} confirmation rate dropped by {:.2} points",
+                -delta
+            ));
+        } else if low > 0.0 && delta > config.max_confirmation_rate_drop {
+            self.improvements.push(format!(
+                "{name} confirmation rate rose by {delta:.2} points"
+            ));
+        }
+
+        for p in percentiles {
+            let index = PERCENTILES.iter().position(|x| x == p).unwrap();
+            let (Some(before), Some(after)) =
+                (baseline.percentiles_ms[index], candidate.percentiles_ms[index])
+            else {
+                println!("{name} latency p{p} : not enough confirmed transactions");
+                continue;
+            };
+            let delta = after as i64 - before as i64;
+            let delta_pct = if before == 0 {
+                0.0
+            } else {
+                delta as f64 * 100.0 / before as f64
+            };
+            // without the individual latencies a change is only judged on its size
+            let (significant_up, significant_down, ci) =
+                match (baseline.percentile_ci(*p), candidate.percentile_ci(*p)) {
+                    (Some((b_low, b_high)), Some((c_low, c_high))) => {
+                        let low = c_low as i64 - b_high as i64;
+                        let high = c_high as i64 - b_low as i64;
+                        (
+                            low > 0,
+                            high < 0,
+                            format!(" (95% CI {low:+} to {high:+} ms)"),
+                        )
+                    }
+                    _ => (true, true, String::new()),
+                };
+            println!(
+                "{name} latency p{p} : {before} ms -> {after} ms, delta {delta:+} ms, {delta_pct:+.1}%{ci}"
+            );
+            if significant_up && delta_pct > config.max_latency_increase_pct {
+                self.regressions
+                    .push(format!("{name} latency p{p} increased by {delta_pct:.1}%"));
+            } else if significant_down && -delta_pct > config.max_latency_increase_pct {
+                self.improvements.push(format!(
+                    "{name} latency p{p} decreased by {:.1}%",
+                    -delta_pct
+                ));
+            }
+        }
+    }
+}
+
+enum RunData {
+    Transactions(Vec<TransactionRow>),
+    Summary(SummaryResults),
+}
+
+async fn load(path: &str) -> anyhow::Result<RunData> {
+    if path.ends_with(".json") {
+        let summary: SummaryFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(RunData::Summary(summary.results))
+    } else {
+        let (_, rows) = read_transactions(path).await?;
+        // chaos transactions are expected to fail
+        Ok(RunData::Transactions(
+            rows.into_iter().filter(|r| r.chaos.is_none()).collect(),
+        ))
+    }
+}
+
+fn market_samples(rows: &[TransactionRow]) -> HashMap<String, RunSample> {
+    rows.iter()
+        .filter(|r| r.keeper_instruction.is_none())
+        .filter_map(|r| r.market.clone().map(|m| (m, r)))
+        .into_group_map()
+        .into_iter()
+        .map(|(market, rows)| (market, RunSample::from_rows(&rows)))
+        .collect()
+}
+
+/// Compares a candidate run against a baseline, each given as a transaction csv or a summary json
+/// file. Fails when a regression beyond the configured tolerances is statistically significant
+pub async fn compare(config: CompareConfig) -> anyhow::Result<()> {
+    let baseline = load(&config.baseline).await?;
+    let candidate = load(&config.candidate).await?;
+    println!(
+        "\n\nComparison of {} (candidate) against {} (baseline)",
+        config.candidate, config.baseline
+    );
+
+    let sample = |data: &RunData| match data {
+        RunData::Transactions(rows) => RunSample::from_rows(&rows.iter().collect_vec()),
+        RunData::Summary(summary) => RunSample::from_summary(summary),
+    };
+    let mut verdict = Verdict::default();
+    verdict.compare(
+        "All transactions",
+        &sample(&baseline),
+        &sample(&candidate),
+        &config,
+        &PERCENTILES,
+    );
+
+    if let (RunData::Transactions(baseline), RunData::Transactions(candidate)) =
+        (&baseline, &candidate)
+    {
+        let baseline_markets = market_samples(baseline);
+        let candidate_markets = market_samples(candidate);
+        for (market, baseline_market) in baseline_markets.iter().sorted_by_key(|x| x.0) {
+            match candidate_markets.get(market) {
+                Some(candidate_market) => verdict.compare(
+                    &format!("Market {market}"),
+                    baseline_market,
+                    candidate_market,
+                    &config,
+                    &[50.0],
+                ),
+                None => println!("Market {market} : not quoted in the candidate run"),
+            }
+        }
+    }
+
+    if !verdict.improvements.is_empty() {
+        println!("\nImprovements :\n  {}", verdict.improvements.join("\n  "));
+    }
+    if verdict.regressions.is_empty() {
+        println!("\nVerdict : no significant regression");
+        Ok(())
+    } else {
+        println!("\nRegressions :\n  {}", verdict.regressions.join("\n  "));
+        anyhow::bail!(
+            "the candidate run regressed : {}",
+            verdict.regressions.join(", ")
+        )
+    }
+}
@@ -0,0 +1,118 @@
+use std::str::FromStr;
+
+use async_compression::futures::{
+    bufread::{GzipDecoder, ZstdDecoder},
+    write::{GzipEncoder, ZstdEncoder},
+};
+use async_std::{
+    fs::File,
+    io::{BufReader, Read, Write},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression {s}")),
+        }
+    }
+}
+
+impl Compression {
+    fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+
+    fn from_path(path: &str) -> Option<Self> {
+        [Compression::Gzip, Compression::Zstd]
+            .into_iter()
+            .find(|c| path.ends_with(&format!(".{}", c.extension())))
+    }
+}
+
+pub type OutputWriter = Box<dyn Write + Send + Unpin>;
+pub type InputReader = Box<dyn Read + Send + Unpin>;
+
+/// path of an output once compressed, tlog.csv is written to tlog.csv.zst
+pub fn compressed_path(path: &str, compression: Option<Compression>) -> String {
+    match compression {
+        Some(compression) => format!("{path}.{}", compression.extension()),
+        None => path.to_string(),
+    }
+}
+
+/// Compresses the rows as they are written, the writer has to be closed to end the stream
+pub fn compress(file: File, compression: Option<Compression>) -> OutputWriter {
+    match compression {
+        Some(Compression::Gzip) => Box::new(GzipEncoder::new(file)),
+        Some(Compression::Zstd) => Box::new(ZstdEncoder::new(file)),
+        None => Box::new(file),
+    }
+}
+
+/// opens an output, decompressed according to the extension of its path
+pub async fn open_decompressed(path: &str) -> std::io::Result<InputReader> {
+    let file = File::open(path).await?;
+    Ok(match Compression::from_path(path) {
+        Some(Compression::Gzip) => Box::new(GzipDecoder::new(BufReader::new(file))),
+        Some(Compression::Zstd) => Box::new(ZstdDecoder::new(BufReader::new(file))),
+        None => Box::new(file),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::io::{ReadExt, WriteExt};
+
+    use super::*;
+
+    #[test]
+    fn compressed_outputs_get_the_extension() {
+        assert_eq!(compressed_path("tlog.csv", None), "tlog.csv");
+        assert_eq!(
+            compressed_path("tlog.csv", Some(Compression::Zstd)),
+            "tlog.csv.zst"
+        );
+        assert_eq!(
+            Compression::from_path("tlog.csv.gz"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::from_path("tlog.csv"), None);
+        assert_eq!("ZSTD".parse::<Compression>(), Ok(Compression::Zstd));
+        assert!("lz4".parse::<Compression>().is_err());
+    }
+
+    #[tokio::test]
+    async fn compressed_outputs_read_back() {
+        let dir = std::env::temp_dir().join(format!("compression-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for compression in [None, Some(Compression::Gzip), Some(Compression::Zstd)] {
+            let path = compressed_path(dir.join("rows.csv").to_str().unwrap(), compression);
+            let mut writer = compress(File::create(&path).await.unwrap(), compression);
+            writer.write_all(b"signature,slot\nabc,1\n").await.unwrap();
+            writer.close().await.unwrap();
+
+            let mut rows = String::new();
+            open_decompressed(&path)
+                .await
+                .unwrap()
+                .read_to_string(&mut rows)
+                .await
+                .unwrap();
+            assert_eq!(rows, "signature,slot\nabc,1\n");
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
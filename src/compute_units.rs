@@ -0,0 +1,110 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use chrono::Utc;
+use log::{info, warn};
+use rand::{rngs::StdRng, SeedableRng};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{commitment_config::CommitmentConfig, hash::Hash};
+
+use crate::{
+    mango::AccountKeys,
+    mango_program::MangoProgram,
+    market_markers::{create_ask_bid_transaction, create_spoofing_transaction},
+    order_mix::QuoteOrder,
+    signing::MarketMakerSigners,
+    states::PerpMarketCache,
+};
+
+// the templates are simulated with the highest limit so that they cannot run out of units
+const SIMULATION_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+// the orders of a quote can match or expire other orders, which costs more than the template
+const COMPUTE_UNIT_MARGIN_PCT: u64 = 20;
+
+/// limit requested for a template which consumed the units, with a margin
+fn compute_unit_limit(units_consumed: u64) -> u32 {
+    (units_consumed * (100 + COMPUTE_UNIT_MARGIN_PCT) / 100)
+        .min(SIMULATION_COMPUTE_UNIT_LIMIT as u64) as u32
+}
+
+/// Simulates the quote transaction of every market once with the first market maker and sets
+/// the compute unit limit of the market to what it consumed, with a margin. The limit of a
+/// market whose template does not simulate is left unset
+pub async fn estimate_compute_unit_limits(
+    rpc_client: &RpcClient,
+    mango_program: &dyn MangoProgram,
+    market_maker: &AccountKeys,
+    perp_market_caches: &mut [PerpMarketCache],
+    spoofing_levels: Option<u8>,
+    blockhash: Hash,
+) -> anyhow::Result<()> {
+    let signers = MarketMakerSigners::load(market_maker)?;
+    let mango_account_pk = Pubkey::from_str(
+        market_maker
+            .mango_account_pks
+            .first()
+            .context("the market maker has no mango account")?,
+    )?;
+    let mut rng = StdRng::seed_from_u64(0);
+    for cache in perp_market_caches.iter_mut() {
+        let mut template = cache.clone();
+        template.quote_config.compute_unit_limit = Some(SIMULATION_COMPUTE_UNIT_LIMIT);
+        let client_order_id = Utc::now().timestamp_micros() as u64;
+        let mut tx = match spoofing_levels {
+            Some(levels) => create_spoofing_transaction(
+                mango_program,
+                &template,
+                mango_account_pk,
+                &signers,
+                0,
+                levels,
+                client_order_id,
+                &mut rng,
+            ),
+            None => create_ask_bid_transaction(
+                mango_program,
+                &template,
+                mango_account_pk,
+                &signers,
+                0,
+                0,
+                client_order_id,
+                QuoteOrder::default(),
+                &mut rng,
+            ),
+        };
+        tx.sign(&signers.signers(), blockhash);
+        let simulation = rpc_client
+            .simulate_transaction_with_config(
+                &tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(CommitmentConfig::processed()),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )
+            .await?
+            .value;
+        match (simulation.err, simulation.units_consumed) {
+            (None, Some(units_consumed)) => {
+                let limit = compute_unit_limit(units_consumed);
+                info!(
+                    "quotes on {} consume {units_consumed} compute units, requesting {limit}",
+                    cache.perp_market_pk
+                );
+                cache.quote_config.compute_unit_limit = Some(limit);
+            }
+            (Some(e), _) => warn!(
+                "could not estimate the compute units of the quotes on {}, the simulation failed : {e}",
+                cache.perp_market_pk
+            ),
+            (None, None) => warn!(
+                "could not estimate the compute units of the quotes on {}, the node did not return them",
+                cache.perp_market_pk
+            ),
+        }
+    }
+    Ok(())
+}
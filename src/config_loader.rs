@@ -0,0 +1,51 @@
+use std::{fs, time::Duration};
+
+use anyhow::Context;
+use log::info;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// true when the config is fetched instead of read from the local filesystem
+pub fn is_remote(location: &str) -> bool {
+    ["http://", "https://", "s3://", "gs://"]
+        .iter()
+        .any(|scheme| location.starts_with(scheme))
+}
+
+// https url of an object of a bucket, with an access token for google cloud storage. s3 needs
+// signed requests, which are not implemented, so its objects are only read from presigned urls
+fn object_url(location: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        !location.starts_with("s3://"),
+        "{location} : s3:// uris are not supported, pass a presigned https url of the object"
+    );
+    if let Some(path) = location.strip_prefix("gs://") {
+        return Ok(format!("https://storage.googleapis.com/{path}"));
+    }
+    Ok(location.to_string())
+}
+
+/// Reads a json config from a local file or fetches it from an http(s) or gs:// uri, so
+/// that a fleet can share its accounts and mango keys without baking them into every image
+pub async fn load_config(location: &str) -> anyhow::Result<String> {
+    if !is_remote(location) {
+        return fs::read_to_string(location).with_context(|| format!("unable to read {location}"));
+    }
+    let url = object_url(location)?;
+    info!("fetching {location} from {url}");
+    let mut request = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()?
+        .get(&url);
+    if location.starts_with("gs://") {
+        if let Ok(token) = std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+            request = request.bearer_auth(token);
+        }
+    }
+    let response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("unable to fetch {location}"))?;
+    Ok(response.text().await?)
+}
@@ -1,52 +1,88 @@
 use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
     time::Duration,
 };
 
-use chrono::Utc;
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
+use futures::StreamExt;
 use log::{debug, warn};
 use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig};
 use solana_lite_rpc_core::notifications::NotificationMsg;
+use solana_metrics::datapoint_info;
 use solana_sdk::{
     commitment_config::{CommitmentConfig, CommitmentLevel},
     signature::Signature,
     slot_history::Slot,
+    transaction::TransactionError,
 };
 use solana_transaction_status::{
     option_serializer::OptionSerializer, RewardType, TransactionDetails, UiConfirmedBlock,
     UiTransactionEncoding,
 };
 
-use crate::states::{BlockData, TransactionConfirmRecord, TransactionSendRecord};
+use crate::{
+    clock,
+    clock_skew::ClockSkew,
+    helpers::{block_cu_utilization, percentile},
+    mango_errors,
+    states::{BlockData, TransactionConfirmRecord, TransactionSendRecord},
+    watchdog::Watchdog,
+};
 
 use tokio::{
-    sync::broadcast::Sender, sync::mpsc::UnboundedReceiver, task::JoinHandle, time::Instant,
+    sync::mpsc::{channel, error::TryRecvError, Receiver, Sender, UnboundedReceiver},
+    task::JoinHandle,
+    time::Instant,
 };
 
+// transactions sent during the last leader window before a block are expected in it
+const LEADER_WINDOW_SLOTS: u64 = 4;
+
+/// min, median and max of the inclusion delays in slots
+fn inclusion_delay_stats(mut delays: Vec<u64>) -> (Option<u64>, Option<u64>, Option<u64>) {
+    delays.sort_unstable();
+    (
+        delays.first().copied(),
+        percentile(&delays, 50.0),
+        delays.last().copied(),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn process_blocks(
     block: &UiConfirmedBlock,
-    tx_confirm_records: Sender<TransactionConfirmRecord>,
-    tx_block_data: Sender<BlockData>,
+    tx_confirm_records: tokio::sync::broadcast::Sender<TransactionConfirmRecord>,
+    tx_block_data: tokio::sync::broadcast::Sender<BlockData>,
     transaction_map: Arc<DashMap<Signature, (TransactionSendRecord, Instant)>>,
     slot: u64,
     commitment: CommitmentLevel,
+    scheduled_leader: Option<String>,
+    clock_skew: &ClockSkew,
 ) {
+    clock_skew.record_block(slot, block.block_time);
     let mut mm_transaction_count: u64 = 0;
-    let rewards = block.rewards.as_ref().unwrap();
-    let slot_leader = match rewards
-        .iter()
-        .find(|r| r.reward_type == Some(RewardType::Fee))
-    {
-        Some(x) => x.pubkey.clone(),
-        None => "".to_string(),
-    };
+    // leader from the schedule if known, otherwise the validator which collected the fees of the block
+    let slot_leader = scheduled_leader.unwrap_or_else(|| {
+        block
+            .rewards
+            .as_ref()
+            .and_then(|rewards| {
+                rewards
+                    .iter()
+                    .find(|r| r.reward_type == Some(RewardType::Fee))
+            })
+            .map(|x| x.pubkey.clone())
+            .unwrap_or_default()
+    });
 
     if let Some(transactions) = &block.transactions {
         let nb_transactions = transactions.len();
+        let mut inclusion_delays = vec![];
         let mut mm_cu_consumed: u64 = 0;
         let mut total_cu_consumed: u64 = 0;
         for solana_transaction_status::EncodedTransactionWithStatusMeta {
@@ -71,12 +107,27 @@ pub async fn process_blocks(
                 if let Some((_, (transaction_record, _))) = transaction_map.remove(signature) {
                     mm_transaction_count += 1;
                     mm_cu_consumed = mm_cu_consumed.saturating_add(tx_cu_consumed);
+                    inclusion_delays.push(slot.saturating_sub(transaction_record.sent_slot));
+                    let failure = meta
+                        .as_ref()
+                        .and_then(|meta| {
+                            let error = meta.err.as_ref()?;
+                            match &meta.log_messages {
+                                OptionSerializer::Some(logs) => mango_errors::from_logs(logs),
+                                _ => None,
+                            }
+                            .or_else(|| mango_errors::from_error(error))
+                        })
+                        .unwrap_or_default();
 
-                    match tx_confirm_records.send(TransactionConfirmRecord {
+                    let confirmed_at = clock::now();
+                    let mut record = TransactionConfirmRecord {
                         signature: transaction_record.signature.to_string(),
                         confirmed_slot: Some(slot),
-                        confirmed_at: Some(Utc::now().to_string()),
+                        confirmed_at: Some(confirmed_at.to_string()),
+                        confirmed_at_us: Some(confirmed_at.timestamp_micros()),
                         sent_at: transaction_record.sent_at.to_string(),
+                        sent_at_us: transaction_record.sent_at_us,
                         sent_slot: transaction_record.sent_slot,
                         successful: if let Some(meta) = &meta {
                             meta.status.is_ok()
@@ -88,15 +139,43 @@ pub async fn process_blocks(
                         } else {
                             None
                         },
+                        mango_error_code: failure.error_code,
+                        failed_instruction: failure.failed_instruction,
                         block_hash: Some(block.blockhash.clone()),
                         market: transaction_record.market.map(|x| x.to_string()),
                         market_maker: transaction_record.market_maker.map(|x| x.to_string()),
                         keeper_instruction: transaction_record.keeper_instruction,
+                        tx_type: transaction_record.tx_type,
+                        blockhash_slot: transaction_record.blockhash_slot,
+                        blockhash_age_slots: transaction_record.blockhash_age_slots,
+                        last_valid_block_height: transaction_record.last_valid_block_height,
+                        chaos: transaction_record.chaos,
+                        send_delay_ms: transaction_record.send_delay_ms,
+                        tx_size_bytes: transaction_record.tx_size_bytes,
+                        num_instructions: transaction_record.num_instructions,
+                        order_type: transaction_record.order_type,
+                        reduce_only: transaction_record.reduce_only,
+                        client_order_id: transaction_record.client_order_id,
+                        route: transaction_record.route,
                         slot_processed: Some(slot),
                         slot_leader: Some(slot_leader.clone()),
+                        expired: false,
                         timed_out: false,
                         priority_fees: transaction_record.priority_fees,
-                    }) {
+                        spoofing: transaction_record.spoofing,
+                        phase: transaction_record.phase,
+                        commitment_processed_at: None,
+                        commitment_confirmed_at: None,
+                        commitment_finalized_at: None,
+                        inclusion_latency_ms: None,
+                        clock_offset_ms: None,
+                        corrected_inclusion_latency_ms: None,
+                        run_id: String::new(),
+                        cluster: None,
+                        shard: None,
+                    };
+                    clock_skew.annotate(&mut record);
+                    match tx_confirm_records.send(record) {
                         Ok(_) => {}
                         Err(e) => {
                             warn!("Tx confirm record channel broken {}", e.to_string());
@@ -107,6 +186,15 @@ pub async fn process_blocks(
         }
         // push block data
         {
+            let (inclusion_delay_min, inclusion_delay_median, inclusion_delay_max) =
+                inclusion_delay_stats(inclusion_delays);
+            let missed_transactions = transaction_map
+                .iter()
+                .filter(|x| {
+                    let sent_slot = x.value().0.sent_slot;
+                    sent_slot <= slot && sent_slot + LEADER_WINDOW_SLOTS >= slot
+                })
+                .count() as u64;
             let _ = tx_block_data.send(BlockData {
                 block_hash: block.blockhash.clone(),
                 block_leader: slot_leader,
@@ -120,13 +208,22 @@ pub async fn process_blocks(
                 total_transactions: nb_transactions as u64,
                 cu_consumed: total_cu_consumed,
                 cu_consumed_by_mango_simulations: mm_cu_consumed,
+                cu_utilization_pct: block_cu_utilization(total_cu_consumed),
                 commitment,
+                inclusion_delay_min,
+                inclusion_delay_median,
+                inclusion_delay_max,
+                missed_transactions,
+                phase: None,
+                run_id: String::new(),
+                cluster: None,
+                shard: None,
             });
         }
     }
 }
 
-async fn get_blocks_with_retry(
+pub(crate) async fn get_blocks_with_retry(
     client: Arc<RpcClient>,
     start_block: u64,
     commitment_confirmation: CommitmentConfig,
@@ -149,30 +246,110 @@ async fn get_blocks_with_retry(
     Err(())
 }
 
+// confirmed and finalized are reported by lite-rpc block notifications, processed transactions
+// are only seen once their block is confirmed
+fn commitment_rank(commitment: CommitmentLevel) -> u8 {
+    let commitment = CommitmentConfig { commitment };
+    if commitment.is_finalized() {
+        2
+    } else if commitment.is_confirmed() {
+        1
+    } else {
+        0
+    }
+}
+
+/// Times at which a transaction was notified at each commitment level, tracked until it is
+/// finalized. A level which was skipped, like processed for a transaction first notified as
+/// confirmed, stays unknown
+#[derive(Clone, Default)]
+struct CommitmentProgress {
+    processed_at: Option<String>,
+    confirmed_at: Option<String>,
+    finalized_at: Option<String>,
+    // record built when the transaction reached the commitment of the benchmark
+    confirm_record: Option<TransactionConfirmRecord>,
+}
+
+impl CommitmentProgress {
+    fn update(&mut self, rank: u8, at: &str) {
+        let time = match rank {
+            0 => &mut self.processed_at,
+            1 => &mut self.confirmed_at,
+            _ => &mut self.finalized_at,
+        };
+        if time.is_none() {
+            *time = Some(at.to_string());
+        }
+    }
+
+    fn into_record(self, track_all_commitments: bool) -> Option<TransactionConfirmRecord> {
+        let mut record = self.confirm_record?;
+        if track_all_commitments {
+            record.commitment_processed_at = self.processed_at;
+            record.commitment_confirmed_at = self.confirmed_at;
+            record.commitment_finalized_at = self.finalized_at;
+        }
+        Some(record)
+    }
+}
+
+/// Confirms the transactions sent through lite-rpc from its block and transaction notifications.
+/// lite-rpc pushes the blocks, so there is nothing to fetch and this path runs as two tasks only :
+/// the confirming task tracks the send records and matches them against the notifications, and
+/// the cleaner reports the transactions which expired or timed out. As with the blocks, the send
+/// records stay on an unbounded channel
+#[allow(clippy::too_many_arguments)]
 pub fn confirmation_by_lite_rpc_notification_stream(
+    client: Arc<RpcClient>,
     tx_record_rx: UnboundedReceiver<TransactionSendRecord>,
     notification_stream: UnboundedReceiver<NotificationMsg>,
     tx_confirm_records: tokio::sync::broadcast::Sender<TransactionConfirmRecord>,
     tx_block_data: tokio::sync::broadcast::Sender<BlockData>,
     exit_signal: Arc<AtomicBool>,
+    commitment: CommitmentLevel,
+    track_all_commitments: bool,
+    clock_skew: ClockSkew,
+    watchdog: Watchdog,
 ) -> Vec<JoinHandle<()>> {
-    let transaction_map: Arc<DashMap<String, (TransactionSendRecord, Instant)>> =
-        Arc::new(DashMap::new());
+    let transaction_map: Arc<
+        DashMap<String, (TransactionSendRecord, Instant, CommitmentProgress)>,
+    > = Arc::new(DashMap::new());
+    // blocks are not notified before they are confirmed
+    let block_rank = commitment_rank(commitment).max(1);
+    // the record is written once the transaction reaches this rank
+    let record_rank = if track_all_commitments {
+        2
+    } else {
+        commitment_rank(commitment)
+    };
 
     let confirming_task = {
         let transaction_map = transaction_map.clone();
         let tx_confirm_records = tx_confirm_records.clone();
+        let clock_skew = clock_skew.clone();
         let exit_signal = exit_signal.clone();
         tokio::spawn(async move {
             let mut tx_record_rx = tx_record_rx;
             let mut notification_stream = notification_stream;
+            // inclusion delays of the transactions per slot, until the block of the slot is notified
+            let mut inclusion_delays: HashMap<Slot, Vec<u64>> = HashMap::new();
+            let mut report_interval = tokio::time::interval(QUEUE_DEPTHS_REPORT_INTERVAL);
 
             while !transaction_map.is_empty() || !exit_signal.load(Ordering::Relaxed) {
+                watchdog.heartbeat("confirmation");
                 tokio::select! {
                     transaction_record = tx_record_rx.recv() => {
                         if let Some(transaction_record) = transaction_record{
-                            transaction_map
-                            .insert(transaction_record.signature.to_string(), (transaction_record, Instant::now()));
+                            let signature = transaction_record.signature.to_string();
+                            match transaction_map.entry(signature.clone()) {
+                                Entry::Occupied(_) => {
+                                    let _ = tx_confirm_records.send(duplicate_record(signature, &transaction_record));
+                                }
+                                Entry::Vacant(entry) => {
+                                    entry.insert((transaction_record, Instant::now(), CommitmentProgress::default()));
+                                }
+                            }
                         }
 
                     },
@@ -181,9 +358,24 @@ pub fn confirmation_by_lite_rpc_notification_stream(
 
                             match notification {
                                 NotificationMsg::BlockNotificationMsg(block_notification) => {
-                                    if block_notification.commitment != CommitmentLevel::Finalized {
+                                    if commitment_rank(block_notification.commitment) != block_rank {
                                         continue;
                                     }
+                                    let block_slot = block_notification.slot;
+                                    clock_skew.record_block(block_slot, Some(block_notification.block_time as i64));
+                                    let (inclusion_delay_min, inclusion_delay_median, inclusion_delay_max) =
+                                        inclusion_delay_stats(inclusion_delays.remove(&block_slot).unwrap_or_default());
+                                    // blocks can be notified out of order, slots without a block would leak otherwise
+                                    inclusion_delays.retain(|slot, _| *slot + 32 > block_slot);
+                                    let missed_transactions = transaction_map
+                                        .iter()
+                                        .filter(|x| {
+                                            let (sent_record, _, progress) = x.value();
+                                            progress.processed_at.is_none()
+                                                && sent_record.sent_slot <= block_slot
+                                                && sent_record.sent_slot + LEADER_WINDOW_SLOTS >= block_slot
+                                        })
+                                        .count() as u64;
                                     let _ = tx_block_data.send(BlockData {
                                         block_hash: block_notification.blockhash.to_string(),
                                         block_leader: block_notification.block_leader,
@@ -193,44 +385,111 @@ pub fn confirmation_by_lite_rpc_notification_stream(
                                         total_transactions: block_notification.total_transactions,
                                         cu_consumed: block_notification.total_cu_consumed,
                                         cu_consumed_by_mango_simulations: block_notification.cu_consumed_by_txs,
+                                        cu_utilization_pct: block_cu_utilization(block_notification.total_cu_consumed),
                                         commitment: block_notification.commitment,
+                                        inclusion_delay_min,
+                                        inclusion_delay_median,
+                                        inclusion_delay_max,
+                                        missed_transactions,
+                                        phase: None,
+                                        run_id: String::new(),
+                                        cluster: None,
+                                        shard: None,
                                     });
                                 }
                                 NotificationMsg::UpdateTransactionMsg(tx_update_notifications) => {
 
                                     for tx_notification in tx_update_notifications {
-                                        if tx_notification.commitment != CommitmentLevel::Finalized {
+                                        let rank = commitment_rank(tx_notification.commitment);
+                                        if !track_all_commitments && rank < record_rank {
                                             continue;
                                         }
 
-                                        if let Some(value) = transaction_map.get(&tx_notification.signature) {
-                                            let (tx_sent_record, _) = value.clone();
+                                        let Some(mut value) = transaction_map.get_mut(&tx_notification.signature) else {
+                                            continue;
+                                        };
+                                        let (tx_sent_record, _, progress) = value.value_mut();
+                                        if rank == block_rank {
+                                            inclusion_delays
+                                                .entry(tx_notification.slot)
+                                                .or_default()
+                                                .push(tx_notification.slot.saturating_sub(tx_sent_record.sent_slot));
+                                        }
+                                        let now_at = clock::now();
+                                        let now = now_at.to_string();
+                                        progress.update(rank, &now);
+                                        if progress.confirm_record.is_none() && rank >= commitment_rank(commitment) {
                                             let error = match &tx_notification.transaction_status {
                                                 Err(e) => {
                                                     Some(e.to_string())
                                                 },
                                                 _ => None
                                             };
-                                            let _ = tx_confirm_records.send(TransactionConfirmRecord {
+                                            // lite-rpc does not notify the logs
+                                            let failure = tx_notification
+                                                .transaction_status
+                                                .as_ref()
+                                                .err()
+                                                .and_then(mango_errors::from_error)
+                                                .unwrap_or_default();
+                                            progress.confirm_record = Some(TransactionConfirmRecord {
                                                 signature: tx_notification.signature.clone(),
                                                 confirmed_slot: Some(tx_notification.slot),
-                                                confirmed_at: Some(Utc::now().to_string()),
+                                                confirmed_at: Some(now),
+                                                confirmed_at_us: Some(now_at.timestamp_micros()),
                                                 sent_at: tx_sent_record.sent_at.to_string(),
+                                                sent_at_us: tx_sent_record.sent_at_us,
                                                 sent_slot: tx_sent_record.sent_slot,
                                                 successful: tx_notification.transaction_status.is_ok(),
                                                 error,
-                                                block_hash: Some(tx_notification.blockhash),
+                                                mango_error_code: failure.error_code,
+                                                failed_instruction: failure.failed_instruction,
+                                                block_hash: Some(tx_notification.blockhash.clone()),
                                                 market: tx_sent_record.market.map(|x| x.to_string()),
                                                 market_maker: tx_sent_record.market_maker.map(|x| x.to_string()),
                                                 keeper_instruction: tx_sent_record.keeper_instruction.clone(),
+                                                tx_type: tx_sent_record.tx_type,
+                                                blockhash_slot: tx_sent_record.blockhash_slot,
+                                                blockhash_age_slots: tx_sent_record.blockhash_age_slots,
+                                                last_valid_block_height: tx_sent_record.last_valid_block_height,
+                                                chaos: tx_sent_record.chaos,
+                                                send_delay_ms: tx_sent_record.send_delay_ms,
+                                                tx_size_bytes: tx_sent_record.tx_size_bytes,
+                                                num_instructions: tx_sent_record.num_instructions,
+                                                order_type: tx_sent_record.order_type,
+                                                reduce_only: tx_sent_record.reduce_only,
+                                                client_order_id: tx_sent_record
+                                                    .client_order_id,
+                                                route: tx_sent_record.route,
                                                 slot_processed: Some(tx_notification.slot),
                                                 slot_leader: Some(tx_notification.leader.to_string()),
+                                                expired: false,
                                                 timed_out: false,
                                                 priority_fees: tx_sent_record.priority_fees,
+                                                spoofing: tx_sent_record.spoofing,
+                                                phase: tx_sent_record.phase.clone(),
+                                                commitment_processed_at: None,
+                                                commitment_confirmed_at: None,
+                                                commitment_finalized_at: None,
+                                                inclusion_latency_ms: None,
+                                                clock_offset_ms: None,
+                                                corrected_inclusion_latency_ms: None,
+                                                run_id: String::new(),
+                                                cluster: None,
+                                                shard: None,
                                             });
                                         }
+                                        if rank < record_rank {
+                                            continue;
+                                        }
+                                        drop(value);
 
-                                        transaction_map.remove(&tx_notification.signature);
+                                        if let Some((_, (_, _, progress))) = transaction_map.remove(&tx_notification.signature) {
+                                            if let Some(mut record) = progress.into_record(track_all_commitments) {
+                                                clock_skew.annotate(&mut record);
+                                                let _ = tx_confirm_records.send(record);
+                                            }
+                                        }
                                     }
                                 },
                                 _ => {
@@ -239,12 +498,16 @@ pub fn confirmation_by_lite_rpc_notification_stream(
                             }
                         }
                     },
-                    _ = tokio::time::sleep(Duration::from_secs(1)) => {
-                        // timeout
-                        continue;
+                    _ = report_interval.tick() => {
+                        // also wakes the loop up to check the exit signal
+                        datapoint_info!(
+                            "bencher-confirmation-queues",
+                            ("pending_transactions", transaction_map.len(), i64),
+                        );
                     }
                 }
             }
+            watchdog.unregister("confirmation");
             log::info!("stopped processing the transactions");
         })
     };
@@ -253,43 +516,70 @@ pub fn confirmation_by_lite_rpc_notification_stream(
         let transaction_map = transaction_map;
         let exit_signal = exit_signal;
         let tx_confirm_records = tx_confirm_records;
+        // processed blocks can be skipped, the height is at least confirmed
+        let height_commitment = CommitmentConfig {
+            commitment: if commitment == CommitmentLevel::Finalized {
+                CommitmentLevel::Finalized
+            } else {
+                CommitmentLevel::Confirmed
+            },
+        };
         tokio::spawn(async move {
             loop {
-                tokio::time::sleep(Duration::from_secs(60)).await;
+                tokio::time::sleep(TIMEOUT_SCAN_INTERVAL).await;
                 {
-                    let mut to_remove = vec![];
-
-                    for tx_data in transaction_map.iter() {
-                        let sent_record = &tx_data.0;
-                        let instant = tx_data.1;
-                        let signature = tx_data.key();
-                        let remove = instant.elapsed() > Duration::from_secs(120);
+                    let block_height = match client
+                        .get_block_height_with_commitment(height_commitment)
+                        .await
+                    {
+                        Ok(block_height) => Some(block_height),
+                        Err(e) => {
+                            warn!("Failed to get the block height : {e}");
+                            None
+                        }
+                    };
+                    // signatures due, expired or timed out, and whether they expired before the timeout
+                    let due = transaction_map
+                        .iter()
+                        .filter_map(|tx_data| {
+                            let (sent_record, instant, progress) = tx_data.value();
+                            // a transaction seen at any commitment may still be notified as confirmed
+                            let expired = progress.processed_at.is_none()
+                                && is_expired(sent_record, block_height);
+                            let timed_out = instant.elapsed() > CONFIRMATION_TIMEOUT;
+                            (expired || timed_out)
+                                .then(|| (tx_data.key().clone(), expired, expired && !timed_out))
+                        })
+                        .collect::<Vec<_>>();
+                    // the notifications can lag behind the block height, an expired transaction
+                    // may have landed before its blockhash expired and still be notified
+                    let early_expiries = due
+                        .iter()
+                        .filter(|(_, _, early)| *early)
+                        .map(|(signature, _, _)| signature.clone())
+                        .collect::<Vec<_>>();
+                    let maybe_landed = maybe_landed_signatures(&client, &early_expiries).await;
 
-                        // add to timeout if not retaining
-                        if remove {
-                            let _ = tx_confirm_records.send(TransactionConfirmRecord {
-                                signature: signature.to_string(),
-                                confirmed_slot: None,
-                                confirmed_at: None,
-                                sent_at: sent_record.sent_at.to_string(),
-                                sent_slot: sent_record.sent_slot,
-                                successful: false,
-                                error: Some("timeout".to_string()),
-                                block_hash: None,
-                                market: sent_record.market.map(|x| x.to_string()),
-                                market_maker: sent_record.market_maker.map(|x| x.to_string()),
-                                keeper_instruction: sent_record.keeper_instruction.clone(),
-                                slot_processed: None,
-                                slot_leader: None,
-                                timed_out: true,
-                                priority_fees: sent_record.priority_fees,
-                            });
-                            to_remove.push(signature.clone());
+                    for (signature, expired, _) in due {
+                        if maybe_landed.contains(&signature) {
+                            continue;
+                        }
+                        let Some((_, (sent_record, _, progress))) = transaction_map.remove(&signature) else {
+                            continue;
+                        };
+                        // transactions which reached the benchmark commitment but not the finalized one
+                        if let Some(mut record) = progress.into_record(track_all_commitments) {
+                            clock_skew.annotate(&mut record);
+                            let _ = tx_confirm_records.send(record);
+                            continue;
                         }
-                    }
 
-                    for signature in to_remove {
-                        transaction_map.remove(&signature);
+                        // add to timeout if not retaining
+                        let _ = tx_confirm_records.send(unconfirmed_record(
+                            signature,
+                            &sent_record,
+                            expired,
+                        ));
                     }
 
                     // if exit and all the transactions are processed
@@ -303,169 +593,630 @@ pub fn confirmation_by_lite_rpc_notification_stream(
     vec![confirming_task, cleaner_jh]
 }
 
-#[deprecated]
+/// signatures of which the RPC node knows a status, or which could not be looked up. They are left
+/// waiting for their notification, at worst until they time out
+async fn maybe_landed_signatures(client: &RpcClient, signatures: &[String]) -> HashSet<String> {
+    let mut maybe_landed = HashSet::new();
+    for batch in signatures.chunks(SIGNATURE_STATUSES_BATCH_SIZE) {
+        let parsed = batch
+            .iter()
+            .map(|signature| Signature::from_str(signature).expect("tracked signatures are valid"))
+            .collect::<Vec<_>>();
+        match client.get_signature_statuses(&parsed).await {
+            Ok(statuses) => maybe_landed.extend(
+                batch
+                    .iter()
+                    .zip(statuses.value)
+                    .filter(|(_, status)| status.is_some())
+                    .map(|(signature, _)| signature.clone()),
+            ),
+            Err(e) => {
+                warn!(
+                    "Failed to get the statuses of {} expired signatures : {e}",
+                    batch.len()
+                );
+                maybe_landed.extend(batch.iter().cloned());
+            }
+        }
+    }
+    maybe_landed
+}
+
+/// Blocks polled from the RPC node waiting to be matched against the sent transactions
+struct FetchedBlock {
+    slot: Slot,
+    scheduled_leader: Option<String>,
+    block: UiConfirmedBlock,
+}
+
+// blocks fetched ahead of the signature matcher, the fetcher waits once the queue is full
+const FETCHED_BLOCKS_QUEUE_SIZE: usize = 64;
+// a block still failing after these attempts is reported as a gap, its transactions time out
+const BLOCK_FETCH_ATTEMPTS: u32 = 5;
+const BLOCK_FETCH_BACKOFF: Duration = Duration::from_millis(200);
+const QUEUE_DEPTHS_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+// transactions not confirmed nor expired after this delay are reported as timed out
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+const TIMEOUT_SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// a transaction cannot land anymore once the chain passed the last valid block height of its
+/// blockhash, it is then reported as expired instead of waiting for the timeout
+fn is_expired(sent_record: &TransactionSendRecord, block_height: Option<u64>) -> bool {
+    match (sent_record.last_valid_block_height, block_height) {
+        (Some(last_valid_block_height), Some(block_height)) => {
+            block_height > last_valid_block_height
+        }
+        _ => false,
+    }
+}
+
+/// record of a transaction which never landed, either expired or timed out
+fn unconfirmed_record(
+    signature: String,
+    sent_record: &TransactionSendRecord,
+    expired: bool,
+) -> TransactionConfirmRecord {
+    TransactionConfirmRecord {
+        signature,
+        confirmed_slot: None,
+        confirmed_at: None,
+        confirmed_at_us: None,
+        sent_at: sent_record.sent_at.to_string(),
+        sent_at_us: sent_record.sent_at_us,
+        sent_slot: sent_record.sent_slot,
+        successful: false,
+        error: Some(if expired { "expired" } else { "timeout" }.to_string()),
+        mango_error_code: None,
+        failed_instruction: None,
+        block_hash: None,
+        market: sent_record.market.map(|x| x.to_string()),
+        market_maker: sent_record.market_maker.map(|x| x.to_string()),
+        keeper_instruction: sent_record.keeper_instruction.clone(),
+        tx_type: sent_record.tx_type,
+        blockhash_slot: sent_record.blockhash_slot,
+        blockhash_age_slots: sent_record.blockhash_age_slots,
+        last_valid_block_height: sent_record.last_valid_block_height,
+        chaos: sent_record.chaos,
+        send_delay_ms: sent_record.send_delay_ms,
+        tx_size_bytes: sent_record.tx_size_bytes,
+        num_instructions: sent_record.num_instructions,
+        order_type: sent_record.order_type,
+        reduce_only: sent_record.reduce_only,
+        client_order_id: sent_record.client_order_id,
+        route: sent_record.route,
+        slot_processed: None,
+        slot_leader: None,
+        expired,
+        timed_out: !expired,
+        priority_fees: sent_record.priority_fees,
+        spoofing: sent_record.spoofing,
+        phase: sent_record.phase.clone(),
+        commitment_processed_at: None,
+        commitment_confirmed_at: None,
+        commitment_finalized_at: None,
+        inclusion_latency_ms: None,
+        clock_offset_ms: None,
+        corrected_inclusion_latency_ms: None,
+        run_id: String::new(),
+        cluster: None,
+        shard: None,
+    }
+}
+
+/// record of a transaction sent again under a signature which is already tracked, the cluster
+/// executes a signature once so the copy is reported as rejected right away and the tracked
+/// record keeps waiting for its own outcome
+fn duplicate_record(
+    signature: String,
+    sent_record: &TransactionSendRecord,
+) -> TransactionConfirmRecord {
+    TransactionConfirmRecord {
+        error: Some("duplicate signature".to_string()),
+        timed_out: false,
+        ..unconfirmed_record(signature, sent_record, false)
+    }
+}
+
+// tracks a sent transaction until its outcome is known, a copy of a tracked one gets its own
+// record
+fn track_record(
+    transaction_map: &DashMap<Signature, (TransactionSendRecord, Instant)>,
+    tx_record: TransactionSendRecord,
+    tx_confirm_records: &tokio::sync::broadcast::Sender<TransactionConfirmRecord>,
+) {
+    match transaction_map.entry(tx_record.signature) {
+        Entry::Occupied(_) => {
+            let _ = tx_confirm_records.send(duplicate_record(
+                tx_record.signature.to_string(),
+                &tx_record,
+            ));
+        }
+        Entry::Vacant(entry) => {
+            entry.insert((tx_record, Instant::now()));
+        }
+    }
+}
+
+/// Confirms the transactions by polling the confirmed blocks of an RPC node, used when the
+/// transactions are not sent through lite-rpc which notifies them. The confirmation runs as three
+/// tasks : the block fetcher polls the blocks into a bounded queue, the signature matcher tracks
+/// the sent transactions and matches them against the fetched blocks, and the timeout scanner
+/// reports the transactions which never landed, as expired once the matched blocks passed the
+/// last valid block height of their blockhash. The send records stay on an unbounded channel, the
+/// market makers would otherwise be slowed down by a lagging confirmation and change the load
+/// under test, the transactions waiting for a block are reported as `pending_transactions`
+#[allow(clippy::too_many_arguments)]
 pub fn confirmations_by_blocks(
     client: Arc<RpcClient>,
-    mut tx_record_rx: UnboundedReceiver<TransactionSendRecord>,
+    tx_record_rx: UnboundedReceiver<TransactionSendRecord>,
     tx_confirm_records: tokio::sync::broadcast::Sender<TransactionConfirmRecord>,
     tx_block_data: tokio::sync::broadcast::Sender<BlockData>,
     from_slot: u64,
+    block_fetch_concurrency: usize,
+    clock_skew: ClockSkew,
     exit_signal: Arc<AtomicBool>,
 ) -> Vec<JoinHandle<()>> {
-    let transaction_map = Arc::new(DashMap::new());
+    let transaction_map: Arc<DashMap<Signature, (TransactionSendRecord, Instant)>> =
+        Arc::new(DashMap::new());
+    let (fetched_blocks_sx, fetched_blocks_rx) = channel(FETCHED_BLOCKS_QUEUE_SIZE);
+    // height of the last block matched, a transaction is not expired before its block is matched
+    let matched_block_height = Arc::new(AtomicU64::new(0));
 
-    let map_filler_jh = {
-        let transaction_map = transaction_map.clone();
-        let exit_signal = exit_signal.clone();
-        tokio::spawn(async move {
-            loop {
-                match tokio::time::timeout(tokio::time::Duration::from_secs(1), tx_record_rx.recv())
-                    .await
-                {
-                    Ok(tx_record) => match tx_record {
-                        Some(tx_record) => {
-                            debug!(
-                                "add to queue len={} sig={}",
-                                transaction_map.len() + 1,
-                                tx_record.signature
-                            );
-                            transaction_map
-                                .insert(tx_record.signature, (tx_record, Instant::now()));
-                        }
-                        None => {
-                            exit_signal.store(true, Ordering::Relaxed);
-                            break;
-                        }
-                    },
-                    Err(_) => {
-                        // on timeout just check if services are being stopped
-                        if exit_signal.load(Ordering::Relaxed) {
-                            break;
-                        }
-                    }
+    let block_fetcher_jh = start_block_fetcher(
+        client,
+        fetched_blocks_sx,
+        from_slot,
+        block_fetch_concurrency,
+        transaction_map.clone(),
+        exit_signal.clone(),
+    );
+    let signature_matcher_jh = start_signature_matcher(
+        tx_record_rx,
+        fetched_blocks_rx,
+        tx_confirm_records.clone(),
+        tx_block_data,
+        transaction_map.clone(),
+        matched_block_height.clone(),
+        clock_skew,
+        exit_signal.clone(),
+    );
+    let timeout_scanner_jh = start_timeout_scanner(
+        tx_confirm_records,
+        transaction_map,
+        matched_block_height,
+        exit_signal,
+    );
+    vec![block_fetcher_jh, signature_matcher_jh, timeout_scanner_jh]
+}
+
+/// the block of a confirmed slot, retried with an exponential backoff as a node may list a slot
+/// before it serves its block
+pub(crate) async fn get_block_with_retry(
+    client: &RpcClient,
+    slot: Slot,
+    config: RpcBlockConfig,
+) -> Option<UiConfirmedBlock> {
+    let mut backoff = BLOCK_FETCH_BACKOFF;
+    for attempt in 1..=BLOCK_FETCH_ATTEMPTS {
+        match client.get_block_with_config(slot, config).await {
+            Ok(block) => return Some(block),
+            Err(e) => {
+                debug!("Failed to get block {slot}, attempt {attempt} : {e}");
+                if attempt < BLOCK_FETCH_ATTEMPTS {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
                 }
             }
-        })
-    };
+        }
+    }
+    None
+}
 
-    let cleaner_jh = {
-        let transaction_map = transaction_map.clone();
-        let exit_signal = exit_signal.clone();
-        let tx_confirm_records = tx_confirm_records.clone();
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(Duration::from_secs(60)).await;
-                {
-                    let mut to_remove = vec![];
+fn start_block_fetcher(
+    client: Arc<RpcClient>,
+    fetched_blocks_sx: Sender<FetchedBlock>,
+    from_slot: u64,
+    block_fetch_concurrency: usize,
+    transaction_map: Arc<DashMap<Signature, (TransactionSendRecord, Instant)>>,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut start_block = from_slot;
+        let mut start_instant = tokio::time::Instant::now();
+        let refresh_in = Duration::from_secs(10);
+        let commitment_confirmation = CommitmentConfig {
+            commitment: CommitmentLevel::Confirmed,
+        };
+        loop {
+            if exit_signal.load(Ordering::Relaxed) && transaction_map.len() == 0 {
+                break;
+            }
 
-                    for tx_data in transaction_map.iter() {
-                        let sent_record = &tx_data.0;
-                        let instant = tx_data.1;
-                        let signature = tx_data.key();
-                        let remove = instant.elapsed() > Duration::from_secs(120);
+            let wait_duration = tokio::time::Instant::now() - start_instant;
+            if wait_duration < refresh_in {
+                tokio::time::sleep(refresh_in - wait_duration).await;
+            }
+            start_instant = tokio::time::Instant::now();
 
-                        // add to timeout if not retaining
-                        if remove {
-                            let _ = tx_confirm_records.send(TransactionConfirmRecord {
-                                signature: signature.to_string(),
-                                confirmed_slot: None,
-                                confirmed_at: None,
-                                sent_at: sent_record.sent_at.to_string(),
-                                sent_slot: sent_record.sent_slot,
-                                successful: false,
-                                error: Some("timeout".to_string()),
-                                block_hash: None,
-                                market: sent_record.market.map(|x| x.to_string()),
-                                market_maker: sent_record.market_maker.map(|x| x.to_string()),
-                                keeper_instruction: sent_record.keeper_instruction.clone(),
-                                slot_processed: None,
-                                slot_leader: None,
-                                timed_out: true,
-                                priority_fees: sent_record.priority_fees,
-                            });
-                            to_remove.push(*signature);
-                        }
-                    }
+            let block_slots =
+                get_blocks_with_retry(client.clone(), start_block, commitment_confirmation).await;
+            if block_slots.is_err() {
+                break;
+            }
 
-                    for signature in to_remove {
-                        transaction_map.remove(&signature);
+            let block_slots = block_slots.unwrap();
+            if block_slots.is_empty() {
+                continue;
+            }
+            let first_slot = block_slots[0];
+            start_block = *block_slots.last().unwrap() + 1;
+
+            // leader identities of the slots from the leader schedule
+            let slot_leaders = match client
+                .get_slot_leaders(first_slot, start_block - first_slot)
+                .await
+            {
+                Ok(leaders) => leaders,
+                Err(e) => {
+                    warn!("Failed to get slot leaders from {first_slot} : {e}");
+                    vec![]
+                }
+            };
+
+            let block_config = RpcBlockConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                transaction_details: Some(TransactionDetails::Full),
+                rewards: Some(true),
+                commitment: Some(commitment_confirmation),
+                max_supported_transaction_version: Some(0),
+            };
+            let nb_blocks = block_slots.len();
+            let mut gaps = 0;
+            // the blocks are fetched concurrently but handed over to the matcher in slot order
+            let mut blocks = futures::stream::iter(block_slots)
+                .map(|slot| {
+                    let client = client.clone();
+                    async move {
+                        (
+                            slot,
+                            get_block_with_retry(&client, slot, block_config).await,
+                        )
                     }
+                })
+                .buffered(block_fetch_concurrency.max(1));
+            while let Some((slot, block)) = blocks.next().await {
+                let Some(block) = block else {
+                    warn!(
+                        "Gap at slot {slot}, the block could not be fetched after {BLOCK_FETCH_ATTEMPTS} attempts"
+                    );
+                    gaps += 1;
+                    continue;
+                };
+                let scheduled_leader = slot_leaders
+                    .get((slot - first_slot) as usize)
+                    .map(|leader| leader.to_string());
+                let fetched = FetchedBlock {
+                    slot,
+                    scheduled_leader,
+                    block,
+                };
+                if fetched_blocks_sx.send(fetched).await.is_err() {
+                    return;
+                }
+            }
+            datapoint_info!(
+                "bencher-block-fetcher",
+                ("blocks", nb_blocks, i64),
+                ("gaps", gaps, i64),
+                ("fetch_ms", start_instant.elapsed().as_millis(), i64),
+            );
+        }
+    })
+}
 
-                    // if exit and all the transactions are processed
-                    if exit_signal.load(Ordering::Relaxed) && transaction_map.len() == 0 {
+#[allow(clippy::too_many_arguments)]
+fn start_signature_matcher(
+    mut tx_record_rx: UnboundedReceiver<TransactionSendRecord>,
+    mut fetched_blocks_rx: Receiver<FetchedBlock>,
+    tx_confirm_records: tokio::sync::broadcast::Sender<TransactionConfirmRecord>,
+    tx_block_data: tokio::sync::broadcast::Sender<BlockData>,
+    transaction_map: Arc<DashMap<Signature, (TransactionSendRecord, Instant)>>,
+    matched_block_height: Arc<AtomicU64>,
+    clock_skew: ClockSkew,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut records_closed = false;
+        let mut report_interval = tokio::time::interval(QUEUE_DEPTHS_REPORT_INTERVAL);
+        // the matcher stops once the fetcher is done and every fetched block was matched
+        loop {
+            tokio::select! {
+                tx_record = tx_record_rx.recv(), if !records_closed => match tx_record {
+                    Some(tx_record) => {
+                        debug!(
+                            "add to queue len={} sig={}",
+                            transaction_map.len() + 1,
+                            tx_record.signature
+                        );
+                        track_record(&transaction_map, tx_record, &tx_confirm_records);
+                    }
+                    None => {
+                        records_closed = true;
+                        exit_signal.store(true, Ordering::Relaxed);
+                    }
+                },
+                fetched = fetched_blocks_rx.recv() => {
+                    let Some(fetched) = fetched else {
                         break;
+                    };
+                    let block_height = fetched.block.block_height;
+                    process_blocks(
+                        &fetched.block,
+                        tx_confirm_records.clone(),
+                        tx_block_data.clone(),
+                        transaction_map.clone(),
+                        fetched.slot,
+                        CommitmentLevel::Confirmed,
+                        fetched.scheduled_leader,
+                        &clock_skew,
+                    )
+                    .await;
+                    if let Some(block_height) = block_height {
+                        matched_block_height.fetch_max(block_height, Ordering::Relaxed);
                     }
+                },
+                _ = report_interval.tick() => {
+                    let fetched_blocks = fetched_blocks_rx.max_capacity() - fetched_blocks_rx.capacity();
+                    datapoint_info!(
+                        "bencher-confirmation-queues",
+                        ("fetched_blocks", fetched_blocks, i64),
+                        ("pending_transactions", transaction_map.len(), i64),
+                    );
                 }
             }
-        })
-    };
+        }
+    })
+}
 
-    let block_confirmation_jh = {
-        let exit_signal = exit_signal;
-        tokio::spawn(async move {
-            let mut start_block = from_slot;
-            let mut start_instant = tokio::time::Instant::now();
-            let refresh_in = Duration::from_secs(10);
-            let commitment_confirmation = CommitmentConfig {
-                commitment: CommitmentLevel::Confirmed,
-            };
-            loop {
-                if exit_signal.load(Ordering::Relaxed) && transaction_map.len() == 0 {
-                    break;
+fn start_timeout_scanner(
+    tx_confirm_records: tokio::sync::broadcast::Sender<TransactionConfirmRecord>,
+    transaction_map: Arc<DashMap<Signature, (TransactionSendRecord, Instant)>>,
+    matched_block_height: Arc<AtomicU64>,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(TIMEOUT_SCAN_INTERVAL).await;
+            let block_height = Some(matched_block_height.load(Ordering::Relaxed))
+                .filter(|block_height| *block_height > 0);
+            let unconfirmed = transaction_map
+                .iter()
+                .filter_map(|tx_data| {
+                    let (sent_record, instant) = tx_data.value();
+                    let expired = is_expired(sent_record, block_height);
+                    (expired || instant.elapsed() > CONFIRMATION_TIMEOUT)
+                        .then_some((*tx_data.key(), expired))
+                })
+                .collect::<Vec<_>>();
+            for (signature, expired) in unconfirmed {
+                // the matcher may have confirmed it in the meantime
+                if let Some((signature, (sent_record, _))) = transaction_map.remove(&signature) {
+                    let _ = tx_confirm_records.send(unconfirmed_record(
+                        signature.to_string(),
+                        &sent_record,
+                        expired,
+                    ));
                 }
+            }
 
-                let wait_duration = tokio::time::Instant::now() - start_instant;
-                if wait_duration < refresh_in {
-                    tokio::time::sleep(refresh_in - wait_duration).await;
-                }
-                start_instant = tokio::time::Instant::now();
+            // if exit and all the transactions are processed
+            if exit_signal.load(Ordering::Relaxed) && transaction_map.len() == 0 {
+                break;
+            }
+        }
+    })
+}
 
-                let block_slots =
-                    get_blocks_with_retry(client.clone(), start_block, commitment_confirmation)
-                        .await;
-                if block_slots.is_err() {
-                    break;
-                }
+/// How the transactions which are not notified by lite-rpc are confirmed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfirmationSource {
+    // matched against the confirmed blocks of the RPC node
+    Blocks,
+    // statuses of the pending signatures polled in batches, for nodes which rate limit getBlock
+    SignatureStatuses,
+}
 
-                let block_slots = block_slots.unwrap();
-                if block_slots.is_empty() {
-                    continue;
+impl FromStr for ConfirmationSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "blocks" => Ok(ConfirmationSource::Blocks),
+            "signature-statuses" => Ok(ConfirmationSource::SignatureStatuses),
+            _ => Err(format!("unknown confirmation source {s}")),
+        }
+    }
+}
+
+// most signatures accepted by a single getSignatureStatuses request
+const SIGNATURE_STATUSES_BATCH_SIZE: usize = 256;
+const STATUS_POLL_MIN_INTERVAL: Duration = Duration::from_millis(400);
+// the confirmation time of a transaction is the time of the poll which found it, the interval
+// stays short while transactions are pending
+const STATUS_POLL_PENDING_INTERVAL: Duration = Duration::from_secs(1);
+const STATUS_POLL_MAX_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Confirms the transactions by polling the statuses of the pending signatures with batched
+/// getSignatureStatuses requests. The poller comes back quickly while transactions land, slows
+/// down to every second while they are pending and backs off further while none is pending or
+/// the node refuses the requests. A transaction is confirmed at the time of the poll which found
+/// it, up to a polling interval after it landed. No block is fetched, so no block data
+/// is produced and the slot leaders are unknown. The timeout scanner reports the transactions
+/// which never landed, as expired once the confirmed block height passed the last valid block
+/// height of their blockhash
+pub fn confirmations_by_signature_statuses(
+    client: Arc<RpcClient>,
+    tx_record_rx: UnboundedReceiver<TransactionSendRecord>,
+    tx_confirm_records: tokio::sync::broadcast::Sender<TransactionConfirmRecord>,
+    clock_skew: ClockSkew,
+    exit_signal: Arc<AtomicBool>,
+) -> Vec<JoinHandle<()>> {
+    let transaction_map: Arc<DashMap<Signature, (TransactionSendRecord, Instant)>> =
+        Arc::new(DashMap::new());
+    // confirmed block height when the last polling round started
+    let polled_block_height = Arc::new(AtomicU64::new(0));
+
+    let status_poller_jh = start_signature_status_poller(
+        client,
+        tx_record_rx,
+        tx_confirm_records.clone(),
+        transaction_map.clone(),
+        polled_block_height.clone(),
+        clock_skew,
+        exit_signal.clone(),
+    );
+    let timeout_scanner_jh = start_timeout_scanner(
+        tx_confirm_records,
+        transaction_map,
+        polled_block_height,
+        exit_signal,
+    );
+    vec![status_poller_jh, timeout_scanner_jh]
+}
+
+/// record of a transaction found by its status, without the block there is no blockhash nor
+/// logs to decode the error from. The confirmation time is the time of the poll
+fn status_record(
+    signature: String,
+    sent_record: &TransactionSendRecord,
+    slot: Slot,
+    error: Option<TransactionError>,
+) -> TransactionConfirmRecord {
+    let failure = error
+        .as_ref()
+        .and_then(mango_errors::from_error)
+        .unwrap_or_default();
+    let confirmed_at = clock::now();
+    TransactionConfirmRecord {
+        confirmed_slot: Some(slot),
+        confirmed_at: Some(confirmed_at.to_string()),
+        confirmed_at_us: Some(confirmed_at.timestamp_micros()),
+        successful: error.is_none(),
+        error: error.map(|e| e.to_string()),
+        mango_error_code: failure.error_code,
+        failed_instruction: failure.failed_instruction,
+        slot_processed: Some(slot),
+        expired: false,
+        timed_out: false,
+        ..unconfirmed_record(signature, sent_record, false)
+    }
+}
+
+fn start_signature_status_poller(
+    client: Arc<RpcClient>,
+    mut tx_record_rx: UnboundedReceiver<TransactionSendRecord>,
+    tx_confirm_records: tokio::sync::broadcast::Sender<TransactionConfirmRecord>,
+    transaction_map: Arc<DashMap<Signature, (TransactionSendRecord, Instant)>>,
+    polled_block_height: Arc<AtomicU64>,
+    clock_skew: ClockSkew,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let commitment_confirmation = CommitmentConfig {
+            commitment: CommitmentLevel::Confirmed,
+        };
+        let mut poll_interval = STATUS_POLL_MIN_INTERVAL;
+        loop {
+            loop {
+                match tx_record_rx.try_recv() {
+                    Ok(tx_record) => {
+                        track_record(&transaction_map, tx_record, &tx_confirm_records);
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        exit_signal.store(true, Ordering::Relaxed);
+                        break;
+                    }
                 }
-                start_block = *block_slots.last().unwrap() + 1;
-
-                let blocks = block_slots.iter().map(|slot| {
-                    client.get_block_with_config(
-                        *slot,
-                        RpcBlockConfig {
-                            encoding: Some(UiTransactionEncoding::Base64),
-                            transaction_details: Some(TransactionDetails::Full),
-                            rewards: Some(true),
-                            commitment: Some(commitment_confirmation),
-                            max_supported_transaction_version: Some(0),
-                        },
-                    )
-                });
-                let blocks = futures::future::join_all(blocks).await;
-                for block_slot in blocks.iter().zip(block_slots) {
-                    let block = match block_slot.0 {
-                        Ok(x) => x,
-                        Err(_) => continue,
+            }
+            if exit_signal.load(Ordering::Relaxed) && transaction_map.len() == 0 {
+                break;
+            }
+
+            let round_start = Instant::now();
+            // fetched before the statuses, a transaction not confirmed by then cannot land anymore
+            // once this height passed its last valid block height
+            let block_height = client
+                .get_block_height_with_commitment(commitment_confirmation)
+                .await;
+            let signatures = transaction_map
+                .iter()
+                .map(|tx_data| *tx_data.key())
+                .collect::<Vec<_>>();
+            let mut requests = 0;
+            let mut confirmed = 0;
+            let mut refused = false;
+            for batch in signatures.chunks(SIGNATURE_STATUSES_BATCH_SIZE) {
+                requests += 1;
+                let statuses = match client.get_signature_statuses(batch).await {
+                    Ok(statuses) => statuses.value,
+                    Err(e) => {
+                        warn!(
+                            "Failed to get the statuses of {} signatures : {e}",
+                            batch.len()
+                        );
+                        refused = true;
+                        break;
+                    }
+                };
+                for (signature, status) in batch.iter().zip(statuses) {
+                    let Some(status) = status.filter(|status| {
+                        status.satisfies_commitment(commitment_confirmation)
+                    }) else {
+                        continue;
                     };
-                    let tx_confirm_records = tx_confirm_records.clone();
-                    let tx_block_data = tx_block_data.clone();
-                    let transaction_map = transaction_map.clone();
-                    process_blocks(
-                        block,
-                        tx_confirm_records,
-                        tx_block_data,
-                        transaction_map,
-                        block_slot.1,
-                        commitment_confirmation.commitment,
-                    )
-                    .await;
+                    if let Some((signature, (sent_record, _))) = transaction_map.remove(signature) {
+                        confirmed += 1;
+                        let mut record = status_record(
+                            signature.to_string(),
+                            &sent_record,
+                            status.slot,
+                            status.err,
+                        );
+                        clock_skew.annotate(&mut record);
+                        if let Err(e) = tx_confirm_records.send(record) {
+                            warn!("Tx confirm record channel broken {}", e.to_string());
+                        }
+                    }
                 }
             }
-        })
-    };
-    vec![map_filler_jh, cleaner_jh, block_confirmation_jh]
+            // only a complete round tells that the pending transactions did not land
+            match block_height {
+                Ok(block_height) if !refused => {
+                    polled_block_height.fetch_max(block_height, Ordering::Relaxed);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to get the block height : {e}"),
+            }
+
+            poll_interval = if refused {
+                (poll_interval * 2).min(STATUS_POLL_MAX_INTERVAL)
+            } else if confirmed > 0 {
+                STATUS_POLL_MIN_INTERVAL
+            } else if transaction_map.is_empty() {
+                STATUS_POLL_MAX_INTERVAL
+            } else {
+                (poll_interval * 3 / 2).min(STATUS_POLL_PENDING_INTERVAL)
+            };
+            datapoint_info!(
+                "bencher-signature-statuses",
+                ("requests", requests, i64),
+                ("confirmed", confirmed, i64),
+                ("pending_transactions", transaction_map.len(), i64),
+                ("poll_ms", round_start.elapsed().as_millis(), i64),
+                ("poll_interval_ms", poll_interval.as_millis(), i64),
+            );
+            tokio::time::sleep(poll_interval.saturating_sub(round_start.elapsed())).await;
+        }
+    })
 }
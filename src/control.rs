@@ -0,0 +1,199 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{atomic::Ordering, Arc},
+};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use log::{error, info};
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::{
+    quic_stats::QuicStats,
+    states::LiveParameters,
+    stats::MangoSimulationStats,
+    summary::{BenchSummary, RunParameters, Thresholds},
+};
+
+/// Everything the control server reads or changes while the run is in progress
+#[derive(Clone)]
+pub struct ControlState {
+    pub stats: MangoSimulationStats,
+    pub live_parameters: Arc<LiveParameters>,
+    pub run_parameters: RunParameters,
+    pub thresholds: Thresholds,
+    pub summary_file: String,
+    // only set with the quic sender
+    pub quic_stats: Option<QuicStats>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveParametersView {
+    quotes_per_second: u64,
+    priority_fees_proba: u8,
+    active_market_makers_pct: u8,
+    paused: bool,
+    throttle_pct: u8,
+    phase: Option<String>,
+}
+
+impl From<&LiveParameters> for LiveParametersView {
+    fn from(live_parameters: &LiveParameters) -> Self {
+        Self {
+            quotes_per_second: live_parameters.quotes_per_second(),
+            priority_fees_proba: live_parameters.priority_fees_proba(),
+            active_market_makers_pct: live_parameters
+                .active_market_makers_pct
+                .load(Ordering::Relaxed),
+            paused: live_parameters.paused.load(Ordering::Relaxed),
+            throttle_pct: live_parameters.throttle_pct.load(Ordering::Relaxed),
+            phase: live_parameters.phase(),
+        }
+    }
+}
+
+fn json_response<T: serde::Serialize>(value: &T) -> Response<Body> {
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_string(value).unwrap()))
+        .unwrap()
+}
+
+fn not_found() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn bad_request(message: String) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .body(Body::from(message))
+        .unwrap()
+}
+
+// value of the `value` query parameter, e.g. /quotes-per-second?value=2
+fn query_value<T: FromStr>(request: &Request<Body>) -> Result<T, Response<Body>> {
+    request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("value="))
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| bad_request("missing or invalid value query parameter".to_string()))
+}
+
+async fn flush(state: &ControlState) -> anyhow::Result<()> {
+    state.live_parameters.flush.flush().await?;
+    if state.summary_file.is_empty() {
+        return Ok(());
+    }
+    BenchSummary::new(
+        state.run_parameters.clone(),
+        state.stats.summary().await,
+        state.thresholds,
+    )
+    .write(&state.summary_file)
+}
+
+async fn handle(request: Request<Body>, state: ControlState) -> Result<Response<Body>, Infallible> {
+    let live_parameters = &state.live_parameters;
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/summary") => json_response(&state.stats.summary().await),
+        (&Method::GET, "/metrics") => Response::builder()
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(
+                state
+                    .quic_stats
+                    .as_ref()
+                    .map(QuicStats::prometheus)
+                    .unwrap_or_default(),
+            ))
+            .unwrap(),
+        (&Method::GET, "/parameters") => {
+            json_response(&LiveParametersView::from(live_parameters.as_ref()))
+        }
+        (&Method::POST, "/pause") => {
+            info!("control : pausing the market makers");
+            live_parameters.paused.store(true, Ordering::Relaxed);
+            json_response(&LiveParametersView::from(live_parameters.as_ref()))
+        }
+        (&Method::POST, "/resume") => {
+            info!("control : resuming the market makers");
+            live_parameters.paused.store(false, Ordering::Relaxed);
+            json_response(&LiveParametersView::from(live_parameters.as_ref()))
+        }
+        (&Method::POST, "/quotes-per-second") => match query_value::<u64>(&request) {
+            Ok(quotes_per_second) => {
+                info!("control : setting quotes per second to {quotes_per_second}");
+                live_parameters
+                    .quotes_per_second
+                    .store(quotes_per_second, Ordering::Relaxed);
+                json_response(&LiveParametersView::from(live_parameters.as_ref()))
+            }
+            Err(response) => response,
+        },
+        (&Method::POST, "/priority-fees-proba") => match query_value::<u8>(&request) {
+            Ok(proba) if proba <= 100 => {
+                info!("control : setting priority fees probability to {proba}%");
+                live_parameters
+                    .priority_fees_proba
+                    .store(proba, Ordering::Relaxed);
+                json_response(&LiveParametersView::from(live_parameters.as_ref()))
+            }
+            Ok(proba) => bad_request(format!("priority fees probability {proba} is above 100")),
+            Err(response) => response,
+        },
+        (&Method::POST, "/reload-accounts") => {
+            info!("control : reloading the accounts file");
+            // kept until the watcher waits again if it is busy
+            live_parameters.reload_accounts.notify_one();
+            json_response(&LiveParametersView::from(live_parameters.as_ref()))
+        }
+        (&Method::POST, "/flush") => match flush(&state).await {
+            Ok(()) => {
+                info!("control : flushed the results to {}", state.summary_file);
+                json_response(&state.summary_file)
+            }
+            Err(e) => {
+                error!("control : could not flush the results {e:?}");
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::from(e.to_string()))
+                    .unwrap()
+            }
+        },
+        _ => not_found(),
+    };
+    Ok(response)
+}
+
+/// Http server to follow and steer the run while it is in progress
+/// GET /summary returns the run summary computed from the live counters
+/// GET /parameters returns the current market making parameters
+/// GET /metrics returns the quic transport counters in the prometheus text format
+/// POST /pause and /resume stop and restart the quotes of every market maker
+/// POST /quotes-per-second?value=N and /priority-fees-proba?value=N change the quoting
+/// POST /reload-accounts reloads the accounts file when the run watches it
+/// POST /flush flushes the csv files and writes the summary file with the results so far
+pub fn start_control_server(addr: SocketAddr, state: ControlState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let make_service = make_service_fn(move |_| {
+            let state = state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |request| handle(request, state.clone()))) }
+        });
+        info!("control server listening on {addr}");
+        if let Err(e) = Server::bind(&addr).serve(make_service).await {
+            error!("control server stopped {e:?}");
+        }
+    })
+}
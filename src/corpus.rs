@@ -0,0 +1,193 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, ErrorKind},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, transaction::Transaction};
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+    sync::{Mutex, Semaphore},
+};
+
+use crate::transaction_sender::TransactionSender;
+
+// transactions of a replay being sent at the same time, the replay falls behind its schedule
+// rather than piling up sends
+const REPLAY_MAX_IN_FLIGHT: usize = 1024;
+
+/// Signed transaction of a corpus with the time it was generated at
+#[derive(Serialize, Deserialize)]
+pub struct CorpusEntry {
+    // since the first transaction of the corpus
+    pub offset_micros: u64,
+    pub transaction: Transaction,
+}
+
+/// Writes the transactions to a corpus file instead of sending them, the generation of the
+/// transactions can then be measured apart from their sending (see `--generate-only`)
+pub struct CorpusWriter {
+    start: Instant,
+    writer: Mutex<BufWriter<File>>,
+    written: AtomicU64,
+}
+
+impl CorpusWriter {
+    pub async fn create(path: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            start: Instant::now(),
+            writer: Mutex::new(BufWriter::new(File::create(path).await?)),
+            written: AtomicU64::new(0),
+        })
+    }
+
+    pub async fn flush(&self) -> anyhow::Result<u64> {
+        self.writer.lock().await.flush().await?;
+        Ok(self.written.load(Ordering::Relaxed))
+    }
+}
+
+#[async_trait]
+impl TransactionSender for CorpusWriter {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        let entry = CorpusEntry {
+            offset_micros: self.start.elapsed().as_micros() as u64,
+            transaction: transaction.clone(),
+        };
+        let bytes = bincode::serialize(&entry)?;
+        self.writer.lock().await.write_all(&bytes).await?;
+        self.written.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+pub async fn read_corpus(path: &str) -> anyhow::Result<Vec<CorpusEntry>> {
+    let mut reader = Cursor::new(tokio::fs::read(path).await?);
+    let mut entries = vec![];
+    loop {
+        match bincode::deserialize_from::<_, CorpusEntry>(&mut reader) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => match *e {
+                bincode::ErrorKind::Io(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                e => anyhow::bail!("corrupted corpus {path} : {e}"),
+            },
+        }
+    }
+    Ok(entries)
+}
+
+/// Throughput achieved by a replay
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub sent: u64,
+    pub failed: u64,
+    // signed with a blockhash which expired before the replay, not sent
+    pub stale: u64,
+    pub elapsed: Duration,
+    // largest delay of a transaction behind its schedule
+    pub max_behind: Duration,
+}
+
+// the transactions keep the blockhash they were signed with and cannot be signed again without
+// the keys, the ones whose blockhash the cluster no longer accepts are left out
+async fn without_stale_blockhashes(
+    entries: Vec<CorpusEntry>,
+    client: &RpcClient,
+) -> anyhow::Result<(Vec<CorpusEntry>, u64)> {
+    let mut valid = HashMap::new();
+    for entry in &entries {
+        let blockhash = entry.transaction.message.recent_blockhash;
+        if !valid.contains_key(&blockhash) {
+            let is_valid = client
+                .is_blockhash_valid(&blockhash, CommitmentConfig::processed())
+                .await?;
+            valid.insert(blockhash, is_valid);
+        }
+    }
+    let total = entries.len() as u64;
+    let fresh = entries
+        .into_iter()
+        .filter(|entry| valid[&entry.transaction.message.recent_blockhash])
+        .collect::<Vec<_>>();
+    let stale = total - fresh.len() as u64;
+    Ok((fresh, stale))
+}
+
+/// Sends the transactions of a corpus in round robin over the senders, keeping the timing of the
+/// generation divided by speed, or as fast as possible when speed is 0. The transactions keep the
+/// blockhash they were signed with, those whose blockhash expired since the generation are not
+/// sent, the corpus has to be replayed within a minute of its generation to be sent whole
+pub async fn replay_corpus(
+    entries: Vec<CorpusEntry>,
+    senders: Vec<Arc<dyn TransactionSender>>,
+    speed: f64,
+    client: &RpcClient,
+) -> anyhow::Result<ReplayReport> {
+    anyhow::ensure!(
+        !senders.is_empty(),
+        "at least one transaction sender is required"
+    );
+    let (entries, stale) = without_stale_blockhashes(entries, client).await?;
+    if stale > 0 {
+        warn!("replay : {stale} transactions were signed with an expired blockhash, they are not sent");
+    }
+    let sent = Arc::new(AtomicU64::new(0));
+    let failed = Arc::new(AtomicU64::new(0));
+    let mut max_behind = Duration::ZERO;
+    let in_flight = Arc::new(Semaphore::new(REPLAY_MAX_IN_FLIGHT));
+    let start = Instant::now();
+    for (index, entry) in entries.into_iter().enumerate() {
+        if speed > 0.0 {
+            let scheduled = Duration::from_micros((entry.offset_micros as f64 / speed) as u64);
+            match scheduled.checked_sub(start.elapsed()) {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => max_behind = max_behind.max(start.elapsed() - scheduled),
+            }
+        }
+        let permit = in_flight.clone().acquire_owned().await?;
+        let sender = senders[index % senders.len()].clone();
+        let sent = sent.clone();
+        let failed = failed.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            match sender.send(&entry.transaction).await {
+                Ok(()) => sent.fetch_add(1, Ordering::Relaxed),
+                Err(e) => {
+                    warn!(
+                        "replay : could not send {} : {e}",
+                        entry.transaction.signatures[0]
+                    );
+                    failed.fetch_add(1, Ordering::Relaxed)
+                }
+            };
+        });
+    }
+    // every permit is back once the last sends are done
+    let _ = in_flight.acquire_many(REPLAY_MAX_IN_FLIGHT as u32).await?;
+
+    let report = ReplayReport {
+        sent: sent.load(Ordering::Relaxed),
+        failed: failed.load(Ordering::Relaxed),
+        stale,
+        elapsed: start.elapsed(),
+        max_behind,
+    };
+    info!(
+        "replay : sent {} transactions in {:?} ({:.0} tps), {} failed, at most {:?} behind schedule",
+        report.sent,
+        report.elapsed,
+        report.sent as f64 / report.elapsed.as_secs_f64().max(f64::EPSILON),
+        report.failed,
+        report.max_behind,
+    );
+    Ok(report)
+}
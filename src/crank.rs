@@ -1,10 +1,13 @@
 use crate::{
-    helpers::to_sp_pk,
+    clock,
+    helpers::{to_sdk_instruction, to_sp_pk},
     mango::GroupConfig,
-    mango_v3_perp_crank_sink::MangoV3PerpCrankSink,
-    noop,
-    states::{KeeperInstruction, TransactionSendRecord},
+    mango_v3_perp_crank_sink::{CrankBatchConfig, MangoV3PerpCrankSink, MAX_EVENTS_PER_TX},
+    noop, serum_v3_spot_crank,
+    states::{KeeperInstruction, TransactionSendRecord, TransactionType},
     tpu_manager::TpuManager,
+    watchdog::Watchdog,
+    ws_failover::WebsocketFailover,
 };
 
 use mango_feeds_connector::{
@@ -13,7 +16,6 @@ use mango_feeds_connector::{
 };
 
 use async_channel::unbounded;
-use chrono::Utc;
 use log::*;
 use solana_sdk::{
     compute_budget::ComputeBudgetInstruction, hash::Hash, instruction::Instruction, pubkey::Pubkey,
@@ -27,13 +29,42 @@ use std::{
     },
     time::Duration,
 };
-use tokio::sync::RwLock;
+use tokio::{sync::RwLock, task::JoinHandle};
 
 #[derive(Debug, Clone)]
 pub struct KeeperConfig {
     pub program_id: Pubkey,
     pub rpc_url: String,
-    pub websocket_url: String,
+    pub websocket: WebsocketFailover,
+    pub batch: CrankBatchConfig,
+}
+
+/// consume events instructions without any mango account, one per perp market of the group
+pub fn create_consume_events_instructions(group: &GroupConfig) -> Vec<(Pubkey, Instruction)> {
+    let group_pk = Pubkey::from_str(&group.public_key).unwrap();
+    let cache_pk = Pubkey::from_str(&group.cache_key).unwrap();
+    let mango_program_id = Pubkey::from_str(&group.mango_program_id).unwrap();
+    group
+        .perp_markets
+        .iter()
+        .map(|m| {
+            let perp_market_pk = Pubkey::from_str(&m.public_key).unwrap();
+            let event_queue_pk = Pubkey::from_str(&m.events_key).unwrap();
+            let ix = to_sdk_instruction(
+                mango::instruction::consume_events(
+                    &to_sp_pk(&mango_program_id),
+                    &to_sp_pk(&group_pk),
+                    &to_sp_pk(&cache_pk),
+                    &to_sp_pk(&perp_market_pk),
+                    &to_sp_pk(&event_queue_pk),
+                    &mut [],
+                    MAX_EVENTS_PER_TX,
+                )
+                .unwrap(),
+            );
+            (perp_market_pk, ix)
+        })
+        .collect()
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -46,7 +77,9 @@ pub fn start(
     group: &GroupConfig,
     identity: &Keypair,
     prioritization_fee: u64,
-) {
+    watchdog: Watchdog,
+    crank_spot_markets: bool,
+) -> Vec<JoinHandle<()>> {
     let perp_queue_pks: Vec<_> = group
         .perp_markets
         .iter()
@@ -70,25 +103,45 @@ pub fn start(
     };
 
     let (instruction_sender, instruction_receiver) = unbounded::<(Pubkey, Vec<Instruction>)>();
+    // the spot consume events are signed and sent with the perp ones
+    let spot_crank_jh = if crank_spot_markets && !group.spot_markets.is_empty() {
+        Some(serum_v3_spot_crank::start(
+            config.rpc_url.clone(),
+            Pubkey::from_str(&group.serum_program_id).unwrap(),
+            serum_v3_spot_crank::spot_market_queues(group),
+            instruction_sender.clone(),
+            exit_signal.clone(),
+        ))
+    } else {
+        None
+    };
     let identity = Keypair::from_bytes(identity.to_bytes().as_slice()).unwrap();
-    tokio::spawn(async move {
+    let tx_sender_jh = tokio::spawn(async move {
         info!(
             "crank-tx-sender signing with keypair pk={:?}",
             identity.pubkey()
         );
 
         loop {
+            watchdog.heartbeat("crank");
             if exit_signal.load(Ordering::Acquire) {
                 break;
             }
 
-            if let Ok((market, mut ixs)) = instruction_receiver.recv().await {
+            // wake up regularly to beat even when there are no events to consume
+            let Ok(received) =
+                tokio::time::timeout(Duration::from_secs(1), instruction_receiver.recv()).await
+            else {
+                continue;
+            };
+            if let Ok((market, mut ixs)) = received {
                 // add priority fees
                 ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
                     prioritization_fee,
                 ));
                 // add timestamp to guarantee unique transactions
                 ixs.push(noop::timestamp());
+                ixs.extend(tpu_manager.memo().map(|memo| memo.instruction()));
 
                 let tx = Transaction::new_signed_with_payer(
                     &ixs,
@@ -97,23 +150,40 @@ pub fn start(
                     *blockhash.read().await,
                 );
 
+                let sent_at = clock::now();
                 let tx_send_record = TransactionSendRecord {
                     signature: tx.signatures[0],
-                    sent_at: Utc::now(),
+                    sent_at,
+                    sent_at_us: sent_at.timestamp_micros(),
                     sent_slot: current_slot.load(Ordering::Acquire),
                     market_maker: None,
                     market: Some(to_sp_pk(&market)),
                     priority_fees: prioritization_fee,
                     keeper_instruction: Some(KeeperInstruction::ConsumeEvents),
+                    tx_type: TransactionType::Crank,
+                    spoofing: false,
+                    phase: None,
+                    blockhash_slot: None,
+                    blockhash_age_slots: None,
+                    last_valid_block_height: None,
+                    chaos: None,
+                    send_delay_ms: None,
+                    tx_size_bytes: 0,
+                    num_instructions: 0,
+                    order_type: None,
+                    reduce_only: false,
+                    client_order_id: None,
+                    route: None,
                 };
 
                 let tpu_manager = tpu_manager.clone();
                 tpu_manager.send_transaction(&tx, tx_send_record).await;
             }
         }
+        watchdog.unregister("crank");
     });
 
-    tokio::spawn(async move {
+    let source_jh = tokio::spawn(async move {
         let metrics_tx = metrics::start(
             MetricsConfig {
                 output_stdout: true,
@@ -137,6 +207,7 @@ pub fn start(
                 cache_pk,
                 mango_program_id,
                 instruction_sender,
+                config.batch,
             )),
             timeout_interval: Duration::default(),
         }];
@@ -156,25 +227,40 @@ pub fn start(
         //     metrics_tx.clone(),
         // ).await;
 
-        info!(
-            "start processing websocket events program_id={:?} ws_url={:?}",
-            config.program_id, config.websocket_url
-        );
-
-        websocket_source::process_events(
-            &SourceConfig {
+        // the source is restarted on the next websocket endpoint when the current one dies
+        let mut websocket = config.websocket;
+        loop {
+            let websocket_url = websocket.current();
+            info!(
+                "start processing websocket events program_id={:?} ws_url={:?}",
+                config.program_id, websocket_url
+            );
+            let source_config = SourceConfig {
                 dedup_queue_size: 0,
                 grpc_sources: vec![],
                 snapshot: SnapshotSourceConfig {
-                    rpc_http_url: config.rpc_url,
+                    rpc_http_url: config.rpc_url.clone(),
                     program_id: config.program_id.to_string(),
                 },
-                rpc_ws_url: config.websocket_url,
-            },
-            &filter_config,
-            account_write_queue_sender,
-            slot_queue_sender,
-        )
-        .await;
+                rpc_ws_url: websocket_url,
+            };
+            tokio::select! {
+                _ = websocket_source::process_events(
+                    &source_config,
+                    &filter_config,
+                    account_write_queue_sender.clone(),
+                    slot_queue_sender.clone(),
+                ) => warn!("the websocket source of the crank stopped, restarting it"),
+                changed = websocket.changed() => {
+                    if !changed {
+                        break;
+                    }
+                }
+            }
+        }
     });
+
+    let mut tasks = vec![tx_sender_jh, source_jh];
+    tasks.extend(spot_crank_jh);
+    tasks
 }
@@ -0,0 +1,736 @@
+use std::{fs, mem::size_of, path::Path};
+
+use anyhow::Context;
+use fixed::types::I80F48;
+use log::info;
+use mango::{
+    matching::BookSide,
+    oracle::StubOracle,
+    queue::{AnyEvent, EventQueueHeader},
+    state::{MangoAccount, MangoCache, MangoGroup, NodeBank, PerpMarket, RootBank, MAX_PAIRS},
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::{instruction::Instruction as MangoInstruction, program_error::ProgramError};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, sysvar,
+    transaction::Transaction,
+};
+
+use crate::{
+    cli::CreateGroupConfig,
+    helpers::{to_sdk_instruction, to_sdk_pk, to_sp_pk},
+    mango::{GroupConfig, MarketConfig, OracleConfig, TokenConfig},
+};
+
+const TOKEN_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+const MINT_SIZE: usize = 82;
+const TOKEN_ACCOUNT_SIZE: usize = 165;
+const INITIALIZE_MINT_TAG: u8 = 0;
+const INITIALIZE_ACCOUNT2_TAG: u8 = 16;
+const MINT_TO_TAG: u8 = 7;
+// every token of the group, the quote included, has the same decimals
+const DECIMALS: u8 = 6;
+
+// serum dex v3 account sizes of the mango clients, the 12 bytes of padding included
+const SERUM_MARKET_SIZE: usize = 388;
+const SERUM_REQUEST_QUEUE_SIZE: usize = 5120 + 12;
+const SERUM_EVENT_QUEUE_SIZE: usize = 262144 + 12;
+const SERUM_BOOK_SIDE_SIZE: usize = 65536 + 12;
+const SERUM_INITIALIZE_MARKET_TAG: u32 = 0;
+const SERUM_PC_DUST_THRESHOLD: u64 = 100;
+
+const PERP_EVENT_QUEUE_LEN: usize = 256;
+// every created account signs the transaction creating it
+const ACCOUNTS_PER_TX: usize = 4;
+
+// liquidation and interest parameters of the mango clients for the banks and markets
+const LIQUIDATION_FEE: f64 = 0.025;
+const OPTIMAL_UTIL: f64 = 0.7;
+const OPTIMAL_RATE: f64 = 0.06;
+const MAX_RATE: f64 = 1.5;
+const MAKER_FEE: f64 = 0.0;
+const TAKER_FEE: f64 = 0.0005;
+const MAX_DEPTH_BPS: f64 = 200.0;
+const TARGET_PERIOD_LENGTH: u64 = 3600;
+// seconds the cached prices and banks stay valid
+const VALID_INTERVAL: u64 = 10;
+
+// every market maker pays the fees of its own quotes
+const MARKET_MAKER_LAMPORTS: u64 = 10_000_000_000;
+// quote tokens deposited in the mango account of every market maker, as collateral of its orders
+const MARKET_MAKER_DEPOSIT: u64 = 1_000_000;
+
+/// signer of a program for an account, derived from the account and the first valid nonce the
+/// way mango and serum do
+fn signer_key_and_nonce(account: &Pubkey, program_id: &Pubkey) -> (Pubkey, u64) {
+    (0..u64::MAX)
+        .find_map(|nonce| {
+            Pubkey::create_program_address(&[account.as_ref(), &nonce.to_le_bytes()], program_id)
+                .ok()
+                .map(|signer| (signer, nonce))
+        })
+        .unwrap()
+}
+
+fn initialize_mint(mint: &Pubkey, authority: &Pubkey) -> Instruction {
+    let mut data = vec![INITIALIZE_MINT_TAG, DECIMALS];
+    data.extend_from_slice(authority.as_ref());
+    // no freeze authority
+    data.push(0);
+    Instruction {
+        program_id: TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*mint, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data,
+    }
+}
+
+fn mint_to(mint: &Pubkey, account: &Pubkey, authority: &Pubkey, amount: u64) -> Instruction {
+    let mut data = vec![MINT_TO_TAG];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*mint, false),
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    }
+}
+
+fn initialize_token_account(account: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> Instruction {
+    let mut data = vec![INITIALIZE_ACCOUNT2_TAG];
+    data.extend_from_slice(owner.as_ref());
+    Instruction {
+        program_id: TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Accounts of a serum spot market, the mango spot listing creates the root and node banks of
+/// the base token from it
+struct SerumMarket {
+    market: Pubkey,
+    request_queue: Pubkey,
+    event_queue: Pubkey,
+    bids: Pubkey,
+    asks: Pubkey,
+    coin_vault: Pubkey,
+    pc_vault: Pubkey,
+    vault_signer_nonce: u64,
+}
+
+impl SerumMarket {
+    fn initialize(
+        &self,
+        dex_program_id: &Pubkey,
+        coin_mint: &Pubkey,
+        pc_mint: &Pubkey,
+        coin_lot_size: u64,
+        pc_lot_size: u64,
+    ) -> Instruction {
+        let mut data = vec![0u8];
+        data.extend_from_slice(&SERUM_INITIALIZE_MARKET_TAG.to_le_bytes());
+        data.extend_from_slice(&coin_lot_size.to_le_bytes());
+        data.extend_from_slice(&pc_lot_size.to_le_bytes());
+        // the fee rate is not used by serum dex v3
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&self.vault_signer_nonce.to_le_bytes());
+        data.extend_from_slice(&SERUM_PC_DUST_THRESHOLD.to_le_bytes());
+        Instruction {
+            program_id: *dex_program_id,
+            accounts: vec![
+                AccountMeta::new(self.market, false),
+                AccountMeta::new(self.request_queue, false),
+                AccountMeta::new(self.event_queue, false),
+                AccountMeta::new(self.bids, false),
+                AccountMeta::new(self.asks, false),
+                AccountMeta::new(self.coin_vault, false),
+                AccountMeta::new(self.pc_vault, false),
+                AccountMeta::new_readonly(*coin_mint, false),
+                AccountMeta::new_readonly(*pc_mint, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+            data,
+        }
+    }
+}
+
+/// Sends the creation transactions one after the other, paid and signed by the admin
+struct Creator {
+    rpc_client: RpcClient,
+    admin: Keypair,
+}
+
+impl Creator {
+    async fn send(&self, ixs: &[Instruction], signers: &[&Keypair]) -> anyhow::Result<()> {
+        let mut all_signers: Vec<&dyn Signer> = vec![&self.admin];
+        all_signers.extend(signers.iter().map(|s| *s as &dyn Signer));
+        let tx = Transaction::new_signed_with_payer(
+            ixs,
+            Some(&self.admin.pubkey()),
+            &all_signers,
+            self.rpc_client.get_latest_blockhash().await?,
+        );
+        self.rpc_client.send_and_confirm_transaction(&tx).await?;
+        Ok(())
+    }
+
+    async fn send_mango(&self, ix: Result<MangoInstruction, ProgramError>) -> anyhow::Result<()> {
+        let ix = ix.map_err(|e| anyhow::anyhow!("could not build the mango instruction : {e}"))?;
+        self.send(&[to_sdk_instruction(ix)], &[]).await
+    }
+
+    async fn create_account(
+        &self,
+        account: &Keypair,
+        size: usize,
+        owner: &Pubkey,
+    ) -> anyhow::Result<Instruction> {
+        let lamports = self
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(size)
+            .await?;
+        Ok(system_instruction::create_account(
+            &self.admin.pubkey(),
+            &account.pubkey(),
+            lamports,
+            size as u64,
+            owner,
+        ))
+    }
+
+    /// new accounts of the sizes owned by the program, in the order of the sizes
+    async fn create_accounts(
+        &self,
+        sizes: &[usize],
+        owner: &Pubkey,
+    ) -> anyhow::Result<Vec<Pubkey>> {
+        let accounts = sizes.iter().map(|_| Keypair::new()).collect::<Vec<_>>();
+        let accounts_and_sizes = accounts.iter().zip(sizes).collect::<Vec<_>>();
+        for chunk in accounts_and_sizes.chunks(ACCOUNTS_PER_TX) {
+            let mut ixs = vec![];
+            for (account, size) in chunk {
+                ixs.push(self.create_account(account, **size, owner).await?);
+            }
+            let signers = chunk
+                .iter()
+                .map(|(account, _)| *account)
+                .collect::<Vec<_>>();
+            self.send(&ixs, &signers).await?;
+        }
+        Ok(accounts.iter().map(|a| a.pubkey()).collect())
+    }
+
+    /// new mint whose authority is the admin
+    async fn create_mint(&self) -> anyhow::Result<Pubkey> {
+        let mint = Keypair::new();
+        let ixs = [
+            self.create_account(&mint, MINT_SIZE, &TOKEN_PROGRAM_ID)
+                .await?,
+            initialize_mint(&mint.pubkey(), &self.admin.pubkey()),
+        ];
+        self.send(&ixs, &[&mint]).await?;
+        Ok(mint.pubkey())
+    }
+
+    async fn create_token_account(&self, mint: &Pubkey, owner: &Pubkey) -> anyhow::Result<Pubkey> {
+        let account = Keypair::new();
+        let ixs = [
+            self.create_account(&account, TOKEN_ACCOUNT_SIZE, &TOKEN_PROGRAM_ID)
+                .await?,
+            initialize_token_account(&account.pubkey(), mint, owner),
+        ];
+        self.send(&ixs, &[&account]).await?;
+        Ok(account.pubkey())
+    }
+
+    async fn create_serum_market(
+        &self,
+        dex_program_id: &Pubkey,
+        coin_mint: &Pubkey,
+        pc_mint: &Pubkey,
+        coin_lot_size: u64,
+        pc_lot_size: u64,
+    ) -> anyhow::Result<SerumMarket> {
+        let [market, request_queue, event_queue, bids, asks]: [Pubkey; 5] = self
+            .create_accounts(
+                &[
+                    SERUM_MARKET_SIZE,
+                    SERUM_REQUEST_QUEUE_SIZE,
+                    SERUM_EVENT_QUEUE_SIZE,
+                    SERUM_BOOK_SIDE_SIZE,
+                    SERUM_BOOK_SIDE_SIZE,
+                ],
+                dex_program_id,
+            )
+            .await?
+            .try_into()
+            .unwrap();
+        let (vault_signer, vault_signer_nonce) = signer_key_and_nonce(&market, dex_program_id);
+        let serum_market = SerumMarket {
+            market,
+            request_queue,
+            event_queue,
+            bids,
+            asks,
+            coin_vault: self.create_token_account(coin_mint, &vault_signer).await?,
+            pc_vault: self.create_token_account(pc_mint, &vault_signer).await?,
+            vault_signer_nonce,
+        };
+        self.send(
+            &[serum_market.initialize(
+                dex_program_id,
+                coin_mint,
+                pc_mint,
+                coin_lot_size,
+                pc_lot_size,
+            )],
+            &[],
+        )
+        .await?;
+        Ok(serum_market)
+    }
+}
+
+// same naming as the discovered groups
+fn symbol(index: usize) -> String {
+    format!("TOKEN{index}")
+}
+
+fn market_config(
+    name: String,
+    index: usize,
+    public_key: Pubkey,
+    bids: Pubkey,
+    asks: Pubkey,
+    events: Pubkey,
+) -> MarketConfig {
+    MarketConfig {
+        name,
+        public_key: public_key.to_string(),
+        base_symbol: symbol(index),
+        base_decimals: DECIMALS as u64,
+        quote_decimals: DECIMALS as u64,
+        market_index: index as u64,
+        bids_key: bids.to_string(),
+        asks_key: asks.to_string(),
+        events_key: events.to_string(),
+        order_size_lots: None,
+        size_jitter_pct: None,
+        max_spread_bps: None,
+        max_price_offset_lots: None,
+        quotes_per_second: None,
+        batch_size: None,
+        inventory_skew_bps: None,
+        order_expiry_secs: None,
+        order_limit: None,
+        compute_unit_limit: None,
+        weight: None,
+        order_types: None,
+        reduce_only_pct: None,
+    }
+}
+
+// writes the group in the mango keys file, replacing a group of the same name, the other
+// entries of an existing file are kept as they are
+fn write_mango_keys(output: &str, group: GroupConfig) -> anyhow::Result<()> {
+    let mut mango_keys = if Path::new(output).exists() {
+        serde_json::from_str(&fs::read_to_string(output)?)
+            .with_context(|| format!("{output} is not a valid mango keys file"))?
+    } else {
+        serde_json::json!({ "groups": [] })
+    };
+    let groups = mango_keys
+        .get_mut("groups")
+        .and_then(|groups| groups.as_array_mut())
+        .with_context(|| format!("{output} has no groups"))?;
+    groups.retain(|g| g.get("name").and_then(|name| name.as_str()) != Some(group.name.as_str()));
+    groups.push(serde_json::to_value(&group)?);
+    fs::write(output, serde_json::to_string_pretty(&mango_keys)?)?;
+    Ok(())
+}
+
+/// Creates a mango v3 group on a private cluster with the given number of perp markets, each one
+/// with a stub oracle and a serum spot market of the same token for its root and node banks, and
+/// writes the group to the mango keys file. The admin pays for every account, is the admin of
+/// the group and the mint authority of all the tokens
+pub async fn create_group(config: CreateGroupConfig) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        (1..=MAX_PAIRS).contains(&config.nb_markets),
+        "a mango v3 group has between 1 and {MAX_PAIRS} markets"
+    );
+    anyhow::ensure!(
+        config.maint_leverage > config.init_leverage && config.init_leverage > 0.0,
+        "the maintenance leverage has to be above the initial leverage"
+    );
+    anyhow::ensure!(
+        config.base_lot_size > 0 && config.quote_lot_size > 0,
+        "lot sizes have to be positive"
+    );
+    let mango_program_id = to_sdk_pk(&config.mango_program_id);
+    let dex_program_id = to_sdk_pk(&config.serum_program_id);
+    let creator = Creator {
+        rpc_client: RpcClient::new_with_commitment(
+            config.json_rpc_url.clone(),
+            CommitmentConfig::confirmed(),
+        ),
+        admin: config.admin,
+    };
+    let admin = creator.admin.pubkey();
+
+    // group with the quote token
+    let quote_mint = creator.create_mint().await?;
+    let [group, cache, quote_root_bank, quote_node_bank]: [Pubkey; 4] = creator
+        .create_accounts(
+            &[
+                size_of::<MangoGroup>(),
+                size_of::<MangoCache>(),
+                size_of::<RootBank>(),
+                size_of::<NodeBank>(),
+            ],
+            &mango_program_id,
+        )
+        .await?
+        .try_into()
+        .unwrap();
+    let (group_signer, signer_nonce) = signer_key_and_nonce(&group, &mango_program_id);
+    let quote_vault = creator
+        .create_token_account(&quote_mint, &group_signer)
+        .await?;
+    let insurance_vault = creator
+        .create_token_account(&quote_mint, &group_signer)
+        .await?;
+    let fees_vault = creator
+        .create_token_account(&quote_mint, &group_signer)
+        .await?;
+    creator
+        .send_mango(mango::instruction::init_mango_group(
+            &to_sp_pk(&mango_program_id),
+            &to_sp_pk(&group),
+            &to_sp_pk(&group_signer),
+            &to_sp_pk(&admin),
+            &to_sp_pk(&quote_mint),
+            &to_sp_pk(&quote_vault),
+            &to_sp_pk(&quote_node_bank),
+            &to_sp_pk(&quote_root_bank),
+            &to_sp_pk(&insurance_vault),
+            // no msrm vault
+            &solana_program::pubkey::Pubkey::default(),
+            &to_sp_pk(&fees_vault),
+            &to_sp_pk(&cache),
+            &to_sp_pk(&dex_program_id),
+            signer_nonce,
+            VALID_INTERVAL,
+            I80F48::from_num(OPTIMAL_UTIL),
+            I80F48::from_num(OPTIMAL_RATE),
+            I80F48::from_num(MAX_RATE),
+        ))
+        .await?;
+    info!("created mango group {group}");
+
+    let maint_leverage = I80F48::from_num(config.maint_leverage);
+    let init_leverage = I80F48::from_num(config.init_leverage);
+    let liquidation_fee = I80F48::from_num(LIQUIDATION_FEE);
+    let mut oracles = vec![];
+    // the quote token comes first in the mango keys, the base tokens follow it
+    let mut tokens = vec![TokenConfig {
+        symbol: "QUOTE".to_string(),
+        mint_key: quote_mint.to_string(),
+        decimals: DECIMALS as u64,
+        root_key: quote_root_bank.to_string(),
+        node_keys: vec![quote_node_bank.to_string()],
+    }];
+    let mut perp_markets = vec![];
+    let mut spot_markets = vec![];
+    let mut oracle_pks = vec![];
+    let mut root_bank_pks = vec![];
+    let mut perp_market_pks = vec![];
+    for index in 0..config.nb_markets {
+        let mint = creator.create_mint().await?;
+
+        let [oracle]: [Pubkey; 1] = creator
+            .create_accounts(&[size_of::<StubOracle>()], &mango_program_id)
+            .await?
+            .try_into()
+            .unwrap();
+        creator
+            .send_mango(mango::instruction::add_oracle(
+                &to_sp_pk(&mango_program_id),
+                &to_sp_pk(&group),
+                &to_sp_pk(&oracle),
+                &to_sp_pk(&admin),
+            ))
+            .await?;
+        creator
+            .send_mango(mango::instruction::set_oracle(
+                &to_sp_pk(&mango_program_id),
+                &to_sp_pk(&group),
+                &to_sp_pk(&oracle),
+                &to_sp_pk(&admin),
+                I80F48::from_num(config.price),
+            ))
+            .await?;
+
+        // the spot listing creates the banks of the token
+        let serum_market = creator
+            .create_serum_market(
+                &dex_program_id,
+                &mint,
+                &quote_mint,
+                config.base_lot_size as u64,
+                config.quote_lot_size as u64,
+            )
+            .await?;
+        let [root_bank, node_bank]: [Pubkey; 2] = creator
+            .create_accounts(
+                &[size_of::<RootBank>(), size_of::<NodeBank>()],
+                &mango_program_id,
+            )
+            .await?
+            .try_into()
+            .unwrap();
+        let vault = creator.create_token_account(&mint, &group_signer).await?;
+        creator
+            .send_mango(mango::instruction::add_spot_market(
+                &to_sp_pk(&mango_program_id),
+                &to_sp_pk(&group),
+                &to_sp_pk(&oracle),
+                &to_sp_pk(&serum_market.market),
+                &to_sp_pk(&dex_program_id),
+                &to_sp_pk(&mint),
+                &to_sp_pk(&node_bank),
+                &to_sp_pk(&vault),
+                &to_sp_pk(&root_bank),
+                &to_sp_pk(&admin),
+                maint_leverage,
+                init_leverage,
+                liquidation_fee,
+                I80F48::from_num(OPTIMAL_UTIL),
+                I80F48::from_num(OPTIMAL_RATE),
+                I80F48::from_num(MAX_RATE),
+            ))
+            .await?;
+
+        let [perp_market, event_queue, bids, asks]: [Pubkey; 4] = creator
+            .create_accounts(
+                &[
+                    size_of::<PerpMarket>(),
+                    size_of::<EventQueueHeader>() + PERP_EVENT_QUEUE_LEN * size_of::<AnyEvent>(),
+                    size_of::<BookSide>(),
+                    size_of::<BookSide>(),
+                ],
+                &mango_program_id,
+            )
+            .await?
+            .try_into()
+            .unwrap();
+        // liquidity mining is disabled, the vault stays empty
+        let mngo_vault = creator
+            .create_token_account(&quote_mint, &group_signer)
+            .await?;
+        creator
+            .send_mango(mango::instruction::add_perp_market(
+                &to_sp_pk(&mango_program_id),
+                &to_sp_pk(&group),
+                &to_sp_pk(&oracle),
+                &to_sp_pk(&perp_market),
+                &to_sp_pk(&event_queue),
+                &to_sp_pk(&bids),
+                &to_sp_pk(&asks),
+                &to_sp_pk(&mngo_vault),
+                &to_sp_pk(&admin),
+                maint_leverage,
+                init_leverage,
+                liquidation_fee,
+                I80F48::from_num(MAKER_FEE),
+                I80F48::from_num(TAKER_FEE),
+                config.base_lot_size,
+                config.quote_lot_size,
+                I80F48::ZERO,
+                I80F48::from_num(MAX_DEPTH_BPS),
+                TARGET_PERIOD_LENGTH,
+                0,
+                2,
+            ))
+            .await?;
+        info!("listed perp market {}-PERP {perp_market}", symbol(index));
+
+        oracle_pks.push(to_sp_pk(&oracle));
+        root_bank_pks.push(to_sp_pk(&root_bank));
+        perp_market_pks.push(to_sp_pk(&perp_market));
+        oracles.push(OracleConfig {
+            symbol: symbol(index),
+            public_key: oracle.to_string(),
+        });
+        tokens.push(TokenConfig {
+            symbol: symbol(index),
+            mint_key: mint.to_string(),
+            decimals: DECIMALS as u64,
+            root_key: root_bank.to_string(),
+            node_keys: vec![node_bank.to_string()],
+        });
+        perp_markets.push(market_config(
+            format!("{}-PERP", symbol(index)),
+            index,
+            perp_market,
+            bids,
+            asks,
+            event_queue,
+        ));
+        spot_markets.push(market_config(
+            format!("{}/QUOTE", symbol(index)),
+            index,
+            serum_market.market,
+            serum_market.bids,
+            serum_market.asks,
+            serum_market.event_queue,
+        ));
+    }
+    root_bank_pks.push(to_sp_pk(&quote_root_bank));
+
+    // the markets can only be quoted once the cache holds their prices and banks
+    creator
+        .send_mango(mango::instruction::cache_prices(
+            &to_sp_pk(&mango_program_id),
+            &to_sp_pk(&group),
+            &to_sp_pk(&cache),
+            &oracle_pks,
+        ))
+        .await?;
+    creator
+        .send_mango(mango::instruction::cache_root_banks(
+            &to_sp_pk(&mango_program_id),
+            &to_sp_pk(&group),
+            &to_sp_pk(&cache),
+            &root_bank_pks,
+        ))
+        .await?;
+    creator
+        .send_mango(mango::instruction::cache_perp_markets(
+            &to_sp_pk(&mango_program_id),
+            &to_sp_pk(&group),
+            &to_sp_pk(&cache),
+            &perp_market_pks,
+        ))
+        .await?;
+
+    write_mango_keys(
+        &config.output,
+        GroupConfig {
+            name: config.group_name.clone(),
+            public_key: group.to_string(),
+            cache_key: cache.to_string(),
+            mango_program_id: mango_program_id.to_string(),
+            serum_program_id: dex_program_id.to_string(),
+            oracles,
+            tokens,
+            perp_markets,
+            spot_markets,
+        },
+    )?;
+    info!(
+        "mango group {} written to {} with {} perp markets",
+        config.group_name, config.output, config.nb_markets
+    );
+
+    // market makers with a funded mango account each, in the format of the accounts file
+    let mut accounts = vec![];
+    for _ in 0..config.nb_market_makers {
+        let owner = Keypair::new();
+        let [mango_account]: [Pubkey; 1] = creator
+            .create_accounts(&[size_of::<MangoAccount>()], &mango_program_id)
+            .await?
+            .try_into()
+            .unwrap();
+        creator
+            .send(
+                &[
+                    system_instruction::transfer(&admin, &owner.pubkey(), MARKET_MAKER_LAMPORTS),
+                    to_sdk_instruction(
+                        mango::instruction::init_mango_account(
+                            &to_sp_pk(&mango_program_id),
+                            &to_sp_pk(&group),
+                            &to_sp_pk(&mango_account),
+                            &to_sp_pk(&owner.pubkey()),
+                        )
+                        .map_err(|e| anyhow::anyhow!("could not build init_mango_account : {e}"))?,
+                    ),
+                ],
+                &[&owner],
+            )
+            .await?;
+
+        let deposit = MARKET_MAKER_DEPOSIT * 10u64.pow(DECIMALS as u32);
+        let token_account = creator
+            .create_token_account(&quote_mint, &owner.pubkey())
+            .await?;
+        creator
+            .send(
+                &[
+                    mint_to(&quote_mint, &token_account, &admin, deposit),
+                    // the deposit needs a valid cache of the quote bank
+                    to_sdk_instruction(
+                        mango::instruction::cache_root_banks(
+                            &to_sp_pk(&mango_program_id),
+                            &to_sp_pk(&group),
+                            &to_sp_pk(&cache),
+                            &[to_sp_pk(&quote_root_bank)],
+                        )
+                        .map_err(|e| anyhow::anyhow!("could not build cache_root_banks : {e}"))?,
+                    ),
+                    to_sdk_instruction(
+                        mango::instruction::deposit(
+                            &to_sp_pk(&mango_program_id),
+                            &to_sp_pk(&group),
+                            &to_sp_pk(&mango_account),
+                            &to_sp_pk(&owner.pubkey()),
+                            &to_sp_pk(&cache),
+                            &to_sp_pk(&quote_root_bank),
+                            &to_sp_pk(&quote_node_bank),
+                            &to_sp_pk(&quote_vault),
+                            &to_sp_pk(&token_account),
+                            deposit,
+                        )
+                        .map_err(|e| anyhow::anyhow!("could not build deposit : {e}"))?,
+                    ),
+                ],
+                &[&owner],
+            )
+            .await?;
+        info!(
+            "created market maker {} with mango account {mango_account}",
+            owner.pubkey()
+        );
+        accounts.push(serde_json::json!({
+            "publicKey": owner.pubkey().to_string(),
+            "secretKey": owner.to_bytes().to_vec(),
+            "mangoAccountPks": [mango_account.to_string()],
+        }));
+    }
+    if !accounts.is_empty() {
+        fs::write(
+            &config.accounts_output,
+            serde_json::to_string_pretty(&accounts)?,
+        )?;
+        info!(
+            "{} market makers written to {}",
+            accounts.len(),
+            config.accounts_output
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use std::{io::SeekFrom, path::Path};
+
+use async_std::{
+    fs::{File, OpenOptions},
+    io::{prelude::BufReadExt, BufReader, ReadExt, SeekExt, WriteExt},
+};
+use csv_async::{AsyncSerializer, AsyncWriterBuilder};
+use futures::StreamExt;
+use log::warn;
+
+use crate::{
+    compression::{compress, open_decompressed, Compression, OutputWriter},
+    partitioned_output::read_manifest,
+};
+
+/// Versions of the csv outputs, bumped when a column is removed or changes meaning. Files
+/// written before the versioning have no marker and are version 1
+pub const TRANSACTION_SCHEMA_VERSION: u32 = 2;
+pub const BLOCK_SCHEMA_VERSION: u32 = 2;
+
+const MARKER_PREFIX: &str = "# mango-simulation ";
+
+/// Creates a csv file starting with a marker line holding the version of its schema, the header
+/// row follows it. The marker is compressed with the rows
+pub async fn create_versioned(
+    path: &str,
+    kind: &str,
+    version: u32,
+    compression: Option<Compression>,
+) -> std::io::Result<OutputWriter> {
+    let mut file = compress(File::create(path).await?, compression);
+    file.write_all(format!("{MARKER_PREFIX}{kind} schema {version}\n").as_bytes())
+        .await?;
+    Ok(file)
+}
+
+/// Opens a versioned csv file to append the rows of a resumed run, created like
+/// `create_versioned` when it does not exist yet. A crash can leave a torn row at the end of
+/// the file, the rows are appended after the last complete one. Returns whether the file
+/// existed, in which case its marker and header are not written again
+pub async fn append_versioned(
+    path: &str,
+    kind: &str,
+    version: u32,
+    compression: Option<Compression>,
+) -> std::io::Result<(OutputWriter, bool)> {
+    if Path::new(path).exists() {
+        let resumed = match compression {
+            None => append_uncompressed(path).await?,
+            Some(_) => recompress(path, compression).await?,
+        };
+        if let Some(file) = resumed {
+            return Ok((file, true));
+        }
+        warn!("{path} has no header, it is written again");
+    }
+    Ok((
+        create_versioned(path, kind, version, compression).await?,
+        false,
+    ))
+}
+
+// truncates the file after its last complete row, none when it does not hold a header yet
+async fn append_uncompressed(path: &str) -> std::io::Result<Option<OutputWriter>> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path).await?;
+    let Some(length) = complete_rows_length(&mut file).await? else {
+        return Ok(None);
+    };
+    file.set_len(length).await?;
+    let file = OpenOptions::new().append(true).open(path).await?;
+    Ok(Some(compress(file, None)))
+}
+
+// length up to the end of the last complete row, read backwards from the end of the file.
+// None unless the file holds at least the marker and the header
+async fn complete_rows_length(file: &mut File) -> std::io::Result<Option<u64>> {
+    let mut end = file.metadata().await?.len();
+    let mut last_row_end = None;
+    let mut buffer = vec![0; 64 * 1024];
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let chunk = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start)).await?;
+        file.read_exact(chunk).await?;
+        for (i, byte) in chunk.iter().enumerate().rev() {
+            if *byte != b'\n' {
+                continue;
+            }
+            match last_row_end {
+                None => last_row_end = Some(start + i as u64 + 1),
+                Some(length) => return Ok(Some(length)),
+            }
+        }
+        end = start;
+    }
+    Ok(None)
+}
+
+// A crash leaves a truncated gzip member or zstd frame which readers can not get past, the
+// rows appended after it would be lost. The complete rows are compressed again in a new file
+// renamed over the previous one, the resumed rows continue its stream
+async fn recompress(
+    path: &str,
+    compression: Option<Compression>,
+) -> std::io::Result<Option<OutputWriter>> {
+    let mut rows = vec![];
+    // the rows decoded before the truncated frame are kept when the read fails
+    if let Err(e) = open_decompressed(path).await?.read_to_end(&mut rows).await {
+        warn!("{path} ends with a truncated frame, its complete rows are kept : {e}");
+    }
+    let newlines = rows.iter().filter(|byte| **byte == b'\n').count();
+    if newlines < 2 {
+        return Ok(None);
+    }
+    let complete = rows.iter().rposition(|byte| *byte == b'\n').unwrap() + 1;
+    let tmp = format!("{path}.tmp");
+    let mut file = compress(File::create(&tmp).await?, compression);
+    file.write_all(&rows[..complete]).await?;
+    async_std::fs::rename(&tmp, path).await?;
+    Ok(Some(file))
+}
+
+/// csv serializer of a versioned file, without header when appending to an existing one
+pub fn versioned_serializer(
+    file: OutputWriter,
+    has_headers: bool,
+) -> AsyncSerializer<OutputWriter> {
+    AsyncWriterBuilder::new()
+        .has_headers(has_headers)
+        .create_serializer(file)
+}
+
+/// schema version of a csv file from its marker line
+pub async fn schema_version(path: &str) -> anyhow::Result<u32> {
+    let file = open_decompressed(path).await?;
+    let first_line = BufReader::new(file).lines().next().await.transpose()?;
+    Ok(first_line
+        .as_deref()
+        .and_then(|line| line.strip_prefix(MARKER_PREFIX))
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|version| version.parse().ok())
+        .unwrap_or(1))
+}
+
+/// Reads the rows of a csv file of any schema version, the columns missing in older versions
+/// take their serde default. Newer versions are read on a best effort basis. An output written
+/// with --partition-output is read from the files listed in its manifest
+pub async fn read_versioned_csv<T: serde::de::DeserializeOwned>(
+    path: &str,
+    supported_version: u32,
+) -> anyhow::Result<(u32, Vec<T>)> {
+    let Some(manifest) = read_manifest(path)? else {
+        return read_versioned_file(path, supported_version).await;
+    };
+    let mut rows = vec![];
+    for partition in &manifest.partitions {
+        let (_, partition_rows) = read_versioned_file(&partition.path, supported_version).await?;
+        rows.extend(partition_rows);
+    }
+    Ok((manifest.schema_version, rows))
+}
+
+async fn read_versioned_file<T: serde::de::DeserializeOwned>(
+    path: &str,
+    supported_version: u32,
+) -> anyhow::Result<(u32, Vec<T>)> {
+    let version = schema_version(path).await?;
+    if version > supported_version {
+        warn!("{path} has schema version {version}, newer than the supported version {supported_version}");
+    }
+    let mut reader = csv_async::AsyncReaderBuilder::new()
+        .comment(Some(b'#'))
+        .create_deserializer(open_decompressed(path).await?);
+    let mut records = reader.deserialize::<T>();
+    let mut rows = vec![];
+    while let Some(record) = records.next().await {
+        rows.push(record?);
+    }
+    Ok((version, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::compression::compressed_path;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Row {
+        signature: String,
+        slot: u64,
+    }
+
+    fn row(signature: &str, slot: u64) -> Row {
+        Row {
+            signature: signature.to_string(),
+            slot,
+        }
+    }
+
+    async fn write(mut file: OutputWriter, rows: &str) {
+        file.write_all(rows.as_bytes()).await.unwrap();
+        file.close().await.unwrap();
+    }
+
+    fn test_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("csv-schema-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name).to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn versioned_files_read_back() {
+        for compression in [None, Some(Compression::Gzip)] {
+            let path = compressed_path(&test_path("tlog.csv"), compression);
+            let file = create_versioned(&path, "transactions", 2, compression)
+                .await
+                .unwrap();
+            write(file, "signature,slot\nabc,1\ndef,2\n").await;
+
+            assert_eq!(schema_version(&path).await.unwrap(), 2);
+            let (version, rows) = read_versioned_csv::<Row>(&path, 2).await.unwrap();
+            assert_eq!(version, 2);
+            assert_eq!(rows, vec![row("abc", 1), row("def", 2)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn files_without_marker_are_version_1() {
+        let path = test_path("blog.csv");
+        write(
+            compress(File::create(&path).await.unwrap(), None),
+            "signature,slot\nabc,1\n",
+        )
+        .await;
+        assert_eq!(schema_version(&path).await.unwrap(), 1);
+        let (_, rows) = read_versioned_csv::<Row>(&path, 2).await.unwrap();
+        assert_eq!(rows, vec![row("abc", 1)]);
+    }
+
+    #[tokio::test]
+    async fn resumed_rows_follow_the_last_complete_row() {
+        for compression in [None, Some(Compression::Zstd)] {
+            let path = compressed_path(&test_path("resumed.csv"), compression);
+            let file = create_versioned(&path, "transactions", 2, compression)
+                .await
+                .unwrap();
+            write(file, "signature,slot\nabc,1\nde").await;
+
+            let (file, existed) = append_versioned(&path, "transactions", 2, compression)
+                .await
+                .unwrap();
+            assert!(existed);
+            write(file, "ghi,3\n").await;
+
+            let (_, rows) = read_versioned_csv::<Row>(&path, 2).await.unwrap();
+            assert_eq!(rows, vec![row("abc", 1), row("ghi", 3)]);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use solana_program::pubkey::Pubkey;
+use solana_runtime::block_cost_limits::{MAX_BLOCK_UNITS, MAX_WRITABLE_ACCOUNT_UNITS};
+
+use crate::states::PerpMarketCache;
+
+const SLOTS_PER_SECOND: f64 = 2.5;
+
+// the cost model reserves the requested compute units of a transaction, market maker transactions
+// do not set a compute unit limit so every instruction reserves the default limit
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+// spoofing transactions request the maximum compute units whatever the number of levels
+const SPOOFING_COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+
+pub struct MarketFeasibility {
+    pub perp_market_pk: Pubkey,
+    pub market_makers: f64,
+    pub txs_per_slot: f64,
+    pub capacity_per_slot: f64,
+}
+
+/// Upper bound of the landed market maker TPS given the write locks shared by the transactions
+/// every transaction on a market writes its bids, asks and event queue, every transaction of a
+/// market maker writes its mango account, keepers and crank are not taken into account
+pub struct FeasibilityReport {
+    pub requested_tps: f64,
+    pub compute_units_per_tx: u64,
+    pub markets: Vec<MarketFeasibility>,
+    pub account_txs_per_slot: f64,
+    pub account_capacity_per_slot: f64,
+    pub block_capacity_per_slot: f64,
+}
+
+impl FeasibilityReport {
+    pub fn new(
+        perp_market_caches: &[PerpMarketCache],
+        nb_market_makers: usize,
+        markets_per_mm: u8,
+        quotes_per_second: u64,
+        spoofing_levels: Option<u8>,
+    ) -> Self {
+        let nb_markets = perp_market_caches.len().max(1);
+        let markets_per_mm = (markets_per_mm as usize).min(nb_markets);
+        let compute_units_per_tx = match spoofing_levels {
+            Some(_) => SPOOFING_COMPUTE_UNIT_LIMIT,
+            // cancel all, place bid and place ask
+            None => 3 * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT,
+        };
+        let capacity_per_slot = (MAX_WRITABLE_ACCOUNT_UNITS / compute_units_per_tx) as f64;
+
+        // markets are chosen uniformly by each market maker
+        let market_makers = nb_market_makers as f64 * markets_per_mm as f64 / nb_markets as f64;
+        let markets = perp_market_caches
+            .iter()
+            .map(|c| MarketFeasibility {
+                perp_market_pk: c.perp_market_pk,
+                market_makers,
+                txs_per_slot: market_makers * quotes_per_second as f64 / SLOTS_PER_SECOND,
+                capacity_per_slot,
+            })
+            .collect();
+
+        Self {
+            requested_tps: (nb_market_makers * markets_per_mm) as f64 * quotes_per_second as f64,
+            compute_units_per_tx,
+            markets,
+            account_txs_per_slot: markets_per_mm as f64 * quotes_per_second as f64
+                / SLOTS_PER_SECOND,
+            account_capacity_per_slot: capacity_per_slot,
+            block_capacity_per_slot: (MAX_BLOCK_UNITS / compute_units_per_tx) as f64,
+        }
+    }
+
+    pub fn max_landed_tps(&self) -> f64 {
+        let per_account_ratio =
+            (self.account_capacity_per_slot / self.account_txs_per_slot).min(1.0);
+        let per_slot = self
+            .markets
+            .iter()
+            .map(|m| (m.txs_per_slot * per_account_ratio).min(m.capacity_per_slot))
+            .sum::<f64>()
+            .min(self.block_capacity_per_slot);
+        per_slot * SLOTS_PER_SECOND
+    }
+
+    pub fn print(&self) {
+        println!("\n\nFeasibility of the configuration (market maker transactions only)");
+        println!(
+            "Requested market maker TPS : {:.0}, {} CU reserved per transaction",
+            self.requested_tps, self.compute_units_per_tx
+        );
+        for market in &self.markets {
+            let saturated = market.txs_per_slot > market.capacity_per_slot;
+            println!(
+                "Market {} : market makers({:.1}) transactions per slot({:.1}) write lock capacity per slot({:.0}){}",
+                market.perp_market_pk,
+                market.market_makers,
+                market.txs_per_slot,
+                market.capacity_per_slot,
+                if saturated { " SATURATED" } else { "" }
+            );
+        }
+        println!(
+            "Per market maker account : transactions per slot({:.1}) write lock capacity per slot({:.0}){}",
+            self.account_txs_per_slot,
+            self.account_capacity_per_slot,
+            if self.account_txs_per_slot > self.account_capacity_per_slot {
+                " SATURATED"
+            } else {
+                ""
+            }
+        );
+        let max_landed_tps = self.max_landed_tps();
+        println!("Maximum landed market maker TPS : {max_landed_tps:.0}");
+        if max_landed_tps + 1.0 < self.requested_tps {
+            println!(
+                "WARNING : this configuration cannot land more than {:.0} market maker TPS ({:.1}% of the requested TPS), \
+                use more markets or less market makers per market",
+                max_landed_tps,
+                max_landed_tps * 100.0 / self.requested_tps
+            );
+        }
+        println!();
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_std::fs::File;
+use log::{info, warn};
+use mango::state::PerpMarket;
+use mango_common::Loadable;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_metrics::datapoint_info;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{clock::Clock, commitment_config::CommitmentConfig, sysvar};
+use tokio::task::JoinHandle;
+
+use crate::{clock, helpers::to_sdk_pk, result_writer::RowTags, states::PerpMarketCache};
+
+/// Funding state of a perp market at a sample
+#[derive(Clone, Serialize)]
+pub struct FundingRecord {
+    pub sampled_at: String,
+    pub slot: u64,
+    pub market: String,
+    pub long_funding: f64,
+    pub short_funding: f64,
+    // unix timestamp of the last update_funding of the market
+    pub last_updated: u64,
+    // seconds between the last update_funding and the cluster clock
+    pub update_age_secs: i64,
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+}
+
+// every perp market at the confirmed slot with the age of its last funding update
+async fn sample_funding(
+    rpc_client: &RpcClient,
+    perp_markets: &[Pubkey],
+) -> anyhow::Result<Vec<(Pubkey, u64, PerpMarket, i64)>> {
+    let mut pks = perp_markets.iter().map(to_sdk_pk).collect::<Vec<_>>();
+    pks.push(sysvar::clock::id());
+    let response = rpc_client
+        .get_multiple_accounts_with_commitment(&pks, CommitmentConfig::confirmed())
+        .await?;
+    let slot = response.context.slot;
+    let mut accounts = response.value;
+    let clock = accounts
+        .pop()
+        .flatten()
+        .ok_or_else(|| anyhow::anyhow!("clock sysvar not found"))?;
+    let clock: Clock = bincode::deserialize(&clock.data)?;
+
+    let mut samples = vec![];
+    for (pk, account) in perp_markets.iter().zip(accounts) {
+        let Some(account) = account else {
+            warn!("funding : perp market {pk} not found");
+            continue;
+        };
+        let perp_market = PerpMarket::load_from_bytes(&account.data)
+            .map_err(|e| anyhow::anyhow!("could not load the perp market {pk} : {e:?}"))?;
+        let update_age_secs = clock.unix_timestamp - perp_market.last_updated as i64;
+        samples.push((*pk, slot, *perp_market, update_age_secs));
+    }
+    Ok(samples)
+}
+
+/// Samples the long and short funding of the quoted perp markets at the interval and writes them
+/// to the funding file, so that a run shows whether update_funding keeps up under load. The
+/// oldest update seen on each market is logged at the end (mango v3 only)
+pub fn start_funding_monitor(
+    rpc_client: Arc<RpcClient>,
+    perp_market_caches: &[PerpMarketCache],
+    funding_file: String,
+    interval: Duration,
+    tags: RowTags,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    let perp_markets = perp_market_caches
+        .iter()
+        .map(|c| c.perp_market_pk)
+        .collect::<Vec<_>>();
+    tokio::spawn(async move {
+        let mut writer =
+            csv_async::AsyncSerializer::from_writer(File::create(funding_file).await.unwrap());
+        let mut max_update_age: HashMap<Pubkey, i64> = HashMap::new();
+        while !exit_signal.load(Ordering::Relaxed) {
+            tokio::time::sleep(interval).await;
+            let samples = match sample_funding(&rpc_client, &perp_markets).await {
+                Ok(samples) => samples,
+                Err(e) => {
+                    warn!("funding : could not sample the perp markets : {e}");
+                    continue;
+                }
+            };
+            let sampled_at = clock::now().to_string();
+            for (pk, slot, perp_market, update_age_secs) in samples {
+                let record = FundingRecord {
+                    sampled_at: sampled_at.clone(),
+                    slot,
+                    market: pk.to_string(),
+                    long_funding: perp_market.long_funding.to_num(),
+                    short_funding: perp_market.short_funding.to_num(),
+                    last_updated: perp_market.last_updated,
+                    update_age_secs,
+                    run_id: tags.run_id.clone(),
+                    cluster: tags.cluster.clone(),
+                    shard: tags.shard.clone(),
+                };
+                datapoint_info!(
+                    "bencher-funding",
+                    ("market", record.market.clone(), String),
+                    ("long_funding", record.long_funding, f64),
+                    ("short_funding", record.short_funding, f64),
+                    ("update_age_secs", update_age_secs, i64),
+                );
+                let max_age = max_update_age.entry(pk).or_insert(update_age_secs);
+                *max_age = (*max_age).max(update_age_secs);
+                writer.serialize(record).await.unwrap();
+            }
+            writer.flush().await.unwrap();
+        }
+
+        for (pk, max_age) in max_update_age {
+            info!("funding : the funding of {pk} was at most {max_age}s old");
+        }
+    })
+}
@@ -0,0 +1,104 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use log::{error, info};
+use tokio::task::JoinHandle;
+
+use crate::watchdog::Watchdog;
+
+/// Readiness of the run, set as the startup goes on
+#[derive(Clone, Default)]
+pub struct Health {
+    blockhash_fetched: Arc<AtomicBool>,
+    keepers_warmed_up: Arc<AtomicBool>,
+}
+
+impl Health {
+    pub fn set_blockhash_fetched(&self) {
+        self.blockhash_fetched.store(true, Ordering::Relaxed);
+    }
+
+    pub fn set_keepers_warmed_up(&self) {
+        self.keepers_warmed_up.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.blockhash_fetched.load(Ordering::Relaxed)
+            && self.keepers_warmed_up.load(Ordering::Relaxed)
+    }
+}
+
+fn plain_response(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn handle(
+    request: Request<Body>,
+    health: Health,
+    watchdog: Watchdog,
+    stall_timeout: Duration,
+) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/healthz") => {
+            let stalled = watchdog.stalled_threads(stall_timeout);
+            if stalled.is_empty() {
+                plain_response(StatusCode::OK, "ok".to_string())
+            } else {
+                plain_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("stalled threads : {}", stalled.join(", ")),
+                )
+            }
+        }
+        (&Method::GET, "/ready") => {
+            if health.is_ready() {
+                plain_response(StatusCode::OK, "ready".to_string())
+            } else {
+                plain_response(StatusCode::SERVICE_UNAVAILABLE, "warming up".to_string())
+            }
+        }
+        _ => plain_response(StatusCode::NOT_FOUND, String::new()),
+    };
+    Ok(response)
+}
+
+/// Http server for the probes of container orchestrators, started before anything else so it
+/// answers during the startup
+/// GET /healthz fails while a thread monitored by the watchdog is stalled
+/// GET /ready fails until the first blockhash is fetched and the keepers are warmed up
+pub fn start_health_server(
+    addr: SocketAddr,
+    health: Health,
+    watchdog: Watchdog,
+    stall_timeout: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let make_service = make_service_fn(move |_| {
+            let health = health.clone();
+            let watchdog = watchdog.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle(request, health.clone(), watchdog.clone(), stall_timeout)
+                }))
+            }
+        });
+        info!("health server listening on {addr}");
+        if let Err(e) = Server::bind(&addr).serve(make_service).await {
+            error!("health server stopped {e:?}");
+        }
+    })
+}
@@ -2,23 +2,29 @@ use std::{
     ops::{Div, Mul},
     str::FromStr,
     sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
         Arc,
     },
     time::{Duration, Instant},
 };
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use fixed::types::I80F48;
-use log::{debug, info};
+use log::{debug, info, warn};
 use mango::state::{MangoCache, MangoGroup, PerpMarket};
 use mango_common::Loadable;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_program::{clock::DEFAULT_MS_PER_SLOT, pubkey::Pubkey};
-use solana_sdk::hash::Hash;
+use solana_runtime::block_cost_limits::MAX_BLOCK_UNITS;
+use solana_sdk::{hash::Hash, instruction::CompiledInstruction, transaction::Transaction};
 use tokio::{sync::RwLock, task::JoinHandle};
 
-use crate::{mango::GroupConfig, states::PerpMarketCache};
+use crate::{
+    mango::{base_token, quote_token, GroupConfig},
+    states::{BlockhashSlots, PerpMarketCache, QuoteConfig},
+    watchdog::Watchdog,
+};
 
 // as there are similar modules solana_sdk and solana_program
 // solana internals use solana_sdk but external dependancies like mango use solana program
@@ -70,13 +76,20 @@ pub async fn get_latest_blockhash(rpc_client: &RpcClient) -> Hash {
     }
 }
 
-pub async fn get_new_latest_blockhash(client: Arc<RpcClient>, blockhash: &Hash) -> Option<Hash> {
+/// new blockhash with the last block height at which transactions using it can land
+pub async fn get_new_latest_blockhash(
+    client: Arc<RpcClient>,
+    blockhash: &Hash,
+) -> Option<(Hash, u64)> {
     let start = Instant::now();
     while start.elapsed().as_secs() < 5 {
-        if let Ok(new_blockhash) = client.get_latest_blockhash().await {
+        if let Ok((new_blockhash, last_valid_block_height)) = client
+            .get_latest_blockhash_with_commitment(client.commitment())
+            .await
+        {
             if new_blockhash != *blockhash {
                 debug!("Got new blockhash ({:?})", blockhash);
-                return Some(new_blockhash);
+                return Some((new_blockhash, last_valid_block_height));
             }
         }
         debug!("Got same blockhash ({:?}), will retry...", blockhash);
@@ -92,10 +105,13 @@ pub async fn poll_blockhash_and_slot(
     blockhash: Arc<RwLock<Hash>>,
     slot: &AtomicU64,
     client: Arc<RpcClient>,
+    watchdog: Watchdog,
+    blockhash_slots: BlockhashSlots,
 ) {
     let mut blockhash_last_updated = Instant::now();
     //let mut last_error_log = Instant::now();
     loop {
+        watchdog.heartbeat("blockhash");
         let client = client.clone();
         let old_blockhash = *blockhash.read().await;
         if exit_signal.load(Ordering::Relaxed) {
@@ -103,15 +119,22 @@ pub async fn poll_blockhash_and_slot(
             break;
         }
 
-        match client.get_slot().await {
-            Ok(new_slot) => slot.store(new_slot, Ordering::Release),
+        let current_slot = match client.get_slot().await {
+            Ok(new_slot) => {
+                slot.store(new_slot, Ordering::Release);
+                new_slot
+            }
             Err(e) => {
                 info!("Failed to download slot: {}, skip", e);
                 continue;
             }
-        }
+        };
 
-        if let Some(new_blockhash) = get_new_latest_blockhash(client, &old_blockhash).await {
+        if let Some((new_blockhash, last_valid_block_height)) =
+            get_new_latest_blockhash(client, &old_blockhash).await
+        {
+            // the slot read just before is close enough to the slot the blockhash was fetched at
+            blockhash_slots.insert(new_blockhash, current_slot, last_valid_block_height);
             {
                 *blockhash.write().await = new_blockhash;
             }
@@ -122,10 +145,53 @@ pub async fn poll_blockhash_and_slot(
 
         tokio::time::sleep(Duration::from_millis(100)).await;
     }
+    watchdog.unregister("blockhash");
+}
+
+/// percentage of the block compute unit limit consumed by a block
+pub fn block_cu_utilization(cu_consumed: u64) -> f64 {
+    cu_consumed as f64 * 100.0 / MAX_BLOCK_UNITS as f64
+}
+
+/// nearest rank percentile of sorted values
+pub fn percentile<T: Copy>(sorted: &[T], percentile: f64) -> Option<T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((percentile / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
 }
 
-pub fn seconds_since(dt: DateTime<Utc>) -> i64 {
-    Utc::now().signed_duration_since(dt).num_seconds()
+/// entry drawn in proportion to its weight out of the total, none for the part of the total which
+/// is not weighted
+pub fn weighted_pick<T: Copy>(rng: &mut impl Rng, weights: &[(T, u32)], total: u32) -> Option<T> {
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.gen_range(0..total);
+    for (entry, weight) in weights {
+        if roll < *weight {
+            return Some(*entry);
+        }
+        roll -= weight;
+    }
+    None
+}
+
+/// random generator of a stream of draws, reproducible with a seed. The users of the seed draw
+/// from distinct streams so that they do not change each other's draws
+pub fn seeded_rng(seed: Option<u64>, stream: u64) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(stream)),
+        None => StdRng::from_entropy(),
+    }
+}
+
+// records are written with the display format of chrono, "2023-05-01 10:00:00.123456 UTC"
+pub fn parse_datetime(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value.trim_end_matches(" UTC"), "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|dt| DateTime::<Utc>::from_utc(dt, Utc))
 }
 
 pub fn start_blockhash_polling_service(
@@ -133,6 +199,8 @@ pub fn start_blockhash_polling_service(
     blockhash: Arc<RwLock<Hash>>,
     current_slot: Arc<AtomicU64>,
     client: Arc<RpcClient>,
+    watchdog: Watchdog,
+    blockhash_slots: BlockhashSlots,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
         poll_blockhash_and_slot(
@@ -140,11 +208,30 @@ pub fn start_blockhash_polling_service(
             blockhash.clone(),
             current_slot.as_ref(),
             client,
+            watchdog,
+            blockhash_slots,
         )
         .await;
     })
 }
 
+/// converts a price in quote tokens per base token into quote lots per base lot
+pub fn to_price_quote_lots(
+    price: I80F48,
+    base_decimals: u64,
+    quote_decimals: u64,
+    perp_market: &PerpMarket,
+) -> i64 {
+    let base_unit = I80F48::from_num(10u64.pow(base_decimals as u32));
+    let quote_unit = I80F48::from_num(10u64.pow(quote_decimals as u32));
+    price
+        .mul(quote_unit)
+        .mul(I80F48::from_num(perp_market.base_lot_size))
+        .div(I80F48::from_num(perp_market.quote_lot_size))
+        .div(base_unit)
+        .to_num()
+}
+
 pub async fn get_mango_market_perps_cache(
     rpc_client: Arc<RpcClient>,
     mango_group_config: &GroupConfig,
@@ -162,30 +249,24 @@ pub async fn get_mango_market_perps_cache(
         let perp_market = load_from_rpc::<PerpMarket>(&rpc_client, &perp_market_pk).await;
 
         // fetch price
-        let base_decimals = mango_group_config.tokens[market_index].decimals;
-        let quote_decimals = mango_group_config.tokens[0].decimals;
-
+        let base_token = base_token(mango_group_config, perp_maket_config);
+        let base_decimals = base_token.decimals;
+        let quote_decimals = quote_token(mango_group_config).decimals;
         let base_unit = I80F48::from_num(10u64.pow(base_decimals as u32));
-        let quote_unit = I80F48::from_num(10u64.pow(quote_decimals as u32));
         let price = mango_cache.price_cache[market_index].price;
         println!(
             "market index {} price of  : {}",
             market_index, mango_cache.price_cache[market_index].price
         );
 
-        let price_quote_lots: i64 = price
-            .mul(quote_unit)
-            .mul(I80F48::from_num(perp_market.base_lot_size))
-            .div(I80F48::from_num(perp_market.quote_lot_size))
-            .div(base_unit)
-            .to_num();
+        let price_quote_lots =
+            to_price_quote_lots(price, base_decimals, quote_decimals, &perp_market);
         let order_base_lots: i64 = base_unit
             .div(I80F48::from_num(perp_market.base_lot_size))
             .to_num();
 
-        let root_bank = &mango_group_config.tokens[market_index].root_key;
-        let root_bank = Pubkey::from_str(root_bank.as_str()).unwrap();
-        let node_banks = mango_group_config.tokens[market_index]
+        let root_bank = Pubkey::from_str(base_token.root_key.as_str()).unwrap();
+        let node_banks = base_token
             .node_keys
             .iter()
             .map(|x| Pubkey::from_str(x.as_str()).unwrap())
@@ -206,7 +287,101 @@ pub async fn get_mango_market_perps_cache(
             price_oracle,
             bids: perp_market.bids,
             asks: perp_market.asks,
+            event_queue: perp_market.event_queue,
+            quote_config: QuoteConfig::default(),
+            quote_price: Arc::new(AtomicI64::new(PerpMarketCache::DEFAULT_QUOTE_PRICE)),
         });
     }
     ret
 }
+
+/// applies the quote config of the cli, overridden by the per market values of the group config
+pub fn apply_quote_config(
+    perp_market_caches: &mut [PerpMarketCache],
+    mango_group_config: &GroupConfig,
+    quote_config: QuoteConfig,
+) {
+    for cache in perp_market_caches {
+        let market_config = mango_group_config
+            .perp_markets
+            .iter()
+            .find(|m| m.public_key == cache.perp_market_pk.to_string());
+        cache.quote_config = match market_config {
+            Some(m) => QuoteConfig {
+                order_size_lots: m.order_size_lots.or(quote_config.order_size_lots),
+                size_jitter_pct: m.size_jitter_pct.unwrap_or(quote_config.size_jitter_pct),
+                max_spread_bps: m.max_spread_bps.unwrap_or(quote_config.max_spread_bps),
+                max_price_offset_lots: m
+                    .max_price_offset_lots
+                    .unwrap_or(quote_config.max_price_offset_lots),
+                quotes_per_second: m.quotes_per_second.or(quote_config.quotes_per_second),
+                batch_size: m.batch_size.or(quote_config.batch_size),
+                inventory_skew_bps: m
+                    .inventory_skew_bps
+                    .unwrap_or(quote_config.inventory_skew_bps),
+                order_expiry_secs: m.order_expiry_secs.or(quote_config.order_expiry_secs),
+                order_limit: m.order_limit.unwrap_or(quote_config.order_limit),
+                compute_unit_limit: m.compute_unit_limit.or(quote_config.compute_unit_limit),
+                weight: m.weight.unwrap_or(quote_config.weight),
+                order_mix: m.order_types.as_ref().map_or(quote_config.order_mix, |x| {
+                    x.parse()
+                        .unwrap_or_else(|e| panic!("invalid order types of {} : {e}", m.name))
+                }),
+                reduce_only_pct: m.reduce_only_pct.unwrap_or(quote_config.reduce_only_pct),
+                ..quote_config
+            },
+            None => quote_config,
+        };
+    }
+}
+
+/// Weighs the markets by their notional open interest, so that the markets with the most
+/// positions get the most market makers. The weights of the group config are kept when no
+/// market has open interest (mango v3 only)
+pub fn weigh_by_open_interest(perp_market_caches: &mut [PerpMarketCache]) {
+    let open_interests = perp_market_caches
+        .iter()
+        .map(|c| {
+            c.perp_market.open_interest as f64
+                * c.perp_market.base_lot_size as f64
+                * c.price.to_num::<f64>()
+        })
+        .collect::<Vec<_>>();
+    if !open_interests.iter().any(|oi| *oi > 0.0) {
+        warn!("no perp market has open interest, the markets keep their configured weights");
+        return;
+    }
+    for (cache, open_interest) in perp_market_caches.iter_mut().zip(open_interests) {
+        info!(
+            "perp market {} weighted by its open interest of {open_interest:.0}",
+            cache.perp_market_pk
+        );
+        cache.quote_config.weight = open_interest;
+    }
+}
+
+/// appends an instruction which takes no account to an unsigned transaction, the program is
+/// added to the readonly unsigned accounts of the message when it is not already in it
+pub fn append_instruction_without_accounts(
+    tx: &mut Transaction,
+    program_id: &solana_sdk::pubkey::Pubkey,
+    data: Vec<u8>,
+) {
+    let message = &mut tx.message;
+    let program_index = match message.account_keys.iter().position(|k| k == program_id) {
+        Some(index) => index,
+        None => {
+            // readonly unsigned accounts come last
+            message.account_keys.push(*program_id);
+            message.header.num_readonly_unsigned_accounts += 1;
+            message.account_keys.len() - 1
+        }
+    };
+    message
+        .instructions
+        .push(CompiledInstruction::new_from_raw_parts(
+            program_index as u8,
+            data,
+            vec![],
+        ));
+}
@@ -0,0 +1,273 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::warn;
+use rand::{rngs::StdRng, Rng};
+use solana_metrics::datapoint_info;
+use solana_sdk::transaction::Transaction;
+
+use crate::{
+    helpers::{seeded_rng, weighted_pick},
+    transaction_sender::TransactionSender,
+};
+
+// draws of the impairment of the first sender, apart from the draws of the market makers
+const IMPAIRMENT_RNG_STREAM: u64 = 1 << 32;
+
+/// Percentages of the outgoing transactions lost, sent twice or held back before they reach the
+/// sender, to check the confirmation timeouts against a known loss rate. Unlike the chaos
+/// duplicates and the send delay, the impairment happens after the transactions are recorded as
+/// sent, like on the network, so it does not show in the records
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetworkImpairment {
+    pub drop_pct: u8,
+    pub duplicate_pct: u8,
+    pub delay_pct: u8,
+    pub delay: Duration,
+}
+
+impl FromStr for NetworkImpairment {
+    type Err = String;
+
+    /// comma separated `kind=value`, e.g. `drop=5,duplicate=2,delay=10,delay-ms=500`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut impairment = NetworkImpairment::default();
+        for entry in s.split(',').filter(|e| !e.is_empty()) {
+            let (kind, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected kind=value, got {entry}"))?;
+            let pct = || {
+                value
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid impairment percentage {value}"))
+            };
+            match kind.trim() {
+                "drop" => impairment.drop_pct = pct()?,
+                "duplicate" => impairment.duplicate_pct = pct()?,
+                "delay" => impairment.delay_pct = pct()?,
+                "delay-ms" => {
+                    impairment.delay = Duration::from_millis(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid impairment delay {value}"))?,
+                    )
+                }
+                _ => return Err(format!("unknown impairment kind {kind}")),
+            }
+        }
+        if impairment.drop_pct as u32
+            + impairment.duplicate_pct as u32
+            + impairment.delay_pct as u32
+            > 100
+        {
+            return Err("impairment percentages add up to more than 100".to_string());
+        }
+        if impairment.delay_pct > 0 && impairment.delay.is_zero() {
+            return Err("delayed transactions need a delay-ms".to_string());
+        }
+        Ok(impairment)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Impairment {
+    Drop,
+    Duplicate,
+    Delay,
+}
+
+impl NetworkImpairment {
+    fn pick(&self, rng: &mut impl Rng) -> Option<Impairment> {
+        weighted_pick(
+            rng,
+            &[
+                (Impairment::Drop, self.drop_pct as u32),
+                (Impairment::Duplicate, self.duplicate_pct as u32),
+                (Impairment::Delay, self.delay_pct as u32),
+            ],
+            100,
+        )
+    }
+}
+
+/// Transactions impaired by all the senders of a run
+#[derive(Clone, Default)]
+pub struct ImpairmentCounters {
+    dropped: Arc<AtomicU64>,
+    duplicated: Arc<AtomicU64>,
+    delayed: Arc<AtomicU64>,
+}
+
+impl ImpairmentCounters {
+    pub fn report(&self) -> String {
+        let (dropped, duplicated, delayed) = (
+            self.dropped.load(Ordering::Relaxed),
+            self.duplicated.load(Ordering::Relaxed),
+            self.delayed.load(Ordering::Relaxed),
+        );
+        datapoint_info!(
+            "bencher-network-impairment",
+            ("dropped", dropped, i64),
+            ("duplicated", duplicated, i64),
+            ("delayed", delayed, i64),
+        );
+        format!("{dropped} transactions dropped, {duplicated} duplicated and {delayed} delayed by the network impairment")
+    }
+}
+
+/// Sender wrapper applying the network impairment. The dropped transactions are reported as sent,
+/// so the confirmation pipeline waits for them like for the transactions lost on the way. The
+/// delayed transactions are sent in the background and do not hold back the rest of their batch
+pub struct ImpairedSender {
+    sender: Arc<dyn TransactionSender>,
+    impairment: NetworkImpairment,
+    counters: ImpairmentCounters,
+    rng: Mutex<StdRng>,
+}
+
+impl ImpairedSender {
+    /// with a seed the impaired transactions of each sender are reproducible
+    pub fn new(
+        sender: Arc<dyn TransactionSender>,
+        impairment: NetworkImpairment,
+        counters: ImpairmentCounters,
+        seed: Option<u64>,
+        sender_index: u64,
+    ) -> Self {
+        Self {
+            sender,
+            impairment,
+            counters,
+            rng: Mutex::new(seeded_rng(seed, IMPAIRMENT_RNG_STREAM + sender_index)),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for ImpairedSender {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        self.send_wire(transaction, bincode::serialize(transaction)?)
+            .await
+    }
+
+    async fn send_wire(
+        &self,
+        transaction: &Transaction,
+        wire_transaction: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let impairment = self.impairment.pick(&mut *self.rng.lock().unwrap());
+        match impairment {
+            Some(Impairment::Drop) => {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Some(Impairment::Duplicate) => {
+                self.counters.duplicated.fetch_add(1, Ordering::Relaxed);
+                self.sender
+                    .send_wire(transaction, wire_transaction.clone())
+                    .await?;
+                self.sender.send_wire(transaction, wire_transaction).await
+            }
+            Some(Impairment::Delay) => {
+                self.counters.delayed.fetch_add(1, Ordering::Relaxed);
+                let sender = self.sender.clone();
+                let transaction = transaction.clone();
+                let delay = self.impairment.delay;
+                // like a dropped transaction, a delayed one which fails to send is left to time out
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    if let Err(e) = sender.send_wire(&transaction, wire_transaction).await {
+                        warn!("error sending a delayed transaction {e:?}");
+                    }
+                });
+                Ok(())
+            }
+            None => self.sender.send_wire(transaction, wire_transaction).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{signature::Keypair, signer::Signer};
+
+    use super::*;
+    use crate::transaction_sender::MockSender;
+
+    fn impaired(mock: &MockSender, impairment: &str) -> (ImpairedSender, ImpairmentCounters) {
+        let counters = ImpairmentCounters::default();
+        let sender = ImpairedSender::new(
+            Arc::new(mock.clone()),
+            impairment.parse().unwrap(),
+            counters.clone(),
+            Some(0),
+            0,
+        );
+        (sender, counters)
+    }
+
+    fn transaction() -> Transaction {
+        Transaction::new_with_payer(&[], Some(&Keypair::new().pubkey()))
+    }
+
+    #[tokio::test]
+    async fn dropped_transactions_are_reported_as_sent() {
+        let mock = MockSender::default();
+        let (sender, counters) = impaired(&mock, "drop=100");
+        for _ in 0..10 {
+            sender.send(&transaction()).await.unwrap();
+        }
+        assert!(mock.sent_signatures().is_empty());
+        assert_eq!(counters.dropped.load(Ordering::Relaxed), 10);
+    }
+
+    #[tokio::test]
+    async fn duplicated_transactions_are_sent_twice() {
+        let mock = MockSender::default();
+        let (sender, counters) = impaired(&mock, "duplicate=100");
+        let tx = transaction();
+        sender.send(&tx).await.unwrap();
+        assert_eq!(mock.sent_signatures(), vec![tx.signatures[0]; 2]);
+        assert_eq!(counters.duplicated.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn delayed_transactions_are_sent_in_the_background() {
+        let mock = MockSender::default();
+        let (sender, counters) = impaired(&mock, "delay=100,delay-ms=20");
+        sender.send(&transaction()).await.unwrap();
+        assert!(mock.sent_signatures().is_empty());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(mock.sent_signatures().len(), 1);
+        assert_eq!(counters.delayed.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn errors_of_the_sender_are_returned() {
+        let mock = MockSender::failing("connection refused");
+        let (sender, _) = impaired(&mock, "");
+        let error = sender.send(&transaction()).await.unwrap_err();
+        assert_eq!(error.to_string(), "connection refused");
+    }
+
+    #[test]
+    fn parses_the_impairment_percentages() {
+        let impairment: NetworkImpairment =
+            "drop=5,duplicate=2,delay=10,delay-ms=500".parse().unwrap();
+        assert_eq!(impairment.drop_pct, 5);
+        assert_eq!(impairment.duplicate_pct, 2);
+        assert_eq!(impairment.delay_pct, 10);
+        assert_eq!(impairment.delay, Duration::from_millis(500));
+
+        assert!("delay=10".parse::<NetworkImpairment>().is_err());
+        assert!("drop=60,duplicate=41".parse::<NetworkImpairment>().is_err());
+        assert!("loss=1".parse::<NetworkImpairment>().is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::Context;
+use fixed::types::I80F48;
+use mango::state::{
+    HealthCache, HealthType, MangoAccount, MangoCache, MangoGroup, UserActiveAssets,
+    FREE_ORDER_SLOT, MAX_PAIRS, QUOTE_INDEX,
+};
+use mango_common::Loadable;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::{account_info::AccountInfo, pubkey::Pubkey};
+
+use crate::{
+    cli::InspectAccountConfig,
+    client_order_ids::ClientOrderIdKey,
+    config_loader::load_config,
+    helpers::{to_sdk_pk, to_sp_pk},
+    mango::{GroupConfig, MangoConfig},
+};
+
+fn ui_amount(native: I80F48, decimals: u64) -> f64 {
+    native.to_num::<f64>() / 10f64.powi(decimals as i32)
+}
+
+// the quote token is the last token of the group on-chain and the first one of the config
+fn token_symbol(group: &GroupConfig, mint: &Pubkey) -> String {
+    let mint = mint.to_string();
+    group
+        .tokens
+        .iter()
+        .find(|t| t.mint_key == mint)
+        .map_or(mint, |t| t.symbol.clone())
+}
+
+fn perp_market_name(group: &GroupConfig, index: usize) -> String {
+    group
+        .perp_markets
+        .iter()
+        .find(|m| m.market_index as usize == index)
+        .map_or_else(|| format!("perp market {index}"), |m| m.name.clone())
+}
+
+/// Health of the account as computed by the mango program, the spot open orders of the margin
+/// basket are fetched as the program needs them
+async fn health(
+    rpc_client: &RpcClient,
+    mango_group: &MangoGroup,
+    mango_cache: &MangoCache,
+    mango_account: &MangoAccount,
+) -> anyhow::Result<(I80F48, I80F48)> {
+    let mut open_orders = vec![];
+    for i in 0..MAX_PAIRS {
+        open_orders.push(if mango_account.in_margin_basket[i] {
+            let pk = mango_account.spot_open_orders[i];
+            let account = rpc_client.get_account(&to_sdk_pk(&pk)).await?;
+            Some((pk, to_sp_pk(&account.owner), account))
+        } else {
+            None
+        });
+    }
+    let open_orders_ais = open_orders
+        .iter_mut()
+        .map(|open_orders| {
+            let (pk, owner, account) = open_orders.as_mut()?;
+            Some(AccountInfo::new(
+                pk,
+                false,
+                false,
+                &mut account.lamports,
+                &mut account.data,
+                owner,
+                false,
+                0,
+            ))
+        })
+        .collect::<Vec<_>>();
+    let open_orders_ais: Vec<Option<&AccountInfo>> =
+        open_orders_ais.iter().map(|ai| ai.as_ref()).collect();
+
+    let active_assets = UserActiveAssets::new(mango_group, mango_account, vec![]);
+    let mut health_cache = HealthCache::new(active_assets);
+    health_cache
+        .init_vals(mango_group, mango_cache, mango_account, &open_orders_ais)
+        .map_err(|e| anyhow::anyhow!("could not compute the health : {e:?}"))?;
+    Ok((
+        health_cache.get_health(mango_group, HealthType::Init),
+        health_cache.get_health(mango_group, HealthType::Maint),
+    ))
+}
+
+/// Prints the deposits, perp positions, open orders and health of a mango account, to find out
+/// why the orders of a market maker fail with InsufficientFunds (mango v3 only)
+pub async fn inspect_account(config: InspectAccountConfig) -> anyhow::Result<()> {
+    let rpc_client = RpcClient::new(config.json_rpc_url.clone());
+    let mango_config: MangoConfig = serde_json::from_str(&load_config(&config.mango_keys).await?)
+        .map_err(|e| {
+        anyhow::anyhow!("{} is not a valid mango keys file : {e}", config.mango_keys)
+    })?;
+    let group = mango_config
+        .groups
+        .iter()
+        .find(|g| g.name == config.mango_cluster)
+        .with_context(|| {
+            format!(
+                "no group named {} in {}",
+                config.mango_cluster, config.mango_keys
+            )
+        })?;
+
+    let account = rpc_client
+        .get_account(&to_sdk_pk(&config.mango_account))
+        .await
+        .with_context(|| format!("mango account {} not found", config.mango_account))?;
+    let mango_account = *MangoAccount::load_from_bytes(&account.data)
+        .map_err(|e| anyhow::anyhow!("{} is not a mango account : {e:?}", config.mango_account))?;
+    let group_pk: Pubkey = group.public_key.parse()?;
+    if mango_account.mango_group != group_pk {
+        anyhow::bail!(
+            "{} belongs to the group {} instead of {} ({group_pk})",
+            config.mango_account,
+            mango_account.mango_group,
+            group.name
+        );
+    }
+    let group_account = rpc_client.get_account(&to_sdk_pk(&group_pk)).await?;
+    let mango_group = *MangoGroup::load_from_bytes(&group_account.data)
+        .map_err(|e| anyhow::anyhow!("could not load the mango group : {e:?}"))?;
+    let cache_account = rpc_client
+        .get_account(&to_sdk_pk(&mango_group.mango_cache))
+        .await?;
+    let mango_cache = *MangoCache::load_from_bytes(&cache_account.data)
+        .map_err(|e| anyhow::anyhow!("could not load the mango cache : {e:?}"))?;
+
+    println!("Mango account {}", config.mango_account);
+    println!("  owner {}", mango_account.owner);
+    println!("  group {} ({group_pk})", group.name);
+    if mango_account.being_liquidated {
+        println!("  being liquidated");
+    }
+    if mango_account.is_bankrupt {
+        println!("  bankrupt");
+    }
+
+    let quote_decimals = mango_group.tokens[QUOTE_INDEX].decimals as u64;
+    match health(&rpc_client, &mango_group, &mango_cache, &mango_account).await {
+        Ok((init, maint)) => {
+            println!("Health");
+            println!("  init {:.6}", ui_amount(init, quote_decimals));
+            println!("  maint {:.6}", ui_amount(maint, quote_decimals));
+            if init < 0 {
+                println!("  the init health is negative, new orders fail with InsufficientFunds");
+            }
+        }
+        Err(e) => println!("Health\n  {e}"),
+    }
+
+    println!("Deposits and borrows");
+    for (i, token) in mango_group.tokens.iter().enumerate() {
+        if token.is_empty() {
+            continue;
+        }
+        let root_bank_cache = &mango_cache.root_bank_cache[i];
+        let deposit = mango_account
+            .get_native_deposit(root_bank_cache, i)
+            .map_err(|e| anyhow::anyhow!("could not read the deposit of token {i} : {e:?}"))?;
+        let borrow = mango_account
+            .get_native_borrow(root_bank_cache, i)
+            .map_err(|e| anyhow::anyhow!("could not read the borrow of token {i} : {e:?}"))?;
+        if deposit == 0 && borrow == 0 {
+            continue;
+        }
+        let decimals = token.decimals as u64;
+        println!(
+            "  {} deposit {:.6} borrow {:.6}",
+            token_symbol(group, &token.mint),
+            ui_amount(deposit, decimals),
+            ui_amount(borrow, decimals)
+        );
+    }
+
+    println!("Perp positions");
+    for (i, perp_account) in mango_account.perp_accounts.iter().enumerate() {
+        if mango_group.perp_markets[i].is_empty() {
+            continue;
+        }
+        if perp_account.base_position == 0
+            && perp_account.quote_position == 0
+            && perp_account.bids_quantity == 0
+            && perp_account.asks_quantity == 0
+        {
+            continue;
+        }
+        println!(
+            "  {} base {} lots, quote {:.6}, bids {} lots, asks {} lots",
+            perp_market_name(group, i),
+            perp_account.base_position,
+            ui_amount(perp_account.quote_position, quote_decimals),
+            perp_account.bids_quantity,
+            perp_account.asks_quantity
+        );
+    }
+
+    println!("Open perp orders");
+    for (slot, market_index) in mango_account.order_market.iter().enumerate() {
+        if *market_index == FREE_ORDER_SLOT {
+            continue;
+        }
+        // the price in quote lots is the upper half of the order id
+        let price_lots = (mango_account.orders[slot] >> 64) as i64;
+        let client_order_id = mango_account.client_order_ids[slot];
+        let quote = match ClientOrderIdKey::decode(client_order_id) {
+            Some(key) => format!(
+                " (market maker {}, tick {})",
+                key.market_maker_index, key.tick
+            ),
+            None => String::new(),
+        };
+        println!(
+            "  {} {:?} price {price_lots} lots, client order id {client_order_id}{quote}",
+            perp_market_name(group, *market_index as usize),
+            mango_account.order_side[slot],
+        );
+    }
+    Ok(())
+}
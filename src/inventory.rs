@@ -0,0 +1,239 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    mem::size_of,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use arrayref::array_ref;
+use async_std::fs::File;
+use bytemuck::cast_ref;
+use dashmap::DashMap;
+use log::{info, warn};
+use mango::{
+    matching::Side,
+    queue::{AnyEvent, EventQueueHeader, EventType, FillEvent, Queue},
+};
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::task::JoinHandle;
+
+use crate::{
+    client_order_ids::ClientOrderIdKey, clock, helpers::to_sdk_pk, market_markers::QuotedMarket,
+    result_writer::RowTags,
+};
+
+// same fixed sizes as the crank sink
+const EVENT_SIZE: usize = 200;
+const QUEUE_LEN: usize = 256;
+type EventQueueEvents = [AnyEvent; QUEUE_LEN];
+
+/// Simulated position in base lots of each (mango account, perp market), built from the fills
+#[derive(Clone, Default)]
+pub struct Inventory {
+    positions: Arc<DashMap<(Pubkey, Pubkey), i64>>,
+}
+
+impl Inventory {
+    pub fn position(&self, mango_account_pk: &Pubkey, perp_market_pk: &Pubkey) -> i64 {
+        self.positions
+            .get(&(*mango_account_pk, *perp_market_pk))
+            .map_or(0, |p| *p)
+    }
+
+    fn add(&self, mango_account_pk: Pubkey, perp_market_pk: Pubkey, base_lots: i64) {
+        *self
+            .positions
+            .entry((mango_account_pk, perp_market_pk))
+            .or_insert(0) += base_lots;
+    }
+}
+
+/// Fill of an order of a market maker, the quotes of the orders are decoded from their client
+/// order ids when they were encoded
+#[derive(Clone, Serialize)]
+pub struct FillRecord {
+    pub polled_at: String,
+    pub market: String,
+    pub seq_num: u64,
+    // unix timestamp of the fill on-chain
+    pub timestamp: u64,
+    pub price: i64,
+    pub quantity: i64,
+    pub taker_side: String,
+    pub maker: String,
+    pub maker_client_order_id: u64,
+    pub maker_market_maker_index: Option<u32>,
+    pub maker_market_index: Option<u8>,
+    pub maker_tick: Option<u64>,
+    pub taker: String,
+    pub taker_client_order_id: u64,
+    pub taker_market_maker_index: Option<u32>,
+    pub taker_market_index: Option<u8>,
+    pub taker_tick: Option<u64>,
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+}
+
+impl FillRecord {
+    fn new(fill: &FillEvent, market: Pubkey, polled_at: String, tags: &RowTags) -> Self {
+        let maker_quote = ClientOrderIdKey::decode(fill.maker_client_order_id);
+        let taker_quote = ClientOrderIdKey::decode(fill.taker_client_order_id);
+        Self {
+            polled_at,
+            market: market.to_string(),
+            seq_num: fill.seq_num as u64,
+            timestamp: fill.timestamp,
+            price: fill.price,
+            quantity: fill.quantity,
+            taker_side: match fill.taker_side {
+                Side::Bid => "bid",
+                Side::Ask => "ask",
+            }
+            .to_string(),
+            maker: fill.maker.to_string(),
+            maker_client_order_id: fill.maker_client_order_id,
+            maker_market_maker_index: maker_quote.map(|q| q.market_maker_index),
+            maker_market_index: maker_quote.map(|q| q.market_index),
+            maker_tick: maker_quote.map(|q| q.tick),
+            taker: fill.taker.to_string(),
+            taker_client_order_id: fill.taker_client_order_id,
+            taker_market_maker_index: taker_quote.map(|q| q.market_maker_index),
+            taker_market_index: taker_quote.map(|q| q.market_index),
+            taker_tick: taker_quote.map(|q| q.tick),
+            run_id: tags.run_id.clone(),
+            cluster: tags.cluster.clone(),
+            shard: tags.shard.clone(),
+        }
+    }
+}
+
+// fills of an event queue account, only the ones with a sequence number from from_seq_num, and
+// the sequence number of the next event
+fn decode_fills(data: &[u8], from_seq_num: usize) -> Option<(Vec<FillEvent>, usize)> {
+    const HEADER_SIZE: usize = size_of::<EventQueueHeader>();
+    const QUEUE_SIZE: usize = EVENT_SIZE * QUEUE_LEN;
+    if data.len() < HEADER_SIZE + QUEUE_SIZE {
+        return None;
+    }
+    let header =
+        RefCell::<EventQueueHeader>::new(*bytemuck::from_bytes(array_ref![data, 0, HEADER_SIZE]));
+    let next_seq_num = header.borrow().seq_num;
+    let events = RefCell::<EventQueueEvents>::new(*bytemuck::from_bytes(array_ref![
+        data,
+        HEADER_SIZE,
+        QUEUE_SIZE
+    ]));
+    let event_queue = Queue {
+        header: header.borrow_mut(),
+        buf: events.borrow_mut(),
+    };
+    let fills = event_queue
+        .iter()
+        .filter(|e| EventType::try_from(e.event_type).ok() == Some(EventType::Fill))
+        .map(|e| *cast_ref::<AnyEvent, FillEvent>(e))
+        .filter(|fill| fill.seq_num >= from_seq_num)
+        .collect();
+    Some((fills, next_seq_num))
+}
+
+/// Polls the event queues of the quoted markets and applies the fills of the market makers to
+/// the inventory, and writes them to the fills file when there is one. Fills consumed by the
+/// crank between two polls are missed, so the inventory is an approximation of the actual
+/// positions (mango v3 only)
+pub fn start_inventory_tracking(
+    rpc_client: Arc<RpcClient>,
+    quoted_markets: &[QuotedMarket],
+    inventory: Inventory,
+    interval: Duration,
+    fills_file: String,
+    tags: RowTags,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    let accounts: HashSet<Pubkey> = quoted_markets.iter().map(|(_, pk, _)| *pk).collect();
+    // event queue to its market
+    let markets: HashMap<Pubkey, Pubkey> = quoted_markets
+        .iter()
+        .map(|(_, _, cache)| (cache.event_queue, cache.perp_market_pk))
+        .collect();
+    let event_queues = markets.keys().copied().collect::<Vec<_>>();
+
+    tokio::spawn(async move {
+        let sdk_pks = event_queues.iter().map(to_sdk_pk).collect::<Vec<_>>();
+        // next sequence number of each event queue, fills from before the run are not counted
+        let mut next_seq_nums: HashMap<Pubkey, usize> = HashMap::new();
+        let mut nb_fills = 0u64;
+        let mut writer = match fills_file.as_str() {
+            "" => None,
+            path => Some(csv_async::AsyncSerializer::from_writer(
+                File::create(path).await.unwrap(),
+            )),
+        };
+        while !exit_signal.load(Ordering::Relaxed) {
+            tokio::time::sleep(interval).await;
+            let queues = match rpc_client
+                .get_multiple_accounts_with_commitment(&sdk_pks, CommitmentConfig::confirmed())
+                .await
+            {
+                Ok(queues) => queues.value,
+                Err(e) => {
+                    warn!("inventory : could not get the event queues {e}");
+                    continue;
+                }
+            };
+            for (event_queue, account) in event_queues.iter().zip(queues) {
+                let Some(account) = account else {
+                    continue;
+                };
+                let from_seq_num = next_seq_nums.get(event_queue).copied();
+                let Some((fills, next_seq_num)) =
+                    decode_fills(&account.data, from_seq_num.unwrap_or(usize::MAX))
+                else {
+                    continue;
+                };
+                next_seq_nums.insert(*event_queue, next_seq_num);
+                let market = markets[event_queue];
+                let polled_at = clock::now().to_string();
+                for fill in fills {
+                    if let Some(writer) = writer.as_mut().filter(|_| {
+                        accounts.contains(&fill.maker) || accounts.contains(&fill.taker)
+                    }) {
+                        let record = FillRecord::new(&fill, market, polled_at.clone(), &tags);
+                        writer.serialize(record).await.unwrap();
+                    }
+                    // the maker takes the other side of the taker
+                    let taker_lots = match fill.taker_side {
+                        Side::Bid => fill.quantity,
+                        Side::Ask => -fill.quantity,
+                    };
+                    if accounts.contains(&fill.maker) {
+                        inventory.add(fill.maker, market, -taker_lots);
+                        nb_fills += 1;
+                    }
+                    if accounts.contains(&fill.taker) {
+                        inventory.add(fill.taker, market, taker_lots);
+                        nb_fills += 1;
+                    }
+                }
+            }
+            if let Some(writer) = writer.as_mut() {
+                writer.flush().await.unwrap();
+            }
+        }
+        let largest = inventory
+            .positions
+            .iter()
+            .map(|p| p.value().abs())
+            .max()
+            .unwrap_or_default();
+        info!("inventory : {nb_fills} fills of the market makers, largest position {largest} base lots");
+    })
+}
@@ -4,12 +4,13 @@ use tokio::spawn;
 
 use {
     crate::{
+        clock,
         helpers::to_sdk_instruction,
         noop,
-        states::{KeeperInstruction, PerpMarketCache, TransactionSendRecord},
+        states::{KeeperInstruction, PerpMarketCache, TransactionSendRecord, TransactionType},
         tpu_manager::TpuManager,
+        watchdog::Watchdog,
     },
-    chrono::Utc,
     iter_tools::Itertools,
     solana_program::pubkey::Pubkey,
     solana_sdk::{
@@ -122,14 +123,30 @@ pub fn prepare_transaction(
     let mut tx = Transaction::new_unsigned(Message::new(&ixs, Some(&payer.pubkey())));
     tx.sign(&[payer], *recent_blockhash);
 
+    let sent_at = clock::now();
     let tx_send_record = TransactionSendRecord {
         signature: tx.signatures[0],
-        sent_at: Utc::now(),
+        sent_at,
+        sent_at_us: sent_at.timestamp_micros(),
         sent_slot: current_slot.load(Ordering::Acquire),
         market_maker: None,
         market: None,
         priority_fees: prioritization_fee,
         keeper_instruction: Some(keeper_instruction),
+        tx_type: TransactionType::Keeper,
+        spoofing: false,
+        phase: None,
+        blockhash_slot: None,
+        blockhash_age_slots: None,
+        last_valid_block_height: None,
+        chaos: None,
+        send_delay_ms: None,
+        tx_size_bytes: 0,
+        num_instructions: 0,
+        order_type: None,
+        reduce_only: false,
+        client_order_id: None,
+        route: None,
     };
     (tx, tx_send_record)
 }
@@ -161,6 +178,40 @@ pub fn create_update_and_cache_quote_banks(
     vec![to_sdk_instruction(ix_update), to_sdk_instruction(ix_cache)]
 }
 
+/// instructions sent by the keepers at every iteration, each entry is sent as one transaction
+pub fn create_keeper_instructions(
+    perp_markets: &[PerpMarketCache],
+    quote_root_bank: Pubkey,
+    quote_node_banks: Vec<Pubkey>,
+) -> Vec<(KeeperInstruction, Vec<Instruction>)> {
+    let mut keeper_instructions = vec![
+        (
+            KeeperInstruction::CachePrice,
+            vec![create_update_price_cache_instructions(perp_markets)],
+        ),
+        (
+            KeeperInstruction::UpdateAndCacheQuoteRootBank,
+            create_update_and_cache_quote_banks(perp_markets, quote_root_bank, quote_node_banks),
+        ),
+    ];
+    for updates in create_update_fundings_instructions(perp_markets).chunks(3) {
+        keeper_instructions.push((KeeperInstruction::UpdateFunding, updates.to_vec()));
+    }
+    keeper_instructions.push((
+        KeeperInstruction::UpdateRootBanks,
+        create_root_bank_update_instructions(perp_markets),
+    ));
+    keeper_instructions.push((
+        KeeperInstruction::UpdatePerpCache,
+        vec![create_cache_perp_markets_instructions(perp_markets)],
+    ));
+    keeper_instructions.push((
+        KeeperInstruction::CacheRootBanks,
+        vec![create_cache_root_bank_instruction(perp_markets)],
+    ));
+    keeper_instructions
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn start_keepers(
     exit_signal: Arc<AtomicBool>,
@@ -172,88 +223,45 @@ pub fn start_keepers(
     quote_root_bank: Pubkey,
     quote_node_banks: Vec<Pubkey>,
     prioritization_fee: u64,
+    watchdog: Watchdog,
 ) -> JoinHandle<()> {
     let authority = Keypair::from_bytes(&authority.to_bytes()).unwrap();
+    let keeper_instructions =
+        create_keeper_instructions(&perp_markets, quote_root_bank, quote_node_banks);
     tokio::spawn(async move {
         let current_slot = current_slot.clone();
 
-        let root_update_ixs = create_root_bank_update_instructions(&perp_markets);
-        let cache_prices = vec![create_update_price_cache_instructions(&perp_markets)];
-        let update_perp_cache = vec![create_cache_perp_markets_instructions(&perp_markets)];
-        let cache_root_bank_ix = vec![create_cache_root_bank_instruction(&perp_markets)];
-        let update_funding_ix = create_update_fundings_instructions(&perp_markets);
-        let quote_root_bank_ix =
-            create_update_and_cache_quote_banks(&perp_markets, quote_root_bank, quote_node_banks);
-
         while !exit_signal.load(Ordering::Relaxed) {
+            watchdog.heartbeat("keepers");
             let recent_blockhash = blockhash.read().await.to_owned();
 
-            let mut tx_batch = vec![];
-            tx_batch.push(prepare_transaction(
-                cache_prices.clone(),
-                &recent_blockhash,
-                current_slot.clone(),
-                &authority,
-                prioritization_fee,
-                KeeperInstruction::CachePrice,
-            ));
-
-            tx_batch.push(prepare_transaction(
-                quote_root_bank_ix.clone(),
-                &recent_blockhash,
-                current_slot.clone(),
-                &authority,
-                prioritization_fee,
-                KeeperInstruction::UpdateAndCacheQuoteRootBank,
-            ));
-
-            for updates in update_funding_ix.chunks(3) {
-                tx_batch.push(prepare_transaction(
-                    updates.to_vec(),
-                    &recent_blockhash,
-                    current_slot.clone(),
-                    &authority,
-                    prioritization_fee,
-                    KeeperInstruction::UpdateFunding,
-                ));
-            }
-            tx_batch.push(prepare_transaction(
-                root_update_ixs.clone(),
-                &recent_blockhash,
-                current_slot.clone(),
-                &authority,
-                prioritization_fee,
-                KeeperInstruction::UpdateRootBanks,
-            ));
-
-            tx_batch.push(prepare_transaction(
-                update_perp_cache.clone(),
-                &recent_blockhash,
-                current_slot.clone(),
-                &authority,
-                prioritization_fee,
-                KeeperInstruction::UpdatePerpCache,
-            ));
-
-            tx_batch.push(prepare_transaction(
-                cache_root_bank_ix.clone(),
-                &recent_blockhash,
-                current_slot.clone(),
-                &authority,
-                prioritization_fee,
-                KeeperInstruction::CacheRootBanks,
-            ));
+            let tx_batch = keeper_instructions
+                .iter()
+                .map(|(keeper_instruction, ixs)| {
+                    let mut ixs = ixs.clone();
+                    ixs.extend(tpu_manager.memo().map(|memo| memo.instruction()));
+                    prepare_transaction(
+                        ixs,
+                        &recent_blockhash,
+                        current_slot.clone(),
+                        &authority,
+                        prioritization_fee,
+                        keeper_instruction.clone(),
+                    )
+                })
+                .collect_vec();
 
             let start_slot = current_slot.load(Ordering::Relaxed);
-            let start_time = Utc::now();
+            let start_time = clock::now();
             let tpu_manager = tpu_manager.clone();
             spawn(async move {
-                if !tpu_manager.send_transaction_batch(&tx_batch).await {
+                if !tpu_manager.send_transaction_batch(tx_batch).await {
                     warn!("issue when sending batch started slot={start_slot} time={start_time} hash={recent_blockhash:?}");
                 }
             });
 
             std::thread::sleep(std::time::Duration::from_secs(1));
         }
+        watchdog.unregister("keepers");
     })
 }
@@ -0,0 +1,74 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+use solana_metrics::datapoint_info;
+use tokio::{
+    sync::broadcast::{error::TryRecvError, Receiver},
+    task::JoinHandle,
+};
+
+use crate::states::{BlockData, LiveParameters};
+
+// quoting never stops completely, the confirmation pipeline needs transactions to see blocks
+const MIN_THROTTLE_PCT: u8 = 10;
+const THROTTLE_STEP_PCT: u8 = 10;
+
+/// Estimates how far the confirmation pipeline is behind the cluster from the slot of the latest
+/// block it processed and the current slot. The quote rate is halved every second the lag is above
+/// max_slot_lag and recovers step by step once the lag is back under half of it
+pub fn start_lag_throttle(
+    current_slot: Arc<AtomicU64>,
+    mut block_status_rx: Receiver<BlockData>,
+    live_parameters: Arc<LiveParameters>,
+    max_slot_lag: u64,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut latest_block_slot = 0;
+        while !exit_signal.load(Ordering::Relaxed) {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            loop {
+                match block_status_rx.try_recv() {
+                    Ok(block) => latest_block_slot = latest_block_slot.max(block.block_slot),
+                    Err(TryRecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                }
+            }
+            // no lag can be estimated before the first block
+            if latest_block_slot == 0 {
+                continue;
+            }
+            let lag = current_slot
+                .load(Ordering::Relaxed)
+                .saturating_sub(latest_block_slot);
+
+            let throttle_pct = live_parameters.throttle_pct.load(Ordering::Relaxed);
+            let new_throttle_pct = if lag > max_slot_lag {
+                (throttle_pct / 2).max(MIN_THROTTLE_PCT)
+            } else if lag <= max_slot_lag / 2 {
+                throttle_pct.saturating_add(THROTTLE_STEP_PCT).min(100)
+            } else {
+                throttle_pct
+            };
+            if new_throttle_pct < throttle_pct {
+                warn!("slot lag of {lag} slots, throttling the quotes to {new_throttle_pct}%");
+            } else if new_throttle_pct > throttle_pct {
+                info!("slot lag of {lag} slots, quotes back to {new_throttle_pct}%");
+            }
+            live_parameters
+                .throttle_pct
+                .store(new_throttle_pct, Ordering::Relaxed);
+            datapoint_info!(
+                "bencher-slot-lag",
+                ("lag", lag, i64),
+                ("throttle_pct", new_throttle_pct, i64),
+            );
+        }
+    })
+}
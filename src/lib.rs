@@ -1,17 +1,78 @@
+pub mod accounts_watcher;
+pub mod affinity;
+pub mod alerts;
+pub mod analyze;
+pub mod baseline;
+pub mod book_recorder;
+pub mod book_validation;
+pub mod cache_health;
+pub mod cancel_storm;
+pub mod chaos;
 pub mod cli;
+pub mod client_order_ids;
+pub mod clock;
+pub mod clock_skew;
+pub mod compare;
+pub mod compression;
+pub mod compute_units;
+pub mod config_loader;
 pub mod confirmation_strategies;
+pub mod control;
+pub mod corpus;
 pub mod crank;
+pub mod create_group;
+pub mod csv_schema;
+pub mod feasibility;
+pub mod funding;
+pub mod health;
 pub mod helpers;
+pub mod impairment;
+pub mod inspect_account;
+pub mod inventory;
 pub mod keeper;
+pub mod lag_throttle;
 pub mod mango;
+pub mod mango_errors;
+pub mod mango_program;
 pub mod mango_v3_perp_crank_sink;
+pub mod market_maker_failures;
 pub mod market_markers;
+pub mod memo;
 pub mod noop;
+pub mod oracle;
+pub mod orchestrator;
+pub mod order_mix;
+pub mod partitioned_output;
+pub mod periphery;
+pub mod preconnect;
+pub mod quic_stats;
+pub mod rate_limiter;
 pub mod result_writer;
+pub mod results_stream;
 pub mod rotating_queue;
+pub mod rpc_cross_check;
+pub mod run_manifest;
+pub mod run_state;
+pub mod scenario;
+pub mod send_delay;
+pub mod serum_v3_spot_crank;
+pub mod shard;
+pub mod shutdown;
+pub mod signing;
+pub mod slot_coverage;
 pub mod states;
 pub mod stats;
+pub mod storage;
+pub mod summary;
+pub mod timeseries;
+pub mod top_up;
 pub mod tpu_manager;
+pub mod transaction_sender;
+pub mod validate_config;
+pub mod validation;
+pub mod warmup;
+pub mod watchdog;
+pub mod ws_failover;
 
 trait AnyhowWrap {
     type Value;
@@ -1,366 +1,428 @@
 use {
-    log::info,
+    log::{info, warn},
     mango_simulation::{
+        affinity::ThreadPinning,
+        analyze::analyze,
         cli,
+        clock_skew::ClockSkew,
+        compare::compare,
+        config_loader::load_config,
         confirmation_strategies::confirmation_by_lite_rpc_notification_stream,
+        corpus::{read_corpus, replay_corpus},
         crank::{self, KeeperConfig},
-        helpers::{
-            get_latest_blockhash, get_mango_market_perps_cache, start_blockhash_polling_service,
-            to_sdk_pk,
-        },
-        keeper::start_keepers,
-        mango::{AccountKeys, MangoConfig},
-        market_markers::{clean_market_makers, start_market_making_threads},
-        result_writer::initialize_result_writers,
-        states::PerpMarketCache,
+        create_group::create_group,
+        helpers::{get_latest_blockhash, start_blockhash_polling_service, to_sdk_pk},
+        inspect_account::inspect_account,
+        mango::MangoConfig,
+        orchestrator::{configure_transaction_service, BenchOrchestrator},
+        result_writer::{initialize_result_writers, ResultWriterConfig, RowTags},
+        run_manifest::new_run_id,
+        shutdown::Shutdown,
+        states::{BlockhashSlots, LiveParameters},
         stats::MangoSimulationStats,
         tpu_manager::TpuManager,
+        transaction_sender::{
+            quic_connection_cache, FixedTpuSender, JitoSender, MockSender, QuicSender, RpcSender,
+            SenderKind, TransactionSender,
+        },
+        validate_config::validate_config,
+        watchdog::{Watchdog, DEFAULT_STALL_TIMEOUT},
+        ws_failover::start_websocket_failover,
     },
     solana_client::nonblocking::rpc_client::RpcClient as NbRpcClient,
-    solana_lite_rpc_core::{
-        block_store::BlockStore,
-        notifications::NotificationMsg,
-        quic_connection_utils::QuicConnectionParameters,
-        tx_store::{empty_tx_store, TxStore},
-    },
-    solana_lite_rpc_services::{
-        block_listenser::BlockListener,
-        tpu_utils::tpu_service::{TpuService, TpuServiceConfig},
-        transaction_replayer::TransactionReplayer,
-        transaction_service::{TransactionService, TransactionServiceBuilder},
-        tx_sender::TxSender,
-    },
+    solana_lite_rpc_core::{block_store::BlockStore, tx_store::empty_tx_store},
     solana_program::pubkey::Pubkey,
-    solana_sdk::{commitment_config::CommitmentConfig, signer::keypair::Keypair},
+    solana_sdk::{
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        signer::keypair::Keypair,
+    },
     std::{
-        fs,
         str::FromStr,
-        sync::atomic::{AtomicBool, AtomicU64, Ordering},
+        sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         sync::Arc,
-        time::Duration,
+        time::{Duration, Instant},
     },
-    tokio::sync::mpsc::{unbounded_channel, UnboundedSender},
-    tokio::{sync::RwLock, task::JoinHandle},
+    tokio::sync::{mpsc::unbounded_channel, RwLock},
 };
 
-const METRICS_NAME: &str = "mango-bencher";
-
-async fn configure_transaction_service(
-    rpc_client: Arc<NbRpcClient>,
-    identity: Keypair,
-    block_store: BlockStore,
-    tx_store: TxStore,
-    notifier: UnboundedSender<NotificationMsg>,
-) -> (TransactionService, JoinHandle<anyhow::Result<()>>) {
-    let slot = rpc_client.get_slot().await.expect("GetSlot should work");
-    let tpu_config = TpuServiceConfig {
-        fanout_slots: 12,
-        number_of_leaders_to_cache: 1024,
-        clusterinfo_refresh_time: Duration::from_secs(60 * 60),
-        leader_schedule_update_frequency: Duration::from_secs(10),
-        maximum_transaction_in_queue: 200_000,
-        maximum_number_of_errors: 10,
-        quic_connection_params: QuicConnectionParameters {
-            connection_timeout: Duration::from_secs(1),
-            connection_retry_count: 10,
-            finalize_timeout: Duration::from_millis(200),
-            max_number_of_connections: 10,
-            unistream_timeout: Duration::from_millis(500),
-            write_timeout: Duration::from_secs(1),
-            number_of_transactions_per_unistream: 10,
-        },
-    };
+const WORKER_THREADS: usize = 10;
 
-    let tpu_service = TpuService::new(
-        tpu_config,
-        Arc::new(identity),
-        slot,
-        rpc_client.clone(),
-        tx_store.clone(),
-    )
-    .await
-    .expect("Should be able to create TPU");
-
-    let tx_sender = TxSender::new(tx_store.clone(), tpu_service.clone());
-    let block_listenser =
-        BlockListener::new(rpc_client.clone(), tx_store.clone(), block_store.clone());
-    let replayer = TransactionReplayer::new(
-        tpu_service.clone(),
-        tx_store.clone(),
-        Duration::from_secs(2),
-    );
-    let builder = TransactionServiceBuilder::new(
-        tx_sender,
-        replayer,
-        block_listenser,
-        tpu_service,
-        1_000_000,
-    );
-    builder.start(Some(notifier), block_store, 10, Duration::from_secs(90))
-}
-
-#[tokio::main(flavor = "multi_thread", worker_threads = 10)]
-pub async fn main() -> anyhow::Result<()> {
+pub fn main() -> anyhow::Result<()> {
     solana_logger::setup_with_default("info");
     solana_metrics::set_panic_hook("bench-mango", /*version:*/ None);
 
     let version = solana_version::version!();
     let matches = cli::build_args(version).get_matches();
-    let cli_config = cli::extract_args(&matches);
+    // the workers are pinned as they start, before any market maker is spawned
+    let pin_threads = matches
+        .subcommand_matches("run")
+        .and_then(cli::extract_thread_pinning);
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    runtime.worker_threads(WORKER_THREADS).enable_all();
+    if let Some(pin_threads) = pin_threads.clone() {
+        // the workers are the first threads started, when the runtime is built, the threads of the
+        // blocking pool start after them on demand and are left unpinned
+        let started = AtomicUsize::new(0);
+        runtime.on_thread_start(move || {
+            let index = started.fetch_add(1, Ordering::Relaxed);
+            if index < WORKER_THREADS {
+                pin_threads.pin_worker(index);
+            }
+        });
+    }
+    runtime
+        .build()?
+        .block_on(run_subcommand(matches, pin_threads))
+}
+
+async fn run_subcommand(
+    matches: clap::ArgMatches<'_>,
+    pin_threads: Option<ThreadPinning>,
+) -> anyhow::Result<()> {
+    match matches.subcommand() {
+        ("run", Some(matches)) => {
+            // the quote builders continue on the cores after the ones of the workers
+            let mut configs = cli::extract_cluster_args(matches);
+            for config in configs.iter_mut() {
+                config.pin_threads = pin_threads.clone();
+            }
+            run_clusters(configs).await
+        }
+        ("analyze", Some(matches)) => analyze(cli::extract_analyze_args(matches)).await,
+        ("compare", Some(matches)) => compare(cli::extract_compare_args(matches)).await,
+        ("crank", Some(matches)) => run_crank(cli::extract_crank_args(matches)).await,
+        ("replay", Some(matches)) => run_replay(cli::extract_replay_args(matches)).await,
+        ("validate-config", Some(matches)) => {
+            validate_config(cli::extract_validate_config_args(matches)).await
+        }
+        ("inspect-account", Some(matches)) => {
+            inspect_account(cli::extract_inspect_account_args(matches)).await
+        }
+        ("create-group", Some(matches)) => {
+            create_group(cli::extract_create_group_args(matches)).await
+        }
+        _ => unreachable!("a subcommand is required"),
+    }
+}
+
+const CRANK_METRICS_NAME: &str = "mango-cranker";
 
-    let cli::Config {
+/// runs the perp crank alone until interrupted or until the duration is elapsed
+async fn run_crank(crank_config: cli::CrankConfig) -> anyhow::Result<()> {
+    let cli::CrankConfig {
         json_rpc_url,
         websocket_url,
         identity,
-        account_keys,
+        authority,
         mango_keys,
+        mango_cluster,
         duration,
-        quotes_per_second,
+        prioritization_fee,
         transaction_save_file,
-        block_data_save_file,
-        mango_cluster,
-        priority_fees_proba,
-        keeper_authority,
-        number_of_markers_per_mm,
-        keeper_prioritization,
-        ..
-    } = &cli_config;
-    let number_of_markers_per_mm = *number_of_markers_per_mm;
-    let keeper_prioritization = *keeper_prioritization;
+        crank_spot_markets,
+        crank_batch,
+    } = crank_config;
 
-    let transaction_save_file = transaction_save_file.clone();
-    let block_data_save_file = block_data_save_file.clone();
-
-    info!(
-        "Connecting to the cluster {}, {}",
-        json_rpc_url, websocket_url
-    );
-
-    let account_keys_json = fs::read_to_string(account_keys).expect("unable to read accounts file");
-    let account_keys_parsed: Vec<AccountKeys> =
-        serde_json::from_str(&account_keys_json).expect("accounts JSON was not well-formatted");
-
-    let mango_keys_json = fs::read_to_string(mango_keys).expect("unable to read mango keys file");
+    let mango_keys_json = load_config(&mango_keys)
+        .await
+        .expect("unable to read mango keys file");
     let mango_keys_parsed: MangoConfig =
         serde_json::from_str(&mango_keys_json).expect("mango JSON was not well-formatted");
-
-    let mango_group_id = mango_cluster;
     let mango_group_config = mango_keys_parsed
         .groups
         .iter()
-        .find(|g| g.name == *mango_group_id)
+        .find(|g| g.name == mango_cluster)
         .expect("Mango group config should exist");
 
     let nb_rpc_client = Arc::new(NbRpcClient::new_with_commitment(
-        json_rpc_url.to_string(),
+        json_rpc_url.clone(),
         CommitmentConfig::finalized(),
     ));
-
-    let tx_store = empty_tx_store();
     let block_store = BlockStore::new(&nb_rpc_client)
         .await
         .expect("Blockstore should be created");
-
     let (notif_sx, notif_rx) = unbounded_channel();
     let (transaction_service, tx_service_jh) = configure_transaction_service(
         nb_rpc_client.clone(),
         Keypair::from_bytes(identity.to_bytes().as_slice()).unwrap(),
         block_store,
-        tx_store,
-        notif_sx,
+        empty_tx_store(),
+        Some(notif_sx),
+        cli::DEFAULT_QUIC_CONNECTION_POOL_SIZE,
     )
     .await;
 
-    let nb_users = account_keys_parsed.len();
-
-    let mut mango_sim_stats = MangoSimulationStats::new(
-        nb_users,
-        *quotes_per_second as usize,
-        number_of_markers_per_mm as usize,
-        duration.as_secs() as usize,
-    );
-
+    let mut crank_stats = MangoSimulationStats::new(0, 0, 0, 0);
     let (tx_record_sx, tx_record_rx) = tokio::sync::mpsc::unbounded_channel();
-
-    // continuosly fetch blockhash
     let exit_signal = Arc::new(AtomicBool::new(false));
-    let latest_blockhash = get_latest_blockhash(&nb_rpc_client.clone()).await;
-    let blockhash = Arc::new(RwLock::new(latest_blockhash));
+    let confirmation_exit_signal = Arc::new(AtomicBool::new(false));
+    let watchdog = Watchdog::default();
+    let watchdog_task = watchdog.start(DEFAULT_STALL_TIMEOUT, false);
+
+    let blockhash = Arc::new(RwLock::new(get_latest_blockhash(&nb_rpc_client).await));
     let current_slot = Arc::new(AtomicU64::new(0));
+    let blockhash_slots = BlockhashSlots::default();
     let blockhash_thread = start_blockhash_polling_service(
         exit_signal.clone(),
         blockhash.clone(),
         current_slot.clone(),
         nb_rpc_client.clone(),
+        watchdog.clone(),
+        blockhash_slots.clone(),
     );
-
+    // send errors are only counted in the crank stats
+    let (send_errors_sx, _) = unbounded_channel();
     let tpu_manager = TpuManager::new(
-        transaction_service,
-        mango_sim_stats.clone(),
-        tx_record_sx.clone(),
+        vec![Arc::new(transaction_service)],
+        crank_stats.clone(),
+        tx_record_sx,
+        None,
+        send_errors_sx,
+        Arc::new(LiveParameters::new(0, 0)),
+        blockhash_slots,
+        None,
+        None,
+        None,
+        None,
     )
     .await?;
 
-    info!(
-        "accounts:{:?} markets:{:?} quotes_per_second:{:?} expected_tps:{:?} duration:{:?}",
-        account_keys_parsed.len(),
-        number_of_markers_per_mm,
-        quotes_per_second,
-        account_keys_parsed.len() * number_of_markers_per_mm as usize * *quotes_per_second as usize,
-        duration
-    );
-
     let mango_program_pk = Pubkey::from_str(mango_group_config.mango_program_id.as_str())
         .expect("Mango program should be able to convert into pubkey");
-    let perp_market_caches: Vec<PerpMarketCache> =
-        get_mango_market_perps_cache(nb_rpc_client.clone(), mango_group_config, &mango_program_pk)
-            .await;
-
-    let quote_root_bank =
-        Pubkey::from_str(mango_group_config.tokens.last().unwrap().root_key.as_str())
-            .expect("Quote root bank should be able to convert into pubkey");
-    let quote_node_banks = mango_group_config
-        .tokens
-        .last()
-        .unwrap()
-        .node_keys
-        .iter()
-        .map(|x| {
-            Pubkey::from_str(x.as_str()).expect("Token mint should be able to convert into pubkey")
-        })
-        .collect();
-
-    clean_market_makers(
-        nb_rpc_client.clone(),
-        &account_keys_parsed,
-        &perp_market_caches,
-        blockhash.clone(),
-    )
-    .await;
-
-    // start keeper if keeper authority is present
-    let keepers_jl = if let Some(keeper_authority) = keeper_authority {
-        let jl = start_keepers(
-            exit_signal.clone(),
-            tpu_manager.clone(),
-            perp_market_caches.clone(),
-            blockhash.clone(),
-            current_slot.clone(),
-            keeper_authority,
-            quote_root_bank,
-            quote_node_banks,
-            keeper_prioritization,
-        );
-        Some(jl)
-    } else {
-        None
-    };
-
-    let keeper_config = KeeperConfig {
-        program_id: to_sdk_pk(&mango_program_pk),
-        rpc_url: json_rpc_url.clone(),
-        websocket_url: websocket_url.clone(),
-    };
-
-    crank::start(
-        keeper_config,
+    let (websocket, websocket_failover_task) =
+        start_websocket_failover(&websocket_url, exit_signal.clone());
+    let mut crank_tasks = crank::start(
+        KeeperConfig {
+            program_id: to_sdk_pk(&mango_program_pk),
+            rpc_url: json_rpc_url,
+            websocket,
+            batch: crank_batch,
+        },
         exit_signal.clone(),
-        blockhash.clone(),
-        current_slot.clone(),
-        tpu_manager.clone(),
+        blockhash,
+        current_slot,
+        tpu_manager,
         mango_group_config,
-        identity,
-        keeper_prioritization,
-    );
-
-    let warmup_duration = Duration::from_secs(20);
-    info!("waiting for keepers to warmup for {warmup_duration:?}");
-    tokio::time::sleep(warmup_duration).await;
-
-    let mm_tasks: Vec<JoinHandle<()>> = start_market_making_threads(
-        account_keys_parsed.clone(),
-        perp_market_caches.clone(),
-        exit_signal.clone(),
-        blockhash.clone(),
-        current_slot.clone(),
-        tpu_manager.clone(),
-        duration,
-        *quotes_per_second,
-        *priority_fees_proba,
-        number_of_markers_per_mm,
+        authority.as_ref().unwrap_or(&identity),
+        prioritization_fee,
+        watchdog.clone(),
+        crank_spot_markets,
     );
-
-    info!("Number of MM threads {}", mm_tasks.len());
-    drop(tx_record_sx);
-    let mut tasks = vec![blockhash_thread];
-
-    let (tx_status_sx, tx_status_rx) = tokio::sync::broadcast::channel(1000000);
-    let (block_status_sx, block_status_rx) = tokio::sync::broadcast::channel(1000000);
-
-    let stats_handle = mango_sim_stats.update_from_tx_status_stream(tx_status_rx);
-    tasks.push(stats_handle);
-
-    let mut writers_jh = initialize_result_writers(
-        transaction_save_file,
-        block_data_save_file,
+    crank_tasks.push(blockhash_thread);
+    crank_tasks.push(websocket_failover_task);
+
+    let (tx_status_sx, tx_status_rx) = tokio::sync::broadcast::channel(cli::DEFAULT_RECORD_BUFFER);
+    let (block_status_sx, _) = tokio::sync::broadcast::channel(cli::DEFAULT_RECORD_BUFFER);
+    let stats_handle = crank_stats.update_from_tx_status_stream(tx_status_rx);
+    let writers_jh = initialize_result_writers(
+        ResultWriterConfig {
+            transaction_save_file,
+            tags: RowTags {
+                run_id: new_run_id(),
+                ..RowTags::default()
+            },
+            dropped_records: crank_stats.dropped_records(),
+            ..ResultWriterConfig::default()
+        },
         tx_status_sx.subscribe(),
-        block_status_rx,
+        block_status_sx.subscribe(),
     );
-    tasks.append(&mut writers_jh);
-
-    let mut confirmation_threads = confirmation_by_lite_rpc_notification_stream(
+    let confirmation_threads = confirmation_by_lite_rpc_notification_stream(
+        nb_rpc_client.clone(),
         tx_record_rx,
         notif_rx,
         tx_status_sx,
         block_status_sx,
-        exit_signal.clone(),
+        confirmation_exit_signal.clone(),
+        CommitmentLevel::Finalized,
+        false,
+        ClockSkew::default(),
+        watchdog,
     );
-    tasks.append(&mut confirmation_threads);
-
-    if let Some(keepers_jl) = keepers_jl {
-        tasks.push(keepers_jl);
-    }
 
-    {
-        let exit_signal = exit_signal.clone();
-        let mut mango_sim_stats = mango_sim_stats.clone();
-        let reporting_thread = tokio::spawn(async move {
-            loop {
-                if exit_signal.load(Ordering::Relaxed) {
-                    break;
+    let reporting_thread = {
+        let confirmation_exit_signal = confirmation_exit_signal.clone();
+        let mut crank_stats = crank_stats.clone();
+        tokio::spawn(async move {
+            let mut last_report = Instant::now();
+            while !confirmation_exit_signal.load(Ordering::Relaxed) {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                if last_report.elapsed() >= Duration::from_secs(60) {
+                    crank_stats.report(false, CRANK_METRICS_NAME).await;
+                    last_report = Instant::now();
                 }
-                tokio::time::sleep(Duration::from_secs(60)).await;
-                mango_sim_stats.report(false, METRICS_NAME).await;
             }
-        });
-        tasks.push(reporting_thread);
-    }
-
-    // when all market makers tasks are joined that means we are ready to exit
-    // we start stopping all other process
-    // some processes like confirmation of transactions will take some time and will get additional 2 minutes
-    // to confirm remaining transactions
-    let market_makers_wait_task = {
-        let exit_signal = exit_signal.clone();
-        tokio::spawn(async move {
-            futures::future::join_all(mm_tasks).await;
-            info!("finished market making, joining all other services");
-            exit_signal.store(true, Ordering::Relaxed);
         })
     };
 
-    tasks.push(market_makers_wait_task);
-
-    let transaction_service = tokio::spawn(async move {
+    let mut transaction_service = tokio::spawn(async move {
         let _ = tx_service_jh.await;
         info!("Transaction service joined");
     });
-
+    let run_duration = async {
+        match duration {
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => futures::future::pending().await,
+        }
+    };
     tokio::select! {
-        _ = futures::future::join_all(tasks) => {},
-        _ = transaction_service => {},
+        _ = tokio::signal::ctrl_c() => {
+            info!("interrupted, stopping the crank");
+        },
+        _ = run_duration => {
+            info!("crank duration elapsed, stopping the crank");
+        },
+        _ = &mut transaction_service => {
+            warn!("transaction service stopped, stopping the crank");
+        },
     };
 
-    mango_sim_stats.report(true, METRICS_NAME).await;
+    let mut shutdown = Shutdown::new();
+    shutdown
+        .add_step(
+            "crank",
+            Some(exit_signal),
+            Duration::ZERO,
+            Duration::from_secs(10),
+            crank_tasks,
+        )
+        .add_step(
+            "confirmations",
+            Some(confirmation_exit_signal),
+            Duration::ZERO,
+            Duration::from_secs(120),
+            confirmation_threads,
+        )
+        .add_step(
+            "result sinks",
+            None,
+            Duration::ZERO,
+            Duration::from_secs(60),
+            writers_jh
+                .into_iter()
+                .chain([stats_handle, reporting_thread])
+                .collect(),
+        )
+        .add_step(
+            "transaction service",
+            None,
+            Duration::ZERO,
+            Duration::from_secs(1),
+            vec![transaction_service],
+        );
+    shutdown.run().await;
+    watchdog_task.abort();
+
+    crank_stats.report(true, CRANK_METRICS_NAME).await;
+    Ok(())
+}
+
+/// sends a corpus written by a run with --generate-only
+async fn run_replay(replay_config: cli::ReplayConfig) -> anyhow::Result<()> {
+    let cli::ReplayConfig {
+        json_rpc_url,
+        websocket_url,
+        identity,
+        corpus_file,
+        sender,
+        jito_url,
+        bind_address,
+        client_node_id,
+        tpu_addrs,
+        speed,
+    } = replay_config;
+
+    let entries = read_corpus(&corpus_file).await?;
+    info!("replaying {} transactions of {corpus_file}", entries.len());
+
+    let nb_rpc_client = Arc::new(NbRpcClient::new_with_commitment(
+        json_rpc_url,
+        CommitmentConfig::finalized(),
+    ));
+    let mut tx_service_jh = None;
+    let mut websocket_failover_task = None;
+    let senders: Vec<Arc<dyn TransactionSender>> = match sender {
+        SenderKind::Tpu => {
+            let block_store = BlockStore::new(&nb_rpc_client)
+                .await
+                .expect("Blockstore should be created");
+            let (transaction_service, jh) = configure_transaction_service(
+                nb_rpc_client.clone(),
+                identity,
+                block_store,
+                empty_tx_store(),
+                None,
+                cli::DEFAULT_QUIC_CONNECTION_POOL_SIZE,
+            )
+            .await;
+            tx_service_jh = Some(jh);
+            vec![Arc::new(transaction_service)]
+        }
+        SenderKind::Rpc => vec![Arc::new(RpcSender::new(nb_rpc_client.clone()))],
+        SenderKind::Jito => vec![Arc::new(JitoSender::new(&jito_url))],
+        SenderKind::Mock => vec![Arc::new(MockSender::default())],
+        SenderKind::Quic => {
+            let connection_cache = Arc::new(
+                quic_connection_cache(
+                    &nb_rpc_client,
+                    bind_address,
+                    client_node_id.as_ref(),
+                    cli::DEFAULT_QUIC_CONNECTION_POOL_SIZE,
+                )
+                .await?,
+            );
+            if tpu_addrs.is_empty() {
+                let (websocket, jh) =
+                    start_websocket_failover(&websocket_url, Arc::new(AtomicBool::new(false)));
+                websocket_failover_task = Some(jh);
+                vec![Arc::new(
+                    QuicSender::new(nb_rpc_client.clone(), websocket, connection_cache).await?,
+                )]
+            } else {
+                vec![Arc::new(FixedTpuSender::new(connection_cache, tpu_addrs))]
+            }
+        }
+    };
+
+    replay_corpus(entries, senders, speed, &nb_rpc_client).await?;
+    if let Some(websocket_failover_task) = websocket_failover_task {
+        websocket_failover_task.abort();
+    }
+    if let Some(tx_service_jh) = tx_service_jh {
+        // the transaction service forwards the last transactions to the leaders in the background
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        tx_service_jh.abort();
+    }
+    Ok(())
+}
+
+/// runs the same simulation against every cluster at once, the first failure is returned once
+/// every run is over
+async fn run_clusters(cluster_configs: Vec<cli::Config>) -> anyhow::Result<()> {
+    let runs = cluster_configs.into_iter().map(|cli_config| async move {
+        let cluster = cli_config.cluster.clone();
+        let result = run_simulation(cli_config).await;
+        if let (Some(cluster), Err(e)) = (&cluster, &result) {
+            warn!("the run against {cluster} failed : {e:?}");
+        }
+        result
+    });
+    futures::future::join_all(runs)
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(())
+}
+/// runs the simulation of one cluster, a run which does not pass the thresholds fails
+async fn run_simulation(cli_config: cli::Config) -> anyhow::Result<()> {
+    let Some(summary) = BenchOrchestrator::new(cli_config).run().await? else {
+        return Ok(());
+    };
+    if !summary.passed {
+        anyhow::bail!(
+            "the run did not pass the thresholds : {}",
+            summary.violations.join(", ")
+        );
+    }
     Ok(())
 }
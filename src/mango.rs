@@ -1,18 +1,42 @@
 extern crate serde;
 extern crate serde_derive;
-use solana_sdk::signature::Keypair;
+use anyhow::Context;
+use log::{info, warn};
+use mango::state::{MangoGroup, PerpMarket, RootBank, QUOTE_INDEX};
+use mango_common::Loadable;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use std::str::FromStr;
+
+use crate::helpers::{to_sdk_pk, to_sp_pk};
 
 #[derive(Clone, Debug, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountKeys {
     pub public_key: String,
-    pub secret_key: Vec<u8>,
+    // one of secret key, keypair path or remote signer url is required, see signing::load_signer
+    #[serde(default)]
+    pub secret_key: Option<Vec<u8>>,
+    #[serde(default)]
+    pub keypair_path: Option<String>,
+    #[serde(default)]
+    pub remote_signer_url: Option<String>,
+    // optional payer of the transaction fees, the owner pays them when not set
+    #[serde(default)]
+    pub fee_payer_keypair_path: Option<String>,
+    #[serde(default)]
+    pub fee_payer_secret_key: Option<Vec<u8>>,
     pub mango_account_pks: Vec<String>,
 }
 
 impl AccountKeys {
-    pub fn to_keypair(&self) -> Keypair {
-        Keypair::from_bytes(self.secret_key.as_slice()).unwrap()
+    /// mango accounts quoted by this signer, at most limit of them
+    pub fn mango_accounts(&self, limit: usize) -> Vec<Pubkey> {
+        self.mango_account_pks
+            .iter()
+            .take(limit)
+            .map(|pk| Pubkey::from_str(pk).expect("mango account should be a valid pubkey"))
+            .collect()
     }
 }
 
@@ -21,7 +45,7 @@ pub struct MangoConfig {
     pub groups: Vec<GroupConfig>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GroupConfig {
     pub name: String,
@@ -35,14 +59,34 @@ pub struct GroupConfig {
     pub spot_markets: Vec<MarketConfig>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+/// The quote token comes first in the tokens of the config, the base tokens of the perp markets
+/// follow it
+pub fn quote_token(group: &GroupConfig) -> &TokenConfig {
+    &group.tokens[0]
+}
+
+/// base token of a perp market of the config, found by its symbol
+pub fn base_token<'a>(group: &'a GroupConfig, market: &MarketConfig) -> &'a TokenConfig {
+    group
+        .tokens
+        .iter()
+        .find(|t| t.symbol == market.base_symbol)
+        .unwrap_or_else(|| {
+            panic!(
+                "no token {} for the perp market {}",
+                market.base_symbol, market.name
+            )
+        })
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OracleConfig {
     pub symbol: String,
     pub public_key: String,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TokenConfig {
     pub symbol: String,
@@ -52,7 +96,7 @@ pub struct TokenConfig {
     pub node_keys: Vec<String>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MarketConfig {
     pub name: String,
@@ -64,4 +108,168 @@ pub struct MarketConfig {
     pub bids_key: String,
     pub asks_key: String,
     pub events_key: String,
+    // optional overrides of the quoting cli arguments for this market
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_size_lots: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_jitter_pct: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_spread_bps: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_price_offset_lots: Option<u64>,
+    // own quoting cadence of the market, quotes_per_second is then not changed by the control
+    // server nor the scenario
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quotes_per_second: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inventory_skew_bps: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_expiry_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_limit: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_unit_limit: Option<u32>,
+    // relative share of the market makers quoting the market
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+    // same format as --order-types
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_types: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reduce_only_pct: Option<u8>,
+}
+
+// reads several accounts of the same mango type in one request
+async fn load_accounts<T: Loadable>(
+    rpc_client: &RpcClient,
+    pks: &[Pubkey],
+) -> anyhow::Result<Vec<T>> {
+    let sdk_pks = pks.iter().map(to_sdk_pk).collect::<Vec<_>>();
+    let mut accounts = vec![];
+    // getMultipleAccounts is limited to 100 accounts per request
+    for chunk in sdk_pks.chunks(100) {
+        for (pk, account) in chunk
+            .iter()
+            .zip(rpc_client.get_multiple_accounts(chunk).await?)
+        {
+            let account = account.with_context(|| format!("account {pk} does not exist"))?;
+            let loaded = T::load_from_bytes(&account.data)
+                .map_err(|e| anyhow::anyhow!("account {pk} could not be loaded : {e:?}"))?;
+            accounts.push(*loaded);
+        }
+    }
+    Ok(accounts)
+}
+
+/// Builds the group config from the on-chain MangoGroup account instead of the mango keys file,
+/// the quote token comes first, followed by the tokens, oracles and perp markets in the order of
+/// their market index up to the first empty slot (mango v3 only). Names are not stored on-chain
+/// so the markets are named after their index
+pub async fn discover_group_config(
+    rpc_client: &RpcClient,
+    group_pk: &Pubkey,
+) -> anyhow::Result<GroupConfig> {
+    let group_account = rpc_client
+        .get_account(&to_sdk_pk(group_pk))
+        .await
+        .with_context(|| format!("could not get the mango group {group_pk}"))?;
+    let group = *MangoGroup::load_from_bytes(&group_account.data)
+        .map_err(|e| anyhow::anyhow!("{group_pk} is not a mango v3 group : {e:?}"))?;
+
+    let nb_markets = (0..QUOTE_INDEX)
+        .take_while(|i| !group.tokens[*i].is_empty() && !group.perp_markets[*i].is_empty())
+        .count();
+    if let Some(skipped) = (nb_markets..QUOTE_INDEX).find(|i| !group.perp_markets[*i].is_empty()) {
+        warn!("discovery stops at the first empty market slot, perp market {skipped} is ignored");
+    }
+    let token_indexes = [QUOTE_INDEX]
+        .into_iter()
+        .chain(0..nb_markets)
+        .collect::<Vec<_>>();
+
+    let root_bank_pks = token_indexes
+        .iter()
+        .map(|i| group.tokens[*i].root_bank)
+        .collect::<Vec<_>>();
+    let root_banks = load_accounts::<RootBank>(rpc_client, &root_bank_pks).await?;
+    let perp_market_pks = (0..nb_markets)
+        .map(|i| group.perp_markets[i].perp_market)
+        .collect::<Vec<_>>();
+    let perp_markets = load_accounts::<PerpMarket>(rpc_client, &perp_market_pks).await?;
+
+    let symbol = |index: usize| {
+        if index == QUOTE_INDEX {
+            "QUOTE".to_string()
+        } else {
+            format!("TOKEN{index}")
+        }
+    };
+    let tokens = token_indexes
+        .iter()
+        .zip(root_banks)
+        .map(|(index, root_bank)| {
+            let token = &group.tokens[*index];
+            TokenConfig {
+                symbol: symbol(*index),
+                mint_key: token.mint.to_string(),
+                decimals: token.decimals as u64,
+                root_key: token.root_bank.to_string(),
+                node_keys: root_bank.node_banks[..root_bank.num_node_banks]
+                    .iter()
+                    .map(|pk| pk.to_string())
+                    .collect(),
+            }
+        })
+        .collect();
+    let quote_decimals = group.tokens[QUOTE_INDEX].decimals as u64;
+    let perp_markets = perp_market_pks
+        .iter()
+        .zip(perp_markets)
+        .enumerate()
+        .map(|(index, (pk, perp_market))| MarketConfig {
+            name: format!("{}-PERP", symbol(index)),
+            public_key: pk.to_string(),
+            base_symbol: symbol(index),
+            base_decimals: group.tokens[index].decimals as u64,
+            quote_decimals,
+            market_index: index as u64,
+            bids_key: perp_market.bids.to_string(),
+            asks_key: perp_market.asks.to_string(),
+            events_key: perp_market.event_queue.to_string(),
+            order_size_lots: None,
+            size_jitter_pct: None,
+            max_spread_bps: None,
+            max_price_offset_lots: None,
+            quotes_per_second: None,
+            batch_size: None,
+            inventory_skew_bps: None,
+            order_expiry_secs: None,
+            order_limit: None,
+            compute_unit_limit: None,
+            weight: None,
+            order_types: None,
+            reduce_only_pct: None,
+        })
+        .collect();
+
+    info!("discovered {nb_markets} perp markets in the mango group {group_pk}");
+    Ok(GroupConfig {
+        name: group_pk.to_string(),
+        public_key: group_pk.to_string(),
+        cache_key: group.mango_cache.to_string(),
+        mango_program_id: to_sp_pk(&group_account.owner).to_string(),
+        serum_program_id: group.dex_program_id.to_string(),
+        oracles: (0..nb_markets)
+            .map(|index| OracleConfig {
+                symbol: symbol(index),
+                public_key: group.oracles[index].to_string(),
+            })
+            .collect(),
+        tokens,
+        perp_markets,
+        // spot markets are not quoted by the simulation
+        spot_markets: vec![],
+    })
 }
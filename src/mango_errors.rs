@@ -0,0 +1,106 @@
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+const CUSTOM_ERROR: &str = "failed: custom program error: 0x";
+
+/// Where a failed transaction failed, the error code is the custom error of the program, which
+/// is the mango error code for the mango instructions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProgramFailure {
+    pub error_code: Option<u32>,
+    pub failed_instruction: Option<u8>,
+}
+
+/// Parses the log messages of a failed transaction. The top level instructions are the ones
+/// invoked at depth 1, the first program failing is the one which returned the error
+pub fn from_logs(logs: &[String]) -> Option<ProgramFailure> {
+    let mut instruction: Option<u8> = None;
+    for log in logs {
+        if log.ends_with(" invoke [1]") {
+            instruction = Some(instruction.map_or(0, |i| i.saturating_add(1)));
+        } else if log.contains(" failed: ") {
+            let error_code = log
+                .find(CUSTOM_ERROR)
+                .and_then(|at| u32::from_str_radix(&log[at + CUSTOM_ERROR.len()..], 16).ok());
+            return Some(ProgramFailure {
+                error_code,
+                failed_instruction: instruction,
+            });
+        }
+    }
+    None
+}
+
+/// Used when the logs are not available, like for the transactions notified by lite-rpc
+pub fn from_error(error: &TransactionError) -> Option<ProgramFailure> {
+    match error {
+        TransactionError::InstructionError(index, instruction_error) => Some(ProgramFailure {
+            error_code: match instruction_error {
+                InstructionError::Custom(code) => Some(*code),
+                _ => None,
+            },
+            failed_instruction: Some(*index),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn finds_the_failed_instruction_and_its_error_code() {
+        let failure = from_logs(&logs(&[
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68 invoke [1]",
+            "Program mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68 success",
+            "Program mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68 invoke [1]",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+            "Program mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68 failed: custom program error: 0x1a",
+        ]));
+        assert_eq!(
+            failure,
+            Some(ProgramFailure {
+                error_code: Some(0x1a),
+                failed_instruction: Some(2),
+            })
+        );
+    }
+
+    #[test]
+    fn failures_without_custom_error_have_no_code() {
+        let failure = from_logs(&logs(&[
+            "Program mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68 invoke [1]",
+            "Program mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68 failed: exceeded CUs meter at BPF instruction",
+        ]));
+        assert_eq!(
+            failure,
+            Some(ProgramFailure {
+                error_code: None,
+                failed_instruction: Some(0),
+            })
+        );
+        assert_eq!(from_logs(&logs(&["Program log: ok"])), None);
+    }
+
+    #[test]
+    fn reads_the_failure_from_the_transaction_error() {
+        assert_eq!(
+            from_error(&TransactionError::InstructionError(
+                3,
+                InstructionError::Custom(7)
+            )),
+            Some(ProgramFailure {
+                error_code: Some(7),
+                failed_instruction: Some(3),
+            })
+        );
+        assert_eq!(from_error(&TransactionError::AccountInUse), None);
+    }
+}
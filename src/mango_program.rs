@@ -0,0 +1,349 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use borsh::BorshSerialize;
+use chrono::Utc;
+use dashmap::DashMap;
+use mango::matching::{ExpiryType, OrderType, Side};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    hash::hash,
+    instruction::{AccountMeta, Instruction},
+};
+
+use crate::{
+    helpers::{to_sdk_instruction, to_sdk_pk},
+    states::PerpMarketCache,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgramVersion {
+    V3,
+    V4,
+}
+
+impl FromStr for ProgramVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "v3" | "3" => Ok(ProgramVersion::V3),
+            "v4" | "4" => Ok(ProgramVersion::V4),
+            _ => Err(format!("unknown mango program version {s}")),
+        }
+    }
+}
+
+impl fmt::Display for ProgramVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgramVersion::V3 => write!(f, "v3"),
+            ProgramVersion::V4 => write!(f, "v4"),
+        }
+    }
+}
+
+/// Instruction builders used by the market makers, implemented for each supported mango program
+pub trait MangoProgram: Send + Sync {
+    fn version(&self) -> ProgramVersion;
+
+    fn cancel_all_perp_orders(
+        &self,
+        c: &PerpMarketCache,
+        mango_account_pk: &Pubkey,
+        owner_pk: &Pubkey,
+        limit: u8,
+    ) -> Instruction;
+
+    #[allow(clippy::too_many_arguments)]
+    fn place_perp_order(
+        &self,
+        c: &PerpMarketCache,
+        mango_account_pk: &Pubkey,
+        owner_pk: &Pubkey,
+        side: Side,
+        price: i64,
+        max_base_quantity: i64,
+        max_quote_quantity: i64,
+        client_order_id: u64,
+        order_type: OrderType,
+        reduce_only: bool,
+        expiry_timestamp: Option<u64>,
+        limit: u8,
+        expiry_type: ExpiryType,
+    ) -> Instruction;
+}
+
+pub fn mango_program(version: ProgramVersion) -> Arc<dyn MangoProgram> {
+    match version {
+        ProgramVersion::V3 => Arc::new(MangoV3),
+        ProgramVersion::V4 => Arc::new(MangoV4::default()),
+    }
+}
+
+/// mango v3, instructions are built by the `mango` crate
+pub struct MangoV3;
+
+impl MangoProgram for MangoV3 {
+    fn version(&self) -> ProgramVersion {
+        ProgramVersion::V3
+    }
+
+    fn cancel_all_perp_orders(
+        &self,
+        c: &PerpMarketCache,
+        mango_account_pk: &Pubkey,
+        owner_pk: &Pubkey,
+        limit: u8,
+    ) -> Instruction {
+        to_sdk_instruction(
+            mango::instruction::cancel_all_perp_orders(
+                &c.mango_program_pk,
+                &c.mango_group_pk,
+                mango_account_pk,
+                owner_pk,
+                &c.perp_market_pk,
+                &c.bids,
+                &c.asks,
+                limit,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn place_perp_order(
+        &self,
+        c: &PerpMarketCache,
+        mango_account_pk: &Pubkey,
+        owner_pk: &Pubkey,
+        side: Side,
+        price: i64,
+        max_base_quantity: i64,
+        max_quote_quantity: i64,
+        client_order_id: u64,
+        order_type: OrderType,
+        reduce_only: bool,
+        expiry_timestamp: Option<u64>,
+        limit: u8,
+        expiry_type: ExpiryType,
+    ) -> Instruction {
+        to_sdk_instruction(
+            mango::instruction::place_perp_order2(
+                &c.mango_program_pk,
+                &c.mango_group_pk,
+                mango_account_pk,
+                owner_pk,
+                &c.mango_cache_pk,
+                &c.perp_market_pk,
+                &c.bids,
+                &c.asks,
+                &c.event_queue,
+                None,
+                &[],
+                side,
+                price,
+                max_base_quantity,
+                max_quote_quantity,
+                client_order_id,
+                order_type,
+                reduce_only,
+                expiry_timestamp,
+                limit,
+                expiry_type,
+            )
+            .unwrap(),
+        )
+    }
+}
+
+/// mango v4, the program is anchor based so instructions are encoded by hand
+/// (8 bytes discriminator followed by borsh serialized arguments)
+#[derive(Default)]
+pub struct MangoV4 {
+    positions: DashMap<Pubkey, V4Positions>,
+}
+
+impl MangoV4 {
+    /// positions of a mango account, loaded from the account before its orders are built
+    pub fn set_positions(&self, mango_account_pk: Pubkey, positions: V4Positions) {
+        self.positions.insert(mango_account_pk, positions);
+    }
+}
+
+/// Token and perp positions of a v4 mango account, the health check of an order reads the banks
+/// and the perp markets of all of them with their oracles
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct V4Positions {
+    /// active token positions as (bank, oracle), the quote bank settling the perp markets included
+    pub banks: Vec<(Pubkey, Pubkey)>,
+    /// perp positions as (perp market, oracle)
+    pub perp_markets: Vec<(Pubkey, Pubkey)>,
+}
+
+impl V4Positions {
+    /// health accounts in the order of the v4 client: the banks, their oracles, the perp markets
+    /// and their oracles. The traded perp market is added when the account has no position on it
+    fn health_accounts(&self, perp_market: (Pubkey, Pubkey)) -> Vec<AccountMeta> {
+        let mut perp_markets = self.perp_markets.clone();
+        if !perp_markets
+            .iter()
+            .any(|(market, _)| *market == perp_market.0)
+        {
+            perp_markets.push(perp_market);
+        }
+        let readonly = |pk: &Pubkey| AccountMeta::new_readonly(to_sdk_pk(pk), false);
+        self.banks
+            .iter()
+            .map(|(bank, _)| readonly(bank))
+            .chain(self.banks.iter().map(|(_, oracle)| readonly(oracle)))
+            .chain(perp_markets.iter().map(|(market, _)| readonly(market)))
+            .chain(perp_markets.iter().map(|(_, oracle)| readonly(oracle)))
+            .collect()
+    }
+}
+
+fn anchor_discriminator(name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(format!("global:{name}").as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+#[derive(BorshSerialize)]
+struct V4PerpPlaceOrderArgs {
+    side: u8,
+    price_lots: i64,
+    max_base_lots: i64,
+    max_quote_lots: i64,
+    client_order_id: u64,
+    order_type: u8,
+    reduce_only: bool,
+    expiry_timestamp: u64,
+    limit: u8,
+}
+
+#[derive(BorshSerialize)]
+struct V4PerpCancelAllOrdersArgs {
+    limit: u8,
+}
+
+fn v4_instruction_data<T: BorshSerialize>(name: &str, args: &T) -> Vec<u8> {
+    let mut data = anchor_discriminator(name).to_vec();
+    data.extend(args.try_to_vec().unwrap());
+    data
+}
+
+impl MangoProgram for MangoV4 {
+    fn version(&self) -> ProgramVersion {
+        ProgramVersion::V4
+    }
+
+    fn cancel_all_perp_orders(
+        &self,
+        c: &PerpMarketCache,
+        mango_account_pk: &Pubkey,
+        owner_pk: &Pubkey,
+        limit: u8,
+    ) -> Instruction {
+        Instruction {
+            program_id: to_sdk_pk(&c.mango_program_pk),
+            accounts: vec![
+                AccountMeta::new_readonly(to_sdk_pk(&c.mango_group_pk), false),
+                AccountMeta::new(to_sdk_pk(mango_account_pk), false),
+                AccountMeta::new_readonly(to_sdk_pk(owner_pk), true),
+                AccountMeta::new(to_sdk_pk(&c.perp_market_pk), false),
+                AccountMeta::new(to_sdk_pk(&c.bids), false),
+                AccountMeta::new(to_sdk_pk(&c.asks), false),
+            ],
+            data: v4_instruction_data(
+                "perp_cancel_all_orders",
+                &V4PerpCancelAllOrdersArgs { limit },
+            ),
+        }
+    }
+
+    fn place_perp_order(
+        &self,
+        c: &PerpMarketCache,
+        mango_account_pk: &Pubkey,
+        owner_pk: &Pubkey,
+        side: Side,
+        price: i64,
+        max_base_quantity: i64,
+        max_quote_quantity: i64,
+        client_order_id: u64,
+        order_type: OrderType,
+        reduce_only: bool,
+        expiry_timestamp: Option<u64>,
+        limit: u8,
+        expiry_type: ExpiryType,
+    ) -> Instruction {
+        // v4 only supports absolute expiry timestamps, 0 means no expiry
+        let expiry_timestamp = match expiry_type {
+            ExpiryType::Absolute => expiry_timestamp,
+            ExpiryType::Relative => {
+                expiry_timestamp.map(|secs| Utc::now().timestamp() as u64 + secs)
+            }
+        };
+        let args = V4PerpPlaceOrderArgs {
+            side: side as u8,
+            price_lots: price,
+            max_base_lots: max_base_quantity,
+            max_quote_lots: max_quote_quantity,
+            client_order_id,
+            order_type: order_type as u8,
+            reduce_only,
+            expiry_timestamp: expiry_timestamp.unwrap_or(0),
+            limit,
+        };
+        let mut accounts = vec![
+            AccountMeta::new_readonly(to_sdk_pk(&c.mango_group_pk), false),
+            AccountMeta::new(to_sdk_pk(mango_account_pk), false),
+            AccountMeta::new_readonly(to_sdk_pk(owner_pk), true),
+            AccountMeta::new(to_sdk_pk(&c.perp_market_pk), false),
+            AccountMeta::new(to_sdk_pk(&c.bids), false),
+            AccountMeta::new(to_sdk_pk(&c.asks), false),
+            AccountMeta::new(to_sdk_pk(&c.event_queue), false),
+            AccountMeta::new_readonly(to_sdk_pk(&c.price_oracle), false),
+        ];
+        let positions = self
+            .positions
+            .get(mango_account_pk)
+            .map(|positions| positions.clone())
+            .unwrap_or_default();
+        accounts.extend(positions.health_accounts((c.perp_market_pk, c.price_oracle)));
+        Instruction {
+            program_id: to_sdk_pk(&c.mango_program_pk),
+            accounts,
+            data: v4_instruction_data("perp_place_order", &args),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_accounts_follow_the_v4_client_order() {
+        let quote = (Pubkey::new_unique(), Pubkey::new_unique());
+        let held = (Pubkey::new_unique(), Pubkey::new_unique());
+        let traded = (Pubkey::new_unique(), Pubkey::new_unique());
+        let positions = V4Positions {
+            banks: vec![quote],
+            perp_markets: vec![held],
+        };
+        let keys = |accounts: Vec<AccountMeta>| {
+            accounts
+                .into_iter()
+                .map(|account| account.pubkey)
+                .collect::<Vec<_>>()
+        };
+        let expected =
+            [quote.0, quote.1, held.0, traded.0, held.1, traded.1].map(|pk| to_sdk_pk(&pk));
+        assert_eq!(keys(positions.health_accounts(traded)), expected);
+        // an open position is not listed twice
+        assert_eq!(
+            keys(positions.health_accounts(held)),
+            [quote.0, quote.1, held.0, held.1].map(|pk| to_sdk_pk(&pk))
+        );
+    }
+}
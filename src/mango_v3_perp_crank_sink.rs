@@ -1,8 +1,10 @@
 use std::{
     cell::RefCell,
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
     mem::size_of,
+    sync::Mutex,
+    time::{Duration, Instant},
 };
 
 use arrayref::array_ref;
@@ -14,7 +16,9 @@ use mango::{
     queue::{AnyEvent, EventQueueHeader, EventType, FillEvent, OutEvent, Queue},
 };
 use mango_feeds_connector::solana_sdk::account::ReadableAccount;
-use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, pubkey::Pubkey,
+};
 
 use bytemuck::cast_ref;
 
@@ -24,7 +28,42 @@ use crate::helpers::{to_sdk_instruction, to_sp_pk};
 
 const MAX_BACKLOG: usize = 2;
 const MAX_ACCS_PER_TX: usize = 24;
-const MAX_EVENTS_PER_TX: usize = 50;
+pub const MAX_EVENTS_PER_TX: usize = 50;
+// units of a consume events instruction besides the ones of its events
+const CONSUME_EVENTS_BASE_COMPUTE_UNITS: u32 = 40_000;
+const COMPUTE_UNITS_PER_EVENT: u32 = 25_000;
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+// a crank transaction which did not move the head of its queue by then is considered dropped
+const CRANK_LANDING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How the events of a perp market are split in crank transactions
+#[derive(Clone, Copy, Debug)]
+pub struct CrankBatchConfig {
+    // limit of the consume events instruction of each transaction
+    pub events_per_tx: usize,
+    // mango accounts passed to each transaction, the events of the other accounts wait
+    pub accounts_per_tx: usize,
+    pub compute_units_per_event: u32,
+}
+
+impl Default for CrankBatchConfig {
+    fn default() -> Self {
+        Self {
+            events_per_tx: MAX_EVENTS_PER_TX,
+            accounts_per_tx: MAX_ACCS_PER_TX,
+            compute_units_per_event: COMPUTE_UNITS_PER_EVENT,
+        }
+    }
+}
+
+impl CrankBatchConfig {
+    pub fn compute_unit_limit(&self, events: usize) -> u32 {
+        self.compute_units_per_event
+            .saturating_mul(events as u32)
+            .saturating_add(CONSUME_EVENTS_BASE_COMPUTE_UNITS)
+            .min(MAX_COMPUTE_UNIT_LIMIT)
+    }
+}
 
 pub struct MangoV3PerpCrankSink {
     mkt_pks_by_evq_pks: BTreeMap<Pubkey, Pubkey>,
@@ -32,6 +71,9 @@ pub struct MangoV3PerpCrankSink {
     cache_pk: Pubkey,
     mango_v3_program: Pubkey,
     instruction_sender: Sender<(Pubkey, Vec<Instruction>)>,
+    batch: CrankBatchConfig,
+    // head of each queue when its last transaction was sent
+    in_flight: Mutex<HashMap<Pubkey, (usize, Instant)>>,
 }
 
 impl MangoV3PerpCrankSink {
@@ -41,6 +83,7 @@ impl MangoV3PerpCrankSink {
         cache_pk: Pubkey,
         mango_v3_program: Pubkey,
         instruction_sender: Sender<(Pubkey, Vec<Instruction>)>,
+        batch: CrankBatchConfig,
     ) -> Self {
         Self {
             mkt_pks_by_evq_pks: pks
@@ -51,6 +94,8 @@ impl MangoV3PerpCrankSink {
             cache_pk,
             mango_v3_program,
             instruction_sender,
+            batch,
+            in_flight: Mutex::default(),
         }
     }
 }
@@ -69,11 +114,12 @@ impl AccountWriteSink for MangoV3PerpCrankSink {
     ) -> Result<(), String> {
         let account = &account.account;
 
-        let (ix, mkt_pk): (Result<Instruction, String>, Pubkey) = {
+        let (ixs, mkt_pk): (Vec<Instruction>, Pubkey) = {
             const HEADER_SIZE: usize = size_of::<EventQueueHeader>();
             let header_data = array_ref![account.data(), 0, HEADER_SIZE];
             let header = RefCell::<EventQueueHeader>::new(*bytemuck::from_bytes(header_data));
             let seq_num = header.clone().into_inner().seq_num;
+            let head = header.clone().into_inner().head;
             // trace!("evq {} seq_num {}", mkt.name, header.seq_num);
 
             const QUEUE_SIZE: usize = EVENT_SIZE * QUEUE_LEN;
@@ -98,30 +144,47 @@ impl AccountWriteSink for MangoV3PerpCrankSink {
 
             trace!("evq {pk:?} seq_num={seq_num} len={len} contains_fill_events={contains_fill_events} has_backlog={has_backlog}");
 
+            let pk = solana_sdk::pubkey::Pubkey::new_from_array(pk.to_bytes());
+            let mkt_pk = self
+                .mkt_pks_by_evq_pks
+                .get(&pk)
+                .unwrap_or_else(|| panic!("{pk:?} is a known public key"));
+
+            // consume events always consumes the head of the queue, a transaction landing before
+            // the previous one of its queue would miss the accounts of the events it consumes. A
+            // deep queue is drained one transaction at a time, the next one is sent once the
+            // head moved
+            {
+                let mut in_flight = self.in_flight.lock().unwrap();
+                if let Some((sent_head, sent_at)) = in_flight.get(&pk) {
+                    if *sent_head == head && sent_at.elapsed() < CRANK_LANDING_TIMEOUT {
+                        return Err("previous crank transaction in flight".into());
+                    }
+                }
+                in_flight.insert(pk, (head, Instant::now()));
+            }
+
+            let events = event_queue
+                .iter()
+                .take(self.batch.events_per_tx.max(1))
+                .collect::<Vec<_>>();
             let mut mango_accounts = HashSet::new();
-            event_queue.iter().take(MAX_EVENTS_PER_TX).for_each(|e| {
-                if mango_accounts.len() < MAX_ACCS_PER_TX {
+            events.iter().for_each(|e| {
+                if mango_accounts.len() < self.batch.accounts_per_tx {
                     match EventType::try_from(e.event_type).expect("mango v4 event") {
                         EventType::Fill => {
-                            let fill: &FillEvent = cast_ref(e);
+                            let fill: &FillEvent = cast_ref(*e);
                             mango_accounts.insert(fill.maker);
                             mango_accounts.insert(fill.taker);
                         }
                         EventType::Out => {
-                            let out: &OutEvent = cast_ref(e);
+                            let out: &OutEvent = cast_ref(*e);
                             mango_accounts.insert(out.owner);
                         }
                         EventType::Liquidate => {}
                     }
                 }
             });
-
-            let pk = solana_sdk::pubkey::Pubkey::new_from_array(pk.to_bytes());
-            let mkt_pk = self
-                .mkt_pks_by_evq_pks
-                .get(&pk)
-                .unwrap_or_else(|| panic!("{pk:?} is a known public key"));
-
             let ix = to_sdk_instruction(
                 consume_events(
                     &to_sp_pk(&self.mango_v3_program),
@@ -130,12 +193,18 @@ impl AccountWriteSink for MangoV3PerpCrankSink {
                     &to_sp_pk(mkt_pk),
                     &to_sp_pk(&pk),
                     &mut mango_accounts.iter().copied().collect::<Vec<_>>(),
-                    MAX_EVENTS_PER_TX,
+                    events.len(),
                 )
                 .unwrap(),
             );
-
-            (Ok(ix), *mkt_pk)
+            let ixs = vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(
+                    self.batch.compute_unit_limit(events.len()),
+                ),
+                ix,
+            ];
+
+            (ixs, *mkt_pk)
         };
 
         // info!(
@@ -143,7 +212,7 @@ impl AccountWriteSink for MangoV3PerpCrankSink {
         //     event_queue.iter().count()
         // );
 
-        if let Err(e) = self.instruction_sender.send((mkt_pk, vec![ix?])).await {
+        if let Err(e) = self.instruction_sender.send((mkt_pk, ixs)).await {
             return Err(e.to_string());
         }
 
@@ -0,0 +1,124 @@
+use std::{any::Any, sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use log::{error, warn};
+use solana_metrics::{datapoint_error, datapoint_warn};
+
+pub const DEFAULT_MAX_MARKET_MAKER_RESTARTS: u32 = 3;
+// batches failing to send in a row before the market maker is considered broken
+pub const SEND_ERROR_BUDGET: u64 = 20;
+// waited before the first restart, doubled on every following one up to the max
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Default)]
+pub struct ThreadFailures {
+    pub restarts: u32,
+    pub last_error: String,
+    // failed once more after its last restart and stopped quoting
+    pub abandoned: bool,
+}
+
+/// Failures of the market maker threads, a failed thread is restarted with a backoff until it
+/// runs out of restarts and is abandoned
+#[derive(Clone)]
+pub struct MarketMakerFailures {
+    max_restarts: u32,
+    threads: Arc<DashMap<String, ThreadFailures>>,
+}
+
+impl Default for MarketMakerFailures {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_MARKET_MAKER_RESTARTS)
+    }
+}
+
+impl MarketMakerFailures {
+    pub fn new(max_restarts: u32) -> Self {
+        Self {
+            max_restarts,
+            threads: Arc::default(),
+        }
+    }
+
+    /// Records the failure of the thread and returns the backoff to wait before restarting it,
+    /// none once the thread used all its restarts and is abandoned
+    pub fn record_failure(&self, thread: &str, error: String) -> Option<Duration> {
+        let mut failures = self.threads.entry(thread.to_string()).or_default();
+        failures.last_error = error;
+        if failures.restarts >= self.max_restarts {
+            failures.abandoned = true;
+            error!(
+                "market maker {thread} abandoned after {} restarts : {}",
+                failures.restarts, failures.last_error
+            );
+            datapoint_error!(
+                "bencher-market-maker-abandoned",
+                ("thread", thread, String),
+                ("restarts", failures.restarts, i64),
+                ("error", failures.last_error, String),
+            );
+            return None;
+        }
+        failures.restarts += 1;
+        let backoff = restart_backoff(failures.restarts);
+        warn!(
+            "market maker {thread} failed, restart {}/{} in {backoff:?} : {}",
+            failures.restarts, self.max_restarts, failures.last_error
+        );
+        datapoint_warn!(
+            "bencher-market-maker-restart",
+            ("thread", thread, String),
+            ("restart", failures.restarts, i64),
+            ("error", failures.last_error, String),
+        );
+        Some(backoff)
+    }
+
+    pub fn restarts(&self) -> u32 {
+        self.threads.iter().map(|entry| entry.restarts).sum()
+    }
+
+    /// the threads which stopped quoting, by name
+    pub fn abandoned(&self) -> Vec<(String, ThreadFailures)> {
+        self.threads
+            .iter()
+            .filter(|entry| entry.abandoned)
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+}
+
+// a large --max-market-maker-restarts would overflow the doubling
+fn restart_backoff(restart: u32) -> Duration {
+    2u32.checked_pow(restart.saturating_sub(1))
+        .and_then(|factor| RESTART_BACKOFF.checked_mul(factor))
+        .map_or(MAX_RESTART_BACKOFF, |backoff| {
+            backoff.min(MAX_RESTART_BACKOFF)
+        })
+}
+
+/// message of a caught panic, which is a &str or a String unless panicked with another payload
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map_or_else(|| "panicked".to_string(), |message| message.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_backoff_doubles_up_to_the_max() {
+        assert_eq!(restart_backoff(1), Duration::from_secs(1));
+        assert_eq!(restart_backoff(2), Duration::from_secs(2));
+        assert_eq!(restart_backoff(6), Duration::from_secs(32));
+        assert_eq!(restart_backoff(7), MAX_RESTART_BACKOFF);
+        assert_eq!(restart_backoff(40), MAX_RESTART_BACKOFF);
+        assert_eq!(restart_backoff(u32::MAX), MAX_RESTART_BACKOFF);
+    }
+}
@@ -1,133 +1,208 @@
 use std::{
+    collections::{HashMap, VecDeque},
+    panic::AssertUnwindSafe,
     str::FromStr,
     sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
     time::{Duration, Instant},
 };
 
-use chrono::Utc;
+use futures::FutureExt;
 use iter_tools::Itertools;
 use log::{debug, info, warn};
-use mango::{
-    instruction::{cancel_all_perp_orders, place_perp_order2},
-    matching::Side,
-};
-use rand::{distributions::Uniform, prelude::Distribution, seq::SliceRandom};
+use mango::matching::{ExpiryType, OrderType, Side};
+use rand::{distributions::Uniform, prelude::Distribution, rngs::StdRng, seq::SliceRandom, Rng};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::{
-    compute_budget, hash::Hash, instruction::Instruction, message::Message, signature::Keypair,
-    signer::Signer, transaction::Transaction,
+    compute_budget, hash::Hash, instruction::Instruction, message::Message, signer::Signer,
+    transaction::Transaction,
 };
 use tokio::{
-    sync::RwLock,
+    sync::{oneshot, RwLock},
     task::{self, JoinHandle},
 };
 
 use crate::{
-    helpers::{to_sdk_instruction, to_sp_pk},
+    chaos::{self, ChaosConfig, ChaosKind},
+    client_order_ids::{side_client_order_id, ClientOrderIdKey, ClientOrderIds, DedupGuard},
+    clock,
+    helpers::{seeded_rng, to_sp_pk},
+    inventory::Inventory,
     mango::AccountKeys,
-    states::{PerpMarketCache, TransactionSendRecord},
+    mango_program::MangoProgram,
+    market_maker_failures::{panic_message, MarketMakerFailures, SEND_ERROR_BUDGET},
+    memo::MemoTagger,
+    order_mix::QuoteOrder,
+    rate_limiter::{Pacing, RateLimiter},
+    shard::Shard,
+    signing::MarketMakerSigners,
+    states::{
+        LiveParameters, PerpMarketCache, QuoteConfig, TransactionSendRecord, TransactionType,
+    },
     tpu_manager::TpuManager,
+    watchdog::Watchdog,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_ask_bid_transaction(
+    mango_program: &dyn MangoProgram,
     c: &PerpMarketCache,
     mango_account_pk: Pubkey,
-    mango_account_signer: &Keypair,
+    signers: &MarketMakerSigners,
     prioritization_fee: u64,
+    position_lots: i64,
+    client_order_id: u64,
+    order: QuoteOrder,
+    rng: &mut impl Rng,
 ) -> Transaction {
-    let mango_account_signer_pk = to_sp_pk(&mango_account_signer.pubkey());
-    let price = 100;
-    let offset = rand::random::<i8>() as i64 % 20;
-    let spread = rand::random::<u8>() as i64 % 10;
+    let mango_account_signer_pk = to_sp_pk(&signers.owner.pubkey());
+    let price = c.quote_price();
+    let offset = c.quote_config.price_offset(rng)
+        + c.quote_config.inventory_skew(
+            price,
+            position_lots,
+            c.quote_config.order_size_lots.unwrap_or(c.order_base_lots),
+        );
+    let spread = c.quote_config.spread(price, rng);
     debug!(
         "price:{:?} price_quote_lots:{:?} order_base_lots:{:?} offset:{:?} spread:{:?}",
         c.price, c.price_quote_lots, c.order_base_lots, offset, spread
     );
     let mut instructions = vec![];
+    // without a limit every instruction requests the default 200k units
+    if let Some(compute_unit_limit) = c.quote_config.compute_unit_limit {
+        instructions.push(
+            compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit),
+        );
+    }
     if prioritization_fee > 0 {
         let pfees =
             compute_budget::ComputeBudgetInstruction::set_compute_unit_price(prioritization_fee);
         instructions.push(pfees);
     }
 
-    let cancel_ix: Instruction = to_sdk_instruction(
-        cancel_all_perp_orders(
-            &c.mango_program_pk,
-            &c.mango_group_pk,
-            &mango_account_pk,
-            &mango_account_signer_pk,
-            &c.perp_market_pk,
-            &c.perp_market.bids,
-            &c.perp_market.asks,
-            10,
-        )
-        .unwrap(),
-    );
+    let cancel_ix: Instruction =
+        mango_program.cancel_all_perp_orders(c, &mango_account_pk, &mango_account_signer_pk, 10);
     instructions.push(cancel_ix);
 
-    let place_bid_ix: Instruction = to_sdk_instruction(
-        place_perp_order2(
-            &c.mango_program_pk,
-            &c.mango_group_pk,
-            &mango_account_pk,
-            &mango_account_signer_pk,
-            &c.mango_cache_pk,
-            &c.perp_market_pk,
-            &c.perp_market.bids,
-            &c.perp_market.asks,
-            &c.perp_market.event_queue,
-            None,
-            &[],
-            Side::Bid,
-            price + offset - spread,
-            c.order_base_lots,
-            i64::MAX,
-            Utc::now().timestamp_micros() as u64,
-            mango::matching::OrderType::Limit,
-            false,
-            None,
-            64,
-            mango::matching::ExpiryType::Absolute,
-        )
-        .unwrap(),
+    let place_bid_ix: Instruction = mango_program.place_perp_order(
+        c,
+        &mango_account_pk,
+        &mango_account_signer_pk,
+        Side::Bid,
+        price + offset - spread,
+        c.quote_config.order_size(c.order_base_lots, rng),
+        c.quote_config.max_quote_lots,
+        side_client_order_id(client_order_id, Side::Bid),
+        order.order_type.order_type(),
+        order.reduce_only,
+        c.quote_config.expiry_timestamp(),
+        c.quote_config.order_limit,
+        c.quote_config.expiry_type,
     );
     instructions.push(place_bid_ix);
 
-    let place_ask_ix: Instruction = to_sdk_instruction(
-        place_perp_order2(
-            &c.mango_program_pk,
-            &c.mango_group_pk,
-            &mango_account_pk,
-            &mango_account_signer_pk,
-            &c.mango_cache_pk,
-            &c.perp_market_pk,
-            &c.perp_market.bids,
-            &c.perp_market.asks,
-            &c.perp_market.event_queue,
-            None,
-            &[],
-            Side::Ask,
-            price + offset + spread,
-            c.order_base_lots,
-            i64::MAX,
-            Utc::now().timestamp_micros() as u64,
-            mango::matching::OrderType::Limit,
-            false,
-            None,
-            64,
-            mango::matching::ExpiryType::Absolute,
-        )
-        .unwrap(),
+    let place_ask_ix: Instruction = mango_program.place_perp_order(
+        c,
+        &mango_account_pk,
+        &mango_account_signer_pk,
+        Side::Ask,
+        price + offset + spread,
+        c.quote_config.order_size(c.order_base_lots, rng),
+        c.quote_config.max_quote_lots,
+        side_client_order_id(client_order_id, Side::Ask),
+        order.order_type.order_type(),
+        order.reduce_only,
+        c.quote_config.expiry_timestamp(),
+        c.quote_config.order_limit,
+        c.quote_config.expiry_type,
     );
     instructions.push(place_ask_ix);
 
     Transaction::new_unsigned(Message::new(
         instructions.as_slice(),
-        Some(&mango_account_signer.pubkey()),
+        Some(&signers.payer()),
+    ))
+}
+
+// more levels would not fit into a single transaction
+pub const MAX_SPOOFING_LEVELS: u8 = 6;
+
+// batches of a market maker being sent at once, a slow transaction service holds back the quoting
+// instead of piling up sending tasks
+const MAX_SENDING_BATCHES: usize = 64;
+
+// adversarial preset : place one lot orders on many price levels on both sides of the book
+// and cancel them all in the same transaction to stress slab allocation and cancel processing
+#[allow(clippy::too_many_arguments)]
+pub fn create_spoofing_transaction(
+    mango_program: &dyn MangoProgram,
+    c: &PerpMarketCache,
+    mango_account_pk: Pubkey,
+    signers: &MarketMakerSigners,
+    prioritization_fee: u64,
+    levels: u8,
+    client_order_id: u64,
+    rng: &mut impl Rng,
+) -> Transaction {
+    let mango_account_signer_pk = to_sp_pk(&signers.owner.pubkey());
+    let price = c.quote_price();
+    let levels = levels.clamp(1, MAX_SPOOFING_LEVELS) as i64;
+    let offset = c.quote_config.price_offset(rng);
+    debug!(
+        "spoofing price:{:?} price_quote_lots:{:?} levels:{:?} offset:{:?}",
+        c.price, c.price_quote_lots, levels, offset
+    );
+
+    let mut instructions = vec![
+        compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
+            c.quote_config.compute_unit_limit.unwrap_or(1_400_000),
+        ),
+    ];
+    if prioritization_fee > 0 {
+        instructions.push(
+            compute_budget::ComputeBudgetInstruction::set_compute_unit_price(prioritization_fee),
+        );
+    }
+
+    for level in 1..=levels {
+        for (side, level_price) in [
+            (Side::Bid, price + offset - level),
+            (Side::Ask, price + offset + level),
+        ] {
+            let place_ix: Instruction = mango_program.place_perp_order(
+                c,
+                &mango_account_pk,
+                &mango_account_signer_pk,
+                side,
+                level_price,
+                1,
+                c.quote_config.max_quote_lots,
+                side_client_order_id(client_order_id, side),
+                OrderType::PostOnly,
+                false,
+                None,
+                64,
+                ExpiryType::Absolute,
+            );
+            instructions.push(place_ix);
+        }
+    }
+
+    let cancel_ix: Instruction = mango_program.cancel_all_perp_orders(
+        c,
+        &mango_account_pk,
+        &mango_account_signer_pk,
+        (2 * levels) as u8,
+    );
+    instructions.push(cancel_ix);
+
+    Transaction::new_unsigned(Message::new(
+        instructions.as_slice(),
+        Some(&signers.payer()),
     ))
 }
 
@@ -136,16 +211,16 @@ fn generate_random_fees(
     n: usize,
     min_fee: u64,
     max_fee: u64,
+    rng: &mut impl Rng,
 ) -> Vec<u64> {
-    let mut rng = rand::thread_rng();
     let range = Uniform::from(min_fee..max_fee);
     let range_probability = Uniform::from(1..100);
     (0..n)
         .map(|_| {
             if prioritization_fee_proba == 0 {
                 0
-            } else if range_probability.sample(&mut rng) <= prioritization_fee_proba {
-                range.sample(&mut rng)
+            } else if range_probability.sample(rng) <= prioritization_fee_proba {
+                range.sample(rng)
             } else {
                 0
             }
@@ -153,217 +228,960 @@ fn generate_random_fees(
         .collect()
 }
 
+/// A signed transaction and its record, the duplicates of the chaos mode come in pairs
+pub type BuiltTransaction = (Transaction, TransactionSendRecord);
+
+/// Builds and signs one quote on every market
 #[allow(clippy::too_many_arguments)]
-pub async fn send_mm_transactions(
-    quotes_per_second: u64,
+pub fn build_mm_quote(
+    mango_program: &dyn MangoProgram,
     perp_market_caches: &[PerpMarketCache],
-    tpu_manager: TpuManager,
+    memo: Option<&MemoTagger>,
     mango_account_pk: Pubkey,
-    mango_account_signer: &Keypair,
-    blockhash: Arc<RwLock<Hash>>,
-    slot: &AtomicU64,
+    signers: &MarketMakerSigners,
+    recent_blockhash: Hash,
+    sent_slot: u64,
     prioritization_fee_proba: u8,
-) {
-    let mango_account_signer_pk = to_sp_pk(&mango_account_signer.pubkey());
-    // update quotes 2x per second
-    for _ in 0..quotes_per_second {
-        let prioritization_fee_by_market = generate_random_fees(
-            prioritization_fee_proba,
-            perp_market_caches.len(),
-            100,
-            1000,
-        );
-        let mut batch_to_send = Vec::with_capacity(perp_market_caches.len());
-        for (i, c) in perp_market_caches.iter().enumerate() {
-            let prioritization_fee = prioritization_fee_by_market[i];
-            let mut tx = create_ask_bid_transaction(
+    spoofing_levels: Option<u8>,
+    chaos: ChaosConfig,
+    inventory: &Inventory,
+    client_order_ids: &mut ClientOrderIds,
+    rng: &mut StdRng,
+) -> Vec<BuiltTransaction> {
+    let mango_account_signer_pk = to_sp_pk(&signers.owner.pubkey());
+    let mut built = vec![];
+    let prioritization_fee_by_market = generate_random_fees(
+        prioritization_fee_proba,
+        perp_market_caches.len(),
+        100,
+        1000,
+        rng,
+    );
+    for (i, c) in perp_market_caches.iter().enumerate() {
+        let prioritization_fee = prioritization_fee_by_market[i];
+        let chaos_kind = chaos.pick(rng);
+        // spoofing quotes are always post only
+        let order = spoofing_levels
+            .is_none()
+            .then(|| c.quote_config.quote_order(rng));
+        let wrong_owner_signers;
+        let signers = if chaos_kind == Some(ChaosKind::WrongOwner) {
+            wrong_owner_signers = chaos::wrong_owner_signers(signers);
+            &wrong_owner_signers
+        } else {
+            signers
+        };
+        let client_order_id = client_order_ids.next();
+        let mut tx = match spoofing_levels {
+            Some(levels) => create_spoofing_transaction(
+                mango_program,
                 c,
                 mango_account_pk,
-                mango_account_signer,
+                signers,
                 prioritization_fee,
-            );
+                levels,
+                client_order_id,
+                rng,
+            ),
+            None => create_ask_bid_transaction(
+                mango_program,
+                c,
+                mango_account_pk,
+                signers,
+                prioritization_fee,
+                inventory.position(&mango_account_pk, &c.perp_market_pk),
+                client_order_id,
+                order.unwrap_or_default(),
+                rng,
+            ),
+        };
+
+        if chaos_kind == Some(ChaosKind::Oversized) {
+            chaos::make_oversized(&mut tx);
+        }
+        if let Some(memo) = memo {
+            memo.tag(&mut tx);
+        }
+        let recent_blockhash = if chaos_kind == Some(ChaosKind::ExpiredBlockhash) {
+            Hash::new_unique()
+        } else {
+            recent_blockhash
+        };
+        tx.sign(&signers.signers(), recent_blockhash);
 
-            let recent_blockhash = *blockhash.read().await;
-            tx.sign(&[mango_account_signer], recent_blockhash);
-
-            let record = TransactionSendRecord {
-                signature: tx.signatures[0],
-                sent_at: Utc::now(),
-                sent_slot: slot.load(Ordering::Acquire),
-                market_maker: Some(mango_account_signer_pk),
-                market: Some(c.perp_market_pk),
-                priority_fees: prioritization_fee,
-                keeper_instruction: None,
+        let sent_at = clock::now();
+        let record = TransactionSendRecord {
+            signature: tx.signatures[0],
+            sent_at,
+            sent_at_us: sent_at.timestamp_micros(),
+            sent_slot,
+            market_maker: Some(mango_account_signer_pk),
+            market: Some(c.perp_market_pk),
+            priority_fees: prioritization_fee,
+            keeper_instruction: None,
+            tx_type: TransactionType::MarketMaker,
+            spoofing: spoofing_levels.is_some(),
+            phase: None,
+            blockhash_slot: None,
+            blockhash_age_slots: None,
+            last_valid_block_height: None,
+            // the duplicate is sent after the original transaction
+            chaos: chaos_kind.filter(|kind| *kind != ChaosKind::Duplicate),
+            send_delay_ms: None,
+            tx_size_bytes: 0,
+            num_instructions: 0,
+            order_type: order.map(|order| order.order_type),
+            reduce_only: order.map_or(false, |order| order.reduce_only),
+            client_order_id: Some(client_order_id),
+            route: None,
+        };
+        if chaos_kind == Some(ChaosKind::Duplicate) {
+            let duplicate = TransactionSendRecord {
+                chaos: chaos_kind,
+                ..record.clone()
             };
-            batch_to_send.push((tx, record));
+            built.push((tx.clone(), record));
+            built.push((tx, duplicate));
+        } else {
+            built.push((tx, record));
         }
+    }
+    built
+}
 
-        let tpu_manager = tpu_manager.clone();
-        task::spawn(async move {
-            if !tpu_manager.send_transaction_batch(&batch_to_send).await {
-                println!("sending failed on tpu client");
+// the batches which failed to send in a row are counted in send_failures
+async fn send_mm_batches(
+    tpu_manager: &TpuManager,
+    mut batch_to_send: Vec<BuiltTransaction>,
+    batch_size: usize,
+    send_failures: &Arc<AtomicU64>,
+    sending: &mut VecDeque<JoinHandle<()>>,
+) {
+    // the batches are moved to the sending tasks, the transactions are not copied
+    while !batch_to_send.is_empty() {
+        let rest = batch_to_send.split_off(batch_size.clamp(1, batch_to_send.len()));
+        let batch = std::mem::replace(&mut batch_to_send, rest);
+        // counted in the pending sends before the task is spawned, the shutdown waits for it
+        let queued = tpu_manager.queue_batch(batch);
+        let send_failures = send_failures.clone();
+        if sending.len() >= MAX_SENDING_BATCHES {
+            if let Some(oldest) = sending.pop_front() {
+                let _ = oldest.await;
+            }
+        }
+        sending.push_back(task::spawn(async move {
+            if queued.send().await {
+                send_failures.store(0, Ordering::Relaxed);
+            } else {
+                warn!("sending failed on tpu client");
+                send_failures.fetch_add(1, Ordering::Relaxed);
             }
+        }));
+    }
+}
+
+/// waits for the batches of a market maker still being sent, its task ends with its sends
+async fn wait_for_sends(sending: &mut VecDeque<JoinHandle<()>>) {
+    while let Some(batch) = sending.pop_front() {
+        let _ = batch.await;
+    }
+}
+
+// quotes built on the build pool with the blockhash they are signed with, with the builder
+// handed back
+type BuildJob = oneshot::Receiver<(QuoteBuilder, Vec<(Hash, Vec<BuiltTransaction>)>)>;
+
+/// What a market maker task needs to build its quotes away from the async runtime. It is moved
+/// to the build pool with each job and handed back with the built quotes
+struct QuoteBuilder {
+    mango_program: Arc<dyn MangoProgram>,
+    perp_market_cache: PerpMarketCache,
+    memo: Option<MemoTagger>,
+    mango_account_pk: Pubkey,
+    signers: MarketMakerSigners,
+    spoofing_levels: Option<u8>,
+    chaos: ChaosConfig,
+    inventory: Inventory,
+    client_order_ids: ClientOrderIds,
+    rng: StdRng,
+}
+
+impl QuoteBuilder {
+    fn build(
+        &mut self,
+        quotes: u64,
+        recent_blockhash: Hash,
+        sent_slot: u64,
+        prioritization_fee_proba: u8,
+    ) -> Vec<Vec<BuiltTransaction>> {
+        (0..quotes)
+            .map(|_| {
+                build_mm_quote(
+                    self.mango_program.as_ref(),
+                    std::slice::from_ref(&self.perp_market_cache),
+                    self.memo.as_ref(),
+                    self.mango_account_pk,
+                    &self.signers,
+                    recent_blockhash,
+                    sent_slot,
+                    prioritization_fee_proba,
+                    self.spoofing_levels,
+                    self.chaos,
+                    &self.inventory,
+                    &mut self.client_order_ids,
+                    &mut self.rng,
+                )
+            })
+            .collect()
+    }
+
+    // the builder comes back with the quotes once the job is done
+    fn spawn(
+        mut self,
+        pool: &rayon::ThreadPool,
+        quotes: u64,
+        recent_blockhash: Hash,
+        sent_slot: u64,
+        prioritization_fee_proba: u8,
+    ) -> BuildJob {
+        let (sx, rx) = oneshot::channel();
+        pool.spawn(move || {
+            let quotes = self
+                .build(
+                    quotes,
+                    recent_blockhash,
+                    sent_slot,
+                    prioritization_fee_proba,
+                )
+                .into_iter()
+                .map(|quote| (recent_blockhash, quote))
+                .collect();
+            let _ = sx.send((self, quotes));
         });
+        rx
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn start_market_making_threads(
-    account_keys_parsed: Vec<AccountKeys>,
-    perp_market_caches: Vec<PerpMarketCache>,
+// quotes per second of a market, the quotes of a second are spread over a longer interval when
+// throttled
+fn quote_rate(quote_config: &QuoteConfig, live_parameters: &LiveParameters) -> f64 {
+    let quotes_per_second = quote_config
+        .quotes_per_second
+        .unwrap_or_else(|| live_parameters.quotes_per_second());
+    quotes_per_second as f64 * 1000.0 / live_parameters.quote_interval_millis() as f64
+}
+
+/// A mango account quoting on a market, its orders have to be cancelled at the end of the run
+pub type QuotedMarket = (MarketMakerSigners, Pubkey, PerpMarketCache);
+
+/// What the market making tasks share, kept to start the market makers added during the run
+#[derive(Clone)]
+struct MarketMakingContext {
+    mango_program: Arc<dyn MangoProgram>,
     exit_signal: Arc<AtomicBool>,
     blockhash: Arc<RwLock<Hash>>,
     current_slot: Arc<AtomicU64>,
     tpu_manager: TpuManager,
-    duration: &Duration,
-    quotes_per_second: u64,
-    prioritization_fee_proba: u8,
+    live_parameters: Arc<LiveParameters>,
     number_of_markers_per_mm: u8,
-) -> Vec<JoinHandle<()>> {
-    let mut rng = rand::thread_rng();
-    account_keys_parsed
-        .iter()
-        .map(|account_keys| {
-            let exit_signal = exit_signal.clone();
-            let blockhash = blockhash.clone();
-            let current_slot = current_slot.clone();
-            let duration = *duration;
-            let perp_market_caches = perp_market_caches.clone();
-            let mango_account_pk =
-                Pubkey::from_str(account_keys.mango_account_pks[0].as_str()).unwrap();
-            let mango_account_signer =
-                Keypair::from_bytes(account_keys.secret_key.as_slice()).unwrap();
-            let tpu_manager = tpu_manager.clone();
-
-            info!(
-                "wallet: {:?} mango account: {:?}",
-                mango_account_signer.pubkey(),
-                mango_account_pk
-            );
-            let perp_market_caches = perp_market_caches
-                .choose_multiple(&mut rng, number_of_markers_per_mm as usize)
-                .cloned()
-                .collect_vec();
+    spoofing_levels: Option<u8>,
+    watchdog: Watchdog,
+    seed: Option<u64>,
+    chaos: ChaosConfig,
+    inventory: Inventory,
+    pacing: Pacing,
+    dedup_guard: DedupGuard,
+    // the client order ids name the quote instead of being read from the clock
+    encoded_client_order_ids: bool,
+    // resumes of the run before this attempt, encoded in the client order ids
+    attempt: u32,
+    // builds and signs the quotes one tick ahead of the sends
+    build_pool: Option<Arc<rayon::ThreadPool>>,
+    started_at: Instant,
+    duration: Duration,
+    // market makers numbered so far, the added ones are numbered after the ones of the file
+    nb_market_makers: Arc<AtomicUsize>,
+    failures: MarketMakerFailures,
+}
 
-            tokio::spawn(async move {
-                for _i in 0..duration.as_secs() {
-                    if exit_signal.load(Ordering::Relaxed) {
-                        break;
-                    }
+impl MarketMakingContext {
+    // with a seed the markets and the order flow of every market maker are reproducible
+    fn rng(&self, stream: u64) -> StdRng {
+        seeded_rng(self.seed, stream)
+    }
 
-                    let start = Instant::now();
+    // market makers added during the run only quote for the time left
+    fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.started_at.elapsed())
+    }
 
-                    // send market maker transactions
-                    send_mm_transactions(
-                        quotes_per_second,
-                        &perp_market_caches,
-                        tpu_manager.clone(),
-                        mango_account_pk,
-                        &mango_account_signer,
-                        blockhash.clone(),
-                        current_slot.as_ref(),
-                        prioritization_fee_proba,
-                    )
-                    .await;
-
-                    let elapsed_millis: u64 = start.elapsed().as_millis() as u64;
-                    if elapsed_millis < 1000 {
-                        tokio::time::sleep(Duration::from_millis(1000 - elapsed_millis)).await;
-                    } else {
-                        warn!(
-                            "time taken to send transactions is greater than 1000ms {}",
-                            elapsed_millis
+    /// Starts one task per market quoted by the mango account, they stop at the end of the run or
+    /// once the market maker is retired. A task which panics or runs out of its send error budget
+    /// is restarted with a backoff, and abandoned once it used all its restarts
+    fn start_market_maker(
+        &self,
+        market_maker_index: usize,
+        // position of the mango account among the accounts of the file, over all the shards
+        account_index: usize,
+        signers: MarketMakerSigners,
+        mango_account_pk: Pubkey,
+        perp_market_caches: Vec<PerpMarketCache>,
+        retired: Arc<AtomicBool>,
+    ) -> Vec<JoinHandle<()>> {
+        info!(
+            "wallet: {:?} fee payer: {:?} mango account: {:?}",
+            signers.owner.pubkey(),
+            signers.payer(),
+            mango_account_pk
+        );
+        // every market is quoted on its own timer, at its own rate
+        perp_market_caches
+            .into_iter()
+            .enumerate()
+            .map(|(market_number, perp_market_cache)| {
+                let context = self.clone();
+                let signers = signers.clone();
+                let retired = retired.clone();
+                let thread_name = format!(
+                    "market-maker-{mango_account_pk}-{}",
+                    perp_market_cache.perp_market_pk
+                );
+                let rng_stream = (market_maker_index * self.number_of_markers_per_mm as usize
+                    + market_number) as u64
+                    + 1;
+
+                tokio::spawn(async move {
+                    let mut restart = 0u32;
+                    loop {
+                        // a restarted market maker starts over with a new builder, with a seed
+                        // it sends the same order flow again
+                        let client_order_ids = if context.encoded_client_order_ids {
+                            ClientOrderIds::encoded(ClientOrderIdKey {
+                                market_maker_index: account_index as u32,
+                                market_index: market_number as u8,
+                                attempt: context.attempt as u8,
+                                restart: restart as u8,
+                                tick: 0,
+                                side: Side::Bid,
+                            })
+                        } else {
+                            ClientOrderIds::new(context.dedup_guard.clone())
+                        };
+                        let quoting = quote_market(
+                            context.clone(),
+                            market_maker_index,
+                            &thread_name,
+                            signers.clone(),
+                            mango_account_pk,
+                            perp_market_cache.clone(),
+                            client_order_ids,
+                            context.rng(rng_stream),
+                            retired.clone(),
                         );
+                        let error = match AssertUnwindSafe(quoting).catch_unwind().await {
+                            Ok(Ok(())) => break,
+                            Ok(Err(e)) => e.to_string(),
+                            Err(panic) => panic_message(panic),
+                        };
+                        if context.exit_signal.load(Ordering::Relaxed)
+                            || retired.load(Ordering::Relaxed)
+                            || context.remaining().is_zero()
+                        {
+                            break;
+                        }
+                        let Some(backoff) = context.failures.record_failure(&thread_name, error)
+                        else {
+                            break;
+                        };
+                        tokio::time::sleep(backoff).await;
+                        restart += 1;
                     }
-                }
+                    context.watchdog.unregister(&thread_name);
+                })
             })
+            .collect_vec()
+    }
+}
+
+// quotes the market until the end of the run or until the market maker is retired, an error
+// when the sends keep failing
+#[allow(clippy::too_many_arguments)]
+async fn quote_market(
+    context: MarketMakingContext,
+    market_maker_index: usize,
+    thread_name: &str,
+    signers: MarketMakerSigners,
+    mango_account_pk: Pubkey,
+    perp_market_cache: PerpMarketCache,
+    client_order_ids: ClientOrderIds,
+    market_maker_rng: StdRng,
+    retired: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    // market makers added or restarted during the run only quote for the time left
+    let duration = context.remaining();
+    let MarketMakingContext {
+        mango_program,
+        exit_signal,
+        blockhash,
+        current_slot,
+        tpu_manager,
+        live_parameters,
+        spoofing_levels,
+        watchdog,
+        chaos,
+        inventory,
+        pacing,
+        build_pool,
+        nb_market_makers,
+        ..
+    } = context;
+    let mut builder = Some(QuoteBuilder {
+        mango_program,
+        perp_market_cache: perp_market_cache.clone(),
+        memo: tpu_manager.memo().cloned(),
+        mango_account_pk,
+        signers,
+        spoofing_levels,
+        chaos,
+        inventory,
+        client_order_ids,
+        rng: market_maker_rng,
+    });
+    let mut building: Option<BuildJob> = None;
+    let mut prebuilt = VecDeque::new();
+    let mut rate_limiter = RateLimiter::new(
+        quote_rate(&perp_market_cache.quote_config, &live_parameters),
+        pacing,
+    );
+    let send_failures = Arc::new(AtomicU64::new(0));
+    let mut sending = VecDeque::new();
+    let started_at = Instant::now();
+    while started_at.elapsed() < duration {
+        watchdog.heartbeat(thread_name);
+        if exit_signal.load(Ordering::Relaxed) || retired.load(Ordering::Relaxed) {
+            break;
+        }
+        let failed_batches = send_failures.load(Ordering::Relaxed);
+        if failed_batches >= SEND_ERROR_BUDGET {
+            wait_for_sends(&mut sending).await;
+            anyhow::bail!("{failed_batches} batches in a row failed to send");
+        }
+
+        if !live_parameters
+            .is_market_maker_active(market_maker_index, nb_market_makers.load(Ordering::Relaxed))
+        {
+            // the quotes built before the pause would be sent with a stale blockhash
+            if let Some(job) = building.take() {
+                builder = Some(job.await.expect("build job should complete").0);
+            }
+            prebuilt.clear();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            continue;
+        }
+
+        // the rate follows the control server, the scenario and the throttle
+        let quote_config = perp_market_cache.quote_config;
+        rate_limiter.set_rate(
+            quote_rate(&quote_config, &live_parameters),
+            pacing.burst_size,
+        );
+        let quotes = rate_limiter.acquire().await;
+        if quotes == 0 || exit_signal.load(Ordering::Relaxed) || retired.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        // send market maker transactions
+        let start = Instant::now();
+        let recent_blockhash = *blockhash.read().await;
+        let prioritization_fee_proba = live_parameters.priority_fees_proba();
+        let batch_to_send = match &build_pool {
+            None => builder
+                .as_mut()
+                .expect("builder is only away without a build pool")
+                .build(
+                    quotes,
+                    recent_blockhash,
+                    current_slot.load(Ordering::Acquire),
+                    prioritization_fee_proba,
+                )
+                .concat(),
+            Some(pool) => {
+                // the quotes built during the previous tick
+                let mut ready = match building.take() {
+                    Some(job) => job.await.expect("build job should complete"),
+                    None => (builder.take().expect("builder is home"), vec![]),
+                };
+                prebuilt.extend(ready.1);
+                // quotes built before the blockhash changed, after a pause of the rate, are built
+                // again instead of being sent with a stale blockhash
+                prebuilt.retain(|(built_with, _)| *built_with == recent_blockhash);
+                if (prebuilt.len() as u64) < quotes {
+                    let missing = quotes - prebuilt.len() as u64;
+                    ready = ready
+                        .0
+                        .spawn(
+                            pool,
+                            missing,
+                            recent_blockhash,
+                            current_slot.load(Ordering::Acquire),
+                            prioritization_fee_proba,
+                        )
+                        .await
+                        .expect("build job should complete");
+                    prebuilt.extend(ready.1);
+                }
+                let mut batch_to_send = prebuilt
+                    .drain(..quotes as usize)
+                    .flat_map(|(_, quote)| quote)
+                    .collect_vec();
+                // the records were made when the quotes were built
+                let sent_slot = current_slot.load(Ordering::Acquire);
+                for (_, record) in batch_to_send.iter_mut() {
+                    record.sent_at = clock::now();
+                    record.sent_at_us = record.sent_at.timestamp_micros();
+                    record.sent_slot = sent_slot;
+                }
+                // the next tick is expected to need as many quotes as this one
+                building = Some(ready.0.spawn(
+                    pool,
+                    quotes.saturating_sub(prebuilt.len() as u64),
+                    recent_blockhash,
+                    sent_slot,
+                    prioritization_fee_proba,
+                ));
+                batch_to_send
+            }
+        };
+        // by default a batch holds a quote on every market
+        send_mm_batches(
+            &tpu_manager,
+            batch_to_send,
+            quote_config.batch_size.unwrap_or(1),
+            &send_failures,
+            &mut sending,
+        )
+        .await;
+
+        let elapsed_millis: u64 = start.elapsed().as_millis() as u64;
+        if elapsed_millis > 1000 {
+            warn!(
+                "time taken to send transactions is greater than 1000ms {}",
+                elapsed_millis
+            );
+        }
+    }
+    wait_for_sends(&mut sending).await;
+    Ok(())
+}
+
+struct RunningMarketMaker {
+    retired: Arc<AtomicBool>,
+    quoted_markets: Vec<QuotedMarket>,
+}
+
+/// Market makers changed by a reload of the accounts file
+pub struct RosterUpdate {
+    pub tasks: Vec<JoinHandle<()>>,
+    pub started: usize,
+    // the orders of the retired market makers are left on the books until cancelled
+    pub retired: Vec<QuotedMarket>,
+    pub nb_retired: usize,
+}
+
+/// The market makers of the shard which are quoting, by mango account, so that the accounts
+/// watcher can start the accounts added to the file and retire the removed ones
+pub struct MarketMakerRoster {
+    context: MarketMakingContext,
+    perp_market_caches: Vec<PerpMarketCache>,
+    accounts_per_signer: usize,
+    shard: Option<Shard>,
+    // the markets of the added accounts are chosen after the ones of the file
+    rng: StdRng,
+    running: HashMap<Pubkey, RunningMarketMaker>,
+}
+
+impl MarketMakerRoster {
+    pub fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    /// true once the duration of the run is over
+    pub fn has_ended(&self) -> bool {
+        self.context.remaining().is_zero()
+    }
+
+    /// the markets quoted by the running market makers
+    pub fn quoted_markets(&self) -> Vec<QuotedMarket> {
+        self.running
+            .values()
+            .flat_map(|mm| mm.quoted_markets.clone())
+            .collect()
+    }
+
+    /// Starts the market makers of the mango accounts of the shard which are not running yet and
+    /// retires the running ones which are no longer in the accounts. A bad entry is skipped
+    /// instead of stopping the run
+    pub fn update(&mut self, account_keys: &[AccountKeys]) -> RosterUpdate {
+        // in the order of the file, so that a seed also gives the markets of the added accounts
+        let mut wanted: Vec<(Pubkey, &AccountKeys, usize)> = vec![];
+        let mut account_index = 0;
+        for (position, keys) in account_keys.iter().enumerate() {
+            let in_shard = self.shard.map_or(true, |shard| shard.contains(position));
+            for mango_account in keys.mango_account_pks.iter().take(self.accounts_per_signer) {
+                account_index += 1;
+                if !in_shard {
+                    continue;
+                }
+                match Pubkey::from_str(mango_account) {
+                    Ok(mango_account_pk) => {
+                        wanted.push((mango_account_pk, keys, account_index - 1))
+                    }
+                    Err(_) => warn!("skipping the invalid mango account {mango_account}"),
+                }
+            }
+        }
+
+        let mut update = RosterUpdate {
+            tasks: vec![],
+            started: 0,
+            retired: vec![],
+            nb_retired: 0,
+        };
+        let retired_pks = self
+            .running
+            .keys()
+            .filter(|pk| !wanted.iter().any(|(wanted_pk, ..)| wanted_pk == *pk))
+            .copied()
+            .collect_vec();
+        for mango_account_pk in retired_pks {
+            let market_maker = self.running.remove(&mango_account_pk).unwrap();
+            info!("retiring the market maker of mango account {mango_account_pk}");
+            market_maker.retired.store(true, Ordering::Relaxed);
+            update.retired.extend(market_maker.quoted_markets);
+            update.nb_retired += 1;
+        }
+
+        // the signer is shared by all the mango accounts it owns
+        let mut signers_by_owner: HashMap<String, MarketMakerSigners> = HashMap::new();
+        for (mango_account_pk, keys, account_index) in wanted {
+            if self.running.contains_key(&mango_account_pk) {
+                continue;
+            }
+            let signers = match signers_by_owner.get(&keys.public_key) {
+                Some(signers) => signers.clone(),
+                None => match MarketMakerSigners::load(keys) {
+                    Ok(signers) => {
+                        signers_by_owner.insert(keys.public_key.clone(), signers.clone());
+                        signers
+                    }
+                    Err(e) => {
+                        warn!("skipping mango account {mango_account_pk}, its signer does not load : {e}");
+                        continue;
+                    }
+                },
+            };
+            let perp_market_caches = choose_markets(
+                &self.perp_market_caches,
+                self.context.number_of_markers_per_mm,
+                &mut self.rng,
+            );
+            let market_maker_index = self
+                .context
+                .nb_market_makers
+                .fetch_add(1, Ordering::Relaxed);
+            let retired = Arc::new(AtomicBool::new(false));
+            let quoted_markets = perp_market_caches
+                .iter()
+                .map(|c| (signers.clone(), mango_account_pk, c.clone()))
+                .collect_vec();
+            update.tasks.extend(self.context.start_market_maker(
+                market_maker_index,
+                account_index,
+                signers,
+                mango_account_pk,
+                perp_market_caches,
+                retired.clone(),
+            ));
+            self.running.insert(
+                mango_account_pk,
+                RunningMarketMaker {
+                    retired,
+                    quoted_markets,
+                },
+            );
+            update.started += 1;
+        }
+        update
+    }
+}
+
+/// Where the weights of the markets come from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarketWeights {
+    // weight of each market in the group config, uniform when not set
+    Config,
+    // notional open interest of each market on-chain
+    OpenInterest,
+}
+
+impl FromStr for MarketWeights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "config" => Ok(MarketWeights::Config),
+            "open-interest" => Ok(MarketWeights::OpenInterest),
+            _ => Err(format!("unknown market weights {s}")),
+        }
+    }
+}
+
+// markets quoted by a market maker, drawn in proportion to their weights. Uniform weights keep
+// the uniform draw so that a seed gives the same markets as before the weights
+fn choose_markets(
+    perp_market_caches: &[PerpMarketCache],
+    number_of_markets: u8,
+    rng: &mut StdRng,
+) -> Vec<PerpMarketCache> {
+    let amount = number_of_markets as usize;
+    if perp_market_caches
+        .iter()
+        .map(|c| c.quote_config.weight.to_bits())
+        .all_equal()
+    {
+        return perp_market_caches
+            .choose_multiple(rng, amount)
+            .cloned()
+            .collect_vec();
+    }
+    perp_market_caches
+        .choose_multiple_weighted(rng, amount, |c| c.quote_config.weight)
+        .expect("market weights should be positive")
+        .cloned()
+        .collect_vec()
+}
+
+/// Starts one task per market quoted by each mango account and returns them with the markets
+/// each account quotes on, and the roster of the running market makers. With a shard, the
+/// markets are still chosen for every account of the file so that all the instances of a fleet
+/// agree on them, but only the accounts of the shard are quoted
+#[allow(clippy::too_many_arguments)]
+pub fn start_market_making_threads(
+    mango_program: Arc<dyn MangoProgram>,
+    account_keys_parsed: &[AccountKeys],
+    shard: Option<Shard>,
+    perp_market_caches: Vec<PerpMarketCache>,
+    exit_signal: Arc<AtomicBool>,
+    blockhash: Arc<RwLock<Hash>>,
+    current_slot: Arc<AtomicU64>,
+    tpu_manager: TpuManager,
+    duration: &Duration,
+    live_parameters: Arc<LiveParameters>,
+    number_of_markers_per_mm: u8,
+    spoofing_levels: Option<u8>,
+    accounts_per_signer: usize,
+    watchdog: Watchdog,
+    seed: Option<u64>,
+    chaos: ChaosConfig,
+    inventory: Inventory,
+    pacing: Pacing,
+    dedup_guard: DedupGuard,
+    encoded_client_order_ids: bool,
+    attempt: u32,
+    build_pool: Option<Arc<rayon::ThreadPool>>,
+    failures: MarketMakerFailures,
+) -> (Vec<JoinHandle<()>>, Vec<QuotedMarket>, MarketMakerRoster) {
+    let market_makers = account_keys_parsed
+        .iter()
+        .enumerate()
+        .flat_map(|(position, account_keys)| {
+            // the signer is shared by all the mango accounts it owns, the keys of the other
+            // shards are not loaded
+            let signers = shard
+                .map_or(true, |shard| shard.contains(position))
+                .then(|| {
+                    MarketMakerSigners::load(account_keys).expect("market maker signer should load")
+                });
+            account_keys
+                .mango_accounts(accounts_per_signer)
+                .into_iter()
+                .map(move |mango_account_pk| (signers.clone(), mango_account_pk))
         })
-        .collect()
+        .collect_vec();
+    let context = MarketMakingContext {
+        mango_program,
+        exit_signal,
+        blockhash,
+        current_slot,
+        tpu_manager,
+        live_parameters,
+        number_of_markers_per_mm,
+        spoofing_levels,
+        watchdog,
+        // the instances of a fleet share a default seed, otherwise their market choices would
+        // differ
+        seed: seed.or(shard.map(|_| 0)),
+        chaos,
+        inventory,
+        pacing,
+        dedup_guard,
+        encoded_client_order_ids,
+        attempt,
+        build_pool,
+        started_at: Instant::now(),
+        duration: *duration,
+        nb_market_makers: Arc::new(AtomicUsize::new(market_makers.len())),
+        failures,
+    };
+    let mut rng = context.rng(0);
+    let mut tasks = vec![];
+    let mut quoted_markets = vec![];
+    let mut running = HashMap::new();
+    for (market_maker_index, (signers, mango_account_pk)) in market_makers.into_iter().enumerate() {
+        let perp_market_caches =
+            choose_markets(&perp_market_caches, number_of_markers_per_mm, &mut rng);
+        let Some(signers) = signers else {
+            continue;
+        };
+        let retired = Arc::new(AtomicBool::new(false));
+        let account_markets = perp_market_caches
+            .iter()
+            .map(|c| (signers.clone(), mango_account_pk, c.clone()))
+            .collect_vec();
+        quoted_markets.extend(account_markets.clone());
+        // every account of the file is numbered, the index is global to the fleet
+        tasks.extend(context.start_market_maker(
+            market_maker_index,
+            market_maker_index,
+            signers,
+            mango_account_pk,
+            perp_market_caches,
+            retired.clone(),
+        ));
+        running.insert(
+            mango_account_pk,
+            RunningMarketMaker {
+                retired,
+                quoted_markets: account_markets,
+            },
+        );
+    }
+    let roster = MarketMakerRoster {
+        context,
+        perp_market_caches,
+        accounts_per_signer,
+        shard,
+        rng,
+        running,
+    };
+    (tasks, quoted_markets, roster)
 }
 
-fn create_cancel_all_orders(
+// micro lamports per compute unit of the cancellations
+pub(crate) const CANCEL_ALL_PRIORITY_FEES: u64 = 1000;
+
+pub(crate) fn create_cancel_all_orders(
+    mango_program: &dyn MangoProgram,
     perp_market: &PerpMarketCache,
     mango_account_pk: Pubkey,
-    mango_account_signer: &Keypair,
+    signers: &MarketMakerSigners,
 ) -> Transaction {
-    let mango_account_signer_pk = to_sp_pk(&mango_account_signer.pubkey());
+    let mango_account_signer_pk = to_sp_pk(&signers.owner.pubkey());
 
     let cb_instruction = compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(1000000);
-    let pf_instruction = compute_budget::ComputeBudgetInstruction::set_compute_unit_price(1000);
-
-    let instruction: Instruction = to_sdk_instruction(
-        cancel_all_perp_orders(
-            &perp_market.mango_program_pk,
-            &perp_market.mango_group_pk,
-            &mango_account_pk,
-            &mango_account_signer_pk,
-            &perp_market.perp_market_pk,
-            &perp_market.bids,
-            &perp_market.asks,
-            255,
-        )
-        .unwrap(),
+    let pf_instruction =
+        compute_budget::ComputeBudgetInstruction::set_compute_unit_price(CANCEL_ALL_PRIORITY_FEES);
+
+    let instruction: Instruction = mango_program.cancel_all_perp_orders(
+        perp_market,
+        &mango_account_pk,
+        &mango_account_signer_pk,
+        255,
     );
 
     Transaction::new_unsigned(Message::new(
         &[cb_instruction, pf_instruction, instruction],
-        Some(&mango_account_signer.pubkey()),
+        Some(&signers.payer()),
     ))
 }
 
 pub async fn clean_market_makers(
+    mango_program: Arc<dyn MangoProgram>,
     rpc_client: Arc<RpcClient>,
     account_keys_parsed: &[AccountKeys],
     perp_market_caches: &Vec<PerpMarketCache>,
     blockhash: Arc<RwLock<Hash>>,
+    accounts_per_signer: usize,
 ) {
     info!("Cleaning previous transactions by market makers");
+    let quoted_markets = account_keys_parsed
+        .iter()
+        .flat_map(|market_maker| {
+            let signers =
+                MarketMakerSigners::load(market_maker).expect("market maker signer should load");
+            market_maker
+                .mango_accounts(accounts_per_signer)
+                .into_iter()
+                .cartesian_product(perp_market_caches)
+                .map(move |(mango_account_pk, perp_market)| {
+                    (signers.clone(), mango_account_pk, perp_market.clone())
+                })
+        })
+        .collect_vec();
+    cancel_all_orders(mango_program, rpc_client, quoted_markets, blockhash).await;
+    info!("finished cleaning market makers");
+}
 
-    for account_keys_parsed in account_keys_parsed.chunks(10) {
+/// Cancels the orders of every mango account on its markets and waits for the confirmations
+pub async fn cancel_all_orders(
+    mango_program: Arc<dyn MangoProgram>,
+    rpc_client: Arc<RpcClient>,
+    quoted_markets: Vec<QuotedMarket>,
+    blockhash: Arc<RwLock<Hash>>,
+) {
+    for quoted_markets in quoted_markets.chunks(50) {
         let mut tasks = vec![];
-        for market_maker in account_keys_parsed {
-            let mango_account_pk =
-                Pubkey::from_str(market_maker.mango_account_pks[0].as_str()).unwrap();
-
-            for perp_market in perp_market_caches {
-                let market_maker = market_maker.clone();
-                let perp_market = perp_market.clone();
-                let rpc_client = rpc_client.clone();
-                let blockhash = blockhash.clone();
-
-                let task = tokio::spawn(async move {
-                    let mango_account_signer =
-                        Keypair::from_bytes(market_maker.secret_key.as_slice()).unwrap();
-
-                    for _ in 0..10 {
-                        let mut tx = create_cancel_all_orders(
-                            &perp_market,
-                            mango_account_pk,
-                            &mango_account_signer,
-                        );
+        for (signers, mango_account_pk, perp_market) in quoted_markets {
+            let signers = signers.clone();
+            let mango_account_pk = *mango_account_pk;
+            let perp_market = perp_market.clone();
+            let rpc_client = rpc_client.clone();
+            let blockhash = blockhash.clone();
+            let mango_program = mango_program.clone();
 
-                        let recent_blockhash = *blockhash.read().await;
-                        tx.sign(&[&mango_account_signer], recent_blockhash);
-                        let sig = tx.signatures[0];
-                        // send and confirm the transaction with an RPC
-                        if let Ok(res) = tokio::time::timeout(
-                            Duration::from_secs(10),
-                            rpc_client.send_and_confirm_transaction(&tx),
-                        )
-                        .await
-                        {
-                            match res {
-                                Ok(_) => break,
-                                Err(e) => info!("Error occured while doing cancel all for ma : {}, sig : {} perp market : {} error : {}", mango_account_pk, sig, perp_market.perp_market_pk, e),
-                            }
+            let task = tokio::spawn(async move {
+                for _ in 0..10 {
+                    let mut tx = create_cancel_all_orders(
+                        mango_program.as_ref(),
+                        &perp_market,
+                        mango_account_pk,
+                        &signers,
+                    );
+
+                    let recent_blockhash = *blockhash.read().await;
+                    tx.sign(&signers.signers(), recent_blockhash);
+                    let sig = tx.signatures[0];
+                    // send and confirm the transaction with an RPC
+                    if let Ok(res) = tokio::time::timeout(
+                        Duration::from_secs(10),
+                        rpc_client.send_and_confirm_transaction(&tx),
+                    )
+                    .await
+                    {
+                        match res {
+                            Ok(_) => break,
+                            Err(e) => info!("Error occured while doing cancel all for ma : {}, sig : {} perp market : {} error : {}", mango_account_pk, sig, perp_market.perp_market_pk, e),
                         }
                     }
-                });
-                tasks.push(task);
-            }
+                }
+            });
+            tasks.push(task);
         }
 
         futures::future::join_all(tasks).await;
     }
-    info!("finished cleaning market makers");
 }
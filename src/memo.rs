@@ -0,0 +1,75 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, transaction::Transaction};
+
+use crate::helpers::append_instruction_without_accounts;
+
+const MEMO_PROGRAM_ID: &str = "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr";
+
+/// Memos carrying the run id and a sequence number unique in the run, so that the transactions of
+/// a run can be found in the blocks without the signature csv
+#[derive(Clone)]
+pub struct MemoTagger {
+    run_id: Arc<String>,
+    sequence: Arc<AtomicU64>,
+}
+
+impl MemoTagger {
+    pub fn new(run_id: String) -> Self {
+        Self {
+            run_id: Arc::new(run_id),
+            sequence: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn program_id() -> Pubkey {
+        Pubkey::from_str(MEMO_PROGRAM_ID).unwrap()
+    }
+
+    fn next_memo(&self) -> Vec<u8> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        format!("mango-simulation:{}:{sequence}", self.run_id).into_bytes()
+    }
+
+    /// memo instruction with the next sequence number, it requires no signer
+    pub fn instruction(&self) -> Instruction {
+        Instruction {
+            program_id: Self::program_id(),
+            accounts: vec![],
+            data: self.next_memo(),
+        }
+    }
+
+    /// appends the memo instruction to an unsigned transaction
+    pub fn tag(&self, tx: &mut Transaction) {
+        append_instruction_without_accounts(tx, &Self::program_id(), self.next_memo());
+    }
+
+    /// number of memos handed out
+    pub fn count(&self) -> u64 {
+        self.sequence.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memos_carry_the_run_id_and_a_sequence() {
+        let memo = MemoTagger::new("run-1".to_string());
+        let first = memo.instruction();
+        assert_eq!(first.program_id, MemoTagger::program_id());
+        assert!(first.accounts.is_empty());
+        assert_eq!(first.data, b"mango-simulation:run-1:0");
+        // the clones share the sequence
+        assert_eq!(memo.clone().instruction().data, b"mango-simulation:run-1:1");
+        assert_eq!(memo.count(), 2);
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use fixed::types::I80F48;
+use log::{info, warn};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::Hash, message::Message, signature::Keypair, signer::Signer, transaction::Transaction,
+};
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::{
+    helpers::{to_price_quote_lots, to_sdk_instruction, to_sp_pk},
+    mango::{base_token, quote_token, GroupConfig},
+    states::PerpMarketCache,
+};
+
+/// Random walk of the stub oracle prices, used on localnet where there is no pyth price
+#[derive(Clone, Copy, Debug)]
+pub struct OracleFeederConfig {
+    // price in quote tokens per base token the walk starts from
+    pub start_price: f64,
+    // maximum move of the price at each step
+    pub volatility_bps: u64,
+    pub interval: Duration,
+}
+
+impl Default for OracleFeederConfig {
+    fn default() -> Self {
+        Self {
+            start_price: 100.0,
+            volatility_bps: 20,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl OracleFeederConfig {
+    fn next_price(&self, price: f64) -> f64 {
+        let max_move = self.volatility_bps as f64 / 10_000.0;
+        let step = (rand::random::<f64>() * 2.0 - 1.0) * max_move;
+        (price * (1.0 + step)).max(f64::EPSILON)
+    }
+}
+
+/// Pushes a new price to the stub oracle of every perp market at each interval and moves the
+/// price the market makers quote around, the admin of the group has to sign the price updates
+pub fn start_oracle_feeders(
+    exit_signal: Arc<AtomicBool>,
+    rpc_client: Arc<RpcClient>,
+    mango_group_config: &GroupConfig,
+    perp_market_caches: &[PerpMarketCache],
+    blockhash: Arc<RwLock<Hash>>,
+    admin: &Keypair,
+    config: OracleFeederConfig,
+) -> Vec<JoinHandle<()>> {
+    let admin = Arc::new(Keypair::from_bytes(&admin.to_bytes()).unwrap());
+    perp_market_caches
+        .iter()
+        .enumerate()
+        .map(|(market_index, cache)| {
+            let exit_signal = exit_signal.clone();
+            let rpc_client = rpc_client.clone();
+            let base_decimals = base_token(
+                mango_group_config,
+                &mango_group_config.perp_markets[market_index],
+            )
+            .decimals;
+            let quote_decimals = quote_token(mango_group_config).decimals;
+            let cache = cache.clone();
+            let blockhash = blockhash.clone();
+            let admin = admin.clone();
+            info!(
+                "feeding oracle {} of perp market {} starting at {}",
+                cache.price_oracle, cache.perp_market_pk, config.start_price
+            );
+            tokio::spawn(async move {
+                let mut price = config.start_price;
+                while !exit_signal.load(Ordering::Relaxed) {
+                    let oracle_price = I80F48::from_num(price);
+                    let ix = mango::instruction::set_oracle(
+                        &cache.mango_program_pk,
+                        &cache.mango_group_pk,
+                        &cache.price_oracle,
+                        &to_sp_pk(&admin.pubkey()),
+                        oracle_price,
+                    )
+                    .unwrap();
+                    let tx = Transaction::new(
+                        &[admin.as_ref()],
+                        Message::new(&[to_sdk_instruction(ix)], Some(&admin.pubkey())),
+                        *blockhash.read().await,
+                    );
+                    match rpc_client.send_transaction(&tx).await {
+                        Ok(_) => {
+                            let price_quote_lots = to_price_quote_lots(
+                                oracle_price,
+                                base_decimals,
+                                quote_decimals,
+                                &cache.perp_market,
+                            );
+                            cache
+                                .quote_price
+                                .store(price_quote_lots.max(1), Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            warn!("could not set oracle {} price : {e}", cache.price_oracle)
+                        }
+                    }
+                    tokio::time::sleep(config.interval).await;
+                    price = config.next_price(price);
+                }
+            })
+        })
+        .collect()
+}
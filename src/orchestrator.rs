@@ -0,0 +1,1440 @@
+use std::{
+    net::SocketAddr,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use solana_client::nonblocking::rpc_client::RpcClient as NbRpcClient;
+use solana_lite_rpc_core::{
+    block_store::BlockStore,
+    notifications::NotificationMsg,
+    quic_connection_utils::QuicConnectionParameters,
+    tx_store::{empty_tx_store, TxStore},
+};
+use solana_lite_rpc_services::{
+    block_listenser::BlockListener,
+    tpu_utils::tpu_service::{TpuService, TpuServiceConfig},
+    transaction_replayer::TransactionReplayer,
+    transaction_service::{TransactionService, TransactionServiceBuilder},
+    tx_sender::TxSender,
+};
+use solana_metrics::datapoint_info;
+use solana_program::{clock::DEFAULT_MS_PER_SLOT, pubkey::Pubkey};
+use solana_sdk::{commitment_config::CommitmentConfig, signer::keypair::Keypair};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedSender},
+        RwLock,
+    },
+    task::JoinHandle,
+};
+
+use crate::{
+    accounts_watcher::start_accounts_watcher,
+    alerts::start_alerts,
+    baseline::observe_baseline,
+    book_recorder::start_book_recorder,
+    book_validation::start_book_validation,
+    cache_health::ensure_fresh_cache,
+    cancel_storm::start_cancel_storms,
+    cli,
+    client_order_ids::DedupGuard,
+    clock_skew::ClockSkew,
+    compression::compressed_path,
+    compute_units::estimate_compute_unit_limits,
+    config_loader::load_config,
+    confirmation_strategies::{
+        confirmation_by_lite_rpc_notification_stream, confirmations_by_blocks,
+        confirmations_by_signature_statuses, ConfirmationSource,
+    },
+    control::{start_control_server, ControlState},
+    corpus::CorpusWriter,
+    crank::{self, KeeperConfig},
+    feasibility::FeasibilityReport,
+    funding::start_funding_monitor,
+    health::{start_health_server, Health},
+    helpers::{
+        apply_quote_config, get_latest_blockhash, get_mango_market_perps_cache,
+        start_blockhash_polling_service, to_sdk_pk, weigh_by_open_interest,
+    },
+    impairment::{ImpairedSender, ImpairmentCounters},
+    inventory::{start_inventory_tracking, Inventory},
+    keeper::start_keepers,
+    lag_throttle::start_lag_throttle,
+    mango::{discover_group_config, quote_token, AccountKeys, MangoConfig},
+    mango_program::{mango_program, ProgramVersion},
+    market_maker_failures::MarketMakerFailures,
+    market_markers::{
+        cancel_all_orders, clean_market_makers, start_market_making_threads, MarketWeights,
+    },
+    memo::MemoTagger,
+    oracle::start_oracle_feeders,
+    partitioned_output::{manifest_path, OutputPartitioning},
+    periphery::start_periphery_traffic,
+    preconnect::start_leader_preconnect,
+    quic_stats::{start_quic_stats_monitor, QuicStats, QUIC_STATS_INTERVAL},
+    result_writer::{
+        initialize_result_writers, start_send_errors_writer, ResultWriterConfig, RowTags,
+    },
+    results_stream::start_results_stream,
+    rpc_cross_check::start_rpc_cross_check,
+    run_manifest::{new_run_id, RunManifest},
+    run_state::{check_no_previous_outputs, start_run_state_writer, RunState},
+    scenario::start_scenario,
+    shutdown::Shutdown,
+    slot_coverage::start_slot_coverage_writer,
+    states::{BlockhashSlots, LiveParameters, PerpMarketCache},
+    stats::MangoSimulationStats,
+    storage,
+    summary::{BenchSummary, RunParameters},
+    timeseries::start_timeseries_writer,
+    top_up::start_top_up_monitor,
+    tpu_manager::{RelayerRoute, TpuManager},
+    transaction_sender::{
+        quic_connection_cache, FixedTpuSender, JitoSender, MockSender, QuicSender, RelayerSender,
+        RpcSender, SenderKind, TransactionSender,
+    },
+    validation::dry_run_keepers_and_crank,
+    warmup::{check_warmup_probes, send_warmup_probes},
+    watchdog::Watchdog,
+    ws_failover::start_websocket_failover,
+};
+
+pub const METRICS_NAME: &str = "mango-bencher";
+
+/// Starts the lite-rpc transaction service which sends the transactions to the leaders over QUIC
+pub async fn configure_transaction_service(
+    rpc_client: Arc<NbRpcClient>,
+    identity: Keypair,
+    block_store: BlockStore,
+    tx_store: TxStore,
+    notifier: Option<UnboundedSender<NotificationMsg>>,
+    quic_connection_pool_size: usize,
+) -> (TransactionService, JoinHandle<anyhow::Result<()>>) {
+    let slot = rpc_client.get_slot().await.expect("GetSlot should work");
+    let tpu_config = TpuServiceConfig {
+        fanout_slots: 12,
+        number_of_leaders_to_cache: 1024,
+        clusterinfo_refresh_time: Duration::from_secs(60 * 60),
+        leader_schedule_update_frequency: Duration::from_secs(10),
+        maximum_transaction_in_queue: 200_000,
+        maximum_number_of_errors: 10,
+        quic_connection_params: QuicConnectionParameters {
+            connection_timeout: Duration::from_secs(1),
+            connection_retry_count: 10,
+            finalize_timeout: Duration::from_millis(200),
+            max_number_of_connections: quic_connection_pool_size,
+            unistream_timeout: Duration::from_millis(500),
+            write_timeout: Duration::from_secs(1),
+            number_of_transactions_per_unistream: 10,
+        },
+    };
+
+    let tpu_service = TpuService::new(
+        tpu_config,
+        Arc::new(identity),
+        slot,
+        rpc_client.clone(),
+        tx_store.clone(),
+    )
+    .await
+    .expect("Should be able to create TPU");
+
+    let tx_sender = TxSender::new(tx_store.clone(), tpu_service.clone());
+    let block_listenser =
+        BlockListener::new(rpc_client.clone(), tx_store.clone(), block_store.clone());
+    let replayer = TransactionReplayer::new(
+        tpu_service.clone(),
+        tx_store.clone(),
+        Duration::from_secs(2),
+    );
+    let builder = TransactionServiceBuilder::new(
+        tx_sender,
+        replayer,
+        block_listenser,
+        tpu_service,
+        1_000_000,
+    );
+    builder.start(notifier, block_store, 10, Duration::from_secs(90))
+}
+
+/// Stops the market makers once the cluster produced the given number of slots after they
+/// started, the slot is the one polled with the blockhash
+fn start_slot_duration(
+    duration_slots: u64,
+    current_slot: Arc<AtomicU64>,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let poll_interval = Duration::from_millis(DEFAULT_MS_PER_SLOT);
+        let mut start_slot = current_slot.load(Ordering::Acquire);
+        while start_slot == 0 {
+            tokio::time::sleep(poll_interval).await;
+            start_slot = current_slot.load(Ordering::Acquire);
+        }
+        while current_slot.load(Ordering::Acquire) < start_slot + duration_slots {
+            if exit_signal.load(Ordering::Relaxed) {
+                return;
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+        info!(
+            "{duration_slots} slots produced since slot {start_slot}, stopping the market makers"
+        );
+        exit_signal.store(true, Ordering::Relaxed);
+    })
+}
+
+/// Runs one simulation against one cluster, from the preparation of the accounts to the summary
+/// of the results. This is what the `run` subcommand does, the integration tests drive it
+/// directly
+pub struct BenchOrchestrator {
+    config: cli::Config,
+}
+
+impl BenchOrchestrator {
+    pub fn new(config: cli::Config) -> Self {
+        Self { config }
+    }
+
+    /// runs with the duration of the config, returns the summary of the run, None after a dry run
+    pub async fn run(self) -> anyhow::Result<Option<BenchSummary>> {
+        let mut cli_config = self.config;
+        // the perp markets and oracles of mango v4 are not loaded, the quotes would be priced
+        // and sized from made up values
+        if cli_config.program_version == ProgramVersion::V4 {
+            anyhow::bail!("mango v4 perp markets can not be loaded yet, only mango v3 can be run");
+        }
+        // a resumed run continues the run id and the remaining duration of its run state
+        let resumed = match cli_config.resume {
+            true => {
+                let mut state = RunState::load(&cli_config.run_state_file)?;
+                if state.completed {
+                    anyhow::bail!(
+                        "the run {} of {} is already completed",
+                        state.run_id,
+                        cli_config.run_state_file
+                    );
+                }
+                info!(
+                    "resuming the run {}, {}s of {}s remaining",
+                    state.run_id,
+                    state.remaining().as_secs(),
+                    state.duration_secs
+                );
+                cli_config.run_id = Some(state.run_id.clone());
+                cli_config.duration = state.remaining();
+                state.attempt += 1;
+                Some(state)
+            }
+            false => None,
+        };
+        let cli::Config {
+            json_rpc_url,
+            websocket_url,
+            identity,
+            account_keys,
+            mango_keys,
+            duration,
+            duration_slots,
+            quotes_per_second,
+            transaction_save_file,
+            block_data_save_file,
+            partition_output,
+            compress,
+            mango_cluster,
+            priority_fees_proba,
+            keeper_authority,
+            top_up,
+            number_of_markers_per_mm,
+            market_weights,
+            keeper_prioritization,
+            crank_spot_markets,
+            crank_batch,
+            spoofing_levels,
+            unique_client_order_ids,
+            encoded_client_order_ids,
+            program_version,
+            db_url,
+            slot_coverage_file,
+            timeseries_file,
+            funding_file,
+            fills_file,
+            funding_interval,
+            dry_run,
+            control_port,
+            control_bind,
+            health_port,
+            health_bind,
+            results_stream_port,
+            results_stream_bind,
+            warmup_probes,
+            warmup_max_failure_pct,
+            quote_config,
+            estimate_compute_units,
+            summary_file,
+            thresholds,
+            alerts,
+            alert_webhook,
+            accounts_per_signer,
+            watch_accounts,
+            quic_connection_pool_size,
+            tpu_max_parallel_streams,
+            num_quic_endpoints,
+            send_errors_file,
+            scenario,
+            cancel_storm_at,
+            periphery,
+            oracle_feeder,
+            commitment,
+            track_all_commitments,
+            block_fetch_concurrency,
+            confirmation_source,
+            stall_timeout,
+            abort_on_stall,
+            max_market_maker_restarts,
+            seed,
+            sender,
+            jito_url,
+            relayer_url,
+            relayer_pct,
+            confirm_rpc_urls,
+            rpc_sightings_file,
+            book_validation_interval,
+            book_snapshot_file,
+            book_snapshot_interval,
+            book_snapshot_depth,
+            record_buffer,
+            chaos,
+            discover_markets,
+            max_slot_lag,
+            generate_only,
+            bind_address,
+            client_node_id,
+            tpu_addrs,
+            preconnect_leaders,
+            send_delay,
+            network_impairment,
+            memo,
+            cluster,
+            run_id,
+            run_manifest_file,
+            run_state_file,
+            resume,
+            force,
+            shard,
+            pacing,
+            build_threads,
+            pin_threads,
+            baseline_secs,
+            ..
+        } = &cli_config;
+        let number_of_markers_per_mm = *number_of_markers_per_mm;
+        let keeper_prioritization = *keeper_prioritization;
+
+        // the health server answers the probes during the whole startup, the watchdog keeps
+        // running during the shutdown to report the threads which do not stop
+        let watchdog = Watchdog::default();
+        let watchdog_task = watchdog.start(*stall_timeout, *abort_on_stall);
+        let health = Health::default();
+        let health_server = health_port.map(|port| {
+            start_health_server(
+                SocketAddr::new(*health_bind, port),
+                health.clone(),
+                watchdog.clone(),
+                *stall_timeout,
+            )
+        });
+
+        let transaction_save_file = transaction_save_file.clone();
+        let block_data_save_file = block_data_save_file.clone();
+
+        if !*force && !*resume {
+            // the blocks are only partitioned by hour
+            let result_output = |path: &String, partitioning: Option<OutputPartitioning>| match (
+                path.is_empty(),
+                partitioning,
+            ) {
+                (true, _) => String::new(),
+                (false, Some(_)) => manifest_path(path),
+                (false, None) => compressed_path(path, *compress),
+            };
+            check_no_previous_outputs(&[
+                result_output(&transaction_save_file, *partition_output),
+                result_output(
+                    &block_data_save_file,
+                    partition_output.filter(|p| *p == OutputPartitioning::ByHour),
+                ),
+                slot_coverage_file.clone(),
+                timeseries_file.clone(),
+                funding_file.clone(),
+                fills_file.clone(),
+                send_errors_file.clone(),
+                rpc_sightings_file.clone(),
+                book_snapshot_file.clone(),
+            ])?;
+        }
+
+        // a database which can not be reached fails the run before any transaction is sent
+        let record_sink = match db_url {
+            Some(db_url) => Some(storage::connect(db_url).await?),
+            None => None,
+        };
+
+        info!(
+            "Connecting to the cluster {}, {}",
+            json_rpc_url, websocket_url
+        );
+
+        let account_keys_json = load_config(account_keys)
+            .await
+            .expect("unable to read accounts file");
+        let all_account_keys: Vec<AccountKeys> =
+            serde_json::from_str(&account_keys_json).expect("accounts JSON was not well-formatted");
+        // every instance of a fleet only prepares and cleans the accounts of its shard
+        let account_keys_parsed = match shard {
+            Some(shard) => {
+                let account_keys_parsed = shard.filter(&all_account_keys);
+                info!(
+                    "shard {shard} quotes {} of the {} accounts",
+                    account_keys_parsed.len(),
+                    all_account_keys.len()
+                );
+                account_keys_parsed
+            }
+            None => all_account_keys.clone(),
+        };
+
+        let nb_rpc_client = Arc::new(NbRpcClient::new_with_commitment(
+            json_rpc_url.to_string(),
+            CommitmentConfig::finalized(),
+        ));
+
+        let mango_group_config = match discover_markets {
+            Some(group_pk) => {
+                if *program_version != ProgramVersion::V3 {
+                    anyhow::bail!("market discovery is only supported for mango v3");
+                }
+                discover_group_config(&nb_rpc_client, group_pk).await?
+            }
+            None => {
+                let mango_keys_json = load_config(mango_keys)
+                    .await
+                    .expect("unable to read mango keys file");
+                let mango_keys_parsed: MangoConfig = serde_json::from_str(&mango_keys_json)
+                    .expect("mango JSON was not well-formatted");
+                mango_keys_parsed
+                    .groups
+                    .into_iter()
+                    .find(|g| g.name == *mango_cluster)
+                    .expect("Mango group config should exist")
+            }
+        };
+        let mango_group_config = &mango_group_config;
+
+        let row_tags = RowTags {
+            run_id: run_id.clone().unwrap_or_else(new_run_id),
+            cluster: cluster.clone(),
+            shard: shard.map(|shard| shard.to_string()),
+        };
+        info!("run id {}", row_tags.run_id);
+
+        let tx_store = empty_tx_store();
+        let block_store = BlockStore::new(&nb_rpc_client)
+            .await
+            .expect("Blockstore should be created");
+
+        // every transaction service has its own QUIC endpoint, they share the transaction store
+        // so that the notifications of the first one cover the transactions sent by all of them
+        let (notif_sx, notif_rx) = unbounded_channel();
+        let mut transaction_services = vec![];
+        let mut tx_service_jhs = vec![];
+        for endpoint in 0..*num_quic_endpoints {
+            let (transaction_service, tx_service_jh) = configure_transaction_service(
+                nb_rpc_client.clone(),
+                Keypair::from_bytes(identity.to_bytes().as_slice()).unwrap(),
+                block_store.clone(),
+                tx_store.clone(),
+                (endpoint == 0 && *sender == SenderKind::Tpu).then(|| notif_sx.clone()),
+                *quic_connection_pool_size,
+            )
+            .await;
+            transaction_services.push(transaction_service);
+            tx_service_jhs.push(tx_service_jh);
+        }
+        drop(notif_sx);
+
+        // every mango account quoted is a market maker
+        let nb_users: usize = account_keys_parsed
+            .iter()
+            .map(|a| a.mango_account_pks.len().min(*accounts_per_signer))
+            .sum();
+
+        let mut run_manifest = RunManifest::start(
+            row_tags.run_id.clone(),
+            RunParameters::new(&cli_config, nb_users),
+            cluster.clone(),
+            row_tags.shard.clone(),
+            &nb_rpc_client,
+        )
+        .await;
+        run_manifest.write(run_manifest_file)?;
+
+        // the expected number of transactions of a run by slots assumes the default slot time
+        let expected_duration = match duration_slots {
+            Some(duration_slots) => {
+                (*duration).min(Duration::from_millis(duration_slots * DEFAULT_MS_PER_SLOT))
+            }
+            None => *duration,
+        };
+        let mut mango_sim_stats = MangoSimulationStats::new(
+            nb_users,
+            *quotes_per_second as usize,
+            number_of_markers_per_mm as usize,
+            expected_duration.as_secs() as usize,
+        );
+
+        let (tx_record_sx, tx_record_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // market makers, keepers (with crank and blockhash polling) and confirmations are stopped in this order
+        let exit_signal = Arc::new(AtomicBool::new(false));
+        let keepers_exit_signal = Arc::new(AtomicBool::new(false));
+        let confirmation_exit_signal = Arc::new(AtomicBool::new(false));
+        let (websocket, websocket_failover_task) =
+            start_websocket_failover(websocket_url, keepers_exit_signal.clone());
+
+        // continuosly fetch blockhash
+        let latest_blockhash = get_latest_blockhash(&nb_rpc_client.clone()).await;
+        health.set_blockhash_fetched();
+        let blockhash = Arc::new(RwLock::new(latest_blockhash));
+        let current_slot = Arc::new(AtomicU64::new(0));
+        let blockhash_slots = BlockhashSlots::default();
+        let blockhash_thread = start_blockhash_polling_service(
+            keepers_exit_signal.clone(),
+            blockhash.clone(),
+            current_slot.clone(),
+            nb_rpc_client.clone(),
+            watchdog.clone(),
+            blockhash_slots.clone(),
+        );
+
+        let (send_errors_sx, send_errors_rx) = unbounded_channel();
+        // the writer lives as long as the tpu manager and is stopped at the very end
+        let send_errors_writer = (!send_errors_file.is_empty()).then(|| {
+            start_send_errors_writer(send_errors_file.clone(), send_errors_rx, row_tags.clone())
+        });
+        let live_parameters = Arc::new(LiveParameters::new(
+            *quotes_per_second,
+            *priority_fees_proba,
+        ));
+        let corpus_writer = match generate_only {
+            Some(path) => Some(Arc::new(CorpusWriter::create(path).await?)),
+            None => None,
+        };
+        let mut preconnect_task = None;
+        let mut quic_stats = None;
+        let senders: Vec<Arc<dyn TransactionSender>> = match sender {
+            _ if corpus_writer.is_some() => vec![corpus_writer.clone().unwrap()],
+            SenderKind::Tpu => transaction_services
+                .into_iter()
+                .map(|s| Arc::new(s) as Arc<dyn TransactionSender>)
+                .collect(),
+            SenderKind::Rpc => vec![Arc::new(RpcSender::new(nb_rpc_client.clone()))],
+            SenderKind::Jito => vec![Arc::new(JitoSender::new(jito_url))],
+            SenderKind::Mock => vec![Arc::new(MockSender::default())],
+            SenderKind::Quic => {
+                let connection_cache = Arc::new(
+                    quic_connection_cache(
+                        &nb_rpc_client,
+                        *bind_address,
+                        client_node_id.as_ref(),
+                        *quic_connection_pool_size,
+                    )
+                    .await?,
+                );
+                // the connections are opened during the warmup, before the market makers start
+                if *preconnect_leaders > 0 && tpu_addrs.is_empty() {
+                    preconnect_task = Some(start_leader_preconnect(
+                        nb_rpc_client.clone(),
+                        connection_cache.clone(),
+                        *preconnect_leaders,
+                        keepers_exit_signal.clone(),
+                    ));
+                }
+                let stats = QuicStats::default();
+                quic_stats = Some((
+                    stats.clone(),
+                    start_quic_stats_monitor(
+                        connection_cache.clone(),
+                        stats,
+                        QUIC_STATS_INTERVAL,
+                        keepers_exit_signal.clone(),
+                    ),
+                ));
+                if tpu_addrs.is_empty() {
+                    vec![Arc::new(
+                        QuicSender::new(nb_rpc_client.clone(), websocket.clone(), connection_cache)
+                            .await?,
+                    )]
+                } else {
+                    vec![Arc::new(FixedTpuSender::new(
+                        connection_cache,
+                        tpu_addrs.clone(),
+                    ))]
+                }
+            }
+        };
+        let (quic_stats, quic_stats_task) = quic_stats.unzip();
+        let impairment_counters = ImpairmentCounters::default();
+        let senders = match network_impairment {
+            Some(impairment) => senders
+                .into_iter()
+                .enumerate()
+                .map(|(sender_index, sender)| {
+                    Arc::new(ImpairedSender::new(
+                        sender,
+                        *impairment,
+                        impairment_counters.clone(),
+                        *seed,
+                        sender_index as u64,
+                    )) as Arc<dyn TransactionSender>
+                })
+                .collect(),
+            None => senders,
+        };
+        // nothing is relayed while generating a corpus
+        let relayer = relayer_url
+            .as_ref()
+            .filter(|_| corpus_writer.is_none())
+            .map(|url| RelayerRoute {
+                sender: Arc::new(RelayerSender::new(url)),
+                pct: *relayer_pct,
+            });
+        let tpu_manager = TpuManager::new(
+            senders,
+            mango_sim_stats.clone(),
+            tx_record_sx.clone(),
+            *tpu_max_parallel_streams,
+            send_errors_sx,
+            live_parameters.clone(),
+            blockhash_slots,
+            *send_delay,
+            memo.then(|| MemoTagger::new(row_tags.run_id.clone())),
+            relayer,
+            *seed,
+        )
+        .await?;
+
+        info!(
+            "accounts:{:?} markets:{:?} quotes_per_second:{:?} expected_tps:{:?} duration:{:?}",
+            nb_users,
+            number_of_markers_per_mm,
+            quotes_per_second,
+            nb_users * number_of_markers_per_mm as usize * *quotes_per_second as usize,
+            duration
+        );
+
+        let mango_program_pk = Pubkey::from_str(mango_group_config.mango_program_id.as_str())
+            .expect("Mango program should be able to convert into pubkey");
+        let mango_program = mango_program(*program_version);
+        let mut perp_market_caches: Vec<PerpMarketCache> = match program_version {
+            ProgramVersion::V3 => {
+                get_mango_market_perps_cache(
+                    nb_rpc_client.clone(),
+                    mango_group_config,
+                    &mango_program_pk,
+                )
+                .await
+            }
+            ProgramVersion::V4 => unreachable!("mango v4 runs are rejected before starting"),
+        };
+
+        apply_quote_config(&mut perp_market_caches, mango_group_config, *quote_config);
+        // the open interest is read from the v3 perp markets
+        if *market_weights == MarketWeights::OpenInterest && *program_version == ProgramVersion::V3
+        {
+            weigh_by_open_interest(&mut perp_market_caches);
+        }
+
+        let quote_root_bank = Pubkey::from_str(quote_token(mango_group_config).root_key.as_str())
+            .expect("Quote root bank should be able to convert into pubkey");
+        let quote_node_banks = quote_token(mango_group_config)
+            .node_keys
+            .iter()
+            .map(|x| {
+                Pubkey::from_str(x.as_str())
+                    .expect("Token mint should be able to convert into pubkey")
+            })
+            .collect();
+
+        FeasibilityReport::new(
+            &perp_market_caches,
+            nb_users,
+            number_of_markers_per_mm,
+            *quotes_per_second,
+            *spoofing_levels,
+        )
+        .print();
+
+        if *dry_run {
+            let payer = keeper_authority.as_ref().unwrap_or(identity);
+            if !dry_run_keepers_and_crank(
+                nb_rpc_client.clone(),
+                mango_group_config,
+                &perp_market_caches,
+                quote_root_bank,
+                quote_node_banks,
+                payer,
+            )
+            .await?
+            {
+                anyhow::bail!("some keeper or crank instructions failed during the dry run");
+            }
+            return Ok(None);
+        }
+
+        // the idle cluster is observed before the cancels of the clean up
+        let baseline_blocks = if *baseline_secs > 0 {
+            observe_baseline(nb_rpc_client.clone(), Duration::from_secs(*baseline_secs)).await
+        } else {
+            vec![]
+        };
+
+        clean_market_makers(
+            mango_program.clone(),
+            nb_rpc_client.clone(),
+            &account_keys_parsed,
+            &perp_market_caches,
+            blockhash.clone(),
+            *accounts_per_signer,
+        )
+        .await;
+
+        // keepers and crank only exist for mango v3
+        let is_v3 = *program_version == ProgramVersion::V3;
+        if !is_v3 {
+            info!("mango {program_version} does not need keepers, skipping keepers and crank");
+        }
+
+        // start keeper if keeper authority is present
+        let keepers_jl = if let Some(keeper_authority) = keeper_authority.as_ref().filter(|_| is_v3)
+        {
+            let jl = start_keepers(
+                keepers_exit_signal.clone(),
+                tpu_manager.clone(),
+                perp_market_caches.clone(),
+                blockhash.clone(),
+                current_slot.clone(),
+                keeper_authority,
+                quote_root_bank,
+                quote_node_banks.clone(),
+                keeper_prioritization,
+                watchdog.clone(),
+            );
+            Some(jl)
+        } else {
+            if is_v3 {
+                warn!("no keeper authority, the mango cache is not refreshed during the run and orders fail with InvalidCache once it is stale");
+            }
+            None
+        };
+
+        let keeper_config = KeeperConfig {
+            program_id: to_sdk_pk(&mango_program_pk),
+            rpc_url: json_rpc_url.clone(),
+            websocket: websocket.clone(),
+            batch: *crank_batch,
+        };
+
+        let crank_tasks = if is_v3 {
+            crank::start(
+                keeper_config,
+                keepers_exit_signal.clone(),
+                blockhash.clone(),
+                current_slot.clone(),
+                tpu_manager.clone(),
+                mango_group_config,
+                identity,
+                keeper_prioritization,
+                watchdog.clone(),
+                *crank_spot_markets,
+            )
+        } else {
+            vec![]
+        };
+
+        // update_funding is sent by the keepers of mango v3
+        let funding_task = (is_v3 && !funding_file.is_empty()).then(|| {
+            start_funding_monitor(
+                nb_rpc_client.clone(),
+                &perp_market_caches,
+                funding_file.clone(),
+                *funding_interval,
+                row_tags.clone(),
+                keepers_exit_signal.clone(),
+            )
+        });
+
+        // stub oracles can only be set on mango v3 groups
+        let oracle_tasks = match oracle_feeder.filter(|_| is_v3) {
+            Some(oracle_feeder) => start_oracle_feeders(
+                exit_signal.clone(),
+                nb_rpc_client.clone(),
+                mango_group_config,
+                &perp_market_caches,
+                blockhash.clone(),
+                keeper_authority.as_ref().unwrap_or(identity),
+                oracle_feeder,
+            ),
+            None => vec![],
+        };
+
+        let warmup_duration = Duration::from_secs(20);
+        info!("waiting for keepers to warmup for {warmup_duration:?}");
+        tokio::time::sleep(warmup_duration).await;
+        health.set_keepers_warmed_up();
+
+        // stale caches make every order fail with InvalidCache
+        if is_v3 && generate_only.is_none() {
+            ensure_fresh_cache(
+                &nb_rpc_client,
+                &perp_market_caches,
+                quote_root_bank,
+                quote_node_banks,
+                keeper_authority.as_ref().unwrap_or(identity),
+            )
+            .await?;
+        }
+
+        if *estimate_compute_units {
+            let market_maker = account_keys_parsed
+                .first()
+                .ok_or_else(|| anyhow::anyhow!("no market maker to estimate the compute units"))?;
+            estimate_compute_unit_limits(
+                &nb_rpc_client,
+                mango_program.as_ref(),
+                market_maker,
+                &mut perp_market_caches,
+                *spoofing_levels,
+                *blockhash.read().await,
+            )
+            .await?;
+        }
+
+        if *warmup_probes > 0 && generate_only.is_none() {
+            let probes = send_warmup_probes(
+                mango_program.clone(),
+                nb_rpc_client.clone(),
+                &account_keys_parsed,
+                &perp_market_caches,
+                blockhash.clone(),
+                *warmup_probes,
+            )
+            .await;
+            check_warmup_probes(&probes, *warmup_max_failure_pct)?;
+        }
+
+        let inventory = Inventory::default();
+        let dedup_guard = DedupGuard::new(*unique_client_order_ids);
+        let mm_failures = MarketMakerFailures::new(*max_market_maker_restarts);
+        let build_pool = build_threads.map(|threads| {
+            let pin_threads = pin_threads.clone();
+            Arc::new(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|i| format!("quote-builder-{i}"))
+                    .start_handler(move |_| {
+                        if let Some(pin_threads) = &pin_threads {
+                            pin_threads.pin_current_thread();
+                        }
+                    })
+                    // the panic was printed by the hook, the market maker waiting for the quotes
+                    // restarts instead of the process aborting
+                    .panic_handler(|_| {})
+                    .build()
+                    .expect("build pool should start"),
+            )
+        });
+        let run_state =
+            resumed.unwrap_or_else(|| RunState::new(row_tags.run_id.clone(), *duration));
+        let (mut mm_tasks, mut quoted_markets, roster) = start_market_making_threads(
+            mango_program.clone(),
+            &all_account_keys,
+            *shard,
+            perp_market_caches.clone(),
+            exit_signal.clone(),
+            blockhash.clone(),
+            current_slot.clone(),
+            tpu_manager.clone(),
+            duration,
+            live_parameters.clone(),
+            number_of_markers_per_mm,
+            *spoofing_levels,
+            *accounts_per_signer,
+            watchdog.clone(),
+            *seed,
+            *chaos,
+            inventory.clone(),
+            *pacing,
+            dedup_guard.clone(),
+            *encoded_client_order_ids,
+            run_state.attempt,
+            build_pool,
+            mm_failures.clone(),
+        );
+        let market_making_start = Instant::now();
+        let records_written = Arc::new(AtomicU64::new(run_state.records_flushed));
+        // fills are decoded from the mango v3 event queues
+        let inventory_task = (is_v3
+            && (!fills_file.is_empty()
+                || perp_market_caches
+                    .iter()
+                    .any(|c| c.quote_config.inventory_skew_bps > 0)))
+        .then(|| {
+            start_inventory_tracking(
+                nb_rpc_client.clone(),
+                &quoted_markets,
+                inventory,
+                Duration::from_secs(1),
+                fills_file.clone(),
+                row_tags.clone(),
+                exit_signal.clone(),
+            )
+        });
+        let top_up_task = top_up.clone().map(|top_up| {
+            start_top_up_monitor(
+                nb_rpc_client.clone(),
+                &quoted_markets,
+                top_up,
+                exit_signal.clone(),
+            )
+        });
+        let mut accounts_watcher = watch_accounts.then(|| {
+            start_accounts_watcher(
+                account_keys.clone(),
+                roster,
+                mango_program.clone(),
+                nb_rpc_client.clone(),
+                blockhash.clone(),
+                live_parameters.reload_accounts.clone(),
+                exit_signal.clone(),
+            )
+        });
+        let slot_duration_task = duration_slots.map(|duration_slots| {
+            start_slot_duration(duration_slots, current_slot.clone(), exit_signal.clone())
+        });
+        let scenario_task = scenario
+            .clone()
+            .map(|scenario| start_scenario(scenario, live_parameters.clone(), exit_signal.clone()));
+        let scenario_storms = scenario.as_ref().map_or(false, |scenario| {
+            scenario.phases.iter().any(|p| p.cancel_storm)
+        });
+        let cancel_storm_task = (!cancel_storm_at.is_empty() || scenario_storms).then(|| {
+            start_cancel_storms(
+                mango_program.clone(),
+                quoted_markets.clone(),
+                tpu_manager.clone(),
+                blockhash.clone(),
+                current_slot.clone(),
+                live_parameters.clone(),
+                cancel_storm_at.clone(),
+                exit_signal.clone(),
+            )
+        });
+        // the periphery instructions are built by the mango v3 crate
+        let periphery_task = periphery.filter(|_| is_v3).map(|periphery| {
+            start_periphery_traffic(
+                nb_rpc_client.clone(),
+                mango_group_config,
+                quoted_markets.clone(),
+                tpu_manager.clone(),
+                blockhash.clone(),
+                current_slot.clone(),
+                periphery,
+                exit_signal.clone(),
+            )
+        });
+
+        info!("Number of MM threads {}", mm_tasks.len());
+        drop(tx_record_sx);
+
+        let mut sink_tasks = vec![];
+        let tx_record_rx = match &record_sink {
+            Some(record_sink) => {
+                let (tx_record_rx, mut jhs) = storage::start_send_records_sink(
+                    record_sink.clone(),
+                    tx_record_rx,
+                    *record_buffer,
+                    mango_sim_stats.dropped_records(),
+                );
+                sink_tasks.append(&mut jhs);
+                tx_record_rx
+            }
+            None => tx_record_rx,
+        };
+        let tx_record_rx = if confirm_rpc_urls.is_empty() {
+            tx_record_rx
+        } else {
+            let (tx_record_rx, mut jhs) = start_rpc_cross_check(
+                confirm_rpc_urls.clone(),
+                tx_record_rx,
+                nb_rpc_client.get_slot().await?,
+                rpc_sightings_file.clone(),
+                row_tags.clone(),
+                confirmation_exit_signal.clone(),
+            );
+            sink_tasks.append(&mut jhs);
+            tx_record_rx
+        };
+
+        // the records are streamed to the writers and aggregators, only this many are kept in memory
+        let (tx_status_sx, tx_status_rx) = tokio::sync::broadcast::channel(*record_buffer);
+        let (block_status_sx, block_status_rx) = tokio::sync::broadcast::channel(*record_buffer);
+
+        let stats_handle = mango_sim_stats.update_from_tx_status_stream(tx_status_rx);
+        let dropped_records = mango_sim_stats.dropped_records();
+        let control_server = control_port.map(|port| {
+            start_control_server(
+                SocketAddr::new(*control_bind, port),
+                ControlState {
+                    stats: mango_sim_stats.clone(),
+                    live_parameters: live_parameters.clone(),
+                    run_parameters: RunParameters::new(&cli_config, nb_users),
+                    thresholds: *thresholds,
+                    summary_file: summary_file.clone(),
+                    quic_stats: quic_stats.clone(),
+                },
+            )
+        });
+
+        // the books are only decoded for mango v3
+        let book_validation_task = book_validation_interval.filter(|_| is_v3).map(|interval| {
+            start_book_validation(
+                nb_rpc_client.clone(),
+                &quoted_markets,
+                tx_status_sx.subscribe(),
+                interval,
+                dropped_records.clone(),
+                exit_signal.clone(),
+            )
+        });
+
+        let book_recorder_task = (is_v3 && !book_snapshot_file.is_empty()).then(|| {
+            start_book_recorder(
+                nb_rpc_client.clone(),
+                &quoted_markets,
+                book_snapshot_file.clone(),
+                *book_snapshot_interval,
+                *book_snapshot_depth,
+                row_tags.clone(),
+                exit_signal.clone(),
+            )
+        });
+
+        let lag_throttle_task = max_slot_lag.map(|max_slot_lag| {
+            start_lag_throttle(
+                current_slot.clone(),
+                block_status_sx.subscribe(),
+                live_parameters.clone(),
+                max_slot_lag,
+                exit_signal.clone(),
+            )
+        });
+
+        let mut writers_jh = initialize_result_writers(
+            ResultWriterConfig {
+                transaction_save_file,
+                block_data_save_file,
+                baseline_blocks,
+                flush: live_parameters.flush.clone(),
+                tags: row_tags.clone(),
+                partitioning: *partition_output,
+                compression: *compress,
+                append: *resume,
+                records_written: records_written.clone(),
+                dropped_records: dropped_records.clone(),
+            },
+            tx_status_sx.subscribe(),
+            block_status_sx.subscribe(),
+        );
+        let run_state_task = start_run_state_writer(
+            run_state_file.clone(),
+            run_state.clone(),
+            live_parameters.flush.clone(),
+            records_written.clone(),
+            exit_signal.clone(),
+        );
+        if !alerts.is_empty() {
+            sink_tasks.push(start_alerts(
+                alerts.clone(),
+                alert_webhook.clone(),
+                mango_sim_stats.clone(),
+                row_tags.clone(),
+                confirmation_exit_signal.clone(),
+            ));
+        }
+        if !slot_coverage_file.is_empty() {
+            sink_tasks.push(start_slot_coverage_writer(
+                slot_coverage_file.clone(),
+                tx_status_sx.subscribe(),
+                row_tags.clone(),
+                dropped_records.clone(),
+            ));
+        }
+        // the time series is only written once every record is received
+        if !timeseries_file.is_empty() {
+            writers_jh.push(start_timeseries_writer(
+                timeseries_file.clone(),
+                tx_status_sx.subscribe(),
+                row_tags.clone(),
+                dropped_records.clone(),
+            ));
+        }
+        if let Some(port) = results_stream_port {
+            sink_tasks.push(start_results_stream(
+                SocketAddr::new(*results_stream_bind, *port),
+                tx_status_sx.subscribe(),
+                row_tags.clone(),
+            ));
+        }
+        if let Some(record_sink) = record_sink {
+            sink_tasks.append(&mut storage::start_record_sinks(
+                record_sink,
+                tx_status_sx.subscribe(),
+                block_status_rx,
+                dropped_records.clone(),
+            ));
+        }
+
+        // the inclusion latencies mix the local clock and the block times of the cluster
+        let clock_skew = ClockSkew::default();
+        // lite-rpc only notifies the transactions it sent itself
+        let confirmation_threads = match sender {
+            // nothing is sent, the records are only counted as sent
+            _ if corpus_writer.is_some() => {
+                drop((tx_status_sx, block_status_sx));
+                let confirmation_exit_signal = confirmation_exit_signal.clone();
+                vec![tokio::spawn(async move {
+                    let mut tx_record_rx = tx_record_rx;
+                    while !confirmation_exit_signal.load(Ordering::Relaxed) {
+                        while tx_record_rx.try_recv().is_ok() {}
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                })]
+            }
+            // the transactions sent through the relayer are confirmed by polling blocks
+            SenderKind::Tpu if relayer_url.is_none() => {
+                confirmation_by_lite_rpc_notification_stream(
+                    nb_rpc_client.clone(),
+                    tx_record_rx,
+                    notif_rx,
+                    tx_status_sx,
+                    block_status_sx,
+                    confirmation_exit_signal.clone(),
+                    *commitment,
+                    *track_all_commitments,
+                    clock_skew.clone(),
+                    watchdog.clone(),
+                )
+            }
+            _ if *confirmation_source == ConfirmationSource::SignatureStatuses => {
+                drop(block_status_sx);
+                confirmations_by_signature_statuses(
+                    nb_rpc_client.clone(),
+                    tx_record_rx,
+                    tx_status_sx,
+                    clock_skew.clone(),
+                    confirmation_exit_signal.clone(),
+                )
+            }
+            _ => confirmations_by_blocks(
+                nb_rpc_client.clone(),
+                tx_record_rx,
+                tx_status_sx,
+                block_status_sx,
+                nb_rpc_client.get_slot().await?,
+                *block_fetch_concurrency,
+                clock_skew.clone(),
+                confirmation_exit_signal.clone(),
+            ),
+        };
+
+        let mut keeper_tasks = vec![blockhash_thread];
+        if let Some(keepers_jl) = keepers_jl {
+            keeper_tasks.push(keepers_jl);
+        }
+        keeper_tasks.extend(crank_tasks);
+        keeper_tasks.extend(funding_task);
+        keeper_tasks.extend(preconnect_task);
+        keeper_tasks.extend(quic_stats_task);
+        keeper_tasks.push(websocket_failover_task);
+
+        let reporting_thread = {
+            let confirmation_exit_signal = confirmation_exit_signal.clone();
+            let mut mango_sim_stats = mango_sim_stats.clone();
+            let serialization = tpu_manager.serialization().clone();
+            tokio::spawn(async move {
+                let mut last_report = Instant::now();
+                while !confirmation_exit_signal.load(Ordering::Relaxed) {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    if last_report.elapsed() >= Duration::from_secs(60) {
+                        mango_sim_stats.report(false, METRICS_NAME).await;
+                        let (tx_per_second, mb_per_second) = serialization.throughput();
+                        datapoint_info!(
+                            "bencher-serialization",
+                            ("transactions", serialization.transactions(), i64),
+                            ("tx_per_second", tx_per_second, f64),
+                            ("mb_per_second", mb_per_second, f64),
+                        );
+                        last_report = Instant::now();
+                    }
+                }
+            })
+        };
+
+        // when all market makers tasks are joined that means we are ready to exit
+        let mut transaction_service = tokio::spawn(async move {
+            let _ = futures::future::select_all(tx_service_jhs).await;
+            info!("Transaction service joined");
+        });
+        // the market makers started by the accounts watcher are joined by the watcher, the joined
+        // tasks are removed so that the ones left can be joined again by the shutdown
+        let market_making = async {
+            while let Some(mm_task) = mm_tasks.last_mut() {
+                let _ = mm_task.await;
+                mm_tasks.pop();
+            }
+            match &mut accounts_watcher {
+                Some(accounts_watcher) => accounts_watcher.await.ok(),
+                None => None,
+            }
+        };
+        let market_making_finished = tokio::select! {
+            watched_markets = market_making => {
+                info!("finished market making, joining all other services");
+                // the watcher cancelled the orders of the market makers it retired
+                if let Some(watched_markets) = watched_markets {
+                    quoted_markets = watched_markets;
+                }
+                true
+            },
+            _ = &mut transaction_service => {
+                warn!("transaction service stopped before the end of market making");
+                false
+            },
+        };
+        // the market makers still running when the transaction service stopped first are stopped
+        // with the tasks following the exit signal of the market makers
+        let mut market_makers = vec![];
+        if !market_making_finished {
+            market_makers.extend(mm_tasks);
+            market_makers.extend(accounts_watcher.map(|accounts_watcher| {
+                tokio::spawn(async move {
+                    let _ = accounts_watcher.await;
+                })
+            }));
+        }
+        market_makers.extend(
+            [
+                scenario_task,
+                cancel_storm_task,
+                periphery_task,
+                top_up_task,
+                slot_duration_task,
+            ]
+            .into_iter()
+            .flatten(),
+        );
+
+        // transactions sent at the end of the run still need keepers and crank to land,
+        // confirmation of the remaining transactions can take up to 2 minutes
+        let sending = {
+            let tpu_manager = tpu_manager.clone();
+            tokio::spawn(async move {
+                while tpu_manager.pending_sends() > 0 {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            })
+        };
+        // orders left on the books would be matched by the next runs, they are cancelled
+        // once the last market maker transactions are sent
+        let cancelling = {
+            let tpu_manager = tpu_manager.clone();
+            let mango_program = mango_program.clone();
+            let nb_rpc_client = nb_rpc_client.clone();
+            let blockhash = blockhash.clone();
+            tokio::spawn(async move {
+                while tpu_manager.pending_sends() > 0 {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                info!(
+                    "cancelling the resting orders of {} mango account and market pairs",
+                    quoted_markets.len()
+                );
+                cancel_all_orders(mango_program, nb_rpc_client, quoted_markets, blockhash).await;
+            })
+        };
+        let mut shutdown = Shutdown::new();
+        shutdown
+            .add_step(
+                "market makers",
+                Some(exit_signal),
+                Duration::ZERO,
+                Duration::from_secs(10),
+                market_makers,
+            )
+            .add_step(
+                "transaction sending",
+                None,
+                Duration::ZERO,
+                Duration::from_secs(30),
+                vec![sending],
+            )
+            .add_step(
+                "resting orders",
+                None,
+                Duration::ZERO,
+                Duration::from_secs(120),
+                vec![cancelling],
+            )
+            .add_step(
+                "keepers and crank",
+                Some(keepers_exit_signal),
+                Duration::from_secs(10),
+                Duration::from_secs(10),
+                keeper_tasks,
+            )
+            .add_step(
+                "confirmations",
+                Some(confirmation_exit_signal),
+                Duration::ZERO,
+                Duration::from_secs(240),
+                confirmation_threads,
+            )
+            .add_step(
+                "result sinks",
+                None,
+                Duration::ZERO,
+                Duration::from_secs(60),
+                writers_jh
+                    .into_iter()
+                    .chain([stats_handle, reporting_thread])
+                    .chain(book_validation_task)
+                    .collect(),
+            )
+            .add_step(
+                "transaction service",
+                None,
+                Duration::ZERO,
+                Duration::from_secs(1),
+                vec![transaction_service],
+            );
+        shutdown.run().await;
+        watchdog_task.abort();
+        if let Some(inventory_task) = inventory_task {
+            inventory_task.abort();
+        }
+        if let Some(lag_throttle_task) = lag_throttle_task {
+            lag_throttle_task.abort();
+        }
+        if let Some(book_recorder_task) = book_recorder_task {
+            book_recorder_task.abort();
+        }
+        run_state_task.abort();
+        for oracle_task in oracle_tasks {
+            oracle_task.abort();
+        }
+        if let Some(control_server) = control_server {
+            control_server.abort();
+        }
+        if let Some(health_server) = health_server {
+            health_server.abort();
+        }
+        if let Some(send_errors_writer) = send_errors_writer {
+            send_errors_writer.abort();
+        }
+
+        if let Some(corpus_writer) = corpus_writer {
+            let written = corpus_writer.flush().await?;
+            info!("{written} transactions written to the corpus");
+        }
+
+        if let Some(offset_ms) = clock_skew.offset_ms() {
+            info!(
+                "the local clock is at most {offset_ms}ms ahead of the block times of the cluster"
+            );
+            datapoint_info!("bencher-clock-skew", ("offset_ms", offset_ms, i64));
+        }
+        if network_impairment.is_some() {
+            info!("{}", impairment_counters.report());
+        }
+        let (tx_per_second, mb_per_second) = tpu_manager.serialization().throughput();
+        info!(
+            "{} transactions serialized at {tx_per_second:.0} tx/s, {mb_per_second:.1} MB/s",
+            tpu_manager.serialization().transactions()
+        );
+        if let Some(memo) = tpu_manager.memo() {
+            info!(
+                "{} transactions tagged with the memo of run {}",
+                memo.count(),
+                row_tags.run_id
+            );
+        }
+        if mm_failures.restarts() > 0 {
+            info!("{} market maker restarts", mm_failures.restarts());
+        }
+        let dropped_records = dropped_records.count();
+        if dropped_records > 0 {
+            warn!(
+                "{dropped_records} records were lost by the writers and aggregators which lagged \
+                behind, raise --record-buffer"
+            );
+        }
+        for (thread, failures) in mm_failures.abandoned() {
+            warn!(
+                "market maker {thread} was abandoned after {} restarts, last error : {}",
+                failures.restarts, failures.last_error
+            );
+        }
+        if *unique_client_order_ids {
+            info!(
+                "{} duplicate client order ids prevented",
+                dedup_guard.prevented_duplicates()
+            );
+        }
+        info!("shutdown : submitting metrics");
+        mango_sim_stats.report(true, METRICS_NAME).await;
+
+        run_manifest.finish();
+        run_manifest.write(run_manifest_file)?;
+
+        // the writers are closed, every record written is flushed
+        let mut run_state = run_state;
+        run_state.elapsed_secs = (run_state.elapsed_secs + market_making_start.elapsed().as_secs())
+            .min(run_state.duration_secs);
+        run_state.records_flushed = records_written.load(Ordering::Relaxed);
+        run_state.completed = true;
+        run_state.write(run_state_file)?;
+
+        let summary = BenchSummary::new(
+            RunParameters::new(&cli_config, nb_users),
+            mango_sim_stats.summary().await,
+            *thresholds,
+        );
+        if !summary_file.is_empty() {
+            summary.write(summary_file)?;
+        }
+        Ok(Some(summary))
+    }
+
+    /// runs for the given duration instead of the one of the config
+    pub async fn run_for(mut self, duration: Duration) -> anyhow::Result<Option<BenchSummary>> {
+        self.config.duration = duration;
+        self.run().await
+    }
+}
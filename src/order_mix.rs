@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use mango::matching::OrderType;
+use rand::Rng;
+use serde::Serialize;
+
+use crate::helpers::weighted_pick;
+
+/// Order type of a quote, each one takes its own path through the mango matching engine
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuoteOrderType {
+    Limit,
+    PostOnly,
+    PostOnlySlide,
+    ImmediateOrCancel,
+    Market,
+}
+
+impl QuoteOrderType {
+    pub fn order_type(self) -> OrderType {
+        match self {
+            QuoteOrderType::Limit => OrderType::Limit,
+            QuoteOrderType::PostOnly => OrderType::PostOnly,
+            QuoteOrderType::PostOnlySlide => OrderType::PostOnlySlide,
+            QuoteOrderType::ImmediateOrCancel => OrderType::ImmediateOrCancel,
+            QuoteOrderType::Market => OrderType::Market,
+        }
+    }
+}
+
+/// Relative weights of the order types of the quotes, only limit orders by default
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OrderMix {
+    pub limit: u32,
+    pub post_only: u32,
+    pub post_only_slide: u32,
+    pub immediate_or_cancel: u32,
+    pub market: u32,
+}
+
+impl Default for OrderMix {
+    fn default() -> Self {
+        Self {
+            limit: 1,
+            post_only: 0,
+            post_only_slide: 0,
+            immediate_or_cancel: 0,
+            market: 0,
+        }
+    }
+}
+
+impl FromStr for OrderMix {
+    type Err = String;
+
+    /// comma separated `type=weight`, e.g. `limit=70,post-only=20,ioc=10`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = OrderMix {
+            limit: 0,
+            ..OrderMix::default()
+        };
+        for entry in s.split(',').filter(|e| !e.is_empty()) {
+            let (order_type, weight) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected type=weight, got {entry}"))?;
+            let weight: u32 = weight
+                .parse()
+                .map_err(|_| format!("invalid order type weight {weight}"))?;
+            match order_type.trim() {
+                "limit" => mix.limit = weight,
+                "post-only" => mix.post_only = weight,
+                "post-only-slide" => mix.post_only_slide = weight,
+                "ioc" => mix.immediate_or_cancel = weight,
+                "market" => mix.market = weight,
+                _ => return Err(format!("unknown order type {order_type}")),
+            }
+        }
+        if mix.weights().iter().all(|(_, weight)| *weight == 0) {
+            return Err("every order type has a weight of 0".to_string());
+        }
+        Ok(mix)
+    }
+}
+
+impl OrderMix {
+    fn weights(&self) -> [(QuoteOrderType, u32); 5] {
+        [
+            (QuoteOrderType::Limit, self.limit),
+            (QuoteOrderType::PostOnly, self.post_only),
+            (QuoteOrderType::PostOnlySlide, self.post_only_slide),
+            (QuoteOrderType::ImmediateOrCancel, self.immediate_or_cancel),
+            (QuoteOrderType::Market, self.market),
+        ]
+    }
+
+    /// order type of the next quote, drawn in proportion to the weights. Nothing is drawn with a
+    /// single order type, so the order flow of a seed does not change
+    pub fn pick(&self, rng: &mut impl Rng) -> QuoteOrderType {
+        let weights = self.weights();
+        let mut used = weights.iter().filter(|(_, weight)| *weight > 0);
+        match (used.next(), used.next()) {
+            (None, _) => return QuoteOrderType::Limit,
+            (Some((order_type, _)), None) => return *order_type,
+            _ => {}
+        }
+        let total: u32 = weights.iter().map(|(_, weight)| weight).sum();
+        weighted_pick(rng, &weights, total).unwrap_or(QuoteOrderType::Limit)
+    }
+}
+
+/// Order type and reduce only flag shared by the bid and the ask of a quote
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuoteOrder {
+    pub order_type: QuoteOrderType,
+    pub reduce_only: bool,
+}
+
+impl Default for QuoteOrder {
+    fn default() -> Self {
+        Self {
+            order_type: QuoteOrderType::Limit,
+            reduce_only: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn parses_the_order_type_weights() {
+        let mix: OrderMix = "limit=70, post-only=20,ioc=10".parse().unwrap();
+        assert_eq!(
+            mix,
+            OrderMix {
+                limit: 70,
+                post_only: 20,
+                post_only_slide: 0,
+                immediate_or_cancel: 10,
+                market: 0,
+            }
+        );
+        // the types left out are not drawn, limit included
+        assert_eq!("market=1".parse::<OrderMix>().unwrap().limit, 0);
+
+        assert!("limit".parse::<OrderMix>().is_err());
+        assert!("limit=-1".parse::<OrderMix>().is_err());
+        assert!("fok=1".parse::<OrderMix>().is_err());
+        assert!("limit=0".parse::<OrderMix>().is_err());
+    }
+
+    #[test]
+    fn picks_the_order_types_in_proportion() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!((0..100).all(|_| OrderMix::default().pick(&mut rng) == QuoteOrderType::Limit));
+
+        let mix: OrderMix = "post-only=3,market=1".parse().unwrap();
+        let post_only = (0..10_000)
+            .filter(|_| mix.pick(&mut rng) == QuoteOrderType::PostOnly)
+            .count();
+        assert!((7_200..7_800).contains(&post_only));
+    }
+}
@@ -0,0 +1,235 @@
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use chrono::Utc;
+use csv_async::AsyncSerializer;
+use futures::AsyncWriteExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compression::{compressed_path, Compression, OutputWriter},
+    csv_schema::{append_versioned, create_versioned, versioned_serializer},
+};
+
+// partitions kept open at the same time, with by-mm the least recently written one is closed
+// beyond it and appended to again when it gets rows, instead of holding a file per market maker
+const MAX_OPEN_PARTITIONS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputPartitioning {
+    // a new file for each hour of the run, the files of the previous hours are closed
+    ByHour,
+    // one file for each market maker, the other transactions are written to a separate file
+    ByMm,
+}
+
+impl FromStr for OutputPartitioning {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "by-hour" => Ok(OutputPartitioning::ByHour),
+            "by-mm" => Ok(OutputPartitioning::ByMm),
+            _ => Err(format!("unknown output partitioning {s}")),
+        }
+    }
+}
+
+/// Files of a partitioned csv output, written next to them in `<path>.manifest.json`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PartitionManifest {
+    pub kind: String,
+    pub schema_version: u32,
+    pub partitions: Vec<Partition>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Partition {
+    pub key: String,
+    pub path: String,
+    pub rows: u64,
+}
+
+pub fn manifest_path(path: &str) -> String {
+    format!("{path}.manifest.json")
+}
+
+/// manifest of a partitioned output, none if the output was written to a single file
+pub fn read_manifest(path: &str) -> anyhow::Result<Option<PartitionManifest>> {
+    let manifest_path = manifest_path(path);
+    if Path::new(path).exists() || !Path::new(&manifest_path).exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&std::fs::read_to_string(
+        manifest_path,
+    )?)?))
+}
+
+fn partition_file(file: &str, key: &str) -> String {
+    let path = Path::new(file);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(file);
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{stem}.{key}.{extension}"),
+        None => format!("{stem}.{key}"),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+/// Key of the partition of a row, the market maker is only used with by-mm
+pub fn partition_key(partitioning: OutputPartitioning, market_maker: Option<&str>) -> String {
+    match partitioning {
+        OutputPartitioning::ByHour => Utc::now().format("%Y%m%dT%H").to_string(),
+        OutputPartitioning::ByMm => market_maker.unwrap_or("other").to_string(),
+    }
+}
+
+/// Csv output split over several versioned files, the manifest is rewritten each time a
+/// partition is opened or flushed so that an interrupted run still lists its files. When
+/// appending, the partitions of the manifest of the previous run are continued
+pub struct PartitionedWriter {
+    path: String,
+    partitioning: OutputPartitioning,
+    compression: Option<Compression>,
+    // index in the manifest, serializer and last write of the open partitions
+    writers: HashMap<String, (usize, AsyncSerializer<OutputWriter>, u64)>,
+    writes: u64,
+    manifest: PartitionManifest,
+}
+
+impl PartitionedWriter {
+    pub fn new(
+        path: String,
+        kind: &str,
+        schema_version: u32,
+        partitioning: OutputPartitioning,
+        compression: Option<Compression>,
+        append: bool,
+    ) -> anyhow::Result<Self> {
+        let previous = match append && Path::new(&manifest_path(&path)).exists() {
+            true => Some(serde_json::from_str::<PartitionManifest>(
+                &std::fs::read_to_string(manifest_path(&path))?,
+            )?),
+            false => None,
+        };
+        Ok(Self {
+            path,
+            partitioning,
+            compression,
+            writers: HashMap::new(),
+            writes: 0,
+            manifest: previous.unwrap_or_else(|| PartitionManifest {
+                kind: kind.to_string(),
+                schema_version,
+                partitions: vec![],
+            }),
+        })
+    }
+
+    pub fn partitioning(&self) -> OutputPartitioning {
+        self.partitioning
+    }
+
+    pub async fn serialize<T: Serialize>(&mut self, key: String, record: T) -> anyhow::Result<()> {
+        if !self.writers.contains_key(&key) {
+            if self.partitioning == OutputPartitioning::ByHour {
+                // rows are written in time order, the previous hour is complete
+                self.close().await?;
+            } else if self.writers.len() >= MAX_OPEN_PARTITIONS {
+                self.close_least_recently_written().await?;
+            }
+            let previous = self.manifest.partitions.iter().position(|p| p.key == key);
+            match previous {
+                Some(index) => {
+                    let (file, existed) = append_versioned(
+                        &self.manifest.partitions[index].path,
+                        &self.manifest.kind,
+                        self.manifest.schema_version,
+                        self.compression,
+                    )
+                    .await?;
+                    self.writers.insert(
+                        key.clone(),
+                        (index, versioned_serializer(file, !existed), 0),
+                    );
+                }
+                None => {
+                    let path = compressed_path(&partition_file(&self.path, &key), self.compression);
+                    let file = create_versioned(
+                        &path,
+                        &self.manifest.kind,
+                        self.manifest.schema_version,
+                        self.compression,
+                    )
+                    .await?;
+                    self.writers.insert(
+                        key.clone(),
+                        (
+                            self.manifest.partitions.len(),
+                            AsyncSerializer::from_writer(file),
+                            0,
+                        ),
+                    );
+                    self.manifest.partitions.push(Partition {
+                        key: key.clone(),
+                        path,
+                        rows: 0,
+                    });
+                }
+            }
+            self.write_manifest().await?;
+        }
+        self.writes += 1;
+        let (index, writer, last_write) = self.writers.get_mut(&key).unwrap();
+        writer.serialize(record).await?;
+        *last_write = self.writes;
+        self.manifest.partitions[*index].rows += 1;
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> anyhow::Result<()> {
+        for (_, writer, _) in self.writers.values_mut() {
+            writer.flush().await?;
+        }
+        self.write_manifest().await
+    }
+
+    /// ends the files of the open partitions, the next rows open new ones
+    pub async fn close(&mut self) -> anyhow::Result<()> {
+        for (_, (_, writer, _)) in self.writers.drain() {
+            close_serializer(writer).await?;
+        }
+        self.write_manifest().await
+    }
+
+    // the partition is listed in the manifest, its next rows are appended to its file
+    async fn close_least_recently_written(&mut self) -> anyhow::Result<()> {
+        let Some(key) = self
+            .writers
+            .iter()
+            .min_by_key(|(_, (_, _, last_write))| *last_write)
+            .map(|(key, _)| key.clone())
+        else {
+            return Ok(());
+        };
+        let (_, writer, _) = self.writers.remove(&key).unwrap();
+        close_serializer(writer).await
+    }
+
+    async fn write_manifest(&self) -> anyhow::Result<()> {
+        async_std::fs::write(
+            manifest_path(&self.path),
+            serde_json::to_string_pretty(&self.manifest)?,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// flushes the rows and closes the file, which writes the end of a compressed stream
+pub async fn close_serializer(writer: AsyncSerializer<OutputWriter>) -> anyhow::Result<()> {
+    let mut file = writer
+        .into_inner()
+        .await
+        .map_err(|_| anyhow::anyhow!("could not flush the csv rows"))?;
+    file.close().await?;
+    Ok(())
+}
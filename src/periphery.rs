@@ -0,0 +1,417 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+use mango::state::{MangoAccount, MangoGroup, NodeBank, MAX_PAIRS};
+use mango_common::Loadable;
+use rand::{seq::SliceRandom, Rng};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{hash::Hash, instruction::Instruction, signer::Signer, transaction::Transaction};
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::{
+    clock,
+    helpers::{to_sdk_instruction, to_sdk_pk, to_sp_pk, weighted_pick},
+    mango::{quote_token, GroupConfig},
+    market_markers::QuotedMarket,
+    states::{TransactionSendRecord, TransactionType},
+    tpu_manager::TpuManager,
+};
+
+pub const DEFAULT_PERIPHERY_INTERVAL: Duration = Duration::from_millis(1000);
+// 0.001 of a 6 decimals quote token, small enough to leave the collateral of the market makers
+pub const DEFAULT_PERIPHERY_QUANTITY: u64 = 1_000;
+
+const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PeripheryInstruction {
+    RedeemMngo,
+    Deposit,
+    Withdraw,
+}
+
+impl PeripheryInstruction {
+    fn name(&self) -> &'static str {
+        match self {
+            PeripheryInstruction::RedeemMngo => "redeem-mngo",
+            PeripheryInstruction::Deposit => "deposit",
+            PeripheryInstruction::Withdraw => "withdraw",
+        }
+    }
+}
+
+/// Relative weights of the instructions sent besides the orders of the market makers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PeripheryMix {
+    pub redeem_mngo: u32,
+    pub deposit: u32,
+    pub withdraw: u32,
+}
+
+impl FromStr for PeripheryMix {
+    type Err = String;
+
+    /// comma separated `instruction=weight`, e.g. `redeem-mngo=1,deposit=2,withdraw=2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = PeripheryMix::default();
+        for entry in s.split(',').filter(|e| !e.is_empty()) {
+            let (instruction, weight) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected instruction=weight, got {entry}"))?;
+            let weight: u32 = weight
+                .parse()
+                .map_err(|_| format!("invalid periphery instruction weight {weight}"))?;
+            match instruction.trim() {
+                "redeem-mngo" => mix.redeem_mngo = weight,
+                "deposit" => mix.deposit = weight,
+                "withdraw" => mix.withdraw = weight,
+                _ => return Err(format!("unknown periphery instruction {instruction}")),
+            }
+        }
+        if mix.weights().iter().all(|(_, weight)| *weight == 0) {
+            return Err("every periphery instruction has a weight of 0".to_string());
+        }
+        Ok(mix)
+    }
+}
+
+impl PeripheryMix {
+    fn weights(&self) -> [(PeripheryInstruction, u32); 3] {
+        [
+            (PeripheryInstruction::RedeemMngo, self.redeem_mngo),
+            (PeripheryInstruction::Deposit, self.deposit),
+            (PeripheryInstruction::Withdraw, self.withdraw),
+        ]
+    }
+
+    /// next instruction, drawn in proportion to the weights, none when every weight is 0
+    pub fn pick(&self, rng: &mut impl Rng) -> Option<PeripheryInstruction> {
+        let weights = self.weights();
+        let total: u32 = weights.iter().map(|(_, weight)| weight).sum();
+        weighted_pick(rng, &weights, total)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PeripheryConfig {
+    pub mix: PeripheryMix,
+    pub interval: Duration,
+    // native units of the quote token deposited or withdrawn by each instruction
+    pub quantity: u64,
+}
+
+// root bank, node bank and vault of a token
+#[derive(Clone, Copy)]
+struct Bank {
+    root_bank: Pubkey,
+    node_bank: Pubkey,
+    vault: Pubkey,
+}
+
+// (root bank, node banks) of a token of the config
+type BankKeys = (String, Vec<String>);
+
+// accounts of the group shared by every periphery instruction
+struct PeripheryAccounts {
+    signer: Pubkey,
+    quote_mint: Pubkey,
+    quote: Bank,
+    // none when the group has no MNGO token, redeem-mngo is then not sent
+    mngo: Option<Bank>,
+    // spot open orders of the margin basket of each mango account, passed to withdraw
+    open_orders: HashMap<Pubkey, Vec<Pubkey>>,
+}
+
+fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    let token_program = Pubkey::from_str(TOKEN_PROGRAM_ID).unwrap();
+    let associated_token_program = Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM_ID).unwrap();
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &associated_token_program,
+    )
+    .0
+}
+
+async fn load_bank(
+    rpc_client: &RpcClient,
+    (root_key, node_keys): &BankKeys,
+) -> anyhow::Result<Bank> {
+    let root_bank = Pubkey::from_str(root_key)?;
+    let node_bank = Pubkey::from_str(
+        node_keys
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("root bank {root_key} has no node bank"))?,
+    )?;
+    let account = rpc_client.get_account(&to_sdk_pk(&node_bank)).await?;
+    let vault = NodeBank::load_from_bytes(&account.data)
+        .map_err(|e| anyhow::anyhow!("could not load the node bank {node_bank} : {e:?}"))?
+        .vault;
+    Ok(Bank {
+        root_bank,
+        node_bank,
+        vault,
+    })
+}
+
+async fn load_accounts(
+    rpc_client: &RpcClient,
+    quoted_markets: &[QuotedMarket],
+    quote_mint: Pubkey,
+    quote: &BankKeys,
+    mngo: Option<&BankKeys>,
+) -> anyhow::Result<PeripheryAccounts> {
+    let group_pk = quoted_markets[0].2.mango_group_pk;
+    let group_account = rpc_client.get_account(&to_sdk_pk(&group_pk)).await?;
+    let signer = MangoGroup::load_from_bytes(&group_account.data)
+        .map_err(|e| anyhow::anyhow!("could not load the mango group : {e:?}"))?
+        .signer_key;
+    let quote = load_bank(rpc_client, quote).await?;
+    let mngo = match mngo {
+        Some(mngo) => Some(load_bank(rpc_client, mngo).await?),
+        None => None,
+    };
+
+    let mut open_orders = HashMap::new();
+    for (_, mango_account_pk, _) in quoted_markets {
+        if open_orders.contains_key(mango_account_pk) {
+            continue;
+        }
+        let account = rpc_client.get_account(&to_sdk_pk(mango_account_pk)).await?;
+        let mango_account = MangoAccount::load_from_bytes(&account.data)
+            .map_err(|e| anyhow::anyhow!("could not load {mango_account_pk} : {e:?}"))?;
+        let basket = (0..MAX_PAIRS)
+            .filter(|i| mango_account.in_margin_basket[*i])
+            .map(|i| mango_account.spot_open_orders[i])
+            .collect();
+        open_orders.insert(*mango_account_pk, basket);
+    }
+
+    Ok(PeripheryAccounts {
+        signer,
+        quote_mint,
+        quote,
+        mngo,
+        open_orders,
+    })
+}
+
+fn periphery_instruction(
+    instruction: PeripheryInstruction,
+    accounts: &PeripheryAccounts,
+    (signers, mango_account_pk, c): &QuotedMarket,
+    quantity: u64,
+) -> Option<Instruction> {
+    let owner = to_sp_pk(&signers.owner.pubkey());
+    let token_account = associated_token_address(&owner, &accounts.quote_mint);
+    let quote = accounts.quote;
+    let instruction = match instruction {
+        PeripheryInstruction::RedeemMngo => {
+            let mngo = accounts.mngo?;
+            mango::instruction::redeem_mngo(
+                &c.mango_program_pk,
+                &c.mango_group_pk,
+                &c.mango_cache_pk,
+                mango_account_pk,
+                &owner,
+                &c.perp_market_pk,
+                &c.perp_market.mngo_vault,
+                &mngo.root_bank,
+                &mngo.node_bank,
+                &mngo.vault,
+                &accounts.signer,
+            )
+        }
+        PeripheryInstruction::Deposit => mango::instruction::deposit(
+            &c.mango_program_pk,
+            &c.mango_group_pk,
+            mango_account_pk,
+            &owner,
+            &c.mango_cache_pk,
+            &quote.root_bank,
+            &quote.node_bank,
+            &quote.vault,
+            &token_account,
+            quantity,
+        ),
+        PeripheryInstruction::Withdraw => mango::instruction::withdraw(
+            &c.mango_program_pk,
+            &c.mango_group_pk,
+            mango_account_pk,
+            &owner,
+            &c.mango_cache_pk,
+            &quote.root_bank,
+            &quote.node_bank,
+            &quote.vault,
+            &token_account,
+            &accounts.signer,
+            accounts
+                .open_orders
+                .get(mango_account_pk)
+                .map_or(&[], |open_orders| open_orders.as_slice()),
+            quantity,
+            false,
+        ),
+    };
+    Some(to_sdk_instruction(instruction.unwrap()))
+}
+
+/// Sends a redeem-mngo, deposit or withdraw instruction of a random market maker at each interval,
+/// picked with the weights of the mix, so that the instruction mix is not only made of orders.
+/// Deposits and withdrawals move the quote token between the mango account and the associated
+/// token account of its owner (mango v3 only)
+#[allow(clippy::too_many_arguments)]
+pub fn start_periphery_traffic(
+    rpc_client: Arc<RpcClient>,
+    group_config: &GroupConfig,
+    quoted_markets: Vec<QuotedMarket>,
+    tpu_manager: TpuManager,
+    blockhash: Arc<RwLock<Hash>>,
+    current_slot: Arc<AtomicU64>,
+    config: PeripheryConfig,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    let quote_token = quote_token(group_config);
+    let quote_mint = Pubkey::from_str(&quote_token.mint_key).unwrap();
+    let quote: BankKeys = (quote_token.root_key.clone(), quote_token.node_keys.clone());
+    let mngo: Option<BankKeys> = group_config
+        .tokens
+        .iter()
+        .find(|t| t.symbol == "MNGO")
+        .map(|t| (t.root_key.clone(), t.node_keys.clone()));
+    tokio::spawn(async move {
+        if quoted_markets.is_empty() {
+            return;
+        }
+        let accounts = match load_accounts(
+            &rpc_client,
+            &quoted_markets,
+            quote_mint,
+            &quote,
+            mngo.as_ref(),
+        )
+        .await
+        {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                warn!("periphery traffic : could not load the accounts, none is sent : {e}");
+                return;
+            }
+        };
+        let mut mix = config.mix;
+        if accounts.mngo.is_none() && mix.redeem_mngo > 0 {
+            warn!("periphery traffic : the group has no MNGO token, redeem-mngo is not sent");
+            mix.redeem_mngo = 0;
+        }
+
+        let mut sent: HashMap<PeripheryInstruction, u64> = HashMap::new();
+        while !exit_signal.load(Ordering::Relaxed) {
+            tokio::time::sleep(config.interval).await;
+            let (instruction, quoted_market) = {
+                let mut rng = rand::thread_rng();
+                let Some(instruction) = mix.pick(&mut rng) else {
+                    return;
+                };
+                (instruction, quoted_markets.choose(&mut rng).unwrap())
+            };
+            let Some(ix) =
+                periphery_instruction(instruction, &accounts, quoted_market, config.quantity)
+            else {
+                continue;
+            };
+            let (signers, _, perp_market) = quoted_market;
+            let mut tx = Transaction::new_with_payer(&[ix], Some(&signers.payer()));
+            if let Some(memo) = tpu_manager.memo() {
+                memo.tag(&mut tx);
+            }
+            tx.sign(&signers.signers(), *blockhash.read().await);
+            let sent_at = clock::now();
+            let record = TransactionSendRecord {
+                signature: tx.signatures[0],
+                sent_at,
+                sent_at_us: sent_at.timestamp_micros(),
+                sent_slot: current_slot.load(Ordering::Acquire),
+                market_maker: Some(to_sp_pk(&signers.owner.pubkey())),
+                market: Some(perp_market.perp_market_pk),
+                priority_fees: 0,
+                keeper_instruction: None,
+                tx_type: TransactionType::Periphery,
+                spoofing: false,
+                phase: None,
+                blockhash_slot: None,
+                blockhash_age_slots: None,
+                last_valid_block_height: None,
+                chaos: None,
+                send_delay_ms: None,
+                tx_size_bytes: 0,
+                num_instructions: 0,
+                order_type: None,
+                reduce_only: false,
+                client_order_id: None,
+                route: None,
+            };
+            if tpu_manager.send_transaction_batch(vec![(tx, record)]).await {
+                *sent.entry(instruction).or_default() += 1;
+            } else {
+                warn!(
+                    "periphery traffic : a {} could not be sent",
+                    instruction.name()
+                );
+            }
+        }
+        for (instruction, count) in sent {
+            info!(
+                "periphery traffic : {count} {} instructions sent",
+                instruction.name()
+            );
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn parses_the_periphery_weights() {
+        let mix: PeripheryMix = "redeem-mngo=1, deposit=2,withdraw=2".parse().unwrap();
+        assert_eq!(
+            mix,
+            PeripheryMix {
+                redeem_mngo: 1,
+                deposit: 2,
+                withdraw: 2,
+            }
+        );
+        assert!("deposit".parse::<PeripheryMix>().is_err());
+        assert!("deposit=x".parse::<PeripheryMix>().is_err());
+        assert!("borrow=1".parse::<PeripheryMix>().is_err());
+        assert!("deposit=0,withdraw=0".parse::<PeripheryMix>().is_err());
+    }
+
+    #[test]
+    fn picks_the_instructions_in_proportion() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(PeripheryMix::default().pick(&mut rng), None);
+
+        let mix: PeripheryMix = "deposit=1,withdraw=3".parse().unwrap();
+        let picks = (0..10_000)
+            .map(|_| mix.pick(&mut rng).unwrap())
+            .collect::<Vec<_>>();
+        let count = |instruction| picks.iter().filter(|pick| **pick == instruction).count();
+        assert!((2_200..2_800).contains(&count(PeripheryInstruction::Deposit)));
+        assert!((7_200..7_800).contains(&count(PeripheryInstruction::Withdraw)));
+        assert_eq!(count(PeripheryInstruction::RedeemMngo), 0);
+    }
+}
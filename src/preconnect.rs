@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use iter_tools::Itertools;
+use log::{info, warn};
+use solana_client::nonblocking::{rpc_client::RpcClient, tpu_connection::TpuConnection};
+use solana_metrics::datapoint_info;
+use solana_sdk::{clock::NUM_CONSECUTIVE_LEADER_SLOTS, pubkey::Pubkey, quic::QUIC_PORT_OFFSET};
+use tokio::task::JoinHandle;
+
+use crate::{helpers::percentile, tpu_manager::QuicConnectionCache};
+
+const PRECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+// idle connections are closed by the leaders, they are warmed again after this delay
+const REWARM_AFTER: Duration = Duration::from_secs(10);
+
+/// distinct leaders of the next slots, in the order of the schedule
+async fn upcoming_leaders(
+    rpc_client: &RpcClient,
+    leaders_ahead: usize,
+) -> anyhow::Result<Vec<Pubkey>> {
+    let slot = rpc_client.get_slot().await?;
+    let leaders = rpc_client
+        .get_slot_leaders(slot, leaders_ahead as u64 * NUM_CONSECUTIVE_LEADER_SLOTS)
+        .await?;
+    let mut upcoming: Vec<Pubkey> = vec![];
+    for leader in leaders {
+        if !upcoming.contains(&leader) {
+            upcoming.push(leader);
+        }
+    }
+    upcoming.truncate(leaders_ahead);
+    Ok(upcoming)
+}
+
+async fn tpu_quic_addresses(rpc_client: &RpcClient) -> anyhow::Result<HashMap<Pubkey, SocketAddr>> {
+    Ok(rpc_client
+        .get_cluster_nodes()
+        .await?
+        .into_iter()
+        .filter_map(|node| {
+            let mut tpu = node.tpu?;
+            tpu.set_port(tpu.port() + QUIC_PORT_OFFSET);
+            Some((Pubkey::from_str(&node.pubkey).ok()?, tpu))
+        })
+        .collect())
+}
+
+/// Opens the QUIC connections to the next leaders of the schedule before the transactions are
+/// sent to them, so that cold handshakes do not skew the first seconds of the run. The latency of
+/// the first connection to each leader is reported apart from the latency of the later warmups
+pub fn start_leader_preconnect(
+    rpc_client: Arc<RpcClient>,
+    connection_cache: Arc<QuicConnectionCache>,
+    leaders_ahead: usize,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut tpu_addresses: HashMap<Pubkey, SocketAddr> = HashMap::new();
+        let mut warmed_at: HashMap<Pubkey, Instant> = HashMap::new();
+        let mut first_connect_ms = vec![];
+        let mut rewarm_ms = vec![];
+        let mut failures = 0u64;
+        while !exit_signal.load(Ordering::Relaxed) {
+            let leaders = match upcoming_leaders(&rpc_client, leaders_ahead).await {
+                Ok(leaders) => leaders,
+                Err(e) => {
+                    warn!("preconnect : could not get the leader schedule {e}");
+                    tokio::time::sleep(PRECONNECT_INTERVAL).await;
+                    continue;
+                }
+            };
+            for leader in leaders {
+                if warmed_at
+                    .get(&leader)
+                    .map_or(false, |at| at.elapsed() < REWARM_AFTER)
+                {
+                    continue;
+                }
+                if !tpu_addresses.contains_key(&leader) {
+                    match tpu_quic_addresses(&rpc_client).await {
+                        Ok(addresses) => tpu_addresses = addresses,
+                        Err(e) => warn!("preconnect : could not get the cluster nodes {e}"),
+                    }
+                }
+                let Some(tpu_address) = tpu_addresses.get(&leader) else {
+                    warn!("preconnect : leader {leader} has no tpu address");
+                    continue;
+                };
+
+                let first_connect = !warmed_at.contains_key(&leader);
+                let started_at = Instant::now();
+                // a one byte packet opens the connection, the leader drops it as an invalid transaction
+                let connection = connection_cache.get_nonblocking_connection(tpu_address);
+                match connection.send_wire_transaction([0u8]).await {
+                    Ok(()) => {
+                        let connect_ms = started_at.elapsed().as_millis() as u64;
+                        datapoint_info!(
+                            "bencher-quic-preconnect",
+                            ("leader", leader.to_string(), String),
+                            ("first_connect", first_connect, bool),
+                            ("connect_ms", connect_ms, i64),
+                        );
+                        if first_connect {
+                            first_connect_ms.push(connect_ms);
+                        } else {
+                            rewarm_ms.push(connect_ms);
+                        }
+                        warmed_at.insert(leader, Instant::now());
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        warn!("preconnect : could not connect to {leader} at {tpu_address} : {e}");
+                    }
+                }
+            }
+            tokio::time::sleep(PRECONNECT_INTERVAL).await;
+        }
+
+        for (name, latencies) in [
+            ("first connections", first_connect_ms),
+            ("rewarms", rewarm_ms),
+        ] {
+            let latencies = latencies.into_iter().sorted().collect_vec();
+            info!(
+                "preconnect : {} {name}, p50 {:?}ms p90 {:?}ms max {:?}ms",
+                latencies.len(),
+                percentile(&latencies, 50.0),
+                percentile(&latencies, 90.0),
+                latencies.last(),
+            );
+        }
+        if failures > 0 {
+            warn!("preconnect : {failures} connections to the leaders failed");
+        }
+    })
+}
@@ -0,0 +1,129 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::info;
+use solana_metrics::datapoint_info;
+use tokio::task::JoinHandle;
+
+use crate::tpu_manager::QuicConnectionCache;
+
+pub const QUIC_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counters of the quic transport since the start of the run, taken from the statistics of the
+/// connection cache. Throttling by the validators shows up here as congestion events, blocked
+/// streams and send timeouts long before it shows up in the landing rates
+#[derive(Clone, Default)]
+pub struct QuicStats {
+    handshakes: Arc<AtomicU64>,
+    connection_reuse: Arc<AtomicU64>,
+    connection_errors: Arc<AtomicU64>,
+    zero_rtt_accepts: Arc<AtomicU64>,
+    zero_rtt_rejects: Arc<AtomicU64>,
+    congestion_events: Arc<AtomicU64>,
+    streams_blocked: Arc<AtomicU64>,
+    data_blocked: Arc<AtomicU64>,
+    acks: Arc<AtomicU64>,
+    send_timeouts: Arc<AtomicU64>,
+    sent_packets: Arc<AtomicU64>,
+    batch_failures: Arc<AtomicU64>,
+    cache_evictions: Arc<AtomicU64>,
+}
+
+impl QuicStats {
+    fn counters(&self) -> [(&'static str, &AtomicU64); 13] {
+        [
+            ("handshakes", &self.handshakes),
+            ("connection_reuse", &self.connection_reuse),
+            ("connection_errors", &self.connection_errors),
+            ("zero_rtt_accepts", &self.zero_rtt_accepts),
+            ("zero_rtt_rejects", &self.zero_rtt_rejects),
+            ("congestion_events", &self.congestion_events),
+            ("streams_blocked", &self.streams_blocked),
+            ("data_blocked", &self.data_blocked),
+            ("acks", &self.acks),
+            ("send_timeouts", &self.send_timeouts),
+            ("sent_packets", &self.sent_packets),
+            ("batch_failures", &self.batch_failures),
+            ("cache_evictions", &self.cache_evictions),
+        ]
+    }
+
+    /// counters in the prometheus text format, for the /metrics endpoint of the control server
+    pub fn prometheus(&self) -> String {
+        let mut text = String::new();
+        for (name, counter) in self.counters() {
+            let _ = writeln!(text, "# TYPE mango_simulation_quic_{name} counter");
+            let _ = writeln!(
+                text,
+                "mango_simulation_quic_{name} {}",
+                counter.load(Ordering::Relaxed)
+            );
+        }
+        text
+    }
+}
+
+/// Takes the statistics of the connection cache at the interval, reports what changed since the
+/// previous interval with a `bencher-quic` datapoint and adds it to the totals. The cache also
+/// resets its statistics when it reports them, so a few counts can be missed around its reports
+pub fn start_quic_stats_monitor(
+    connection_cache: Arc<QuicConnectionCache>,
+    stats: QuicStats,
+    interval: Duration,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while !exit_signal.load(Ordering::Relaxed) {
+            tokio::time::sleep(interval).await;
+            let cache_stats = connection_cache.connection_stats();
+            let client_stats = &cache_stats.total_client_stats;
+            let taken = [
+                client_stats.total_connections.swap(0, Ordering::Relaxed),
+                client_stats.connection_reuse.swap(0, Ordering::Relaxed),
+                client_stats.connection_errors.swap(0, Ordering::Relaxed),
+                client_stats.zero_rtt_accepts.swap(0, Ordering::Relaxed),
+                client_stats.zero_rtt_rejects.swap(0, Ordering::Relaxed),
+                client_stats.congestion_events.load_and_reset(),
+                client_stats.streams_blocked_uni.load_and_reset(),
+                client_stats.data_blocked.load_and_reset(),
+                client_stats.acks.load_and_reset(),
+                client_stats.send_timeout.swap(0, Ordering::Relaxed),
+                cache_stats.sent_packets.swap(0, Ordering::Relaxed),
+                cache_stats.batch_failure.swap(0, Ordering::Relaxed),
+                cache_stats.cache_evictions.swap(0, Ordering::Relaxed),
+            ];
+            for ((_, total), value) in stats.counters().into_iter().zip(taken) {
+                total.fetch_add(value, Ordering::Relaxed);
+            }
+            datapoint_info!(
+                "bencher-quic",
+                ("handshakes", taken[0], i64),
+                ("connection_reuse", taken[1], i64),
+                ("connection_errors", taken[2], i64),
+                ("zero_rtt_accepts", taken[3], i64),
+                ("zero_rtt_rejects", taken[4], i64),
+                ("congestion_events", taken[5], i64),
+                ("streams_blocked", taken[6], i64),
+                ("data_blocked", taken[7], i64),
+                ("acks", taken[8], i64),
+                ("send_timeouts", taken[9], i64),
+                ("sent_packets", taken[10], i64),
+                ("batch_failures", taken[11], i64),
+                ("cache_evictions", taken[12], i64),
+            );
+        }
+        let totals = stats
+            .counters()
+            .iter()
+            .map(|(name, counter)| format!("{name} {}", counter.load(Ordering::Relaxed)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        info!("quic : {totals}");
+    })
+}
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Burst size and pacing of the market maker quotes
+#[derive(Clone, Copy, Debug)]
+pub struct Pacing {
+    // most quotes sent at once, defaults to the quotes of a second
+    pub burst_size: Option<u64>,
+    // quotes are released at most this often
+    pub interval: Duration,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Self {
+            burst_size: None,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Token bucket filled at the quote rate, up to the burst size. The whole tokens available are
+/// released together, at most once per pacing interval, so the default pacing sends the quotes of
+/// a second in one clump while shorter intervals spread them over the second
+pub struct RateLimiter {
+    rate_per_second: f64,
+    burst_size: f64,
+    pacing_interval: Duration,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// the bucket starts full
+    pub fn new(rate_per_second: f64, pacing: Pacing) -> Self {
+        let burst_size = Self::burst_size(rate_per_second, pacing.burst_size);
+        Self {
+            rate_per_second,
+            burst_size,
+            pacing_interval: pacing.interval,
+            tokens: burst_size,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn burst_size(rate_per_second: f64, burst_size: Option<u64>) -> f64 {
+        burst_size.map_or(rate_per_second, |b| b as f64).max(1.0)
+    }
+
+    /// the rate can change while the simulation is running, tokens above a smaller burst are lost
+    pub fn set_rate(&mut self, rate_per_second: f64, burst_size: Option<u64>) {
+        self.refill();
+        self.rate_per_second = rate_per_second;
+        self.burst_size = Self::burst_size(rate_per_second, burst_size);
+        self.tokens = self.tokens.min(self.burst_size);
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate_per_second).min(self.burst_size);
+        self.last_refill = now;
+    }
+
+    /// waits for at least one token and takes all the whole tokens available, returns 0 after a
+    /// pacing interval when the rate is 0
+    pub async fn acquire(&mut self) -> u64 {
+        loop {
+            self.refill();
+            if self.tokens >= 1.0 {
+                let tokens = self.tokens.floor();
+                self.tokens -= tokens;
+                return tokens as u64;
+            }
+            if self.rate_per_second <= 0.0 {
+                tokio::time::sleep(self.pacing_interval).await;
+                return 0;
+            }
+            let until_next_token =
+                Duration::from_secs_f64((1.0 - self.tokens) / self.rate_per_second);
+            tokio::time::sleep(until_next_token.max(self.pacing_interval)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn the_bucket_starts_full() {
+        let mut limiter = RateLimiter::new(10.0, Pacing::default());
+        assert_eq!(limiter.acquire().await, 10);
+
+        let mut limiter = RateLimiter::new(
+            10.0,
+            Pacing {
+                burst_size: Some(4),
+                interval: Duration::from_millis(10),
+            },
+        );
+        assert_eq!(limiter.acquire().await, 4);
+    }
+
+    #[tokio::test]
+    async fn a_smaller_burst_drops_the_tokens_above_it() {
+        let mut limiter = RateLimiter::new(100.0, Pacing::default());
+        limiter.set_rate(100.0, Some(5));
+        assert_eq!(limiter.acquire().await, 5);
+    }
+
+    #[tokio::test]
+    async fn a_zero_rate_releases_nothing_after_the_interval() {
+        let pacing = Pacing {
+            burst_size: None,
+            interval: Duration::from_millis(10),
+        };
+        let mut limiter = RateLimiter::new(0.0, pacing);
+        // the burst is at least one token
+        assert_eq!(limiter.acquire().await, 1);
+        let start = Instant::now();
+        assert_eq!(limiter.acquire().await, 0);
+        assert!(start.elapsed() >= pacing.interval);
+    }
+}
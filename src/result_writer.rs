@@ -1,41 +1,313 @@
-use crate::states::{BlockData, TransactionConfirmRecord};
+use crate::{
+    compression::{compressed_path, Compression, OutputWriter},
+    csv_schema::{
+        append_versioned, create_versioned, versioned_serializer, BLOCK_SCHEMA_VERSION,
+        TRANSACTION_SCHEMA_VERSION,
+    },
+    partitioned_output::{close_serializer, partition_key, OutputPartitioning, PartitionedWriter},
+    states::{BlockData, TransactionConfirmRecord, TransactionSendError},
+    stats::DroppedRecords,
+};
 use async_std::fs::File;
-use tokio::{sync::broadcast::Receiver, task::JoinHandle};
+use csv_async::AsyncSerializer;
+use log::error;
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError, Receiver},
+        mpsc::{self, UnboundedReceiver},
+    },
+    task::JoinHandle,
+};
+
+// a writer which did not acknowledge a flush request by then is reported as not flushed
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Flush requests sent to the result writers. A writer acknowledges a request once the rows it
+/// wrote so far reached its file, even when it was busy writing a row when the request was sent
+#[derive(Clone)]
+pub struct FlushRequests {
+    requests: broadcast::Sender<mpsc::Sender<()>>,
+}
+
+impl Default for FlushRequests {
+    fn default() -> Self {
+        Self {
+            requests: broadcast::channel(16).0,
+        }
+    }
+}
+
+impl FlushRequests {
+    /// the writers subscribe before they write their first row
+    pub fn subscribe(&self) -> Receiver<mpsc::Sender<()>> {
+        self.requests.subscribe()
+    }
+
+    /// Waits until every writer flushed, the rows written before the call are then on disk
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let writers = self.requests.receiver_count();
+        let (ack_sx, mut ack_rx) = mpsc::channel(writers.max(1));
+        if writers == 0 || self.requests.send(ack_sx).is_err() {
+            return Ok(());
+        }
+        for flushed in 0..writers {
+            match tokio::time::timeout(FLUSH_TIMEOUT, ack_rx.recv()).await {
+                Ok(Some(())) => {}
+                _ => anyhow::bail!("only {flushed} of the {writers} result writers flushed"),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Columns identifying the run added to every row of the csv outputs, so that the results of
+/// several runs can be merged
+#[derive(Clone, Debug, Default)]
+pub struct RowTags {
+    pub run_id: String,
+    // only set when running against several clusters
+    pub cluster: Option<String>,
+    // i/n, only set with --shard
+    pub shard: Option<String>,
+}
+
+/// A csv output written to a single file or split with --partition-output
+enum CsvOutput {
+    Single(AsyncSerializer<OutputWriter>),
+    Partitioned(PartitionedWriter),
+}
+
+impl CsvOutput {
+    async fn create(
+        path: String,
+        kind: &str,
+        schema_version: u32,
+        partitioning: Option<OutputPartitioning>,
+        compression: Option<Compression>,
+        // rows of a resumed run are appended to the output of the previous attempt
+        append: bool,
+    ) -> anyhow::Result<Self> {
+        let path = match partitioning {
+            Some(partitioning) => {
+                return Ok(Self::Partitioned(PartitionedWriter::new(
+                    path,
+                    kind,
+                    schema_version,
+                    partitioning,
+                    compression,
+                    append,
+                )?))
+            }
+            None => compressed_path(&path, compression),
+        };
+        Ok(Self::Single(if append {
+            let (file, existed) =
+                append_versioned(&path, kind, schema_version, compression).await?;
+            versioned_serializer(file, !existed)
+        } else {
+            AsyncSerializer::from_writer(
+                create_versioned(&path, kind, schema_version, compression).await?,
+            )
+        }))
+    }
+
+    async fn serialize<T: Serialize>(
+        &mut self,
+        market_maker: Option<&str>,
+        record: T,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Single(writer) => writer.serialize(record).await?,
+            Self::Partitioned(writer) => {
+                let key = partition_key(writer.partitioning(), market_maker);
+                writer.serialize(key, record).await?
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Single(writer) => writer.flush().await?,
+            Self::Partitioned(writer) => writer.flush().await?,
+        }
+        Ok(())
+    }
+
+    async fn close(self) -> anyhow::Result<()> {
+        match self {
+            Self::Single(writer) => close_serializer(writer).await,
+            Self::Partitioned(mut writer) => writer.close().await,
+        }
+    }
+}
+
+/// Outputs of the transaction and block writers, a file left empty is not written
+#[derive(Default)]
+pub struct ResultWriterConfig {
+    pub transaction_save_file: String,
+    pub block_data_save_file: String,
+    // blocks observed before the run, written before the blocks of the run
+    pub baseline_blocks: Vec<BlockData>,
+    // the writers flush on each request, before the end of the run
+    pub flush: FlushRequests,
+    pub tags: RowTags,
+    pub partitioning: Option<OutputPartitioning>,
+    pub compression: Option<Compression>,
+    // the rows are appended to the files of a resumed run
+    pub append: bool,
+    // transaction records written, read by the run state
+    pub records_written: Arc<AtomicU64>,
+    pub dropped_records: DroppedRecords,
+}
 
 pub fn initialize_result_writers(
-    transaction_save_file: String,
-    block_data_save_file: String,
+    config: ResultWriterConfig,
     tx_data: Receiver<TransactionConfirmRecord>,
     block_data: Receiver<BlockData>,
 ) -> Vec<JoinHandle<()>> {
+    let ResultWriterConfig {
+        transaction_save_file,
+        block_data_save_file,
+        baseline_blocks,
+        flush,
+        tags,
+        partitioning,
+        compression,
+        append,
+        records_written,
+        dropped_records,
+    } = config;
     let mut tasks = vec![];
 
     if !transaction_save_file.is_empty() {
+        let mut flush_requests = flush.subscribe();
+        let tags = tags.clone();
+        let dropped_records = dropped_records.clone();
         let tx_data_jh = tokio::spawn(async move {
-            let mut writer = csv_async::AsyncSerializer::from_writer(
-                File::create(transaction_save_file).await.unwrap(),
-            );
+            let mut writer = CsvOutput::create(
+                transaction_save_file,
+                "transactions",
+                TRANSACTION_SCHEMA_VERSION,
+                partitioning,
+                compression,
+                append,
+            )
+            .await
+            .unwrap();
             let mut tx_data = tx_data;
-            while let Ok(record) = tx_data.recv().await {
-                writer.serialize(record).await.unwrap();
+            let mut flush_open = true;
+            loop {
+                tokio::select! {
+                    record = tx_data.recv() => match record {
+                        Ok(mut record) => {
+                            record.run_id = tags.run_id.clone();
+                            record.cluster = tags.cluster.clone();
+                            record.shard = tags.shard.clone();
+                            let market_maker = record.market_maker.clone();
+                            writer.serialize(market_maker.as_deref(), record).await.unwrap();
+                            records_written.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(RecvError::Lagged(n)) => {
+                            error!("transaction writer lagged behind, {n} records were not written");
+                            dropped_records.add(n);
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    request = flush_requests.recv(), if flush_open => match request {
+                        Ok(ack) => {
+                            writer.flush().await.unwrap();
+                            let _ = ack.send(()).await;
+                        }
+                        Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => flush_open = false,
+                    },
+                }
             }
-            writer.flush().await.unwrap();
+            writer.close().await.unwrap();
         });
         tasks.push(tx_data_jh);
     }
 
     if !block_data_save_file.is_empty() {
+        let mut flush_requests = flush.subscribe();
         let block_data_jh = tokio::spawn(async move {
-            let mut writer = csv_async::AsyncSerializer::from_writer(
-                File::create(block_data_save_file).await.unwrap(),
-            );
+            // the blocks are shared by the market makers, they are only split by hour
+            let partitioning = partitioning.filter(|p| *p == OutputPartitioning::ByHour);
+            let mut writer = CsvOutput::create(
+                block_data_save_file,
+                "blocks",
+                BLOCK_SCHEMA_VERSION,
+                partitioning,
+                compression,
+                append,
+            )
+            .await
+            .unwrap();
             let mut block_data = block_data;
-            while let Ok(record) = block_data.recv().await {
-                writer.serialize(record).await.unwrap();
+            let mut flush_open = true;
+            for mut record in baseline_blocks {
+                record.run_id = tags.run_id.clone();
+                record.cluster = tags.cluster.clone();
+                record.shard = tags.shard.clone();
+                writer.serialize(None, record).await.unwrap()
             }
-            writer.flush().await.unwrap();
+            loop {
+                tokio::select! {
+                    record = block_data.recv() => match record {
+                        Ok(mut record) => {
+                            record.run_id = tags.run_id.clone();
+                            record.cluster = tags.cluster.clone();
+                            record.shard = tags.shard.clone();
+                            writer.serialize(None, record).await.unwrap()
+                        }
+                        Err(RecvError::Lagged(n)) => {
+                            error!("block writer lagged behind, {n} records were not written");
+                            dropped_records.add(n);
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    request = flush_requests.recv(), if flush_open => match request {
+                        Ok(ack) => {
+                            writer.flush().await.unwrap();
+                            let _ = ack.send(()).await;
+                        }
+                        Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => flush_open = false,
+                    },
+                }
+            }
+            writer.close().await.unwrap();
         });
         tasks.push(block_data_jh);
     }
     tasks
 }
+
+pub fn start_send_errors_writer(
+    send_errors_file: String,
+    send_errors: UnboundedReceiver<TransactionSendError>,
+    tags: RowTags,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut writer =
+            csv_async::AsyncSerializer::from_writer(File::create(send_errors_file).await.unwrap());
+        let mut send_errors = send_errors;
+        // send errors are rare and the senders live until the end of the process, flush each of them
+        while let Some(mut record) = send_errors.recv().await {
+            record.run_id = tags.run_id.clone();
+            record.cluster = tags.cluster.clone();
+            record.shard = tags.shard.clone();
+            writer.serialize(record).await.unwrap();
+            writer.flush().await.unwrap();
+        }
+    })
+}
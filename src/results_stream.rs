@@ -0,0 +1,125 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use futures::SinkExt;
+use log::{error, info, warn};
+use serde::Serialize;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, error::RecvError, Receiver},
+    task::JoinHandle,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    clock,
+    result_writer::RowTags,
+    states::TransactionConfirmRecord,
+    timeseries::{TimeSeries, TimeSeriesRow},
+};
+
+// messages kept for the slowest client before it skips some
+const CLIENT_BUFFER: usize = 10_000;
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamMessage<'a> {
+    Confirmation(&'a TransactionConfirmRecord),
+    Second(&'a TimeSeriesRow),
+}
+
+fn to_message(message: StreamMessage) -> Arc<String> {
+    Arc::new(serde_json::to_string(&message).unwrap())
+}
+
+fn second_message(timeseries: &TimeSeries, second: i64, tags: &RowTags) -> Arc<String> {
+    let mut row = timeseries.second(second);
+    row.run_id = tags.run_id.clone();
+    row.cluster = tags.cluster.clone();
+    row.shard = tags.shard.clone();
+    to_message(StreamMessage::Second(&row))
+}
+
+async fn serve_client(stream: TcpStream, peer: SocketAddr, mut messages: Receiver<Arc<String>>) {
+    let mut websocket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(websocket) => websocket,
+        Err(e) => {
+            warn!("results stream : could not accept {peer} : {e}");
+            return;
+        }
+    };
+    info!("results stream : {peer} connected");
+    loop {
+        match messages.recv().await {
+            Ok(message) => {
+                if websocket
+                    .send(Message::Text(message.as_ref().clone()))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Err(RecvError::Lagged(n)) => {
+                warn!("results stream : {peer} lagged behind, {n} messages were skipped");
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+    let _ = websocket.close(None).await;
+    info!("results stream : {peer} disconnected");
+}
+
+/// Websocket server publishing the confirmation records as they are produced, and every second
+/// the time series row of the second which just ended, so that dashboards follow the run without
+/// reading the csv files. The row holds the counts known when it is published, the transactions
+/// sent in the second may still expire or time out. Every message is a json object whose `type`
+/// is `confirmation` or `second`. The server stops once the last record is published
+pub fn start_results_stream(
+    addr: SocketAddr,
+    tx_data: Receiver<TransactionConfirmRecord>,
+    tags: RowTags,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("results stream could not listen on {addr} : {e}");
+                return;
+            }
+        };
+        info!("results stream listening on {addr}");
+        let (messages, _) = broadcast::channel::<Arc<String>>(CLIENT_BUFFER);
+        let mut tx_data = tx_data;
+        let mut tick = tokio::time::interval(Duration::from_secs(1));
+        let mut timeseries = TimeSeries::default();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, peer)) => {
+                        tokio::spawn(serve_client(stream, peer, messages.subscribe()));
+                    }
+                    Err(e) => warn!("results stream : could not accept a connection : {e}"),
+                },
+                record = tx_data.recv() => match record {
+                    Ok(mut record) => {
+                        record.run_id = tags.run_id.clone();
+                        record.cluster = tags.cluster.clone();
+                        record.shard = tags.shard.clone();
+                        timeseries.record(&record);
+                        // no client is connected when the send fails
+                        let _ = messages.send(to_message(StreamMessage::Confirmation(&record)));
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("results stream lagged behind, {n} records were not published");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = tick.tick() => {
+                    let second = clock::now().timestamp() - 1;
+                    let _ = messages.send(second_message(&timeseries, second, &tags));
+                }
+            }
+        }
+        let _ = messages.send(second_message(&timeseries, clock::now().timestamp(), &tags));
+    })
+}
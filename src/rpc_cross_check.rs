@@ -0,0 +1,233 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use async_std::fs::File;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use iter_tools::Itertools;
+use log::{info, warn};
+use serde::Serialize;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig};
+use solana_metrics::datapoint_info;
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    signature::Signature,
+};
+use solana_transaction_status::TransactionDetails;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    task::JoinHandle,
+};
+
+use crate::{
+    clock, confirmation_strategies::get_blocks_with_retry, helpers::percentile,
+    result_writer::RowTags, states::TransactionSendRecord,
+};
+
+// transactions not seen by every node after this delay are reported as missed by the others
+const SIGHTING_TIMEOUT: Duration = Duration::from_secs(120);
+const POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Time at which a RPC node first returned a sent transaction in a confirmed block
+#[derive(Clone, Serialize)]
+pub struct RpcSightingRecord {
+    pub signature: String,
+    pub rpc_url: String,
+    pub sent_at: String,
+    // not set when the node did not return the transaction before the timeout
+    pub first_seen_at: Option<String>,
+    // delay behind the first node which returned the transaction
+    pub skew_ms: Option<i64>,
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+}
+
+struct Sightings {
+    sent_at: DateTime<Utc>,
+    tracked_since: Instant,
+    // first seen time for each node, in the order of the urls
+    first_seen: Vec<Option<DateTime<Utc>>>,
+}
+
+/// Polls the confirmed blocks of every RPC node in parallel and records when each of them first
+/// returned the sent transactions, so that the propagation skew between the nodes does not
+/// silently bias the latencies. The send records are forwarded to the returned receiver.
+pub fn start_rpc_cross_check(
+    rpc_urls: Vec<String>,
+    tx_record_rx: UnboundedReceiver<TransactionSendRecord>,
+    from_slot: u64,
+    sightings_file: String,
+    tags: RowTags,
+    exit_signal: Arc<AtomicBool>,
+) -> (
+    UnboundedReceiver<TransactionSendRecord>,
+    Vec<JoinHandle<()>>,
+) {
+    let pending: Arc<DashMap<Signature, Sightings>> = Arc::new(DashMap::new());
+    let (forward_sx, forward_rx) = unbounded_channel();
+    let nb_nodes = rpc_urls.len();
+    let mut tasks = vec![];
+
+    tasks.push({
+        let pending = pending.clone();
+        tokio::spawn(async move {
+            let mut tx_record_rx = tx_record_rx;
+            while let Some(record) = tx_record_rx.recv().await {
+                pending.insert(
+                    record.signature,
+                    Sightings {
+                        sent_at: record.sent_at,
+                        tracked_since: Instant::now(),
+                        first_seen: vec![None; nb_nodes],
+                    },
+                );
+                let _ = forward_sx.send(record);
+            }
+        })
+    });
+
+    for (node, rpc_url) in rpc_urls.iter().enumerate() {
+        let client = Arc::new(RpcClient::new(rpc_url.clone()));
+        let pending = pending.clone();
+        let exit_signal = exit_signal.clone();
+        let rpc_url = rpc_url.clone();
+        tasks.push(tokio::spawn(async move {
+            let commitment = CommitmentConfig {
+                commitment: CommitmentLevel::Confirmed,
+            };
+            let mut start_block = from_slot;
+            while !(exit_signal.load(Ordering::Relaxed) && pending.is_empty()) {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                let Ok(block_slots) = get_blocks_with_retry(client.clone(), start_block, commitment).await else {
+                    warn!("cross check : could not get the blocks of {rpc_url}");
+                    continue;
+                };
+                let Some(last_slot) = block_slots.last() else {
+                    continue;
+                };
+                start_block = last_slot + 1;
+
+                let blocks = block_slots.iter().map(|slot| {
+                    client.get_block_with_config(
+                        *slot,
+                        RpcBlockConfig {
+                            transaction_details: Some(TransactionDetails::Signatures),
+                            rewards: Some(false),
+                            commitment: Some(commitment),
+                            max_supported_transaction_version: Some(0),
+                            ..RpcBlockConfig::default()
+                        },
+                    )
+                });
+                let blocks = futures::future::join_all(blocks).await;
+                let seen_at = clock::now();
+                for block in blocks {
+                    let signatures = match block {
+                        Ok(block) => block.signatures.unwrap_or_default(),
+                        Err(e) => {
+                            warn!("cross check : could not get a block of {rpc_url} : {e}");
+                            continue;
+                        }
+                    };
+                    for signature in signatures {
+                        let Ok(signature) = signature.parse::<Signature>() else {
+                            continue;
+                        };
+                        if let Some(mut sightings) = pending.get_mut(&signature) {
+                            sightings.first_seen[node].get_or_insert(seen_at);
+                        }
+                    }
+                }
+            }
+        }));
+    }
+
+    tasks.push(tokio::spawn(async move {
+        let mut writer = if sightings_file.is_empty() {
+            None
+        } else {
+            Some(csv_async::AsyncSerializer::from_writer(
+                File::create(sightings_file).await.unwrap(),
+            ))
+        };
+        let mut skews_ms: Vec<Vec<i64>> = vec![vec![]; nb_nodes];
+        let mut missed = vec![0u64; nb_nodes];
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            let done = pending
+                .iter()
+                .filter(|s| {
+                    s.first_seen.iter().all(Option::is_some)
+                        || s.tracked_since.elapsed() > SIGHTING_TIMEOUT
+                })
+                .map(|s| *s.key())
+                .collect_vec();
+            for signature in done {
+                let Some((signature, sightings)) = pending.remove(&signature) else {
+                    continue;
+                };
+                let first = sightings.first_seen.iter().flatten().min().copied();
+                // transactions which never landed say nothing about the propagation
+                let Some(first) = first else {
+                    continue;
+                };
+                for (node, seen_at) in sightings.first_seen.iter().enumerate() {
+                    let skew_ms = seen_at.map(|s| (s - first).num_milliseconds());
+                    match skew_ms {
+                        Some(skew_ms) => skews_ms[node].push(skew_ms),
+                        None => missed[node] += 1,
+                    }
+                    if let Some(writer) = writer.as_mut() {
+                        writer
+                            .serialize(RpcSightingRecord {
+                                signature: signature.to_string(),
+                                rpc_url: rpc_urls[node].clone(),
+                                sent_at: sightings.sent_at.to_string(),
+                                first_seen_at: seen_at.map(|s| s.to_string()),
+                                skew_ms,
+                                run_id: tags.run_id.clone(),
+                                cluster: tags.cluster.clone(),
+                                shard: tags.shard.clone(),
+                            })
+                            .await
+                            .unwrap();
+                    }
+                }
+            }
+            if exit_signal.load(Ordering::Relaxed) && pending.is_empty() {
+                break;
+            }
+        }
+        if let Some(writer) = writer.as_mut() {
+            writer.flush().await.unwrap();
+        }
+
+        for (node, rpc_url) in rpc_urls.iter().enumerate() {
+            let skews = skews_ms[node].iter().copied().sorted().collect_vec();
+            info!(
+                "cross check : {rpc_url} returned {} transactions, missed {}, skew behind the first node p50 {:?}ms p90 {:?}ms max {:?}ms",
+                skews.len(),
+                missed[node],
+                percentile(&skews, 50.0),
+                percentile(&skews, 90.0),
+                skews.last(),
+            );
+            datapoint_info!(
+                "bencher-rpc-skew",
+                ("rpc_url", rpc_url.clone(), String),
+                ("seen", skews.len(), i64),
+                ("missed", missed[node], i64),
+                ("skew_p50_ms", percentile(&skews, 50.0).unwrap_or_default(), i64),
+                ("skew_p90_ms", percentile(&skews, 90.0).unwrap_or_default(), i64),
+            );
+        }
+    }));
+
+    (forward_rx, tasks)
+}
@@ -0,0 +1,80 @@
+use chrono::Utc;
+use log::warn;
+use rand::Rng;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+use crate::summary::RunParameters;
+
+/// Identifier of a run written in every row of its outputs, sortable by start time
+pub fn new_run_id() -> String {
+    format!(
+        "{}-{:08x}",
+        Utc::now().format("%Y%m%dT%H%M%S"),
+        rand::thread_rng().gen::<u32>()
+    )
+}
+
+/// Metadata of a run written next to its results, written when the run starts and again with
+/// its end time so that interrupted runs still have one
+#[derive(Clone, Debug, Serialize)]
+pub struct RunManifest {
+    pub run_id: String,
+    pub git_commit: String,
+    pub bencher_version: String,
+    pub command_line: Vec<String>,
+    pub parameters: RunParameters,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+    pub genesis_hash: Option<String>,
+    pub cluster_version: Option<String>,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+}
+
+impl RunManifest {
+    pub async fn start(
+        run_id: String,
+        parameters: RunParameters,
+        cluster: Option<String>,
+        shard: Option<String>,
+        rpc_client: &RpcClient,
+    ) -> Self {
+        let genesis_hash = match rpc_client.get_genesis_hash().await {
+            Ok(hash) => Some(hash.to_string()),
+            Err(e) => {
+                warn!("could not get the genesis hash of the cluster : {e}");
+                None
+            }
+        };
+        let cluster_version = match rpc_client.get_version().await {
+            Ok(version) => Some(version.solana_core),
+            Err(e) => {
+                warn!("could not get the version of the cluster : {e}");
+                None
+            }
+        };
+        Self {
+            run_id,
+            git_commit: env!("GIT_COMMIT").to_string(),
+            bencher_version: env!("CARGO_PKG_VERSION").to_string(),
+            command_line: std::env::args().collect(),
+            parameters,
+            cluster,
+            shard,
+            genesis_hash,
+            cluster_version,
+            started_at: Utc::now().to_string(),
+            ended_at: None,
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.ended_at = Some(Utc::now().to_string());
+    }
+
+    pub fn write(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio::{task::JoinHandle, time::Instant};
+
+use crate::result_writer::FlushRequests;
+
+const RUN_STATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Progress of a run, rewritten while the market makers run so that a crashed run can be
+/// continued with --resume
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunState {
+    pub run_id: String,
+    // resumes of the run so far
+    #[serde(default)]
+    pub attempt: u32,
+    // duration of the whole run, over all its attempts
+    pub duration_secs: u64,
+    // market making time of all the attempts
+    pub elapsed_secs: u64,
+    // transaction records flushed to the transaction file
+    pub records_flushed: u64,
+    pub completed: bool,
+    pub updated_at: String,
+}
+
+impl RunState {
+    pub fn new(run_id: String, duration: Duration) -> Self {
+        Self {
+            run_id,
+            attempt: 0,
+            duration_secs: duration.as_secs(),
+            elapsed_secs: 0,
+            records_flushed: 0,
+            completed: false,
+            updated_at: Utc::now().to_string(),
+        }
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let state = std::fs::read_to_string(path)
+            .with_context(|| format!("no run state to resume in {path}"))?;
+        serde_json::from_str(&state).with_context(|| format!("{path} is not a valid run state"))
+    }
+
+    /// written to a temporary file renamed over the previous state, a crash never leaves a
+    /// partial state
+    pub fn write(&mut self, path: &str) -> anyhow::Result<()> {
+        self.updated_at = Utc::now().to_string();
+        let tmp = format!("{path}.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    pub fn remaining(&self) -> Duration {
+        Duration::from_secs(self.duration_secs.saturating_sub(self.elapsed_secs))
+    }
+}
+
+/// Refuses to start a run which would overwrite the outputs of a previous one
+pub fn check_no_previous_outputs(paths: &[String]) -> anyhow::Result<()> {
+    if let Some(path) = paths
+        .iter()
+        .find(|path| !path.is_empty() && Path::new(path).exists())
+    {
+        anyhow::bail!(
+            "{path} already exists, use --force to overwrite the outputs of the previous run \
+            or --resume to continue it"
+        );
+    }
+    Ok(())
+}
+
+/// Rewrites the run state every few seconds until the market makers stop. The writers count
+/// a record once it is serialized, the records counted before a flush are recorded as flushed
+/// once every writer acknowledged it
+pub fn start_run_state_writer(
+    path: String,
+    mut state: RunState,
+    flush: FlushRequests,
+    records_written: Arc<AtomicU64>,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let start = Instant::now();
+        let elapsed_before = state.elapsed_secs;
+        let mut interval = tokio::time::interval(RUN_STATE_INTERVAL);
+        while !exit_signal.load(Ordering::Relaxed) {
+            interval.tick().await;
+            state.elapsed_secs = elapsed_before + start.elapsed().as_secs();
+            let counted = records_written.load(Ordering::Relaxed);
+            match flush.flush().await {
+                Ok(()) => state.records_flushed = counted,
+                Err(e) => warn!("could not flush the result writers : {e}"),
+            }
+            if let Err(e) = state.write(&path) {
+                warn!("could not write the run state to {path} : {e}");
+            }
+        }
+    })
+}
@@ -0,0 +1,98 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use log::info;
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+
+use crate::states::LiveParameters;
+
+/// A phase of the scenario, parameters which are not set keep their previous value
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScenarioPhase {
+    pub name: String,
+    // offset from the start of market making
+    pub at_secs: u64,
+    pub quotes_per_second: Option<u64>,
+    pub priority_fees_proba: Option<u8>,
+    pub active_market_makers_pct: Option<u8>,
+    // every market maker cancels all its orders at once when the phase starts
+    #[serde(default)]
+    pub cancel_storm: bool,
+}
+
+/// Time phased changes of the market making parameters, read from a json file
+/// ```json
+/// { "phases": [
+///     { "name": "baseline", "atSecs": 0 },
+///     { "name": "double-qps", "atSecs": 60, "quotesPerSecond": 2 },
+///     { "name": "fees", "atSecs": 120, "priorityFeesProba": 50 },
+///     { "name": "half-mm", "atSecs": 180, "activeMarketMakersPct": 50 },
+///     { "name": "storm", "atSecs": 240, "cancelStorm": true }
+/// ] }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct Scenario {
+    pub phases: Vec<ScenarioPhase>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let mut scenario: Scenario = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        scenario.phases.sort_by_key(|p| p.at_secs);
+        Ok(scenario)
+    }
+}
+
+impl ScenarioPhase {
+    fn apply(&self, live_parameters: &LiveParameters) {
+        if let Some(quotes_per_second) = self.quotes_per_second {
+            live_parameters
+                .quotes_per_second
+                .store(quotes_per_second, Ordering::Relaxed);
+        }
+        if let Some(priority_fees_proba) = self.priority_fees_proba {
+            live_parameters
+                .priority_fees_proba
+                .store(priority_fees_proba.min(100), Ordering::Relaxed);
+        }
+        if let Some(active_market_makers_pct) = self.active_market_makers_pct {
+            live_parameters
+                .active_market_makers_pct
+                .store(active_market_makers_pct.min(100), Ordering::Relaxed);
+        }
+        *live_parameters.phase.write().unwrap() = Some(self.name.clone());
+        if self.cancel_storm {
+            // kept until the storm task waits again if it is busy
+            live_parameters.cancel_storm.notify_one();
+        }
+    }
+}
+
+/// applies each phase of the scenario when its offset is reached, should be started with market making
+pub fn start_scenario(
+    scenario: Scenario,
+    live_parameters: Arc<LiveParameters>,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let start = Instant::now();
+        for phase in scenario.phases {
+            let at = Duration::from_secs(phase.at_secs);
+            while start.elapsed() < at {
+                if exit_signal.load(Ordering::Relaxed) {
+                    return;
+                }
+                tokio::time::sleep((at - start.elapsed()).min(Duration::from_secs(1))).await;
+            }
+            info!("scenario : entering phase {} {:?}", phase.name, phase);
+            phase.apply(&live_parameters);
+        }
+    })
+}
@@ -0,0 +1,109 @@
+use std::{str::FromStr, time::Duration};
+
+use rand::Rng;
+
+/// Distribution of the artificial delay inserted between the signing of a transaction and its
+/// hand over to the sender
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SendDelay {
+    Constant(u64),
+    // bounds in milliseconds, both included
+    Uniform(u64, u64),
+    // mean in milliseconds
+    Exponential(f64),
+}
+
+impl FromStr for SendDelay {
+    type Err = String;
+
+    /// `constant:<ms>`, `uniform:<min ms>-<max ms>` or `exponential:<mean ms>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected distribution:value, got {s}"))?;
+        let parse_ms = |x: &str| {
+            x.trim()
+                .parse::<u64>()
+                .map_err(|_| format!("invalid delay {x}"))
+        };
+        match kind.trim() {
+            "constant" => Ok(SendDelay::Constant(parse_ms(value)?)),
+            "uniform" => {
+                let (min, max) = value
+                    .split_once('-')
+                    .ok_or_else(|| format!("expected min-max, got {value}"))?;
+                let (min, max) = (parse_ms(min)?, parse_ms(max)?);
+                if min > max {
+                    return Err(format!(
+                        "uniform delay minimum {min} is above maximum {max}"
+                    ));
+                }
+                Ok(SendDelay::Uniform(min, max))
+            }
+            "exponential" => Ok(SendDelay::Exponential(parse_ms(value)? as f64)),
+            _ => Err(format!("unknown delay distribution {kind}")),
+        }
+    }
+}
+
+impl SendDelay {
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        let millis = match *self {
+            SendDelay::Constant(ms) => ms as f64,
+            SendDelay::Uniform(min, max) => rng.gen_range(min..=max) as f64,
+            // inverse of the cumulative distribution
+            SendDelay::Exponential(mean) => -mean * (1.0 - rng.gen::<f64>()).ln(),
+        };
+        Duration::from_secs_f64(millis / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn parses_the_delay_distributions() {
+        assert_eq!(
+            "constant:50".parse::<SendDelay>(),
+            Ok(SendDelay::Constant(50))
+        );
+        assert_eq!(
+            "uniform:10-20".parse::<SendDelay>(),
+            Ok(SendDelay::Uniform(10, 20))
+        );
+        assert_eq!(
+            "exponential:30".parse::<SendDelay>(),
+            Ok(SendDelay::Exponential(30.0))
+        );
+
+        assert!("constant".parse::<SendDelay>().is_err());
+        assert!("uniform:20-10".parse::<SendDelay>().is_err());
+        assert!("uniform:20".parse::<SendDelay>().is_err());
+        assert!("normal:10".parse::<SendDelay>().is_err());
+    }
+
+    #[test]
+    fn samples_follow_the_distribution() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            SendDelay::Constant(50).sample(&mut rng),
+            Duration::from_millis(50)
+        );
+
+        let uniform = SendDelay::Uniform(10, 20);
+        assert!((0..1_000).all(|_| {
+            let delay = uniform.sample(&mut rng);
+            delay >= Duration::from_millis(10) && delay <= Duration::from_millis(20)
+        }));
+
+        let exponential = SendDelay::Exponential(30.0);
+        let mean_ms = (0..10_000)
+            .map(|_| exponential.sample(&mut rng).as_secs_f64() * 1000.0)
+            .sum::<f64>()
+            / 10_000.0;
+        assert!((28.0..32.0).contains(&mean_ms));
+    }
+}
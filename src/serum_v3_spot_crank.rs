@@ -0,0 +1,223 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_channel::Sender;
+use log::*;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use tokio::task::JoinHandle;
+
+use crate::mango::GroupConfig;
+
+const MAX_BACKLOG: u64 = 2;
+const MAX_ACCS_PER_TX: usize = 24;
+const MAX_EVENTS_PER_TX: u64 = 50;
+// the spot event queues are not owned by the mango program followed by the perp crank, they
+// are polled instead
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// serum dex v3 accounts are wrapped in "serum" and "padding"
+const ACCOUNT_HEAD_PADDING: usize = 5;
+const ACCOUNT_TAIL_PADDING: usize = 7;
+// account flags, head, count and sequence number
+const QUEUE_HEADER_SIZE: usize = 32;
+const EVENT_SIZE: usize = 88;
+// event flags, owner slot, fee tier, padding, quantities and fee, then the order id
+const EVENT_OWNER_OFFSET: usize = 48;
+const EVENT_FLAG_FILL: u8 = 0x1;
+const CONSUME_EVENTS_TAG: u32 = 3;
+
+/// Event queue of a spot market of the group on the serum dex v3, or its openbook fork
+#[derive(Clone, Debug)]
+pub struct SpotMarketQueue {
+    pub market_pk: Pubkey,
+    pub event_queue_pk: Pubkey,
+}
+
+/// the spot markets of the group config with their event queues
+pub fn spot_market_queues(group: &GroupConfig) -> Vec<SpotMarketQueue> {
+    group
+        .spot_markets
+        .iter()
+        .map(|m| SpotMarketQueue {
+            market_pk: Pubkey::from_str(&m.public_key).unwrap(),
+            event_queue_pk: Pubkey::from_str(&m.events_key).unwrap(),
+        })
+        .collect()
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// open orders accounts owning the first events of the queue, in the order the dex looks them
+/// up, with the number of events they cover. None while the queue does not need to be cranked
+fn pending_open_orders(data: &[u8]) -> Option<(Vec<Pubkey>, u16)> {
+    let queue = data.get(ACCOUNT_HEAD_PADDING..data.len().checked_sub(ACCOUNT_TAIL_PADDING)?)?;
+    let capacity = queue.len().checked_sub(QUEUE_HEADER_SIZE)? / EVENT_SIZE;
+    let head = read_u64(queue, 8);
+    let count = read_u64(queue, 16);
+    if capacity == 0 || count == 0 {
+        return None;
+    }
+
+    let mut owners: Vec<[u64; 4]> = vec![];
+    let mut nb_events = 0;
+    let mut contains_fill_events = false;
+    for i in 0..count.min(MAX_EVENTS_PER_TX) {
+        let offset = QUEUE_HEADER_SIZE + ((head + i) as usize % capacity) * EVENT_SIZE;
+        let owner =
+            [0, 1, 2, 3].map(|word| read_u64(queue, offset + EVENT_OWNER_OFFSET + word * 8));
+        // the dex stops at the first event whose owner is missing
+        if !owners.contains(&owner) {
+            if owners.len() == MAX_ACCS_PER_TX {
+                break;
+            }
+            owners.push(owner);
+        }
+        contains_fill_events |= queue[offset] & EVENT_FLAG_FILL != 0;
+        nb_events += 1;
+    }
+    // same throttling as the perp crank
+    if !contains_fill_events && count <= MAX_BACKLOG {
+        return None;
+    }
+
+    // the dex binary searches the owners by their aligned bytes
+    owners.sort_unstable();
+    let open_orders = owners
+        .into_iter()
+        .map(|owner| Pubkey::new_from_array(bytemuck::cast(owner)))
+        .collect();
+    Some((open_orders, nb_events))
+}
+
+fn consume_events(
+    dex_program_id: &Pubkey,
+    queue: &SpotMarketQueue,
+    open_orders: Vec<Pubkey>,
+    limit: u16,
+) -> Instruction {
+    let mut accounts: Vec<_> = open_orders
+        .into_iter()
+        .map(|pk| AccountMeta::new(pk, false))
+        .collect();
+    accounts.push(AccountMeta::new(queue.market_pk, false));
+    accounts.push(AccountMeta::new(queue.event_queue_pk, false));
+    // the fee receivable accounts are not used by the dex
+    accounts.push(AccountMeta::new(queue.market_pk, false));
+    accounts.push(AccountMeta::new(queue.market_pk, false));
+
+    let mut data = vec![0u8];
+    data.extend_from_slice(&CONSUME_EVENTS_TAG.to_le_bytes());
+    data.extend_from_slice(&limit.to_le_bytes());
+    Instruction {
+        program_id: *dex_program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Polls the event queues of the spot markets and sends their consume events instructions to
+/// the crank transaction sender, so that the spot fills settle
+pub fn start(
+    rpc_url: String,
+    dex_program_id: Pubkey,
+    queues: Vec<SpotMarketQueue>,
+    instruction_sender: Sender<(Pubkey, Vec<Instruction>)>,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let rpc_client = RpcClient::new(rpc_url);
+        let event_queue_pks: Vec<_> = queues.iter().map(|q| q.event_queue_pk).collect();
+        info!("cranking {} spot markets", queues.len());
+
+        while !exit_signal.load(Ordering::Acquire) {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let accounts = match rpc_client.get_multiple_accounts(&event_queue_pks).await {
+                Ok(accounts) => accounts,
+                Err(e) => {
+                    warn!("could not get the spot event queues : {e}");
+                    continue;
+                }
+            };
+            for (queue, account) in queues.iter().zip(accounts) {
+                let Some(account) = account else {
+                    continue;
+                };
+                let Some((open_orders, nb_events)) = pending_open_orders(&account.data) else {
+                    continue;
+                };
+                trace!(
+                    "spot evq {} events={nb_events} open_orders={}",
+                    queue.event_queue_pk,
+                    open_orders.len()
+                );
+                let ix = consume_events(&dex_program_id, queue, open_orders, nb_events);
+                if instruction_sender
+                    .send((queue.market_pk, vec![ix]))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // serum event queue account holding the events from its head, wrapping around its capacity
+    fn event_queue(capacity: usize, head: usize, events: &[(u8, Pubkey)]) -> Vec<u8> {
+        let mut queue = vec![0; QUEUE_HEADER_SIZE + capacity * EVENT_SIZE];
+        queue[8..16].copy_from_slice(&(head as u64).to_le_bytes());
+        queue[16..24].copy_from_slice(&(events.len() as u64).to_le_bytes());
+        for (i, (flags, owner)) in events.iter().enumerate() {
+            let offset = QUEUE_HEADER_SIZE + ((head + i) % capacity) * EVENT_SIZE;
+            queue[offset] = *flags;
+            queue[offset + EVENT_OWNER_OFFSET..offset + EVENT_OWNER_OFFSET + 32]
+                .copy_from_slice(owner.as_ref());
+        }
+        [
+            vec![0; ACCOUNT_HEAD_PADDING],
+            queue,
+            vec![0; ACCOUNT_TAIL_PADDING],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn reads_the_owners_of_the_pending_events() {
+        let (first, second) = (
+            Pubkey::new_from_array([2; 32]),
+            Pubkey::new_from_array([1; 32]),
+        );
+        let data = event_queue(4, 3, &[(0, first), (EVENT_FLAG_FILL, second), (0, first)]);
+        assert_eq!(pending_open_orders(&data), Some((vec![second, first], 3)));
+    }
+
+    #[test]
+    fn small_backlogs_without_fills_are_left() {
+        let owner = Pubkey::new_unique();
+        assert_eq!(pending_open_orders(&event_queue(4, 0, &[])), None);
+        assert_eq!(
+            pending_open_orders(&event_queue(4, 0, &[(0, owner), (0, owner)])),
+            None
+        );
+        assert!(pending_open_orders(&event_queue(4, 0, &[(EVENT_FLAG_FILL, owner)])).is_some());
+        // too short to hold a queue
+        assert_eq!(pending_open_orders(&[0; 8]), None);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{fmt, str::FromStr};
+
+/// Part of the accounts file quoted by one bencher of a fleet, accounts are assigned to the
+/// shards in round robin by their position in the file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    /// `i/n` with i starting at 0
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected i/n, got {s}"))?;
+        let index: usize = index
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid shard index {index}"))?;
+        let count: usize = count
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid shard count {count}"))?;
+        if index >= count {
+            return Err(format!(
+                "shard index {index} should be below the count {count}"
+            ));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl Shard {
+    pub fn contains(&self, position: usize) -> bool {
+        position % self.count == self.index
+    }
+
+    pub fn filter<T: Clone>(&self, items: &[T]) -> Vec<T> {
+        items
+            .iter()
+            .enumerate()
+            .filter(|(position, _)| self.contains(*position))
+            .map(|(_, item)| item.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_shard() {
+        let shard: Shard = " 2/ 4".parse().unwrap();
+        assert_eq!(shard, Shard { index: 2, count: 4 });
+        assert_eq!(shard.to_string(), "2/4");
+
+        assert!("2".parse::<Shard>().is_err());
+        assert!("a/4".parse::<Shard>().is_err());
+        assert!("4/4".parse::<Shard>().is_err());
+        assert!("0/0".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn shards_split_the_accounts_in_round_robin() {
+        let accounts = (0..10).collect::<Vec<_>>();
+        let shards = (0..3)
+            .map(|index| Shard { index, count: 3 }.filter(&accounts))
+            .collect::<Vec<_>>();
+        assert_eq!(shards[0], vec![0, 3, 6, 9]);
+        assert_eq!(shards[1], vec![1, 4, 7]);
+        assert_eq!(shards[2], vec![2, 5, 8]);
+    }
+}
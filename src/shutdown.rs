@@ -0,0 +1,91 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+use tokio::{task::JoinHandle, time::Instant};
+
+struct ShutdownStep {
+    name: &'static str,
+    exit_signal: Option<Arc<AtomicBool>>,
+    grace_period: Duration,
+    timeout: Duration,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+/// Stops the subsystems of the simulation one after the other
+/// each step raises its exit signal after an optional grace period and then waits for its tasks,
+/// tasks still running after the step timeout are aborted so that a stuck or panicked
+/// subsystem can not block the steps after it
+#[derive(Default)]
+pub struct Shutdown {
+    steps: Vec<ShutdownStep>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_step(
+        &mut self,
+        name: &'static str,
+        exit_signal: Option<Arc<AtomicBool>>,
+        grace_period: Duration,
+        timeout: Duration,
+        tasks: Vec<JoinHandle<()>>,
+    ) -> &mut Self {
+        self.steps.push(ShutdownStep {
+            name,
+            exit_signal,
+            grace_period,
+            timeout,
+            tasks,
+        });
+        self
+    }
+
+    pub async fn run(self) {
+        for step in self.steps {
+            let ShutdownStep {
+                name,
+                exit_signal,
+                grace_period,
+                timeout,
+                mut tasks,
+            } = step;
+
+            if !grace_period.is_zero() {
+                info!("shutdown : waiting {grace_period:?} before stopping {name}");
+                tokio::time::sleep(grace_period).await;
+            }
+
+            info!("shutdown : stopping {name} ({} tasks)", tasks.len());
+            if let Some(exit_signal) = exit_signal {
+                exit_signal.store(true, Ordering::Relaxed);
+            }
+
+            let start = Instant::now();
+            match tokio::time::timeout(timeout, futures::future::join_all(tasks.iter_mut())).await {
+                Ok(results) => {
+                    for err in results.into_iter().filter_map(|r| r.err()) {
+                        if err.is_panic() {
+                            warn!("shutdown : a task of {name} panicked : {err}");
+                        }
+                    }
+                    info!("shutdown : {name} stopped in {:?}", start.elapsed());
+                }
+                Err(_) => {
+                    warn!("shutdown : {name} did not stop within {timeout:?}, aborting remaining tasks");
+                    for task in &tasks {
+                        task.abort();
+                    }
+                }
+            }
+        }
+    }
+}
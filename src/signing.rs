@@ -0,0 +1,150 @@
+use std::{str::FromStr, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature},
+    signer::{Signer, SignerError},
+};
+
+use crate::mango::AccountKeys;
+
+pub type MangoSigner = Arc<dyn Signer + Send + Sync>;
+
+#[derive(Serialize)]
+struct SignRequest {
+    pubkey: String,
+    // base58 encoded serialized message
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    // base58 encoded signature
+    signature: String,
+}
+
+/// Signer delegating signatures to an http signing service
+/// the service receives `POST <url>/sign` with `{"pubkey", "message"}` and answers `{"signature"}`
+pub struct RemoteSigner {
+    pubkey: Pubkey,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    pub fn new(pubkey: Pubkey, url: String) -> Self {
+        Self {
+            pubkey,
+            url: url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let request = SignRequest {
+            pubkey: self.pubkey.to_string(),
+            message: bs58::encode(message).into_string(),
+        };
+        let response = self
+            .client
+            .post(format!("{}/sign", self.url))
+            .json(&request)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| SignerError::Connection(e.to_string()))?
+            .json::<SignResponse>()
+            .await
+            .map_err(|e| SignerError::InvalidInput(e.to_string()))?;
+        Signature::from_str(&response.signature)
+            .map_err(|e| SignerError::InvalidInput(e.to_string()))
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        // signing is synchronous in the sdk, the signer is only used from the multi threaded runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.sign(message))
+        })
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Loads the signer of a market maker, in order of precedence from the remote signer,
+/// the keypair file or the secret key embedded in the accounts file
+pub fn load_signer(account_keys: &AccountKeys) -> anyhow::Result<MangoSigner> {
+    if let Some(url) = &account_keys.remote_signer_url {
+        let pubkey = Pubkey::from_str(&account_keys.public_key)?;
+        return Ok(Arc::new(RemoteSigner::new(pubkey, url.clone())));
+    }
+
+    let keypair = if let Some(path) = &account_keys.keypair_path {
+        read_keypair_file(path)
+            .map_err(|e| anyhow::anyhow!("could not read keypair file {path} : {e}"))?
+    } else if let Some(secret_key) = &account_keys.secret_key {
+        Keypair::from_bytes(secret_key.as_slice())?
+    } else {
+        anyhow::bail!(
+            "no secret key, keypair path or remote signer for {}",
+            account_keys.public_key
+        );
+    };
+    if keypair.pubkey().to_string() != account_keys.public_key {
+        anyhow::bail!(
+            "keypair {} does not match public key {}",
+            keypair.pubkey(),
+            account_keys.public_key
+        );
+    }
+    Ok(Arc::new(keypair))
+}
+
+fn load_fee_payer(account_keys: &AccountKeys) -> anyhow::Result<Option<MangoSigner>> {
+    let keypair = if let Some(path) = &account_keys.fee_payer_keypair_path {
+        read_keypair_file(path)
+            .map_err(|e| anyhow::anyhow!("could not read fee payer keypair file {path} : {e}"))?
+    } else if let Some(secret_key) = &account_keys.fee_payer_secret_key {
+        Keypair::from_bytes(secret_key.as_slice())?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Arc::new(keypair)))
+}
+
+/// Owner of the mango accounts of a market maker and the optional separate payer of the fees,
+/// the owner pays the fees when there is no fee payer
+#[derive(Clone)]
+pub struct MarketMakerSigners {
+    pub owner: MangoSigner,
+    pub fee_payer: Option<MangoSigner>,
+}
+
+impl MarketMakerSigners {
+    pub fn load(account_keys: &AccountKeys) -> anyhow::Result<Self> {
+        Ok(Self {
+            owner: load_signer(account_keys)?,
+            fee_payer: load_fee_payer(account_keys)?,
+        })
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.fee_payer.as_ref().unwrap_or(&self.owner).pubkey()
+    }
+
+    /// every signer required by the market maker transactions, the payer first
+    pub fn signers(&self) -> Vec<&dyn Signer> {
+        match &self.fee_payer {
+            Some(fee_payer) => vec![fee_payer.as_ref() as &dyn Signer, self.owner.as_ref()],
+            None => vec![self.owner.as_ref() as &dyn Signer],
+        }
+    }
+}
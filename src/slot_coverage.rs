@@ -0,0 +1,159 @@
+use async_std::fs::File;
+use log::warn;
+use serde::Serialize;
+use solana_sdk::slot_history::Slot;
+use tokio::{sync::broadcast::Receiver, task::JoinHandle};
+
+use crate::{result_writer::RowTags, states::TransactionConfirmRecord, stats::DroppedRecords};
+
+/// Set of slots stored as bits relative to the lowest slot inserted
+#[derive(Default)]
+struct SlotBitmap {
+    base: Slot,
+    words: Vec<u64>,
+}
+
+impl SlotBitmap {
+    fn insert(&mut self, slot: Slot) {
+        if self.words.is_empty() {
+            self.base = slot - slot % 64;
+        } else if slot < self.base {
+            // rebase so that the new slot fits, rare as records mostly arrive in slot order
+            let new_base = slot - slot % 64;
+            let shift = ((self.base - new_base) / 64) as usize;
+            self.words.splice(0..0, std::iter::repeat(0).take(shift));
+            self.base = new_base;
+        }
+        let index = (slot - self.base) as usize;
+        if index / 64 >= self.words.len() {
+            self.words.resize(index / 64 + 1, 0);
+        }
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    fn contains(&self, slot: Slot) -> bool {
+        if slot < self.base {
+            return false;
+        }
+        let index = (slot - self.base) as usize;
+        self.words
+            .get(index / 64)
+            .map_or(false, |word| word & (1 << (index % 64)) != 0)
+    }
+
+    fn first(&self) -> Option<Slot> {
+        self.words
+            .iter()
+            .enumerate()
+            .find(|(_, w)| **w != 0)
+            .map(|(i, w)| self.base + i as u64 * 64 + w.trailing_zeros() as u64)
+    }
+
+    fn last(&self) -> Option<Slot> {
+        self.words
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, w)| **w != 0)
+            .map(|(i, w)| self.base + i as u64 * 64 + 63 - w.leading_zeros() as u64)
+    }
+}
+
+/// A run of consecutive slots sharing the same coverage
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct SlotCoverageRun {
+    pub first_slot: Slot,
+    pub last_slot: Slot,
+    pub had_sends: bool,
+    pub had_landed: bool,
+    // set by the writer, the cluster only when running against several clusters
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+}
+
+/// Tracks for every slot of the run if we sent transactions during it and if any of our transactions landed in it
+#[derive(Default)]
+pub struct SlotCoverage {
+    sent: SlotBitmap,
+    landed: SlotBitmap,
+}
+
+impl SlotCoverage {
+    pub fn record(&mut self, record: &TransactionConfirmRecord) {
+        self.sent.insert(record.sent_slot);
+        if let Some(confirmed_slot) = record.confirmed_slot {
+            self.landed.insert(confirmed_slot);
+        }
+    }
+
+    /// run length encoding of the coverage over the whole run window
+    pub fn runs(&self) -> Vec<SlotCoverageRun> {
+        let first = [self.sent.first(), self.landed.first()]
+            .into_iter()
+            .flatten()
+            .min();
+        let last = [self.sent.last(), self.landed.last()]
+            .into_iter()
+            .flatten()
+            .max();
+        let (first, last) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return vec![],
+        };
+
+        let mut runs: Vec<SlotCoverageRun> = vec![];
+        for slot in first..=last {
+            let had_sends = self.sent.contains(slot);
+            let had_landed = self.landed.contains(slot);
+            match runs.last_mut() {
+                Some(run) if run.had_sends == had_sends && run.had_landed == had_landed => {
+                    run.last_slot = slot;
+                }
+                _ => runs.push(SlotCoverageRun {
+                    first_slot: slot,
+                    last_slot: slot,
+                    had_sends,
+                    had_landed,
+                    run_id: String::new(),
+                    cluster: None,
+                    shard: None,
+                }),
+            }
+        }
+        runs
+    }
+}
+
+pub fn start_slot_coverage_writer(
+    slot_coverage_file: String,
+    tx_data: Receiver<TransactionConfirmRecord>,
+    tags: RowTags,
+    dropped_records: DroppedRecords,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut coverage = SlotCoverage::default();
+        let mut tx_data = tx_data;
+        loop {
+            match tx_data.recv().await {
+                Ok(record) => coverage.record(&record),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("slot coverage lagged behind, {n} records were not counted");
+                    dropped_records.add(n);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+
+        let mut writer = csv_async::AsyncSerializer::from_writer(
+            File::create(slot_coverage_file).await.unwrap(),
+        );
+        for mut run in coverage.runs() {
+            run.run_id = tags.run_id.clone();
+            run.cluster = tags.cluster.clone();
+            run.shard = tags.shard.clone();
+            writer.serialize(run).await.unwrap();
+        }
+        writer.flush().await.unwrap();
+    })
+}
@@ -1,10 +1,25 @@
 use chrono::{DateTime, Utc};
+use dashmap::DashMap;
 use fixed::types::I80F48;
-use mango::state::PerpMarket;
+use mango::{matching::ExpiryType, state::PerpMarket};
+use rand::Rng;
 use serde::Serialize;
 use solana_program::{pubkey::Pubkey, slot_history::Slot};
-use solana_sdk::{commitment_config::CommitmentLevel, signature::Signature};
-use std::fmt;
+use solana_sdk::{commitment_config::CommitmentLevel, hash::Hash, signature::Signature};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicU8, Ordering},
+        Arc, RwLock,
+    },
+};
+use tokio::sync::Notify;
+
+use crate::{
+    chaos::ChaosKind,
+    order_mix::{OrderMix, QuoteOrder, QuoteOrderType},
+    result_writer::FlushRequests,
+};
 
 #[derive(Clone, Debug, Serialize)]
 pub enum KeeperInstruction {
@@ -33,15 +48,143 @@ impl fmt::Display for KeeperInstruction {
     }
 }
 
+/// Path a transaction was handed over through, direct to the cluster or through --relayer-url
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionRoute {
+    Direct,
+    Relayer,
+}
+
+/// Origin of a transaction, the landing rates are reported for each of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionType {
+    #[serde(rename = "mm")]
+    MarketMaker,
+    Keeper,
+    Crank,
+    // cancel-all transactions sent by every market maker at once
+    #[serde(rename = "cancel-storm")]
+    CancelStorm,
+    // redeem-mngo, deposit and withdraw instructions of the market makers
+    Periphery,
+}
+
+impl fmt::Display for TransactionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransactionType::MarketMaker => write!(f, "mm"),
+            TransactionType::Keeper => write!(f, "keeper"),
+            TransactionType::Crank => write!(f, "crank"),
+            TransactionType::CancelStorm => write!(f, "cancel-storm"),
+            TransactionType::Periphery => write!(f, "periphery"),
+        }
+    }
+}
+
 #[derive(Clone, Serialize)]
 pub struct TransactionSendRecord {
     pub signature: Signature,
     pub sent_at: DateTime<Utc>,
+    // microseconds since the unix epoch, confirmations often differ by less than a second
+    pub sent_at_us: i64,
     pub sent_slot: Slot,
     pub market_maker: Option<Pubkey>,
     pub market: Option<Pubkey>,
     pub keeper_instruction: Option<KeeperInstruction>,
+    pub tx_type: TransactionType,
     pub priority_fees: u64,
+    pub spoofing: bool,
+    // scenario phase active when the transaction was sent
+    pub phase: Option<String>,
+    // slot at which the blockhash of the transaction was fetched and its age when sent
+    pub blockhash_slot: Option<Slot>,
+    pub blockhash_age_slots: Option<u64>,
+    // the transaction expires once the chain passes this block height without including it
+    pub last_valid_block_height: Option<u64>,
+    pub chaos: Option<ChaosKind>,
+    // artificial delay waited between signing and sending with --send-delay-ms-distribution
+    pub send_delay_ms: Option<u64>,
+    // serialized size and number of instructions, set when the transaction is sent
+    pub tx_size_bytes: u64,
+    pub num_instructions: u64,
+    // order type of the quotes of the market makers
+    pub order_type: Option<QuoteOrderType>,
+    pub reduce_only: bool,
+    // client order id of the bid of the quotes, see ClientOrderIdKey for the encoded ids
+    pub client_order_id: Option<u64>,
+    // set when the transaction is sent
+    pub route: Option<TransactionRoute>,
+}
+
+// blockhashes expire after 150 blocks, older ones are not needed to stamp the transactions
+const BLOCKHASH_RETENTION_SLOTS: u64 = 300;
+
+/// Slot at which the polling service fetched each recent blockhash, with the last block height
+/// at which the transactions using it can land
+#[derive(Clone, Default)]
+pub struct BlockhashSlots {
+    slots: Arc<DashMap<Hash, (Slot, u64)>>,
+}
+
+impl BlockhashSlots {
+    pub fn insert(&self, blockhash: Hash, slot: Slot, last_valid_block_height: u64) {
+        self.slots
+            .entry(blockhash)
+            .or_insert((slot, last_valid_block_height));
+        self.slots
+            .retain(|_, (fetched_at, _)| *fetched_at + BLOCKHASH_RETENTION_SLOTS >= slot);
+    }
+
+    pub fn get(&self, blockhash: &Hash) -> Option<Slot> {
+        self.slots.get(blockhash).map(|entry| entry.0)
+    }
+
+    pub fn last_valid_block_height(&self, blockhash: &Hash) -> Option<u64> {
+        self.slots.get(blockhash).map(|entry| entry.1)
+    }
+}
+
+/// A transaction which could not be handed over to the TPU
+#[derive(Clone, Serialize)]
+pub struct TransactionSendError {
+    pub signature: String,
+    pub sent_at: String,
+    pub sent_slot: Slot,
+    pub market_maker: Option<String>,
+    pub market: Option<String>,
+    pub keeper_instruction: Option<KeeperInstruction>,
+    pub tx_type: TransactionType,
+    pub chaos: Option<ChaosKind>,
+    pub error: String,
+    pub phase: Option<String>,
+    pub route: Option<TransactionRoute>,
+    // set by the writer, the cluster only when running against several clusters
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+}
+
+impl TransactionSendError {
+    pub fn new(record: &TransactionSendRecord, error: String) -> Self {
+        Self {
+            signature: record.signature.to_string(),
+            sent_at: record.sent_at.to_string(),
+            sent_slot: record.sent_slot,
+            market_maker: record.market_maker.map(|x| x.to_string()),
+            market: record.market.map(|x| x.to_string()),
+            keeper_instruction: record.keeper_instruction.clone(),
+            tx_type: record.tx_type,
+            chaos: record.chaos,
+            error,
+            phase: record.phase.clone(),
+            route: record.route,
+            run_id: String::new(),
+            cluster: None,
+            shard: None,
+        }
+    }
 }
 
 #[derive(Clone, Serialize)]
@@ -49,18 +192,231 @@ pub struct TransactionConfirmRecord {
     pub signature: String,
     pub sent_slot: Slot,
     pub sent_at: String,
+    pub sent_at_us: i64,
     pub confirmed_slot: Option<Slot>,
     pub confirmed_at: Option<String>,
+    pub confirmed_at_us: Option<i64>,
     pub successful: bool,
     pub slot_leader: Option<String>,
     pub error: Option<String>,
+    // custom error code and index of the instruction which failed, decoded from the logs
+    pub mango_error_code: Option<u32>,
+    pub failed_instruction: Option<u8>,
     pub market_maker: Option<String>,
     pub market: Option<String>,
     pub block_hash: Option<String>,
     pub slot_processed: Option<Slot>,
     pub keeper_instruction: Option<KeeperInstruction>,
+    pub tx_type: TransactionType,
+    pub blockhash_slot: Option<Slot>,
+    pub blockhash_age_slots: Option<u64>,
+    pub last_valid_block_height: Option<u64>,
+    pub chaos: Option<ChaosKind>,
+    pub send_delay_ms: Option<u64>,
+    pub tx_size_bytes: u64,
+    pub num_instructions: u64,
+    pub order_type: Option<QuoteOrderType>,
+    pub reduce_only: bool,
+    pub client_order_id: Option<u64>,
+    pub route: Option<TransactionRoute>,
+    // not confirmed before the chain passed the last valid block height of its blockhash
+    pub expired: bool,
+    // not confirmed before the confirmation timeout, without being known to be expired
     pub timed_out: bool,
     pub priority_fees: u64,
+    pub spoofing: bool,
+    pub phase: Option<String>,
+    // time at which each commitment level was notified, only set with --track-all-commitments.
+    // A level the transaction skipped is left empty
+    pub commitment_processed_at: Option<String>,
+    pub commitment_confirmed_at: Option<String>,
+    pub commitment_finalized_at: Option<String>,
+    // block time of the confirmed slot minus the send time, the clocks of the client and of the
+    // cluster differ by the estimated offset, which corrects the latency
+    pub inclusion_latency_ms: Option<i64>,
+    pub clock_offset_ms: Option<i64>,
+    pub corrected_inclusion_latency_ms: Option<i64>,
+    // set by the writer, the cluster only when running against several clusters
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+}
+
+/// Market making parameters which can change while the simulation is running
+pub struct LiveParameters {
+    pub quotes_per_second: AtomicU64,
+    pub priority_fees_proba: AtomicU8,
+    // percentage of the market makers sending quotes
+    pub active_market_makers_pct: AtomicU8,
+    pub phase: RwLock<Option<String>>,
+    // no market maker quotes while paused, the keepers keep running
+    pub paused: AtomicBool,
+    // asks the result writers to flush what they wrote so far
+    pub flush: FlushRequests,
+    // wakes up the accounts watcher to reload the accounts file
+    pub reload_accounts: Arc<Notify>,
+    // percentage of the quote rate allowed by the slot lag throttle
+    pub throttle_pct: AtomicU8,
+    // wakes up the cancel storms to send one right away
+    pub cancel_storm: Arc<Notify>,
+}
+
+impl LiveParameters {
+    pub fn new(quotes_per_second: u64, priority_fees_proba: u8) -> Self {
+        Self {
+            quotes_per_second: AtomicU64::new(quotes_per_second),
+            priority_fees_proba: AtomicU8::new(priority_fees_proba),
+            active_market_makers_pct: AtomicU8::new(100),
+            phase: RwLock::new(None),
+            paused: AtomicBool::new(false),
+            flush: FlushRequests::default(),
+            reload_accounts: Arc::new(Notify::new()),
+            throttle_pct: AtomicU8::new(100),
+            cancel_storm: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn quotes_per_second(&self) -> u64 {
+        self.quotes_per_second.load(Ordering::Relaxed)
+    }
+
+    pub fn priority_fees_proba(&self) -> u8 {
+        self.priority_fees_proba.load(Ordering::Relaxed)
+    }
+
+    /// market makers are numbered from 0, the lowest ones stay active
+    pub fn is_market_maker_active(&self, index: usize, nb_market_makers: usize) -> bool {
+        if self.paused.load(Ordering::Relaxed) {
+            return false;
+        }
+        let active_pct = self.active_market_makers_pct.load(Ordering::Relaxed) as usize;
+        index * 100 < nb_market_makers * active_pct
+    }
+
+    /// time between two rounds of quotes of a market maker
+    pub fn quote_interval_millis(&self) -> u64 {
+        100_000 / self.throttle_pct.load(Ordering::Relaxed).max(1) as u64
+    }
+
+    pub fn phase(&self) -> Option<String> {
+        self.phase.read().unwrap().clone()
+    }
+}
+
+/// Sizing and pricing of the market maker quotes on a market
+#[derive(Clone, Copy, Debug)]
+pub struct QuoteConfig {
+    // order size in base lots, defaults to one unit of the base token
+    pub order_size_lots: Option<i64>,
+    // each order size is randomized by up to this percentage
+    pub size_jitter_pct: u8,
+    // spread between the bid and the ask is random up to this value
+    pub max_spread_bps: u64,
+    // both quotes are moved by a random offset below this many price lots, 0 disables it
+    pub max_price_offset_lots: u64,
+    // quotes per second on the market, follows the live rate of the run when not set
+    pub quotes_per_second: Option<u64>,
+    // transactions handed over together to the tpu manager, one quote per market when not set
+    pub batch_size: Option<usize>,
+    // quotes lean against the inventory by this much of the price per order size held, 0 disables it
+    pub inventory_skew_bps: u64,
+    // time in force of the quotes, they rest until cancelled when not set
+    pub order_expiry_secs: Option<u64>,
+    pub expiry_type: ExpiryType,
+    // maximum number of orders matched or expired orders removed by a single quote
+    pub order_limit: u8,
+    pub max_quote_lots: i64,
+    // compute units requested by each quote, the default of the runtime when not set
+    pub compute_unit_limit: Option<u32>,
+    // share of the market makers quoting the market, relative to the other markets
+    pub weight: f64,
+    // order types of the quotes and percentage of them sent reduce only
+    pub order_mix: OrderMix,
+    pub reduce_only_pct: u8,
+}
+
+impl Default for QuoteConfig {
+    fn default() -> Self {
+        Self {
+            order_size_lots: None,
+            size_jitter_pct: 0,
+            max_spread_bps: 1000,
+            max_price_offset_lots: 20,
+            quotes_per_second: None,
+            batch_size: None,
+            inventory_skew_bps: 0,
+            order_expiry_secs: None,
+            expiry_type: ExpiryType::Absolute,
+            order_limit: 64,
+            max_quote_lots: i64::MAX,
+            compute_unit_limit: None,
+            weight: 1.0,
+            order_mix: OrderMix::default(),
+            reduce_only_pct: 0,
+        }
+    }
+}
+
+impl QuoteConfig {
+    pub fn order_size(&self, order_base_lots: i64, rng: &mut impl Rng) -> i64 {
+        let size = self.order_size_lots.unwrap_or(order_base_lots);
+        if self.size_jitter_pct == 0 {
+            return size;
+        }
+        let jitter_pct = rng.gen::<u64>() % (2 * self.size_jitter_pct as u64 + 1);
+        let size = size as i128 * (100 + jitter_pct as i128 - self.size_jitter_pct as i128) / 100;
+        (size as i64).max(1)
+    }
+
+    /// shift of both quotes in price lots, a long position lowers them so that the asks get filled
+    /// first and a short one raises them, capped at 10% of the price
+    pub fn inventory_skew(&self, price: i64, position_lots: i64, order_size_lots: i64) -> i64 {
+        if self.inventory_skew_bps == 0 || position_lots == 0 {
+            return 0;
+        }
+        let skew = -(price as i128 * self.inventory_skew_bps as i128 * position_lots as i128
+            / (order_size_lots.max(1) as i128 * 10_000));
+        let max_skew = (price / 10).abs() as i128;
+        skew.clamp(-max_skew, max_skew) as i64
+    }
+
+    /// order type and reduce only flag of the next quote, nothing is drawn for reduce only at 0%
+    pub fn quote_order(&self, rng: &mut impl Rng) -> QuoteOrder {
+        QuoteOrder {
+            order_type: self.order_mix.pick(rng),
+            reduce_only: self.reduce_only_pct > 0 && rng.gen_range(0..100u8) < self.reduce_only_pct,
+        }
+    }
+
+    /// expiry of a quote built now, a unix timestamp for absolute expiries and a number of
+    /// seconds for relative ones
+    pub fn expiry_timestamp(&self) -> Option<u64> {
+        let secs = self.order_expiry_secs?;
+        match self.expiry_type {
+            ExpiryType::Absolute => Some(Utc::now().timestamp() as u64 + secs),
+            ExpiryType::Relative => Some(secs),
+        }
+    }
+
+    /// shift of both quotes in price lots, random between minus and plus the max offset, excluded
+    pub fn price_offset(&self, rng: &mut impl Rng) -> i64 {
+        let max_offset = self.max_price_offset_lots.min(i64::MAX as u64) as i64;
+        if max_offset == 0 {
+            0
+        } else {
+            rng.gen_range(1 - max_offset..max_offset)
+        }
+    }
+
+    /// spread in price lots, random between 0 and the max spread
+    pub fn spread(&self, price: i64, rng: &mut impl Rng) -> i64 {
+        let max_spread = (price as i128 * self.max_spread_bps as i128 / 10_000) as i64;
+        if max_spread <= 0 {
+            0
+        } else {
+            (rng.gen::<u64>() % max_spread as u64) as i64
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -78,6 +434,18 @@ pub struct PerpMarketCache {
     pub node_banks: Vec<Pubkey>,
     pub bids: Pubkey,
     pub asks: Pubkey,
+    pub event_queue: Pubkey,
+    pub quote_config: QuoteConfig,
+    // price in quote lots around which the market makers quote, moved by the oracle feeder
+    pub quote_price: Arc<AtomicI64>,
+}
+
+impl PerpMarketCache {
+    pub const DEFAULT_QUOTE_PRICE: i64 = 100;
+
+    pub fn quote_price(&self) -> i64 {
+        self.quote_price.load(Ordering::Relaxed)
+    }
 }
 
 pub struct _TransactionInfo {
@@ -103,5 +471,18 @@ pub struct BlockData {
     pub block_time: u64,
     pub cu_consumed: u64,
     pub cu_consumed_by_mango_simulations: u64,
+    pub cu_utilization_pct: f64,
     pub commitment: CommitmentLevel,
+    // block_slot - sent_slot of the benchmark transactions included in the block
+    pub inclusion_delay_min: Option<u64>,
+    pub inclusion_delay_median: Option<u64>,
+    pub inclusion_delay_max: Option<u64>,
+    // benchmark transactions sent during the leader window of the block but not included yet
+    pub missed_transactions: u64,
+    // only set for the blocks observed before the run
+    pub phase: Option<String>,
+    // set by the writer, the cluster only when running against several clusters
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
 }
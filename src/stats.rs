@@ -1,17 +1,31 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, VecDeque},
     sync::Mutex,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use crate::states::{KeeperInstruction, TransactionConfirmRecord};
+use crate::states::{
+    KeeperInstruction, TransactionConfirmRecord, TransactionSendError, TransactionSendRecord,
+    TransactionType,
+};
 use iter_tools::Itertools;
+use log::warn;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use solana_metrics::datapoint_info;
-use tokio::{sync::RwLock, task::JoinHandle};
+use tokio::{
+    sync::{broadcast, RwLock},
+    task::JoinHandle,
+};
+
+// the index of the instruction is removed so that the same error of any instruction is counted once
+static INSTRUCTION_PREFIX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"Error processing Instruction \d+: ").unwrap());
 
 // Non atomic version of counters
 #[derive(Clone, Default, Debug)]
@@ -19,8 +33,10 @@ struct NACounters {
     num_confirmed_txs: u64,
     num_error_txs: u64,
     num_timeout_txs: u64,
+    num_expired_txs: u64,
     num_successful: u64,
     num_sent: u64,
+    num_send_errors: u64,
 
     // sent transasctions
     num_market_makers_txs: u64,
@@ -31,6 +47,7 @@ struct NACounters {
     num_cache_root_banks_txs: u64,
     num_update_perp_cache_txs: u64,
     num_update_funding_txs: u64,
+    num_spoofing_txs: u64,
 
     // successful transasctions
     succ_market_makers_txs: u64,
@@ -41,6 +58,32 @@ struct NACounters {
     succ_cache_root_banks_txs: u64,
     succ_update_perp_cache_txs: u64,
     succ_update_funding_txs: u64,
+    succ_spoofing_txs: u64,
+    num_spoofing_errors: u64,
+
+    // landing of each transaction type, the crank is counted apart from the keepers
+    num_keeper_txs: u64,
+    num_crank_txs: u64,
+    num_cancel_storm_txs: u64,
+    num_periphery_txs: u64,
+    conf_market_makers_txs: u64,
+    conf_keeper_txs: u64,
+    conf_crank_txs: u64,
+    conf_cancel_storm_txs: u64,
+    conf_periphery_txs: u64,
+    succ_keeper_txs: u64,
+    succ_crank_txs: u64,
+    succ_cancel_storm_txs: u64,
+    succ_periphery_txs: u64,
+
+    // chaos transactions, excluded from every other counter
+    num_chaos_txs: u64,
+    conf_chaos_txs: u64,
+    succ_chaos_txs: u64,
+
+    // serialized size and instructions of the sent transactions
+    bytes_sent: u64,
+    num_instructions_sent: u64,
 
     // errors section
     errors: HashMap<String, u64>,
@@ -60,8 +103,10 @@ impl NACounters {
             num_confirmed_txs: self.num_confirmed_txs - other.num_confirmed_txs,
             num_error_txs: self.num_error_txs - other.num_error_txs,
             num_timeout_txs: self.num_timeout_txs - other.num_timeout_txs,
+            num_expired_txs: self.num_expired_txs - other.num_expired_txs,
             num_successful: self.num_successful - other.num_successful,
             num_sent: self.num_sent - other.num_sent,
+            num_send_errors: self.num_send_errors - other.num_send_errors,
             num_market_makers_txs: self.num_market_makers_txs - other.num_market_makers_txs,
             num_consume_events_txs: self.num_consume_events_txs - other.num_consume_events_txs,
             num_cache_price_txs: self.num_cache_price_txs - other.num_cache_price_txs,
@@ -86,18 +131,56 @@ impl NACounters {
             succ_update_perp_cache_txs: self.succ_update_perp_cache_txs
                 - other.succ_update_perp_cache_txs,
             succ_update_funding_txs: self.succ_update_funding_txs - other.succ_update_funding_txs,
+            num_spoofing_txs: self.num_spoofing_txs - other.num_spoofing_txs,
+            succ_spoofing_txs: self.succ_spoofing_txs - other.succ_spoofing_txs,
+            num_spoofing_errors: self.num_spoofing_errors - other.num_spoofing_errors,
+            num_keeper_txs: self.num_keeper_txs - other.num_keeper_txs,
+            num_crank_txs: self.num_crank_txs - other.num_crank_txs,
+            conf_market_makers_txs: self.conf_market_makers_txs - other.conf_market_makers_txs,
+            conf_keeper_txs: self.conf_keeper_txs - other.conf_keeper_txs,
+            conf_crank_txs: self.conf_crank_txs - other.conf_crank_txs,
+            succ_keeper_txs: self.succ_keeper_txs - other.succ_keeper_txs,
+            succ_crank_txs: self.succ_crank_txs - other.succ_crank_txs,
+            num_cancel_storm_txs: self.num_cancel_storm_txs - other.num_cancel_storm_txs,
+            conf_cancel_storm_txs: self.conf_cancel_storm_txs - other.conf_cancel_storm_txs,
+            succ_cancel_storm_txs: self.succ_cancel_storm_txs - other.succ_cancel_storm_txs,
+            num_periphery_txs: self.num_periphery_txs - other.num_periphery_txs,
+            conf_periphery_txs: self.conf_periphery_txs - other.conf_periphery_txs,
+            succ_periphery_txs: self.succ_periphery_txs - other.succ_periphery_txs,
+            num_chaos_txs: self.num_chaos_txs - other.num_chaos_txs,
+            conf_chaos_txs: self.conf_chaos_txs - other.conf_chaos_txs,
+            succ_chaos_txs: self.succ_chaos_txs - other.succ_chaos_txs,
+            bytes_sent: self.bytes_sent - other.bytes_sent,
+            num_instructions_sent: self.num_instructions_sent - other.num_instructions_sent,
             errors: new_error_count,
         }
     }
 }
 
+/// Records skipped by a writer or an aggregator which fell behind the record channel by more than
+/// the record buffer, counted in the summary
+#[derive(Clone, Debug, Default)]
+pub struct DroppedRecords(Arc<AtomicU64>);
+
+impl DroppedRecords {
+    pub fn add(&self, records: u64) {
+        self.0.fetch_add(records, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 struct Counters {
     num_confirmed_txs: Arc<AtomicU64>,
     num_error_txs: Arc<AtomicU64>,
     num_timeout_txs: Arc<AtomicU64>,
+    num_expired_txs: Arc<AtomicU64>,
     num_successful: Arc<AtomicU64>,
     num_sent: Arc<AtomicU64>,
+    num_send_errors: Arc<AtomicU64>,
 
     // sent transasctions
     num_market_makers_txs: Arc<AtomicU64>,
@@ -108,6 +191,7 @@ struct Counters {
     num_cache_root_banks_txs: Arc<AtomicU64>,
     num_update_perp_cache_txs: Arc<AtomicU64>,
     num_update_funding_txs: Arc<AtomicU64>,
+    num_spoofing_txs: Arc<AtomicU64>,
 
     // successful transasctions
     succ_market_makers_txs: Arc<AtomicU64>,
@@ -118,9 +202,38 @@ struct Counters {
     succ_cache_root_banks_txs: Arc<AtomicU64>,
     succ_update_perp_cache_txs: Arc<AtomicU64>,
     succ_update_funding_txs: Arc<AtomicU64>,
+    succ_spoofing_txs: Arc<AtomicU64>,
+    num_spoofing_errors: Arc<AtomicU64>,
+
+    num_keeper_txs: Arc<AtomicU64>,
+    num_crank_txs: Arc<AtomicU64>,
+    num_cancel_storm_txs: Arc<AtomicU64>,
+    num_periphery_txs: Arc<AtomicU64>,
+    conf_market_makers_txs: Arc<AtomicU64>,
+    conf_keeper_txs: Arc<AtomicU64>,
+    conf_crank_txs: Arc<AtomicU64>,
+    conf_cancel_storm_txs: Arc<AtomicU64>,
+    conf_periphery_txs: Arc<AtomicU64>,
+    succ_keeper_txs: Arc<AtomicU64>,
+    succ_crank_txs: Arc<AtomicU64>,
+    succ_cancel_storm_txs: Arc<AtomicU64>,
+    succ_periphery_txs: Arc<AtomicU64>,
+
+    num_chaos_txs: Arc<AtomicU64>,
+    conf_chaos_txs: Arc<AtomicU64>,
+    succ_chaos_txs: Arc<AtomicU64>,
+
+    bytes_sent: Arc<AtomicU64>,
+    num_instructions_sent: Arc<AtomicU64>,
 
     // Errors
     errors: Arc<RwLock<HashMap<String, u64>>>,
+
+    // confirmation latencies, bounded in memory whatever the length of the run
+    latencies_ms: Arc<RwLock<LatencyHistogram>>,
+    // sends and confirmations of the last seconds
+    recent: Arc<Mutex<RateWindow>>,
+    dropped_records: DroppedRecords,
 }
 
 impl Counters {
@@ -129,8 +242,10 @@ impl Counters {
             num_confirmed_txs: self.num_confirmed_txs.load(Ordering::Relaxed),
             num_error_txs: self.num_error_txs.load(Ordering::Relaxed),
             num_timeout_txs: self.num_timeout_txs.load(Ordering::Relaxed),
+            num_expired_txs: self.num_expired_txs.load(Ordering::Relaxed),
             num_successful: self.num_successful.load(Ordering::Relaxed),
             num_sent: self.num_sent.load(Ordering::Relaxed),
+            num_send_errors: self.num_send_errors.load(Ordering::Relaxed),
 
             // sent transasctions
             num_market_makers_txs: self.num_market_makers_txs.load(Ordering::Relaxed),
@@ -155,11 +270,322 @@ impl Counters {
             succ_cache_root_banks_txs: self.succ_cache_root_banks_txs.load(Ordering::Relaxed),
             succ_update_perp_cache_txs: self.succ_update_perp_cache_txs.load(Ordering::Relaxed),
             succ_update_funding_txs: self.succ_update_funding_txs.load(Ordering::Relaxed),
+            num_spoofing_txs: self.num_spoofing_txs.load(Ordering::Relaxed),
+            succ_spoofing_txs: self.succ_spoofing_txs.load(Ordering::Relaxed),
+            num_spoofing_errors: self.num_spoofing_errors.load(Ordering::Relaxed),
+            num_keeper_txs: self.num_keeper_txs.load(Ordering::Relaxed),
+            num_crank_txs: self.num_crank_txs.load(Ordering::Relaxed),
+            conf_market_makers_txs: self.conf_market_makers_txs.load(Ordering::Relaxed),
+            conf_keeper_txs: self.conf_keeper_txs.load(Ordering::Relaxed),
+            conf_crank_txs: self.conf_crank_txs.load(Ordering::Relaxed),
+            succ_keeper_txs: self.succ_keeper_txs.load(Ordering::Relaxed),
+            succ_crank_txs: self.succ_crank_txs.load(Ordering::Relaxed),
+            num_cancel_storm_txs: self.num_cancel_storm_txs.load(Ordering::Relaxed),
+            conf_cancel_storm_txs: self.conf_cancel_storm_txs.load(Ordering::Relaxed),
+            succ_cancel_storm_txs: self.succ_cancel_storm_txs.load(Ordering::Relaxed),
+            num_periphery_txs: self.num_periphery_txs.load(Ordering::Relaxed),
+            conf_periphery_txs: self.conf_periphery_txs.load(Ordering::Relaxed),
+            succ_periphery_txs: self.succ_periphery_txs.load(Ordering::Relaxed),
+            num_chaos_txs: self.num_chaos_txs.load(Ordering::Relaxed),
+            conf_chaos_txs: self.conf_chaos_txs.load(Ordering::Relaxed),
+            succ_chaos_txs: self.succ_chaos_txs.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            num_instructions_sent: self.num_instructions_sent.load(Ordering::Relaxed),
             errors: self.errors.read().await.clone(),
         }
     }
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorCount {
+    pub error: String,
+    pub count: u64,
+}
+
+/// Aggregated results of the run, rates are in percent of the sent transactions
+#[derive(Clone, Debug, Serialize)]
+pub struct RunSummary {
+    pub elapsed_secs: u64,
+    pub expected_market_maker_txs: u64,
+    pub sent: u64,
+    pub confirmed: u64,
+    pub successful: u64,
+    pub errors: u64,
+    pub timed_out: u64,
+    // the chain passed the last valid block height of their blockhash, not counted as timed out
+    pub expired: u64,
+    // could not be handed over to the TPU, not counted as sent
+    pub send_errors: u64,
+    // sent but neither confirmed, expired nor timed out yet
+    pub pending: u64,
+    pub market_maker_sent: u64,
+    pub market_maker_successful: u64,
+    // spoofing transactions included
+    pub market_maker_confirmation_rate: f64,
+    pub keeper_sent: u64,
+    pub keeper_successful: u64,
+    pub keeper_confirmation_rate: f64,
+    pub crank_sent: u64,
+    pub crank_successful: u64,
+    pub crank_confirmation_rate: f64,
+    pub cancel_storm_sent: u64,
+    pub cancel_storm_successful: u64,
+    pub cancel_storm_confirmation_rate: f64,
+    pub periphery_sent: u64,
+    pub periphery_successful: u64,
+    pub periphery_confirmation_rate: f64,
+    // chaos transactions are not part of the other counts
+    pub chaos_sent: u64,
+    pub chaos_confirmed: u64,
+    // packing of the transactions, quotes per block depend on their size
+    pub bytes_sent: u64,
+    pub bytes_per_second: f64,
+    pub average_tx_size_bytes: f64,
+    pub average_instructions_per_tx: f64,
+    pub confirmation_rate: f64,
+    pub success_rate: f64,
+    pub error_rate: f64,
+    pub timeout_rate: f64,
+    pub expiry_rate: f64,
+    pub latency_ms: LatencyPercentiles,
+    // rates of the last seconds of the run
+    pub recent: WindowedRates,
+    pub top_errors: Vec<ErrorCount>,
+    // lost by the writers and aggregators which lagged behind, the other counts miss them too
+    pub dropped_records: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LatencyPercentiles {
+    pub p50: Option<u64>,
+    pub p90: Option<u64>,
+    pub p95: Option<u64>,
+    pub p99: Option<u64>,
+}
+
+// 1ms buckets up to 1s, 10ms up to 10s, 100ms up to 2min and a last bucket for the rest
+const LATENCY_BUCKETS: usize = 1000 + 900 + 1100 + 1;
+
+/// Streaming histogram of latencies in milliseconds, percentiles are rounded down to the bucket
+#[derive(Clone, Debug)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; LATENCY_BUCKETS],
+            total: 0,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    fn bucket(ms: u64) -> usize {
+        match ms {
+            0..=999 => ms as usize,
+            1_000..=9_999 => 1000 + (ms as usize - 1_000) / 10,
+            10_000..=119_999 => 1900 + (ms as usize - 10_000) / 100,
+            _ => LATENCY_BUCKETS - 1,
+        }
+    }
+
+    // lowest latency of the bucket
+    fn bucket_start(bucket: usize) -> u64 {
+        match bucket {
+            0..=999 => bucket as u64,
+            1000..=1899 => 1_000 + (bucket as u64 - 1000) * 10,
+            _ => 10_000 + (bucket as u64 - 1900) * 100,
+        }
+    }
+
+    pub fn record(&mut self, ms: u64) {
+        self.counts[Self::bucket(ms)] += 1;
+        self.total += 1;
+    }
+
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        if self.total == 0 {
+            return None;
+        }
+        let rank = (((percentile / 100.0) * self.total as f64).ceil() as u64).clamp(1, self.total);
+        let mut seen = 0;
+        self.counts.iter().enumerate().find_map(|(bucket, count)| {
+            seen += count;
+            (seen >= rank).then(|| Self::bucket_start(bucket))
+        })
+    }
+}
+
+/// Length of the window of the recent rates
+pub const RATE_WINDOW_SECS: u64 = 60;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WindowedRates {
+    // shorter than the window during its first seconds
+    pub window_secs: u64,
+    pub sent_per_second: f64,
+    pub confirmed_per_second: f64,
+    pub successful_per_second: f64,
+    // confirmations of the window relative to its sends, the confirmations lag behind the sends
+    pub confirmation_rate: f64,
+    // transactions of the window, the rates of a few transactions are not significant
+    pub sent: u64,
+    pub confirmed: u64,
+    pub p95_latency_ms: Option<u64>,
+}
+
+// transactions of one second of a rate window
+#[derive(Clone, Debug, Default)]
+struct SecondCounts {
+    second: u64,
+    sent: u64,
+    confirmed: u64,
+    successful: u64,
+    // confirmation latencies by bucket of the latency histogram
+    latencies_ms: BTreeMap<usize, u64>,
+}
+
+/// Sent, confirmed and successful transactions counted per second over the last seconds, so that
+/// the recent rates are not averaged with the whole run
+#[derive(Clone, Debug)]
+pub struct RateWindow {
+    window_secs: u64,
+    // seconds kept, longer than the window when the alerts look further back
+    kept_secs: u64,
+    // the oldest second first
+    seconds: VecDeque<SecondCounts>,
+}
+
+impl Default for RateWindow {
+    fn default() -> Self {
+        Self::new(RATE_WINDOW_SECS)
+    }
+}
+
+impl RateWindow {
+    pub fn new(window_secs: u64) -> Self {
+        let window_secs = window_secs.max(1);
+        Self {
+            window_secs,
+            kept_secs: window_secs,
+            seconds: VecDeque::new(),
+        }
+    }
+
+    /// keeps the seconds of a longer window than the one of the recent rates
+    pub fn keep(&mut self, secs: u64) {
+        self.kept_secs = self.kept_secs.max(secs);
+    }
+
+    // counts of the second, the seconds which are not kept anymore are dropped. A record of an
+    // earlier second than the last one is counted in the last one
+    fn second_mut(&mut self, second: u64) -> &mut SecondCounts {
+        while self
+            .seconds
+            .front()
+            .map_or(false, |counts| counts.second + self.kept_secs <= second)
+        {
+            self.seconds.pop_front();
+        }
+        if self
+            .seconds
+            .back()
+            .map_or(true, |counts| counts.second < second)
+        {
+            self.seconds.push_back(SecondCounts {
+                second,
+                ..SecondCounts::default()
+            });
+        }
+        self.seconds.back_mut().unwrap()
+    }
+
+    pub fn record_sent(&mut self, second: u64) {
+        self.second_mut(second).sent += 1;
+    }
+
+    pub fn record_confirmed(&mut self, second: u64, successful: bool) {
+        let counts = self.second_mut(second);
+        counts.confirmed += 1;
+        if successful {
+            counts.successful += 1;
+        }
+    }
+
+    pub fn record_latency(&mut self, second: u64, ms: u64) {
+        *self
+            .second_mut(second)
+            .latencies_ms
+            .entry(LatencyHistogram::bucket(ms))
+            .or_default() += 1;
+    }
+
+    /// rates over the window ending with the given second, seconds are counted from the start
+    pub fn rates(&self, second: u64) -> WindowedRates {
+        self.rates_over(second, self.window_secs)
+    }
+
+    /// rates over a window of the given length ending with the given second, at most as long as
+    /// the seconds kept
+    pub fn rates_over(&self, second: u64, window_secs: u64) -> WindowedRates {
+        let window_secs = window_secs.clamp(1, self.kept_secs);
+        let (mut sent, mut confirmed, mut successful) = (0, 0, 0);
+        let mut latencies = LatencyHistogram::default();
+        for counts in self
+            .seconds
+            .iter()
+            .filter(|counts| counts.second + window_secs > second && counts.second <= second)
+        {
+            sent += counts.sent;
+            confirmed += counts.confirmed;
+            successful += counts.successful;
+            for (bucket, count) in &counts.latencies_ms {
+                latencies.counts[*bucket] += count;
+                latencies.total += count;
+            }
+        }
+        let window_secs = (second + 1).min(window_secs);
+        WindowedRates {
+            window_secs,
+            sent_per_second: average(sent, window_secs),
+            confirmed_per_second: average(confirmed, window_secs),
+            successful_per_second: average(successful, window_secs),
+            confirmation_rate: percent(confirmed, sent),
+            sent,
+            confirmed,
+            p95_latency_ms: latencies.percentile(95.0),
+        }
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+fn average(sum: u64, count: u64) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        sum as f64 / count as f64
+    }
+}
+
+fn per_second(count: u64, elapsed: std::time::Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs == 0.0 {
+        0.0
+    } else {
+        count as f64 / secs
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MangoSimulationStats {
     recv_limit: usize,
@@ -183,70 +609,184 @@ impl MangoSimulationStats {
         }
     }
 
+    /// counter of the records the writers and aggregators of the run lost
+    pub fn dropped_records(&self) -> DroppedRecords {
+        self.counters.dropped_records.clone()
+    }
+
     pub fn update_from_tx_status_stream(
         &self,
         tx_confirm_record_reciever: tokio::sync::broadcast::Receiver<TransactionConfirmRecord>,
     ) -> JoinHandle<()> {
-        let counters = self.counters.clone();
-        let regex = regex::Regex::new(r"Error processing Instruction \d+: ").unwrap();
+        let stats = self.clone();
         tokio::spawn(async move {
             let mut tx_confirm_record_reciever = tx_confirm_record_reciever;
-            while let Ok(tx_data) = tx_confirm_record_reciever.recv().await {
-                if tx_data.confirmed_at.is_some() {
-                    counters.num_confirmed_txs.fetch_add(1, Ordering::Relaxed);
-                    if let Some(error) = tx_data.error {
-                        let error = regex.replace_all(&error, "").to_string();
-                        counters.num_error_txs.fetch_add(1, Ordering::Relaxed);
-                        let mut lock = counters.errors.write().await;
-                        if let Some(value) = lock.get_mut(&error) {
-                            *value += 1;
-                        } else {
-                            lock.insert(error, 1);
-                        }
-                    } else {
-                        counters.num_successful.fetch_add(1, Ordering::Relaxed);
-
-                        if let Some(keeper_instruction) = tx_data.keeper_instruction {
-                            match keeper_instruction {
-                                KeeperInstruction::CachePrice => counters
-                                    .succ_cache_price_txs
-                                    .fetch_add(1, Ordering::Relaxed),
-                                KeeperInstruction::CacheRootBanks => counters
-                                    .succ_cache_root_banks_txs
-                                    .fetch_add(1, Ordering::Relaxed),
-                                KeeperInstruction::ConsumeEvents => counters
-                                    .succ_consume_events_txs
-                                    .fetch_add(1, Ordering::Relaxed),
-                                KeeperInstruction::UpdateAndCacheQuoteRootBank => counters
-                                    .succ_update_and_cache_quote_bank_txs
-                                    .fetch_add(1, Ordering::Relaxed),
-                                KeeperInstruction::UpdateFunding => counters
-                                    .succ_update_funding_txs
-                                    .fetch_add(1, Ordering::Relaxed),
-                                KeeperInstruction::UpdatePerpCache => counters
-                                    .succ_update_perp_cache_txs
-                                    .fetch_add(1, Ordering::Relaxed),
-                                KeeperInstruction::UpdateRootBanks => counters
-                                    .succ_update_root_banks_txs
-                                    .fetch_add(1, Ordering::Relaxed),
-                            };
-                        } else {
-                            counters
-                                .succ_market_makers_txs
-                                .fetch_add(1, Ordering::Relaxed);
-                        }
+            loop {
+                let tx_data = match tx_confirm_record_reciever.recv().await {
+                    Ok(tx_data) => tx_data,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("stats lagged behind, {n} records were not counted");
+                        stats.counters.dropped_records.add(n);
+                        continue;
                     }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                stats.record_confirmation(tx_data).await;
+            }
+        })
+    }
+
+    // seconds since the start of the stats, the unit of the recent rates
+    fn second(&self) -> u64 {
+        self.instant.elapsed().as_secs()
+    }
+
+    /// counts the outcome of a sent transaction, confirmed, expired or timed out
+    pub async fn record_confirmation(&self, tx_data: TransactionConfirmRecord) {
+        let counters = &self.counters;
+        if tx_data.chaos.is_some() {
+            if tx_data.confirmed_at.is_some() {
+                counters.conf_chaos_txs.fetch_add(1, Ordering::Relaxed);
+                if tx_data.error.is_none() {
+                    counters.succ_chaos_txs.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            return;
+        }
+        if let Some(confirmed_at_us) = tx_data.confirmed_at_us {
+            counters.num_confirmed_txs.fetch_add(1, Ordering::Relaxed);
+            let (confirmed, successful) = match tx_data.tx_type {
+                TransactionType::MarketMaker => (
+                    &counters.conf_market_makers_txs,
+                    // market maker successes are counted with the spoofing ones below
+                    None,
+                ),
+                TransactionType::Keeper => {
+                    (&counters.conf_keeper_txs, Some(&counters.succ_keeper_txs))
+                }
+                TransactionType::Crank => {
+                    (&counters.conf_crank_txs, Some(&counters.succ_crank_txs))
+                }
+                TransactionType::CancelStorm => (
+                    &counters.conf_cancel_storm_txs,
+                    Some(&counters.succ_cancel_storm_txs),
+                ),
+                TransactionType::Periphery => (
+                    &counters.conf_periphery_txs,
+                    Some(&counters.succ_periphery_txs),
+                ),
+            };
+            confirmed.fetch_add(1, Ordering::Relaxed);
+            // microsecond timestamps, confirmations often differ by less than a second
+            let latency_ms = (confirmed_at_us - tx_data.sent_at_us).max(0) / 1000;
+            {
+                let mut recent = counters.recent.lock().unwrap();
+                recent.record_confirmed(self.second(), tx_data.error.is_none());
+                recent.record_latency(self.second(), latency_ms as u64);
+            }
+            if let (Some(successful), None) = (successful, &tx_data.error) {
+                successful.fetch_add(1, Ordering::Relaxed);
+            }
+            counters
+                .latencies_ms
+                .write()
+                .await
+                .record(latency_ms as u64);
+            if let Some(error) = tx_data.error {
+                let error = INSTRUCTION_PREFIX.replace_all(&error, "").to_string();
+                counters.num_error_txs.fetch_add(1, Ordering::Relaxed);
+                if tx_data.spoofing {
+                    counters.num_spoofing_errors.fetch_add(1, Ordering::Relaxed);
+                }
+                let mut lock = counters.errors.write().await;
+                if let Some(value) = lock.get_mut(&error) {
+                    *value += 1;
                 } else {
-                    counters.num_timeout_txs.fetch_add(1, Ordering::Relaxed);
+                    lock.insert(error, 1);
+                }
+            } else {
+                counters.num_successful.fetch_add(1, Ordering::Relaxed);
+
+                if let Some(keeper_instruction) = tx_data.keeper_instruction {
+                    match keeper_instruction {
+                        KeeperInstruction::CachePrice => counters
+                            .succ_cache_price_txs
+                            .fetch_add(1, Ordering::Relaxed),
+                        KeeperInstruction::CacheRootBanks => counters
+                            .succ_cache_root_banks_txs
+                            .fetch_add(1, Ordering::Relaxed),
+                        KeeperInstruction::ConsumeEvents => counters
+                            .succ_consume_events_txs
+                            .fetch_add(1, Ordering::Relaxed),
+                        KeeperInstruction::UpdateAndCacheQuoteRootBank => counters
+                            .succ_update_and_cache_quote_bank_txs
+                            .fetch_add(1, Ordering::Relaxed),
+                        KeeperInstruction::UpdateFunding => counters
+                            .succ_update_funding_txs
+                            .fetch_add(1, Ordering::Relaxed),
+                        KeeperInstruction::UpdatePerpCache => counters
+                            .succ_update_perp_cache_txs
+                            .fetch_add(1, Ordering::Relaxed),
+                        KeeperInstruction::UpdateRootBanks => counters
+                            .succ_update_root_banks_txs
+                            .fetch_add(1, Ordering::Relaxed),
+                    };
+                } else if tx_data.spoofing {
+                    counters.succ_spoofing_txs.fetch_add(1, Ordering::Relaxed);
+                } else if tx_data.tx_type == TransactionType::MarketMaker {
+                    counters
+                        .succ_market_makers_txs
+                        .fetch_add(1, Ordering::Relaxed);
                 }
             }
-        })
+        } else if tx_data.expired {
+            counters.num_expired_txs.fetch_add(1, Ordering::Relaxed);
+        } else {
+            counters.num_timeout_txs.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
-    pub fn inc_send(&self, keeper_instruction: &Option<KeeperInstruction>) {
+    pub fn inc_send(&self, record: &TransactionSendRecord) {
+        if record.chaos.is_some() {
+            self.counters.num_chaos_txs.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
         self.counters.num_sent.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .recent
+            .lock()
+            .unwrap()
+            .record_sent(self.second());
+        self.counters
+            .bytes_sent
+            .fetch_add(record.tx_size_bytes, Ordering::Relaxed);
+        self.counters
+            .num_instructions_sent
+            .fetch_add(record.num_instructions, Ordering::Relaxed);
+        match record.tx_type {
+            TransactionType::Keeper => {
+                self.counters.num_keeper_txs.fetch_add(1, Ordering::Relaxed);
+            }
+            TransactionType::Crank => {
+                self.counters.num_crank_txs.fetch_add(1, Ordering::Relaxed);
+            }
+            TransactionType::CancelStorm => {
+                self.counters
+                    .num_cancel_storm_txs
+                    .fetch_add(1, Ordering::Relaxed);
+                // not part of the market maker counts
+                return;
+            }
+            TransactionType::Periphery => {
+                self.counters
+                    .num_periphery_txs
+                    .fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            TransactionType::MarketMaker => {}
+        }
 
-        if let Some(keeper_instruction) = keeper_instruction {
+        if let Some(keeper_instruction) = &record.keeper_instruction {
             match keeper_instruction {
                 KeeperInstruction::CachePrice => self
                     .counters
@@ -277,6 +817,10 @@ impl MangoSimulationStats {
                     .num_update_root_banks_txs
                     .fetch_add(1, Ordering::Relaxed),
             };
+        } else if record.spoofing {
+            self.counters
+                .num_spoofing_txs
+                .fetch_add(1, Ordering::Relaxed);
         } else {
             self.counters
                 .num_market_makers_txs
@@ -284,6 +828,103 @@ impl MangoSimulationStats {
         }
     }
 
+    /// rates of the last RATE_WINDOW_SECS seconds
+    pub fn recent_rates(&self) -> WindowedRates {
+        self.counters.recent.lock().unwrap().rates(self.second())
+    }
+
+    /// rates of the last seconds, the window is kept once it was asked for with keep_rates_for
+    pub fn rates_over(&self, window: Duration) -> WindowedRates {
+        self.counters
+            .recent
+            .lock()
+            .unwrap()
+            .rates_over(self.second(), window.as_secs())
+    }
+
+    pub fn keep_rates_for(&self, window: Duration) {
+        self.counters.recent.lock().unwrap().keep(window.as_secs());
+    }
+
+    /// computes the summary from the live counters, can be called at any time during the run
+    pub async fn summary(&self) -> RunSummary {
+        let counters = self.counters.to_na_counters().await;
+        let latencies_ms = self.counters.latencies_ms.read().await;
+        RunSummary {
+            elapsed_secs: self.instant.elapsed().as_secs(),
+            expected_market_maker_txs: self.recv_limit as u64,
+            sent: counters.num_sent,
+            confirmed: counters.num_confirmed_txs,
+            successful: counters.num_successful,
+            errors: counters.num_error_txs,
+            timed_out: counters.num_timeout_txs,
+            expired: counters.num_expired_txs,
+            send_errors: counters.num_send_errors,
+            pending: counters.num_sent.saturating_sub(
+                counters.num_confirmed_txs + counters.num_timeout_txs + counters.num_expired_txs,
+            ),
+            market_maker_sent: counters.num_market_makers_txs,
+            market_maker_successful: counters.succ_market_makers_txs,
+            market_maker_confirmation_rate: percent(
+                counters.conf_market_makers_txs,
+                counters.num_market_makers_txs + counters.num_spoofing_txs,
+            ),
+            keeper_sent: counters.num_keeper_txs,
+            keeper_successful: counters.succ_keeper_txs,
+            keeper_confirmation_rate: percent(counters.conf_keeper_txs, counters.num_keeper_txs),
+            crank_sent: counters.num_crank_txs,
+            crank_successful: counters.succ_crank_txs,
+            crank_confirmation_rate: percent(counters.conf_crank_txs, counters.num_crank_txs),
+            cancel_storm_sent: counters.num_cancel_storm_txs,
+            cancel_storm_successful: counters.succ_cancel_storm_txs,
+            cancel_storm_confirmation_rate: percent(
+                counters.conf_cancel_storm_txs,
+                counters.num_cancel_storm_txs,
+            ),
+            periphery_sent: counters.num_periphery_txs,
+            periphery_successful: counters.succ_periphery_txs,
+            periphery_confirmation_rate: percent(
+                counters.conf_periphery_txs,
+                counters.num_periphery_txs,
+            ),
+            chaos_sent: counters.num_chaos_txs,
+            chaos_confirmed: counters.conf_chaos_txs,
+            bytes_sent: counters.bytes_sent,
+            bytes_per_second: per_second(counters.bytes_sent, self.instant.elapsed()),
+            average_tx_size_bytes: average(counters.bytes_sent, counters.num_sent),
+            average_instructions_per_tx: average(counters.num_instructions_sent, counters.num_sent),
+            confirmation_rate: percent(counters.num_confirmed_txs, counters.num_sent),
+            success_rate: percent(counters.num_successful, counters.num_sent),
+            error_rate: percent(counters.num_error_txs, counters.num_sent),
+            timeout_rate: percent(counters.num_timeout_txs, counters.num_sent),
+            expiry_rate: percent(counters.num_expired_txs, counters.num_sent),
+            latency_ms: LatencyPercentiles {
+                p50: latencies_ms.percentile(50.0),
+                p90: latencies_ms.percentile(90.0),
+                p95: latencies_ms.percentile(95.0),
+                p99: latencies_ms.percentile(99.0),
+            },
+            recent: self.recent_rates(),
+            top_errors: counters
+                .errors
+                .into_iter()
+                .sorted_by(|x, y| y.1.cmp(&x.1))
+                .take(5)
+                .map(|(error, count)| ErrorCount { error, count })
+                .collect(),
+            dropped_records: self.counters.dropped_records.count(),
+        }
+    }
+
+    pub fn inc_send_error(&self, send_error: &TransactionSendError) {
+        if send_error.chaos.is_some() {
+            return;
+        }
+        self.counters
+            .num_send_errors
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
     pub async fn report(&mut self, is_final: bool, name: &'static str) {
         let time_diff = std::time::Instant::now() - self.instant;
         let counters = self.counters.to_na_counters().await;
@@ -307,6 +948,10 @@ impl MangoSimulationStats {
             "Number of transactions Sent:({}) (including keeper) (Diff:({}))",
             counters.num_sent, diff.num_sent,
         );
+        println!(
+            "Number of transactions which could not be sent:({}) (Diff:({}))",
+            counters.num_send_errors, diff.num_send_errors,
+        );
 
         println!(
             "Market Maker transactions : Sent({}), Successful({}) (Diff : Sent({}), Successful({}))",
@@ -315,6 +960,77 @@ impl MangoSimulationStats {
             diff.num_market_makers_txs,
             diff.succ_market_makers_txs,
         );
+        if counters.num_spoofing_txs > 0 {
+            println!(
+                "Spoofing transactions : Sent({}), Successful({}), Errors({}) (Diff : Sent({}), Successful({}), Errors({}))",
+                counters.num_spoofing_txs,
+                counters.succ_spoofing_txs,
+                counters.num_spoofing_errors,
+                diff.num_spoofing_txs,
+                diff.succ_spoofing_txs,
+                diff.num_spoofing_errors,
+            );
+        }
+        if counters.num_chaos_txs > 0 {
+            println!(
+                "Chaos transactions : Sent({}), Confirmed({}), Successful({}) (Diff : Sent({}), Confirmed({}))",
+                counters.num_chaos_txs,
+                counters.conf_chaos_txs,
+                counters.succ_chaos_txs,
+                diff.num_chaos_txs,
+                diff.conf_chaos_txs,
+            );
+        }
+        println!(
+            "Bytes sent : {} ({:.0} bytes/s), average transaction size {:.0} bytes with {:.2} instructions (Diff : {} bytes in {} transactions)",
+            counters.bytes_sent,
+            per_second(counters.bytes_sent, time_diff),
+            average(counters.bytes_sent, counters.num_sent),
+            average(counters.num_instructions_sent, counters.num_sent),
+            diff.bytes_sent,
+            diff.num_sent,
+        );
+        for (name, sent, confirmed, successful, diff_sent, diff_confirmed) in [
+            (
+                "Keeper",
+                counters.num_keeper_txs,
+                counters.conf_keeper_txs,
+                counters.succ_keeper_txs,
+                diff.num_keeper_txs,
+                diff.conf_keeper_txs,
+            ),
+            (
+                "Crank",
+                counters.num_crank_txs,
+                counters.conf_crank_txs,
+                counters.succ_crank_txs,
+                diff.num_crank_txs,
+                diff.conf_crank_txs,
+            ),
+            (
+                "Cancel storm",
+                counters.num_cancel_storm_txs,
+                counters.conf_cancel_storm_txs,
+                counters.succ_cancel_storm_txs,
+                diff.num_cancel_storm_txs,
+                diff.conf_cancel_storm_txs,
+            ),
+            (
+                "Periphery",
+                counters.num_periphery_txs,
+                counters.conf_periphery_txs,
+                counters.succ_periphery_txs,
+                diff.num_periphery_txs,
+                diff.conf_periphery_txs,
+            ),
+        ] {
+            if sent > 0 {
+                println!(
+                    "{name} transactions : Sent({sent}), Confirmed({confirmed}), Successful({successful}), Landed({:.2}%) (Diff : Sent({diff_sent}), Confirmed({diff_confirmed}))",
+                    percent(confirmed, sent),
+                );
+            }
+        }
         println!(
             "Keeper Cosume Events : Sent({}), Successful({}) (Diff : Sent({}), Successful({}))",
             counters.num_consume_events_txs,
@@ -362,6 +1078,19 @@ impl MangoSimulationStats {
             diff.succ_update_funding_txs
         );
 
+        let recent = self.recent_rates();
+        println!(
+            "Last {}s : Sent({:.2}/s), Confirmed({:.2}/s), Successful({:.2}/s), Landed({:.2}%)",
+            recent.window_secs,
+            recent.sent_per_second,
+            recent.confirmed_per_second,
+            recent.successful_per_second,
+            recent.confirmation_rate,
+        );
+        let (latency_p50_ms, latency_p95_ms) = {
+            let latencies_ms = self.counters.latencies_ms.read().await;
+            (latencies_ms.percentile(50.0), latencies_ms.percentile(95.0))
+        };
         println!(
             "Transactions confirmed : {}%",
             (counters.num_confirmed_txs * 100)
@@ -380,6 +1109,12 @@ impl MangoSimulationStats {
                 .checked_div(counters.num_sent)
                 .unwrap_or(0)
         );
+        println!(
+            "Transactions expired : {}%",
+            (counters.num_expired_txs * 100)
+                .checked_div(counters.num_sent)
+                .unwrap_or(0)
+        );
         let top_5_errors = counters
             .errors
             .iter()
@@ -398,10 +1133,12 @@ impl MangoSimulationStats {
             datapoint_info!(
                 name,
                 ("num_txs_sent", diff.num_sent, i64),
+                ("num_send_errors", diff.num_send_errors, i64),
                 ("num_confirmed_txs", diff.num_confirmed_txs, i64),
                 ("num_successful_txs", diff.num_successful, i64),
                 ("num_error_txs", diff.num_error_txs, i64),
                 ("num_timeout_txs", diff.num_timeout_txs, i64),
+                ("num_expired_txs", diff.num_expired_txs, i64),
                 (
                     "percent_confirmed_txs",
                     (diff.num_confirmed_txs * 100)
@@ -492,6 +1229,39 @@ impl MangoSimulationStats {
                     diff.succ_update_funding_txs,
                     i64
                 ),
+                ("spoofing_sent", diff.num_spoofing_txs, i64),
+                ("spoofing_success", diff.succ_spoofing_txs, i64),
+                ("spoofing_errors", diff.num_spoofing_errors, i64),
+                ("keeper_sent", diff.num_keeper_txs, i64),
+                ("keeper_confirmed", diff.conf_keeper_txs, i64),
+                ("crank_sent", diff.num_crank_txs, i64),
+                ("crank_confirmed", diff.conf_crank_txs, i64),
+                ("cancel_storm_sent", diff.num_cancel_storm_txs, i64),
+                ("cancel_storm_confirmed", diff.conf_cancel_storm_txs, i64),
+                ("periphery_sent", diff.num_periphery_txs, i64),
+                ("periphery_confirmed", diff.conf_periphery_txs, i64),
+                ("chaos_sent", diff.num_chaos_txs, i64),
+                ("chaos_confirmed", diff.conf_chaos_txs, i64),
+                ("bytes_sent", diff.bytes_sent, i64),
+                (
+                    "average_tx_size_bytes",
+                    average(diff.bytes_sent, diff.num_sent),
+                    f64
+                ),
+                (
+                    "average_instructions_per_tx",
+                    average(diff.num_instructions_sent, diff.num_sent),
+                    f64
+                ),
+                ("recent_sent_per_second", recent.sent_per_second, f64),
+                (
+                    "recent_confirmed_per_second",
+                    recent.confirmed_per_second,
+                    f64
+                ),
+                ("recent_confirmation_rate", recent.confirmation_rate, f64),
+                ("latency_p50_ms", latency_p50_ms.unwrap_or_default(), i64),
+                ("latency_p95_ms", latency_p95_ms.unwrap_or_default(), i64),
                 ("top_5_errors", errors_to_print, String)
             );
         }
@@ -0,0 +1,335 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use log::{error, info};
+use serde::Serialize;
+use tokio::{
+    sync::{broadcast, mpsc::UnboundedReceiver},
+    task::JoinHandle,
+};
+
+use crate::{
+    states::{BlockData, TransactionConfirmRecord, TransactionSendRecord},
+    stats::DroppedRecords,
+};
+
+// rows are written in batches, a batch is flushed as soon as the channel is drained
+const MAX_BATCH_SIZE: usize = 1024;
+
+const SEND_RECORDS_TABLE: &str = "transaction_send_records";
+const CONFIRM_RECORDS_TABLE: &str = "transaction_confirm_records";
+const BLOCK_DATA_TABLE: &str = "block_data";
+
+/// A row stored in the database, records are kept as json so that adding
+/// columns to the csv records does not require a migration
+pub struct Row {
+    pub key: String,
+    pub slot: i64,
+    pub data: String,
+}
+
+impl Row {
+    fn new<T: Serialize>(key: String, slot: u64, record: &T) -> Self {
+        Self {
+            key,
+            slot: slot as i64,
+            data: serde_json::to_string(record).unwrap(),
+        }
+    }
+}
+
+/// Storage which receives records incrementally while the simulation is running
+#[async_trait]
+pub trait RecordSink: Send + Sync {
+    async fn write_rows(&self, table: &'static str, rows: Vec<Row>) -> anyhow::Result<()>;
+
+    async fn write_send_records(&self, records: &[TransactionSendRecord]) -> anyhow::Result<()> {
+        let rows = records
+            .iter()
+            .map(|r| Row::new(r.signature.to_string(), r.sent_slot, r))
+            .collect();
+        self.write_rows(SEND_RECORDS_TABLE, rows).await
+    }
+
+    async fn write_confirm_records(
+        &self,
+        records: &[TransactionConfirmRecord],
+    ) -> anyhow::Result<()> {
+        let rows = records
+            .iter()
+            .map(|r| {
+                Row::new(
+                    r.signature.clone(),
+                    r.confirmed_slot.unwrap_or(r.sent_slot),
+                    r,
+                )
+            })
+            .collect();
+        self.write_rows(CONFIRM_RECORDS_TABLE, rows).await
+    }
+
+    async fn write_block_data(&self, records: &[BlockData]) -> anyhow::Result<()> {
+        let rows = records
+            .iter()
+            .map(|r| Row::new(r.block_hash.clone(), r.block_slot, r))
+            .collect();
+        self.write_rows(BLOCK_DATA_TABLE, rows).await
+    }
+}
+
+pub struct SqliteSink {
+    connection: Arc<Mutex<rusqlite::Connection>>,
+}
+
+impl SqliteSink {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let connection = rusqlite::Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        for table in [SEND_RECORDS_TABLE, CONFIRM_RECORDS_TABLE, BLOCK_DATA_TABLE] {
+            connection.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {table} (key TEXT NOT NULL, slot INTEGER NOT NULL, data TEXT NOT NULL)"
+                ),
+                [],
+            )?;
+        }
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+}
+
+#[async_trait]
+impl RecordSink for SqliteSink {
+    async fn write_rows(&self, table: &'static str, rows: Vec<Row>) -> anyhow::Result<()> {
+        // rusqlite is blocking, the writes run on the blocking pool instead of a worker thread,
+        // which also works on a current thread runtime
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap();
+            let transaction = connection.transaction()?;
+            {
+                let mut statement = transaction.prepare_cached(&format!(
+                    "INSERT INTO {table} (key, slot, data) VALUES (?1, ?2, ?3)"
+                ))?;
+                for row in &rows {
+                    statement.execute(rusqlite::params![row.key, row.slot, row.data])?;
+                }
+            }
+            transaction.commit()?;
+            Ok(())
+        })
+        .await?
+    }
+}
+
+pub struct PostgresSink {
+    client: tokio_postgres::Client,
+}
+
+impl PostgresSink {
+    pub async fn connect(db_url: &str) -> anyhow::Result<Self> {
+        let (client, connection) = tokio_postgres::connect(db_url, tokio_postgres::NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                error!("postgres connection error {e:?}");
+            }
+        });
+        for table in [SEND_RECORDS_TABLE, CONFIRM_RECORDS_TABLE, BLOCK_DATA_TABLE] {
+            client
+                .batch_execute(&format!(
+                    "CREATE TABLE IF NOT EXISTS {table} (key TEXT NOT NULL, slot BIGINT NOT NULL, data JSONB NOT NULL)"
+                ))
+                .await?;
+        }
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl RecordSink for PostgresSink {
+    async fn write_rows(&self, table: &'static str, rows: Vec<Row>) -> anyhow::Result<()> {
+        let statement = self
+            .client
+            .prepare(&format!(
+                "INSERT INTO {table} (key, slot, data) SELECT * FROM UNNEST($1::text[], $2::bigint[], $3::text[]::jsonb[])"
+            ))
+            .await?;
+        let keys = rows.iter().map(|r| r.key.as_str()).collect::<Vec<_>>();
+        let slots = rows.iter().map(|r| r.slot).collect::<Vec<_>>();
+        let data = rows.iter().map(|r| r.data.as_str()).collect::<Vec<_>>();
+        self.client
+            .execute(&statement, &[&keys, &slots, &data])
+            .await?;
+        Ok(())
+    }
+}
+
+/// opens the sink matching the url scheme, sqlite://path or postgres://...
+pub async fn connect(db_url: &str) -> anyhow::Result<Arc<dyn RecordSink>> {
+    if let Some(path) = db_url.strip_prefix("sqlite://") {
+        info!("writing records to sqlite database {path}");
+        Ok(Arc::new(SqliteSink::open(path)?))
+    } else if db_url.starts_with("postgres://") || db_url.starts_with("postgresql://") {
+        info!("writing records to postgres");
+        Ok(Arc::new(PostgresSink::connect(db_url).await?))
+    } else {
+        anyhow::bail!("unsupported database url {db_url}, expected sqlite:// or postgres://")
+    }
+}
+
+/// forwards the send records to the returned receiver as they come, the sink writes a copy of
+/// them from its own channel so that a slow database does not hold back the confirmation. The
+/// records the sink falls behind on are counted as dropped
+pub fn start_send_records_sink(
+    sink: Arc<dyn RecordSink>,
+    tx_record_rx: UnboundedReceiver<TransactionSendRecord>,
+    record_buffer: usize,
+    dropped_records: DroppedRecords,
+) -> (
+    UnboundedReceiver<TransactionSendRecord>,
+    Vec<JoinHandle<()>>,
+) {
+    let (forward_sx, forward_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (sink_sx, mut sink_rx) = broadcast::channel(record_buffer);
+    let forward_jh = tokio::spawn(async move {
+        let mut tx_record_rx = tx_record_rx;
+        while let Some(record) = tx_record_rx.recv().await {
+            let _ = sink_sx.send(record.clone());
+            let _ = forward_sx.send(record);
+        }
+    });
+    let sink_jh = tokio::spawn(async move {
+        while let Some(batch) = drain_batch(&mut sink_rx, &dropped_records).await {
+            if let Err(e) = sink.write_send_records(&batch).await {
+                error!("could not write send records {e:?}");
+            }
+        }
+    });
+    (forward_rx, vec![forward_jh, sink_jh])
+}
+
+async fn drain_batch<T: Clone>(
+    rx: &mut broadcast::Receiver<T>,
+    dropped_records: &DroppedRecords,
+) -> Option<Vec<T>> {
+    let mut batch = loop {
+        match rx.recv().await {
+            Ok(record) => break vec![record],
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                error!("record sink lagged behind, {n} records were lost");
+                dropped_records.add(n);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    };
+    while batch.len() < MAX_BATCH_SIZE {
+        match rx.try_recv() {
+            Ok(record) => batch.push(record),
+            Err(_) => break,
+        }
+    }
+    Some(batch)
+}
+
+pub fn start_record_sinks(
+    sink: Arc<dyn RecordSink>,
+    tx_data: broadcast::Receiver<TransactionConfirmRecord>,
+    block_data: broadcast::Receiver<BlockData>,
+    dropped_records: DroppedRecords,
+) -> Vec<JoinHandle<()>> {
+    let confirm_jh = {
+        let sink = sink.clone();
+        let dropped_records = dropped_records.clone();
+        tokio::spawn(async move {
+            let mut tx_data = tx_data;
+            while let Some(batch) = drain_batch(&mut tx_data, &dropped_records).await {
+                if let Err(e) = sink.write_confirm_records(&batch).await {
+                    error!("could not write confirmation records {e:?}");
+                }
+            }
+        })
+    };
+    let block_jh = tokio::spawn(async move {
+        let mut block_data = block_data;
+        while let Some(batch) = drain_batch(&mut block_data, &dropped_records).await {
+            if let Err(e) = sink.write_block_data(&batch).await {
+                error!("could not write block data {e:?}");
+            }
+        }
+    });
+    vec![confirm_jh, block_jh]
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use solana_sdk::signature::Signature;
+
+    use super::*;
+    use crate::states::TransactionType;
+
+    fn send_record() -> TransactionSendRecord {
+        let sent_at = Utc::now();
+        TransactionSendRecord {
+            signature: Signature::new_unique(),
+            sent_at,
+            sent_at_us: sent_at.timestamp_micros(),
+            sent_slot: 1,
+            market_maker: None,
+            market: None,
+            keeper_instruction: None,
+            tx_type: TransactionType::MarketMaker,
+            priority_fees: 0,
+            spoofing: false,
+            phase: None,
+            blockhash_slot: None,
+            blockhash_age_slots: None,
+            last_valid_block_height: None,
+            chaos: None,
+            send_delay_ms: None,
+            tx_size_bytes: 200,
+            num_instructions: 2,
+            order_type: None,
+            reduce_only: false,
+            client_order_id: None,
+            route: None,
+        }
+    }
+
+    // the default test runtime has a single thread
+    #[tokio::test]
+    async fn send_records_are_forwarded_and_written_to_sqlite() {
+        let path = std::env::temp_dir().join(format!("mango-simulation-{}.db", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let sink = Arc::new(SqliteSink::open(&path).unwrap());
+        let (tx_record_sx, tx_record_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (mut forward_rx, jhs) =
+            start_send_records_sink(sink.clone(), tx_record_rx, 16, DroppedRecords::default());
+        for _ in 0..3 {
+            tx_record_sx.send(send_record()).unwrap();
+        }
+        drop(tx_record_sx);
+        for _ in 0..3 {
+            assert!(forward_rx.recv().await.is_some());
+        }
+        for jh in jhs {
+            jh.await.unwrap();
+        }
+
+        let rows: i64 = sink
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {SEND_RECORDS_TABLE}"),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rows, 3);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{path}{suffix}"));
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+use crate::{cli::Config, stats::RunSummary};
+
+/// Parameters of the run recorded with its results
+#[derive(Clone, Debug, Serialize)]
+pub struct RunParameters {
+    pub json_rpc_url: String,
+    pub mango_cluster: String,
+    pub program_version: String,
+    pub duration_secs: u64,
+    pub duration_slots: Option<u64>,
+    pub quotes_per_second: u64,
+    pub market_makers: usize,
+    pub accounts_per_signer: usize,
+    pub markets_per_market_maker: u8,
+    pub priority_fees_proba: u8,
+    pub keeper_prioritization: u64,
+    pub spoofing_levels: Option<u8>,
+    pub seed: Option<u64>,
+}
+
+impl RunParameters {
+    pub fn new(config: &Config, market_makers: usize) -> Self {
+        Self {
+            json_rpc_url: config.json_rpc_url.clone(),
+            mango_cluster: config.mango_cluster.clone(),
+            program_version: config.program_version.to_string(),
+            duration_secs: config.duration.as_secs(),
+            duration_slots: config.duration_slots,
+            quotes_per_second: config.quotes_per_second,
+            market_makers,
+            accounts_per_signer: config.accounts_per_signer,
+            markets_per_market_maker: config.number_of_markers_per_mm,
+            priority_fees_proba: config.priority_fees_proba,
+            keeper_prioritization: config.keeper_prioritization,
+            spoofing_levels: config.spoofing_levels,
+            seed: config.seed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Thresholds {
+    // in percent of the sent transactions
+    pub min_confirmation_rate: Option<f64>,
+    pub max_p95_latency_ms: Option<u64>,
+}
+
+impl Thresholds {
+    pub fn violations(&self, results: &RunSummary) -> Vec<String> {
+        let mut violations = vec![];
+        if let Some(min_confirmation_rate) = self.min_confirmation_rate {
+            if results.confirmation_rate < min_confirmation_rate {
+                violations.push(format!(
+                    "confirmation rate {:.2}% is below the minimum of {min_confirmation_rate}%",
+                    results.confirmation_rate
+                ));
+            }
+        }
+        if let Some(max_p95_latency_ms) = self.max_p95_latency_ms {
+            match results.latency_ms.p95 {
+                Some(p95) if p95 <= max_p95_latency_ms => {}
+                Some(p95) => violations.push(format!(
+                    "p95 latency of {p95}ms is above the maximum of {max_p95_latency_ms}ms"
+                )),
+                None => violations
+                    .push("no transaction was confirmed to compute the p95 latency".to_string()),
+            }
+        }
+        violations
+    }
+}
+
+/// Machine readable outcome of a run, written at the end of the run
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchSummary {
+    pub parameters: RunParameters,
+    pub results: RunSummary,
+    pub thresholds: Thresholds,
+    pub violations: Vec<String>,
+    pub passed: bool,
+}
+
+impl BenchSummary {
+    pub fn new(parameters: RunParameters, results: RunSummary, thresholds: Thresholds) -> Self {
+        let violations = thresholds.violations(&results);
+        Self {
+            parameters,
+            results,
+            thresholds,
+            passed: violations.is_empty(),
+            violations,
+        }
+    }
+
+    pub fn write(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
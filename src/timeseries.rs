@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use async_std::fs::File;
+use log::warn;
+use serde::Serialize;
+use solana_sdk::slot_history::Slot;
+use tokio::{sync::broadcast::Receiver, task::JoinHandle};
+
+use crate::{result_writer::RowTags, states::TransactionConfirmRecord, stats::DroppedRecords};
+
+/// Counts of one second of the run. Sent transactions, expiries, timeouts, priority fees and
+/// slot are counted in the second the transactions were sent, confirmations, errors and
+/// confirmation times in the second they were confirmed
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TimeSeriesRow {
+    // unix timestamp of the second
+    pub second: i64,
+    pub sent: u64,
+    pub confirmed: u64,
+    pub errors: u64,
+    pub expired: u64,
+    pub timeouts: u64,
+    pub average_priority_fee: f64,
+    // between the send and the confirmation of the transactions confirmed in the second
+    pub average_confirmation_ms: Option<f64>,
+    // highest slot at which a transaction of the second was sent
+    pub slot: Option<Slot>,
+    // set by the writer, the cluster only when running against several clusters
+    pub run_id: String,
+    pub cluster: Option<String>,
+    pub shard: Option<String>,
+}
+
+#[derive(Default)]
+pub struct TimeSeries {
+    rows: BTreeMap<i64, TimeSeriesRow>,
+    priority_fees: BTreeMap<i64, u64>,
+    confirmation_ms: BTreeMap<i64, i64>,
+}
+
+impl TimeSeries {
+    fn row(&mut self, second: i64) -> &mut TimeSeriesRow {
+        self.rows.entry(second).or_insert_with(|| TimeSeriesRow {
+            second,
+            ..TimeSeriesRow::default()
+        })
+    }
+
+    pub fn record(&mut self, record: &TransactionConfirmRecord) {
+        let sent_second = record.sent_at_us.div_euclid(1_000_000);
+        let row = self.row(sent_second);
+        row.sent += 1;
+        row.expired += record.expired as u64;
+        row.timeouts += record.timed_out as u64;
+        row.slot = row.slot.max(Some(record.sent_slot));
+        *self.priority_fees.entry(sent_second).or_default() += record.priority_fees;
+
+        if let Some(confirmed_at_us) = record.confirmed_at_us {
+            let confirmed_second = confirmed_at_us.div_euclid(1_000_000);
+            let row = self.row(confirmed_second);
+            row.confirmed += 1;
+            row.errors += record.error.is_some() as u64;
+            *self.confirmation_ms.entry(confirmed_second).or_default() +=
+                (confirmed_at_us - record.sent_at_us) / 1000;
+        }
+    }
+
+    /// row of a second with its averages, as counted from the records recorded so far
+    pub fn second(&self, second: i64) -> TimeSeriesRow {
+        let mut row = self.rows.get(&second).cloned().unwrap_or(TimeSeriesRow {
+            second,
+            ..TimeSeriesRow::default()
+        });
+        if row.sent > 0 {
+            row.average_priority_fee = self.priority_fees[&second] as f64 / row.sent as f64;
+        }
+        if row.confirmed > 0 {
+            row.average_confirmation_ms =
+                Some(self.confirmation_ms[&second] as f64 / row.confirmed as f64);
+        }
+        row
+    }
+
+    /// one row per second from the first to the last second of the run, seconds without any
+    /// transaction included
+    pub fn rows(&self) -> Vec<TimeSeriesRow> {
+        let (Some(first), Some(last)) = (self.rows.keys().next(), self.rows.keys().next_back())
+        else {
+            return vec![];
+        };
+        (*first..=*last).map(|second| self.second(second)).collect()
+    }
+}
+
+/// Aggregates the confirmation records per second and writes the time series at the end of the
+/// run, the records of a second keep arriving until its transactions are confirmed or timed out
+pub fn start_timeseries_writer(
+    timeseries_file: String,
+    tx_data: Receiver<TransactionConfirmRecord>,
+    tags: RowTags,
+    dropped_records: DroppedRecords,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut timeseries = TimeSeries::default();
+        let mut tx_data = tx_data;
+        loop {
+            match tx_data.recv().await {
+                Ok(record) => timeseries.record(&record),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("time series lagged behind, {n} records were not counted");
+                    dropped_records.add(n);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+
+        let mut writer =
+            csv_async::AsyncSerializer::from_writer(File::create(timeseries_file).await.unwrap());
+        for mut row in timeseries.rows() {
+            row.run_id = tags.run_id.clone();
+            row.cluster = tags.cluster.clone();
+            row.shard = tags.shard.clone();
+            writer.serialize(row).await.unwrap();
+        }
+        writer.flush().await.unwrap();
+    })
+}
@@ -0,0 +1,152 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use iter_tools::Itertools;
+use log::{info, warn};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_metrics::datapoint_info;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+    transaction::Transaction,
+};
+use tokio::task::JoinHandle;
+
+use crate::market_markers::QuotedMarket;
+
+// transfers sent in a single transaction
+const TRANSFERS_PER_TX: usize = 10;
+// accounts read with a single getMultipleAccounts
+const ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// Keeps the fee payers of the market makers funded during long runs
+#[derive(Clone)]
+pub struct TopUpConfig {
+    pub funder: Arc<Keypair>,
+    // payers below this balance get topped up
+    pub threshold_lamports: u64,
+    pub amount_lamports: u64,
+    pub interval: Duration,
+}
+
+impl TopUpConfig {
+    pub fn new(funder: Keypair) -> Self {
+        Self {
+            funder: Arc::new(funder),
+            threshold_lamports: sol_to_lamports(0.5),
+            amount_lamports: sol_to_lamports(1.0),
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+// payers whose balance is under the threshold, with their balance
+async fn low_balances(
+    rpc_client: &RpcClient,
+    payers: &[Pubkey],
+    threshold_lamports: u64,
+) -> anyhow::Result<Vec<(Pubkey, u64)>> {
+    let mut low = vec![];
+    for payers in payers.chunks(ACCOUNTS_PER_REQUEST) {
+        let accounts = rpc_client
+            .get_multiple_accounts_with_commitment(payers, CommitmentConfig::confirmed())
+            .await?
+            .value;
+        for (payer, account) in payers.iter().zip(accounts) {
+            let lamports = account.map_or(0, |account| account.lamports);
+            if lamports < threshold_lamports {
+                low.push((*payer, lamports));
+            }
+        }
+    }
+    Ok(low)
+}
+
+/// Checks the balance of the fee payer of every market maker at the interval and transfers
+/// lamports from the funder to the ones below the threshold, so that a run of several hours does
+/// not stop half way once the market makers run out of fees. The transfers are sent through the
+/// rpc and are not part of the results
+pub fn start_top_up_monitor(
+    rpc_client: Arc<RpcClient>,
+    quoted_markets: &[QuotedMarket],
+    config: TopUpConfig,
+    exit_signal: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    let payers = quoted_markets
+        .iter()
+        .map(|(signers, _, _)| signers.payer())
+        .unique()
+        .collect_vec();
+    tokio::spawn(async move {
+        let funder = config.funder.pubkey();
+        info!(
+            "topping up {} fee payers under {} SOL from {funder}",
+            payers.len(),
+            lamports_to_sol(config.threshold_lamports)
+        );
+        let mut topped_up_lamports = 0;
+        while !exit_signal.load(Ordering::Relaxed) {
+            tokio::time::sleep(config.interval).await;
+            let low = match low_balances(&rpc_client, &payers, config.threshold_lamports).await {
+                Ok(low) => low,
+                Err(e) => {
+                    warn!("top up : could not read the balances of the fee payers : {e}");
+                    continue;
+                }
+            };
+            for transfers in low.chunks(TRANSFERS_PER_TX) {
+                let instructions = transfers
+                    .iter()
+                    .map(|(payer, _)| {
+                        system_instruction::transfer(&funder, payer, config.amount_lamports)
+                    })
+                    .collect_vec();
+                let recent_blockhash = match rpc_client.get_latest_blockhash().await {
+                    Ok(blockhash) => blockhash,
+                    Err(e) => {
+                        warn!("top up : could not get a blockhash : {e}");
+                        break;
+                    }
+                };
+                let tx = Transaction::new_signed_with_payer(
+                    &instructions,
+                    Some(&funder),
+                    &[config.funder.as_ref()],
+                    recent_blockhash,
+                );
+                match rpc_client.send_and_confirm_transaction(&tx).await {
+                    Ok(signature) => {
+                        for (payer, lamports) in transfers {
+                            info!(
+                                "top up : {payer} had {} SOL, sent {} SOL in {signature}",
+                                lamports_to_sol(*lamports),
+                                lamports_to_sol(config.amount_lamports)
+                            );
+                        }
+                        let lamports = config.amount_lamports * transfers.len() as u64;
+                        topped_up_lamports += lamports;
+                        datapoint_info!(
+                            "bencher-top-up",
+                            ("payers", transfers.len(), i64),
+                            ("lamports", lamports, i64),
+                        );
+                    }
+                    Err(e) => warn!("top up : transfer from {funder} failed : {e}"),
+                }
+            }
+        }
+        info!(
+            "top up : {} SOL sent to the fee payers during the run",
+            lamports_to_sol(topped_up_lamports)
+        );
+    })
+}
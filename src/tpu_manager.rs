@@ -1,42 +1,281 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
 use log::warn;
+use rand::{rngs::StdRng, Rng};
 use solana_client::connection_cache::ConnectionCache;
-use solana_lite_rpc_services::transaction_service::TransactionService;
 use solana_sdk::transaction::Transaction;
 
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{mpsc::UnboundedSender, Semaphore};
 
-use crate::{states::TransactionSendRecord, stats::MangoSimulationStats};
+use crate::{
+    clock,
+    helpers::seeded_rng,
+    memo::MemoTagger,
+    send_delay::SendDelay,
+    states::{
+        BlockhashSlots, LiveParameters, TransactionRoute, TransactionSendError,
+        TransactionSendRecord,
+    },
+    stats::MangoSimulationStats,
+    transaction_sender::TransactionSender,
+};
 pub type QuicConnectionCache = ConnectionCache;
 
+// draws of the send delays and of the relayed transactions, apart from the other users of the seed
+const SEND_RNG_STREAM: u64 = 2 << 32;
+
 #[derive(Clone)]
 pub struct TpuManager {
-    // why arc twice / one is so that we clone rwlock and other so that we can clone tpu client
-    transaction_service: TransactionService,
+    // transactions are sent in round robin over the senders, shared by the clones of the manager
+    senders: Arc<Vec<Arc<dyn TransactionSender>>>,
+    next_sender: Arc<AtomicUsize>,
     stats: MangoSimulationStats,
     tx_send_record: UnboundedSender<TransactionSendRecord>,
+    pending_sends: Arc<AtomicU64>,
+    // limits the number of transactions being sent in parallel, each send of the quic senders
+    // opens its own stream
+    parallel_streams: Option<Arc<Semaphore>>,
+    send_errors: UnboundedSender<TransactionSendError>,
+    live_parameters: Arc<LiveParameters>,
+    blockhash_slots: BlockhashSlots,
+    // artificial delay between the signing and the sending of each transaction
+    send_delay: Option<SendDelay>,
+    // memo tagging the transactions of the run
+    memo: Option<MemoTagger>,
+    relayer: Option<RelayerRoute>,
+    serialization: SerializationStats,
+    // reproducible send delays and relayer split with a seed
+    rng: Arc<Mutex<StdRng>>,
+}
+
+/// Transactions serialized to their wire format and the time spent serializing them, each
+/// transaction is serialized once and its wire bytes are handed over to the sender
+#[derive(Clone, Default)]
+pub struct SerializationStats {
+    transactions: Arc<AtomicU64>,
+    bytes: Arc<AtomicU64>,
+    nanos: Arc<AtomicU64>,
+}
+
+impl SerializationStats {
+    fn record(&self, bytes: usize, elapsed: Duration) {
+        self.transactions.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn transactions(&self) -> u64 {
+        self.transactions.load(Ordering::Relaxed)
+    }
+
+    /// transactions and megabytes serialized per second spent serializing, what a single core
+    /// achieves
+    pub fn throughput(&self) -> (f64, f64) {
+        let seconds = self.nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        if seconds == 0.0 {
+            return (0.0, 0.0);
+        }
+        (
+            self.transactions() as f64 / seconds,
+            self.bytes.load(Ordering::Relaxed) as f64 / 1e6 / seconds,
+        )
+    }
+}
+
+/// A transaction handed over to the manager and not sent yet, counted in the pending sends from
+/// the time it is queued, through the send delay and the wait for a stream, until it is sent or
+/// dropped
+struct PendingSend {
+    pending_sends: Arc<AtomicU64>,
+}
+
+impl Drop for PendingSend {
+    fn drop(&mut self) {
+        self.pending_sends.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A batch counted in the pending sends before the task sending it is spawned
+pub struct QueuedBatch {
+    tpu_manager: TpuManager,
+    batch: Vec<(Transaction, TransactionSendRecord, PendingSend)>,
+}
+
+impl QueuedBatch {
+    pub async fn send(self) -> bool {
+        // the transactions of a batch are signed together, their delays do not add up, and are
+        // sent in parallel up to the limit of parallel streams
+        let signed_at = Instant::now();
+        let tpu_manager = &self.tpu_manager;
+        let sends = self
+            .batch
+            .into_iter()
+            .map(|(tx, record, pending)| async move {
+                tpu_manager
+                    .send_signed_at(&tx, record, signed_at, pending)
+                    .await
+            });
+        futures::future::join_all(sends)
+            .await
+            .into_iter()
+            .all(|sent| sent)
+    }
+}
+
+/// Share of the transactions sent through an external relayer instead of the senders
+#[derive(Clone)]
+pub struct RelayerRoute {
+    pub sender: Arc<dyn TransactionSender>,
+    // percentage of the transactions, between 0 and 100
+    pub pct: u8,
 }
 
 impl TpuManager {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
-        transaction_service: TransactionService,
+        senders: Vec<Arc<dyn TransactionSender>>,
         stats: MangoSimulationStats,
         tx_send_record: UnboundedSender<TransactionSendRecord>,
+        max_parallel_streams: Option<usize>,
+        send_errors: UnboundedSender<TransactionSendError>,
+        live_parameters: Arc<LiveParameters>,
+        blockhash_slots: BlockhashSlots,
+        send_delay: Option<SendDelay>,
+        memo: Option<MemoTagger>,
+        relayer: Option<RelayerRoute>,
+        seed: Option<u64>,
     ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !senders.is_empty(),
+            "at least one transaction sender is required"
+        );
         Ok(Self {
-            transaction_service,
+            senders: Arc::new(senders),
+            next_sender: Arc::new(AtomicUsize::new(0)),
             stats,
             tx_send_record,
+            pending_sends: Arc::new(AtomicU64::new(0)),
+            parallel_streams: max_parallel_streams.map(|n| Arc::new(Semaphore::new(n))),
+            send_errors,
+            live_parameters,
+            blockhash_slots,
+            send_delay,
+            memo,
+            relayer,
+            serialization: SerializationStats::default(),
+            rng: Arc::new(Mutex::new(seeded_rng(seed, SEND_RNG_STREAM))),
         })
     }
 
+    pub fn serialization(&self) -> &SerializationStats {
+        &self.serialization
+    }
+
+    pub fn memo(&self) -> Option<&MemoTagger> {
+        self.memo.as_ref()
+    }
+
     pub async fn send_transaction(
         &self,
         transaction: &solana_sdk::transaction::Transaction,
         transaction_sent_record: TransactionSendRecord,
     ) -> bool {
-        self.stats
-            .inc_send(&transaction_sent_record.keeper_instruction);
+        let pending = self.pending_send();
+        self.send_signed_at(
+            transaction,
+            transaction_sent_record,
+            Instant::now(),
+            pending,
+        )
+        .await
+    }
+
+    fn pending_send(&self) -> PendingSend {
+        self.pending_sends.fetch_add(1, Ordering::Relaxed);
+        PendingSend {
+            pending_sends: self.pending_sends.clone(),
+        }
+    }
+
+    async fn send_signed_at(
+        &self,
+        transaction: &solana_sdk::transaction::Transaction,
+        mut transaction_sent_record: TransactionSendRecord,
+        signed_at: Instant,
+        pending: PendingSend,
+    ) -> bool {
+        if let Some(send_delay) = &self.send_delay {
+            let delay = send_delay.sample(&mut *self.rng.lock().unwrap());
+            tokio::time::sleep_until(signed_at + delay).await;
+            transaction_sent_record.send_delay_ms = Some(signed_at.elapsed().as_millis() as u64);
+            // the latencies are measured from the actual send
+            let sent_at = clock::now();
+            transaction_sent_record.sent_at = sent_at;
+            transaction_sent_record.sent_at_us = sent_at.timestamp_micros();
+        }
+        transaction_sent_record.phase = self.live_parameters.phase();
+        transaction_sent_record.blockhash_slot = self
+            .blockhash_slots
+            .get(&transaction.message.recent_blockhash);
+        transaction_sent_record.blockhash_age_slots = transaction_sent_record
+            .blockhash_slot
+            .map(|slot| transaction_sent_record.sent_slot.saturating_sub(slot));
+        transaction_sent_record.last_valid_block_height = self
+            .blockhash_slots
+            .last_valid_block_height(&transaction.message.recent_blockhash);
+        let serialize_start = Instant::now();
+        let wire_transaction =
+            bincode::serialize(transaction).expect("a signed transaction should serialize");
+        self.serialization
+            .record(wire_transaction.len(), serialize_start.elapsed());
+        transaction_sent_record.tx_size_bytes = wire_transaction.len() as u64;
+        transaction_sent_record.num_instructions = transaction.message.instructions.len() as u64;
+
+        let _stream = match &self.parallel_streams {
+            Some(semaphore) => Some(semaphore.acquire().await.unwrap()),
+            None => None,
+        };
+        let relayer = self
+            .relayer
+            .as_ref()
+            .filter(|relayer| self.rng.lock().unwrap().gen_range(0..100) < relayer.pct);
+        let res = match relayer {
+            Some(relayer) => {
+                transaction_sent_record.route = Some(TransactionRoute::Relayer);
+                relayer
+                    .sender
+                    .send_wire(transaction, wire_transaction)
+                    .await
+            }
+            None => {
+                transaction_sent_record.route = Some(TransactionRoute::Direct);
+                let sender = self.next_sender.fetch_add(1, Ordering::Relaxed) % self.senders.len();
+                self.senders[sender]
+                    .send_wire(transaction, wire_transaction)
+                    .await
+            }
+        };
+        drop(pending);
+
+        // transactions which could not be sent are not waited for by the confirmation
+        if let Err(e) = &res {
+            warn!("error sending txs {e:?}");
+            let send_error = TransactionSendError::new(&transaction_sent_record, e.to_string());
+            self.stats.inc_send_error(&send_error);
+            let _ = self.send_errors.send(send_error);
+            return false;
+        }
 
+        self.stats.inc_send(&transaction_sent_record);
         let tx_sent_record = self.tx_send_record.clone();
         let sent = tx_sent_record.send(transaction_sent_record);
         if sent.is_err() {
@@ -45,27 +284,30 @@ impl TpuManager {
                 sent.err().unwrap().to_string()
             );
         }
-        let transaction = bincode::serialize(transaction).unwrap();
-
-        let res = self
-            .transaction_service
-            .send_transaction(transaction, None)
-            .await;
+        true
+    }
 
-        if let Err(e) = &res {
-            print!("error sending txs on custom tpu {e:?}");
-        }
-        res.is_ok()
+    /// number of transactions queued or being handed over to the transaction service
+    pub fn pending_sends(&self) -> u64 {
+        self.pending_sends.load(Ordering::Relaxed)
     }
 
     pub async fn send_transaction_batch(
         &self,
-        batch: &Vec<(Transaction, TransactionSendRecord)>,
+        batch: Vec<(Transaction, TransactionSendRecord)>,
     ) -> bool {
-        let mut value = true;
-        for (tx, record) in batch {
-            value &= self.send_transaction(tx, record.clone()).await;
+        self.queue_batch(batch).send().await
+    }
+
+    /// counts the transactions of the batch as pending until they are sent, the batch can then be
+    /// sent from a task spawned later without the shutdown missing it
+    pub fn queue_batch(&self, batch: Vec<(Transaction, TransactionSendRecord)>) -> QueuedBatch {
+        QueuedBatch {
+            tpu_manager: self.clone(),
+            batch: batch
+                .into_iter()
+                .map(|(tx, record)| (tx, record, self.pending_send()))
+                .collect(),
         }
-        value
     }
 }
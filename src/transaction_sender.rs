@@ -0,0 +1,431 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use async_trait::async_trait;
+use log::{info, warn};
+use solana_client::{
+    connection_cache::ConnectionCache,
+    nonblocking::{rpc_client::RpcClient, tpu_client::TpuClient, tpu_connection::TpuConnection},
+    rpc_config::RpcSendTransactionConfig,
+    tpu_client::TpuClientConfig,
+};
+use solana_lite_rpc_services::transaction_service::TransactionService;
+use solana_sdk::{
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+use solana_streamer::streamer::StakedNodes;
+
+use crate::{tpu_manager::QuicConnectionCache, ws_failover::WebsocketFailover};
+
+/// Path used to hand the transactions over to the cluster
+#[async_trait]
+pub trait TransactionSender: Send + Sync {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()>;
+
+    /// sends a transaction already serialized by the caller, the senders which only need its wire
+    /// bytes do not serialize it again
+    async fn send_wire(
+        &self,
+        transaction: &Transaction,
+        _wire_transaction: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        self.send(transaction).await
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SenderKind {
+    // QUIC to the upcoming leaders through lite-rpc
+    Tpu,
+    Rpc,
+    Jito,
+    // QUIC to the upcoming leaders with the connection cache of the solana client
+    Quic,
+    // kept in memory, nothing reaches the cluster
+    Mock,
+}
+
+impl FromStr for SenderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tpu" => Ok(SenderKind::Tpu),
+            "rpc" => Ok(SenderKind::Rpc),
+            "jito" => Ok(SenderKind::Jito),
+            "quic" => Ok(SenderKind::Quic),
+            "mock" => Ok(SenderKind::Mock),
+            _ => Err(format!("unknown transaction sender {s}")),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for TransactionService {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        self.send_wire(transaction, bincode::serialize(transaction)?)
+            .await
+    }
+
+    async fn send_wire(
+        &self,
+        _transaction: &Transaction,
+        wire_transaction: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        self.send_transaction(wire_transaction, None).await?;
+        Ok(())
+    }
+}
+
+/// sends through the sendTransaction method of an RPC node, without preflight
+pub struct RpcSender {
+    rpc_client: Arc<RpcClient>,
+}
+
+impl RpcSender {
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        Self { rpc_client }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for RpcSender {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        self.rpc_client
+            .send_transaction_with_config(
+                transaction,
+                RpcSendTransactionConfig {
+                    skip_preflight: true,
+                    ..RpcSendTransactionConfig::default()
+                },
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+// calls the json rpc sendTransaction method of an http endpoint with the base58 wire transaction,
+// an error returned in the response fails the send
+async fn json_rpc_send_transaction(
+    client: &reqwest::Client,
+    url: &str,
+    wire_transaction: Vec<u8>,
+    config: Option<serde_json::Value>,
+) -> anyhow::Result<()> {
+    let mut params = vec![serde_json::Value::String(
+        bs58::encode(wire_transaction).into_string(),
+    )];
+    params.extend(config);
+    let response: serde_json::Value = client
+        .post(url)
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendTransaction",
+            "params": params,
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(error) = response.get("error") {
+        anyhow::bail!("{error}");
+    }
+    Ok(())
+}
+
+/// sends through the sendTransaction method of a jito block engine
+pub struct JitoSender {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl JitoSender {
+    pub fn new(block_engine_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: format!(
+                "{}/api/v1/transactions",
+                block_engine_url.trim_end_matches('/')
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for JitoSender {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        self.send_wire(transaction, bincode::serialize(transaction)?)
+            .await
+    }
+
+    async fn send_wire(
+        &self,
+        _transaction: &Transaction,
+        wire_transaction: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        json_rpc_send_transaction(&self.client, &self.url, wire_transaction, None)
+            .await
+            .map_err(|e| anyhow::anyhow!("block engine error {e}"))
+    }
+}
+
+/// sends through the json rpc sendTransaction method of an external relayer over http, with
+/// --relayer-url. gRPC relayers are not supported
+pub struct RelayerSender {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl RelayerSender {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for RelayerSender {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        self.send_wire(transaction, bincode::serialize(transaction)?)
+            .await
+    }
+
+    async fn send_wire(
+        &self,
+        _transaction: &Transaction,
+        wire_transaction: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        json_rpc_send_transaction(
+            &self.client,
+            &self.url,
+            wire_transaction,
+            Some(serde_json::json!({ "skipPreflight": true })),
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("relayer error {e}"))
+    }
+}
+
+/// Connection cache binding the QUIC client to bind_address, like solana-bench-tps. With a client
+/// node id the client is registered with the identity and the stake of that node, so that its
+/// connections get the bandwidth of a staked node, otherwise they are unstaked
+pub async fn quic_connection_cache(
+    rpc_client: &RpcClient,
+    bind_address: IpAddr,
+    client_node_id: Option<&Keypair>,
+    connection_pool_size: usize,
+) -> anyhow::Result<QuicConnectionCache> {
+    let Some(client_node_id) = client_node_id else {
+        return Ok(ConnectionCache::new_with_client_options(
+            connection_pool_size,
+            None,
+            Some((&Keypair::new(), bind_address)),
+            None,
+        ));
+    };
+
+    let node_pubkey = client_node_id.pubkey();
+    let vote_accounts = rpc_client.get_vote_accounts().await?;
+    let vote_accounts = vote_accounts
+        .current
+        .iter()
+        .chain(vote_accounts.delinquent.iter());
+    let total_stake = vote_accounts.clone().map(|v| v.activated_stake).sum();
+    let stake = vote_accounts
+        .filter(|v| v.node_pubkey == node_pubkey.to_string())
+        .map(|v| v.activated_stake)
+        .sum();
+    if stake == 0 {
+        warn!("client node {node_pubkey} has no activated stake, its connections are unstaked");
+    } else {
+        info!("client node {node_pubkey} connects with {stake} of {total_stake} staked lamports");
+    }
+    let staked_nodes = Arc::new(RwLock::new(StakedNodes {
+        total_stake,
+        max_stake: stake,
+        min_stake: stake,
+        pubkey_stake_map: HashMap::from([(node_pubkey, stake)]),
+        ..StakedNodes::default()
+    }));
+    Ok(ConnectionCache::new_with_client_options(
+        connection_pool_size,
+        None,
+        Some((client_node_id, bind_address)),
+        Some((&staked_nodes, &node_pubkey)),
+    ))
+}
+
+// tpu client on the first websocket endpoint it can connect to, starting with the current one
+async fn connect_tpu_client(
+    rpc_client: &Arc<RpcClient>,
+    websocket: &WebsocketFailover,
+    connection_cache: &Arc<QuicConnectionCache>,
+) -> anyhow::Result<TpuClient> {
+    let mut last_error = None;
+    for websocket_url in websocket.candidates() {
+        match TpuClient::new_with_connection_cache(
+            rpc_client.clone(),
+            &websocket_url,
+            TpuClientConfig::default(),
+            connection_cache.clone(),
+        )
+        .await
+        {
+            Ok(tpu_client) => return Ok(tpu_client),
+            Err(e) => {
+                warn!("could not create the tpu client on {websocket_url} : {e}");
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap().into())
+}
+
+/// sends over QUIC to the upcoming leaders through the tpu client of the solana client, which
+/// is created again on the next websocket endpoint when the current one dies
+pub struct QuicSender {
+    tpu_client: Arc<RwLock<Arc<TpuClient>>>,
+}
+
+impl QuicSender {
+    pub async fn new(
+        rpc_client: Arc<RpcClient>,
+        websocket: WebsocketFailover,
+        connection_cache: Arc<QuicConnectionCache>,
+    ) -> anyhow::Result<Self> {
+        let tpu_client = Arc::new(RwLock::new(Arc::new(
+            connect_tpu_client(&rpc_client, &websocket, &connection_cache).await?,
+        )));
+        let reconnected = tpu_client.clone();
+        let mut websocket = websocket;
+        tokio::spawn(async move {
+            while websocket.changed().await {
+                match connect_tpu_client(&rpc_client, &websocket, &connection_cache).await {
+                    Ok(client) => {
+                        info!("tpu client reconnected to {}", websocket.current());
+                        *reconnected.write().unwrap() = Arc::new(client);
+                    }
+                    Err(e) => warn!("keeping the previous tpu client, could not reconnect : {e}"),
+                }
+            }
+        });
+        Ok(Self { tpu_client })
+    }
+}
+
+#[async_trait]
+impl TransactionSender for QuicSender {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        self.send_wire(transaction, bincode::serialize(transaction)?)
+            .await
+    }
+
+    async fn send_wire(
+        &self,
+        _transaction: &Transaction,
+        wire_transaction: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let tpu_client = self.tpu_client.read().unwrap().clone();
+        if !tpu_client.send_wire_transaction(wire_transaction).await {
+            anyhow::bail!("transaction could not be sent to any leader");
+        }
+        Ok(())
+    }
+}
+
+/// Sends over QUIC to fixed tpu addresses without following the leader schedule, for a lab with
+/// a single validator or a tpu proxy. The send fails only when no address took the transaction
+pub struct FixedTpuSender {
+    connection_cache: Arc<QuicConnectionCache>,
+    tpu_addrs: Vec<SocketAddr>,
+}
+
+impl FixedTpuSender {
+    pub fn new(connection_cache: Arc<QuicConnectionCache>, tpu_addrs: Vec<SocketAddr>) -> Self {
+        info!("sending every transaction to the tpu addresses {tpu_addrs:?}");
+        Self {
+            connection_cache,
+            tpu_addrs,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for FixedTpuSender {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        self.send_wire(transaction, bincode::serialize(transaction)?)
+            .await
+    }
+
+    async fn send_wire(
+        &self,
+        _transaction: &Transaction,
+        wire_transaction: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let mut last_error = None;
+        let mut sent = false;
+        for tpu_addr in &self.tpu_addrs {
+            let connection = self.connection_cache.get_nonblocking_connection(tpu_addr);
+            match connection
+                .send_wire_transaction(wire_transaction.clone())
+                .await
+            {
+                Ok(()) => sent = true,
+                Err(e) => last_error = Some(format!("{tpu_addr} : {e}")),
+            }
+        }
+        match (sent, last_error) {
+            (false, Some(e)) => {
+                anyhow::bail!("transaction could not be sent to any tpu address, {e}")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps the transactions in memory instead of sending them, for tests without a cluster
+#[derive(Clone, Default)]
+pub struct MockSender {
+    sent: Arc<Mutex<Vec<Transaction>>>,
+    // every send fails with this error when set
+    error: Option<String>,
+}
+
+impl MockSender {
+    pub fn failing(error: &str) -> Self {
+        Self {
+            sent: Arc::default(),
+            error: Some(error.to_string()),
+        }
+    }
+
+    pub fn sent_signatures(&self) -> Vec<Signature> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|tx| tx.signatures[0])
+            .collect()
+    }
+}
+
+#[async_trait]
+impl TransactionSender for MockSender {
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        if let Some(error) = &self.error {
+            anyhow::bail!("{error}");
+        }
+        self.sent.lock().unwrap().push(transaction.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,365 @@
+use std::{collections::HashMap, str::FromStr};
+
+use mango::state::{MangoAccount, MangoGroup, PerpMarket, RootBank};
+use mango_common::Loadable;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+
+use crate::{
+    cli::ValidateConfig,
+    config_loader::load_config,
+    helpers::{to_sdk_pk, to_sp_pk},
+    mango::{AccountKeys, GroupConfig, MangoConfig},
+};
+
+/// Problems found in the config files, each one names the key at fault
+#[derive(Default)]
+struct Problems(Vec<String>);
+
+impl Problems {
+    fn push(&mut self, problem: String) {
+        println!("  {problem}");
+        self.0.push(problem);
+    }
+
+    // the pubkey when it parses
+    fn pubkey(&mut self, name: &str, value: &str) -> Option<Pubkey> {
+        match Pubkey::from_str(value) {
+            Ok(pk) => Some(pk),
+            Err(_) => {
+                self.push(format!("{name} {value:?} is not a valid pubkey"));
+                None
+            }
+        }
+    }
+}
+
+async fn fetch_accounts(
+    rpc_client: &RpcClient,
+    pks: &[Pubkey],
+) -> anyhow::Result<HashMap<Pubkey, Account>> {
+    let mut accounts = HashMap::new();
+    // getMultipleAccounts is limited to 100 accounts per request
+    for chunk in pks.chunks(100) {
+        let sdk_pks = chunk.iter().map(to_sdk_pk).collect::<Vec<_>>();
+        for (pk, account) in chunk
+            .iter()
+            .zip(rpc_client.get_multiple_accounts(&sdk_pks).await?)
+        {
+            if let Some(account) = account {
+                accounts.insert(*pk, account);
+            }
+        }
+    }
+    Ok(accounts)
+}
+
+/// Fetched accounts of the config, checked for existence and ownership before being decoded
+struct OnChain<'a> {
+    accounts: HashMap<Pubkey, Account>,
+    program_id: Pubkey,
+    problems: &'a mut Problems,
+}
+
+impl OnChain<'_> {
+    fn owned(&mut self, name: &str, pk: &Pubkey) -> Option<&Account> {
+        let Some(account) = self.accounts.get(pk) else {
+            self.problems
+                .push(format!("{name} {pk} does not exist on the cluster"));
+            return None;
+        };
+        if to_sp_pk(&account.owner) != self.program_id {
+            self.problems.push(format!(
+                "{name} {pk} is owned by {} instead of the mango program {}",
+                account.owner, self.program_id
+            ));
+            return None;
+        }
+        Some(account)
+    }
+
+    fn load<T: Loadable>(&mut self, name: &str, pk: &Pubkey) -> Option<T> {
+        let loaded = T::load_from_bytes(&self.owned(name, pk)?.data).map(|loaded| *loaded);
+        match loaded {
+            Ok(loaded) => Some(loaded),
+            Err(e) => {
+                self.problems
+                    .push(format!("{name} {pk} could not be decoded : {e:?}"));
+                None
+            }
+        }
+    }
+}
+
+// every pubkey of the group config, with the name used in the problems
+fn group_pubkeys(group: &GroupConfig, problems: &mut Problems) -> Vec<(String, Pubkey)> {
+    let mut keys = vec![
+        ("mango group".to_string(), group.public_key.clone()),
+        ("mango cache".to_string(), group.cache_key.clone()),
+    ];
+    for token in &group.tokens {
+        keys.push((
+            format!("root bank of {}", token.symbol),
+            token.root_key.clone(),
+        ));
+        keys.extend(
+            token
+                .node_keys
+                .iter()
+                .map(|node_key| (format!("node bank of {}", token.symbol), node_key.clone())),
+        );
+    }
+    for market in &group.perp_markets {
+        keys.push((
+            format!("perp market {}", market.name),
+            market.public_key.clone(),
+        ));
+        keys.push((format!("bids of {}", market.name), market.bids_key.clone()));
+        keys.push((format!("asks of {}", market.name), market.asks_key.clone()));
+        keys.push((
+            format!("event queue of {}", market.name),
+            market.events_key.clone(),
+        ));
+    }
+    keys.into_iter()
+        .filter_map(|(name, value)| Some((name.clone(), problems.pubkey(&name, &value)?)))
+        .collect()
+}
+
+async fn validate_group(
+    rpc_client: &RpcClient,
+    group: &GroupConfig,
+    problems: &mut Problems,
+) -> anyhow::Result<Option<(Pubkey, Pubkey)>> {
+    let program_id = problems.pubkey("mango program id", &group.mango_program_id);
+    let keys = group_pubkeys(group, problems);
+    let Some(program_id) = program_id else {
+        return Ok(None);
+    };
+    match rpc_client.get_account(&to_sdk_pk(&program_id)).await {
+        Ok(program) if program.executable => {}
+        Ok(_) => problems.push(format!("mango program id {program_id} is not a program")),
+        Err(_) => problems.push(format!(
+            "mango program {program_id} is not deployed on the cluster"
+        )),
+    }
+
+    let pks = keys.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+    let mut on_chain = OnChain {
+        accounts: fetch_accounts(rpc_client, &pks).await?,
+        program_id,
+        problems,
+    };
+    let pk_of = |name: &str| keys.iter().find(|(n, _)| n == name).map(|(_, pk)| *pk);
+
+    let Some(group_pk) = pk_of("mango group") else {
+        return Ok(None);
+    };
+    let Some(mango_group) = on_chain.load::<MangoGroup>("mango group", &group_pk) else {
+        return Ok(None);
+    };
+    if let Some(cache_pk) = pk_of("mango cache") {
+        if mango_group.mango_cache != cache_pk {
+            on_chain.problems.push(format!(
+                "mango cache {cache_pk} is not the cache of the group, the group uses {}",
+                mango_group.mango_cache
+            ));
+        }
+    }
+
+    for token in &group.tokens {
+        let Some(root_bank_pk) = pk_of(&format!("root bank of {}", token.symbol)) else {
+            continue;
+        };
+        if !mango_group
+            .tokens
+            .iter()
+            .any(|t| t.root_bank == root_bank_pk)
+        {
+            on_chain.problems.push(format!(
+                "root bank of {} {root_bank_pk} does not belong to the group",
+                token.symbol
+            ));
+        }
+        let Some(root_bank) =
+            on_chain.load::<RootBank>(&format!("root bank of {}", token.symbol), &root_bank_pk)
+        else {
+            continue;
+        };
+        let node_banks = &root_bank.node_banks[..root_bank.num_node_banks];
+        for node_key in &token.node_keys {
+            let Ok(node_bank_pk) = Pubkey::from_str(node_key) else {
+                continue;
+            };
+            on_chain.owned(&format!("node bank of {}", token.symbol), &node_bank_pk);
+            if !node_banks.contains(&node_bank_pk) {
+                on_chain.problems.push(format!(
+                    "node bank of {} {node_bank_pk} does not belong to the root bank {root_bank_pk}",
+                    token.symbol
+                ));
+            }
+        }
+    }
+
+    for market in &group.perp_markets {
+        let Some(market_pk) = pk_of(&format!("perp market {}", market.name)) else {
+            continue;
+        };
+        match mango_group.perp_markets.get(market.market_index as usize) {
+            Some(info) if info.perp_market == market_pk => {}
+            _ => on_chain.problems.push(format!(
+                "perp market {} {market_pk} is not the market {} of the group",
+                market.name, market.market_index
+            )),
+        }
+        let Some(perp_market) =
+            on_chain.load::<PerpMarket>(&format!("perp market {}", market.name), &market_pk)
+        else {
+            continue;
+        };
+        if perp_market.mango_group != group_pk {
+            on_chain.problems.push(format!(
+                "perp market {} {market_pk} belongs to the group {} instead of {group_pk}",
+                market.name, perp_market.mango_group
+            ));
+        }
+        for (side, on_chain_pk) in [
+            ("bids", perp_market.bids),
+            ("asks", perp_market.asks),
+            ("event queue", perp_market.event_queue),
+        ] {
+            let name = format!("{side} of {}", market.name);
+            let Some(config_pk) = pk_of(&name) else {
+                continue;
+            };
+            on_chain.owned(&name, &config_pk);
+            if config_pk != on_chain_pk {
+                on_chain.problems.push(format!(
+                    "{name} {config_pk} does not match the perp market, it uses {on_chain_pk}"
+                ));
+            }
+        }
+    }
+    Ok(Some((program_id, group_pk)))
+}
+
+async fn validate_accounts(
+    rpc_client: &RpcClient,
+    account_keys: &[AccountKeys],
+    program_id: Pubkey,
+    group_pk: Pubkey,
+    problems: &mut Problems,
+) -> anyhow::Result<()> {
+    let mut mango_accounts = vec![];
+    for (position, keys) in account_keys.iter().enumerate() {
+        let owner = problems.pubkey(
+            &format!("public key of account {position}"),
+            &keys.public_key,
+        );
+        if keys.secret_key.is_none()
+            && keys.keypair_path.is_none()
+            && keys.remote_signer_url.is_none()
+        {
+            problems.push(format!(
+                "account {position} has no secret key, keypair path nor remote signer url"
+            ));
+        }
+        if keys.mango_account_pks.is_empty() {
+            problems.push(format!("account {position} has no mango account"));
+        }
+        for mango_account in &keys.mango_account_pks {
+            let name = format!("mango account of account {position}");
+            if let (Some(owner), Some(pk)) = (owner, problems.pubkey(&name, mango_account)) {
+                mango_accounts.push((name, owner, pk));
+            }
+        }
+    }
+
+    let pks = mango_accounts
+        .iter()
+        .map(|(_, _, pk)| *pk)
+        .collect::<Vec<_>>();
+    let mut on_chain = OnChain {
+        accounts: fetch_accounts(rpc_client, &pks).await?,
+        program_id,
+        problems,
+    };
+    for (name, owner, pk) in mango_accounts {
+        let Some(mango_account) = on_chain.load::<MangoAccount>(&name, &pk) else {
+            continue;
+        };
+        if mango_account.mango_group != group_pk {
+            on_chain.problems.push(format!(
+                "{name} {pk} belongs to the group {} instead of {group_pk}",
+                mango_account.mango_group
+            ));
+        }
+        if mango_account.owner != owner {
+            on_chain.problems.push(format!(
+                "{name} {pk} is owned by {} instead of {owner}",
+                mango_account.owner
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the mango keys and the accounts files against the cluster before a run, so that a bad
+/// key is reported with the others instead of panicking in the middle of a run (mango v3 only)
+pub async fn validate_config(config: ValidateConfig) -> anyhow::Result<()> {
+    let rpc_client = RpcClient::new(config.json_rpc_url.clone());
+    let mut problems = Problems::default();
+
+    let mango_config: MangoConfig = serde_json::from_str(&load_config(&config.mango_keys).await?)
+        .map_err(|e| {
+        anyhow::anyhow!("{} is not a valid mango keys file : {e}", config.mango_keys)
+    })?;
+    let Some(group) = mango_config
+        .groups
+        .iter()
+        .find(|g| g.name == config.mango_cluster)
+    else {
+        anyhow::bail!(
+            "no group named {} in {}, the groups are {}",
+            config.mango_cluster,
+            config.mango_keys,
+            mango_config
+                .groups
+                .iter()
+                .map(|g| g.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    };
+
+    println!("Checking the group {} of {}", group.name, config.mango_keys);
+    let group_keys = validate_group(&rpc_client, group, &mut problems).await?;
+
+    if let Some(account_keys) = &config.account_keys {
+        println!("Checking the accounts of {account_keys}");
+        let account_keys: Vec<AccountKeys> =
+            serde_json::from_str(&load_config(account_keys).await?).map_err(|e| {
+                anyhow::anyhow!("{account_keys} is not a valid accounts file : {e}")
+            })?;
+        match group_keys {
+            Some((program_id, group_pk)) => {
+                validate_accounts(
+                    &rpc_client,
+                    &account_keys,
+                    program_id,
+                    group_pk,
+                    &mut problems,
+                )
+                .await?
+            }
+            None => println!("  skipped, the group has to be valid first"),
+        }
+    }
+
+    if !problems.0.is_empty() {
+        anyhow::bail!("{} problems found in the config", problems.0.len());
+    }
+    println!("The config is valid");
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use log::info;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction, hash::Hash,
+    instruction::Instruction, message::Message, signature::Keypair, signer::Signer,
+    transaction::Transaction,
+};
+
+use crate::{
+    crank::create_consume_events_instructions, keeper::create_keeper_instructions,
+    mango::GroupConfig, states::PerpMarketCache,
+};
+
+pub struct SimulationReport {
+    pub name: String,
+    pub error: Option<String>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+async fn simulate_instructions(
+    rpc_client: &RpcClient,
+    name: String,
+    mut ixs: Vec<Instruction>,
+    payer: &Keypair,
+    blockhash: Hash,
+) -> SimulationReport {
+    ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(1_400_000));
+    let mut tx = Transaction::new_unsigned(Message::new(&ixs, Some(&payer.pubkey())));
+    tx.sign(&[payer], blockhash);
+
+    let result = rpc_client
+        .simulate_transaction_with_config(
+            &tx,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .await;
+    match result {
+        Ok(response) => SimulationReport {
+            name,
+            error: response.value.err.map(|e| e.to_string()),
+            units_consumed: response.value.units_consumed,
+            logs: response.value.logs.unwrap_or_default(),
+        },
+        Err(e) => SimulationReport {
+            name,
+            error: Some(format!("simulation request failed : {e}")),
+            units_consumed: None,
+            logs: vec![],
+        },
+    }
+}
+
+/// Builds every keeper and crank instruction of the group and simulates each of them once
+/// nothing is sent to the cluster, returns false if any of the simulations failed
+pub async fn dry_run_keepers_and_crank(
+    rpc_client: Arc<RpcClient>,
+    group: &GroupConfig,
+    perp_market_caches: &[PerpMarketCache],
+    quote_root_bank: solana_program::pubkey::Pubkey,
+    quote_node_banks: Vec<solana_program::pubkey::Pubkey>,
+    payer: &Keypair,
+) -> anyhow::Result<bool> {
+    info!("dry run of keeper and crank instructions, nothing will be sent");
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+
+    let mut reports = vec![];
+    for (keeper_instruction, ixs) in
+        create_keeper_instructions(perp_market_caches, quote_root_bank, quote_node_banks)
+    {
+        reports.push(
+            simulate_instructions(
+                &rpc_client,
+                keeper_instruction.to_string(),
+                ixs,
+                payer,
+                blockhash,
+            )
+            .await,
+        );
+    }
+    for (perp_market_pk, ix) in create_consume_events_instructions(group) {
+        let market_name = group
+            .perp_markets
+            .iter()
+            .find(|m| m.public_key == perp_market_pk.to_string())
+            .map(|m| m.name.clone())
+            .unwrap_or_else(|| perp_market_pk.to_string());
+        reports.push(
+            simulate_instructions(
+                &rpc_client,
+                format!("ConsumeEvents {market_name}"),
+                vec![ix],
+                payer,
+                blockhash,
+            )
+            .await,
+        );
+    }
+
+    println!("\n\nKeeper and crank dry run for group {}", group.name);
+    for report in &reports {
+        match &report.error {
+            None => println!(
+                "{} : OK, CU consumed {}",
+                report.name,
+                report.units_consumed.unwrap_or_default()
+            ),
+            Some(error) => {
+                println!("{} : FAILED with {error}", report.name);
+                for log in &report.logs {
+                    println!("    {log}");
+                }
+            }
+        }
+    }
+    let failed = reports.iter().filter(|r| r.error.is_some()).count();
+    println!(
+        "{} instructions simulated, {failed} failed\n",
+        reports.len()
+    );
+    Ok(failed == 0)
+}
@@ -0,0 +1,146 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use log::info;
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    hash::Hash, instruction::InstructionError, signer::Signer, transaction::TransactionError,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    helpers::to_sp_pk, mango::AccountKeys, mango_program::MangoProgram,
+    market_markers::create_ask_bid_transaction, order_mix::QuoteOrder, signing::MarketMakerSigners,
+    states::PerpMarketCache,
+};
+
+pub struct ProbeResult {
+    pub market_maker: Pubkey,
+    pub perp_market_pk: Pubkey,
+    pub error: Option<String>,
+}
+
+// probes are sent without prioritization fees, instructions are cancel all, place bid and place ask
+fn decode_probe_error(error: &ClientError) -> String {
+    match error.get_transaction_error() {
+        Some(TransactionError::InstructionError(index, instruction_error)) => {
+            let instruction = match index {
+                0 => "cancel all perp orders",
+                1 => "place bid",
+                2 => "place ask",
+                _ => "unknown instruction",
+            };
+            match instruction_error {
+                InstructionError::Custom(code) => {
+                    format!("{instruction} failed with program error {code}")
+                }
+                e => format!("{instruction} failed with {e}"),
+            }
+        }
+        Some(e) => e.to_string(),
+        None => error.to_string(),
+    }
+}
+
+/// Sends probe quotes for every market maker through the rpc and waits for their confirmation,
+/// makes sure that the mango accounts can place orders before the benchmark starts
+pub async fn send_warmup_probes(
+    mango_program: Arc<dyn MangoProgram>,
+    rpc_client: Arc<RpcClient>,
+    account_keys_parsed: &[AccountKeys],
+    perp_market_caches: &[PerpMarketCache],
+    blockhash: Arc<RwLock<Hash>>,
+    probes_per_market_maker: usize,
+) -> Vec<ProbeResult> {
+    info!("sending {probes_per_market_maker} warmup probes per market maker");
+    let mut results = vec![];
+    for market_makers in account_keys_parsed.chunks(10) {
+        let mut tasks = vec![];
+        for market_maker in market_makers {
+            for perp_market in perp_market_caches
+                .iter()
+                .cycle()
+                .take(probes_per_market_maker)
+            {
+                let market_maker = market_maker.clone();
+                let perp_market = perp_market.clone();
+                let rpc_client = rpc_client.clone();
+                let blockhash = blockhash.clone();
+                let mango_program = mango_program.clone();
+                tasks.push(tokio::spawn(async move {
+                    let mango_account_pk =
+                        Pubkey::from_str(market_maker.mango_account_pks[0].as_str()).unwrap();
+                    let signers = MarketMakerSigners::load(&market_maker)
+                        .expect("market maker signer should load");
+                    let mut tx = create_ask_bid_transaction(
+                        mango_program.as_ref(),
+                        &perp_market,
+                        mango_account_pk,
+                        &signers,
+                        0,
+                        0,
+                        Utc::now().timestamp_micros() as u64,
+                        QuoteOrder::default(),
+                        &mut rand::thread_rng(),
+                    );
+                    tx.sign(&signers.signers(), *blockhash.read().await);
+                    let error = match tokio::time::timeout(
+                        Duration::from_secs(30),
+                        rpc_client.send_and_confirm_transaction(&tx),
+                    )
+                    .await
+                    {
+                        Ok(Ok(_)) => None,
+                        Ok(Err(e)) => Some(decode_probe_error(&e)),
+                        Err(_) => Some("not confirmed after 30s".to_string()),
+                    };
+                    ProbeResult {
+                        market_maker: to_sp_pk(&signers.owner.pubkey()),
+                        perp_market_pk: perp_market.perp_market_pk,
+                        error,
+                    }
+                }));
+            }
+        }
+        for result in futures::future::join_all(tasks).await {
+            results.push(result.expect("warmup probe task should not panic"));
+        }
+    }
+    results
+}
+
+/// Prints the failed probes and fails if more than the allowed percentage of them failed
+pub fn check_warmup_probes(results: &[ProbeResult], max_failure_pct: u8) -> anyhow::Result<()> {
+    let failed = results
+        .iter()
+        .filter(|r| r.error.is_some())
+        .collect::<Vec<_>>();
+    let failure_pct = if results.is_empty() {
+        0.0
+    } else {
+        failed.len() as f64 * 100.0 / results.len() as f64
+    };
+    println!(
+        "\n\nWarmup probes : Sent({}) Failed({}, {:.2}%)",
+        results.len(),
+        failed.len(),
+        failure_pct
+    );
+    for probe in &failed {
+        println!(
+            "Market maker {} on market {} : {}",
+            probe.market_maker,
+            probe.perp_market_pk,
+            probe.error.as_deref().unwrap_or_default()
+        );
+    }
+    println!();
+    if failure_pct > max_failure_pct as f64 {
+        anyhow::bail!(
+            "{:.2}% of the warmup probes failed, more than the allowed {max_failure_pct}%",
+            failure_pct
+        );
+    }
+    Ok(())
+}
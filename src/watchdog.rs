@@ -0,0 +1,83 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use log::{error, info};
+use solana_metrics::datapoint_error;
+use tokio::task::JoinHandle;
+
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Last heartbeat of every monitored thread, threads beat at least once per iteration of their loop
+#[derive(Clone, Default)]
+pub struct Watchdog {
+    heartbeats: Arc<DashMap<String, Instant>>,
+}
+
+impl Watchdog {
+    pub fn heartbeat(&self, name: &str) {
+        match self.heartbeats.get_mut(name) {
+            Some(mut last) => *last = Instant::now(),
+            None => {
+                self.heartbeats.insert(name.to_string(), Instant::now());
+            }
+        }
+    }
+
+    // threads which stop on their own are not monitored anymore
+    pub fn unregister(&self, name: &str) {
+        self.heartbeats.remove(name);
+    }
+
+    /// threads which did not beat for the stall timeout
+    pub fn stalled_threads(&self, stall_timeout: Duration) -> Vec<String> {
+        self.heartbeats
+            .iter()
+            .filter(|entry| entry.value().elapsed() >= stall_timeout)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Reports every thread which did not beat for the stall timeout with a `bencher-stall`
+    /// datapoint, the process exits on the first stall if abort_on_stall is set
+    pub fn start(&self, stall_timeout: Duration, abort_on_stall: bool) -> JoinHandle<()> {
+        let heartbeats = self.heartbeats.clone();
+        tokio::spawn(async move {
+            // a stall is only reported once until the thread beats again
+            let mut stalled = HashSet::new();
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                for entry in heartbeats.iter() {
+                    let name = entry.key();
+                    let since_last_beat = entry.value().elapsed();
+                    if since_last_beat < stall_timeout {
+                        if stalled.remove(name) {
+                            info!("watchdog : thread {name} is beating again");
+                        }
+                        continue;
+                    }
+                    if !stalled.insert(name.clone()) {
+                        continue;
+                    }
+                    error!(
+                        "watchdog : thread {name} has not beaten for {}s",
+                        since_last_beat.as_secs()
+                    );
+                    datapoint_error!(
+                        "bencher-stall",
+                        ("thread", name.clone(), String),
+                        ("stalled_secs", since_last_beat.as_secs(), i64),
+                    );
+                    if abort_on_stall {
+                        error!("watchdog : aborting the run");
+                        solana_metrics::flush();
+                        std::process::exit(1);
+                    }
+                }
+            }
+        })
+    }
+}
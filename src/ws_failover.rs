@@ -0,0 +1,114 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::StreamExt;
+use log::{info, warn};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_metrics::datapoint_info;
+use tokio::{sync::watch, task::JoinHandle};
+
+// a healthy endpoint notifies a slot every 400ms
+const SLOT_TIMEOUT: Duration = Duration::from_secs(10);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// urls of a comma separated list, in the order they are tried
+pub fn websocket_urls(websocket_url: &str) -> Vec<String> {
+    websocket_url
+        .split(',')
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect()
+}
+
+/// Websocket endpoint currently used for the cluster, out of the urls given with --ws. The
+/// users of the websocket reconnect to the new endpoint when it changes
+#[derive(Clone, Debug)]
+pub struct WebsocketFailover {
+    urls: Arc<Vec<String>>,
+    current: watch::Receiver<String>,
+}
+
+impl WebsocketFailover {
+    pub fn current(&self) -> String {
+        self.current.borrow().clone()
+    }
+
+    /// the urls starting with the current one, for the users which could not connect to it
+    pub fn candidates(&self) -> Vec<String> {
+        let current = self.current();
+        let start = self.urls.iter().position(|u| *u == current).unwrap_or(0);
+        self.urls[start..]
+            .iter()
+            .chain(&self.urls[..start])
+            .cloned()
+            .collect()
+    }
+
+    /// waits for the next endpoint, false once the failover is stopped
+    pub async fn changed(&mut self) -> bool {
+        self.current.changed().await.is_ok()
+    }
+}
+
+// follows the slots notified by the endpoint until it fails or the run is over
+async fn watch_slots(url: &str, exit_signal: &AtomicBool) -> anyhow::Result<()> {
+    let client = PubsubClient::new(url).await?;
+    let (mut slots, _unsubscribe) = client.slot_subscribe().await?;
+    while !exit_signal.load(Ordering::Relaxed) {
+        match tokio::time::timeout(SLOT_TIMEOUT, slots.next()).await {
+            Ok(Some(_)) => {}
+            Ok(None) => anyhow::bail!("the slot subscription ended"),
+            Err(_) => anyhow::bail!("no slot notified for {}s", SLOT_TIMEOUT.as_secs()),
+        }
+    }
+    Ok(())
+}
+
+/// Watches the slot notifications of the current websocket endpoint and moves to the next url
+/// of the list when the endpoint dies or stops notifying, so that the users of the websocket
+/// reconnect instead of silently degrading. With a single url, the users reconnect to it
+pub fn start_websocket_failover(
+    websocket_url: &str,
+    exit_signal: Arc<AtomicBool>,
+) -> (WebsocketFailover, JoinHandle<()>) {
+    let urls = Arc::new(websocket_urls(websocket_url));
+    assert!(!urls.is_empty(), "a websocket url is required");
+    let (current_sx, current) = watch::channel(urls[0].clone());
+    let failover = WebsocketFailover {
+        urls: urls.clone(),
+        current,
+    };
+    let jh = tokio::spawn(async move {
+        let mut index = 0;
+        let mut failovers = 0;
+        while !exit_signal.load(Ordering::Relaxed) {
+            let url = &urls[index];
+            let Err(e) = watch_slots(url, &exit_signal).await else {
+                break;
+            };
+            index = (index + 1) % urls.len();
+            failovers += 1;
+            warn!(
+                "websocket {url} failed : {e}, reconnecting to {}",
+                urls[index]
+            );
+            datapoint_info!(
+                "bencher-websocket-failover",
+                ("failed_url", url.clone(), String),
+                ("url", urls[index].clone(), String),
+                ("failovers", failovers, i64),
+            );
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            current_sx.send_replace(urls[index].clone());
+        }
+        if failovers > 0 {
+            info!("{failovers} websocket failovers during the run");
+        }
+    });
+    (failover, jh)
+}
@@ -0,0 +1,171 @@
+//! End to end run of the bencher against a local solana-test-validator.
+//!
+//! The mango v3 program and a mango group with its markets and market maker accounts are loaded
+//! into the validator from a fixtures directory. `solana-test-validator` has to be in the PATH:
+//!
+//! `MANGO_SIMULATION_FIXTURES=<dir> cargo test --test end_to_end -- --ignored`
+//!
+//! The fixtures directory holds
+//! - `mango.so`, the mango v3 program deployed at the `mangoProgramId` of the group config
+//! - `ids.json`, the group config in the format of mango-client-v3, with a `testnet.0` group
+//! - `accounts.json`, the market maker accounts
+//! - `authority.json`, the keeper authority and admin of the group, also used as identity
+//! - `accounts/*.json`, the accounts of the group dumped with `solana account --output json`
+//!
+//! `scripts/build-end-to-end-fixtures.sh <dir> <mango.so> <serum_dex.so>` builds them with the
+//! `create-group` subcommand.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use mango_simulation::{
+    analyze::read_transactions, cli::Config, csv_schema::TRANSACTION_SCHEMA_VERSION,
+    mango::MangoConfig, oracle::OracleFeederConfig, orchestrator::BenchOrchestrator,
+    transaction_sender::SenderKind,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentLevel, signature::read_keypair_file};
+
+const RPC_PORT: u16 = 18899;
+const VALIDATOR_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+const RUN_DURATION: Duration = Duration::from_secs(30);
+
+fn fixtures_dir() -> PathBuf {
+    std::env::var("MANGO_SIMULATION_FIXTURES")
+        .expect("MANGO_SIMULATION_FIXTURES should point to the fixtures directory")
+        .into()
+}
+
+/// solana-test-validator killed when dropped
+struct TestValidator {
+    process: Child,
+    ledger: PathBuf,
+}
+
+impl TestValidator {
+    async fn start(fixtures: &Path, ledger: PathBuf) -> TestValidator {
+        let ids: MangoConfig =
+            serde_json::from_str(&fs::read_to_string(fixtures.join("ids.json")).unwrap())
+                .expect("ids.json should be a mango group config");
+        let program_id = ids
+            .groups
+            .iter()
+            .find(|g| g.name == Config::default().mango_cluster)
+            .expect("ids.json should have the default group")
+            .mango_program_id
+            .clone();
+
+        let mut command = Command::new("solana-test-validator");
+        command
+            .arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(&ledger)
+            .arg("--rpc-port")
+            .arg(RPC_PORT.to_string())
+            .arg("--bpf-program")
+            .arg(program_id)
+            .arg(fixtures.join("mango.so"));
+        for entry in fs::read_dir(fixtures.join("accounts")).unwrap() {
+            let path = entry.unwrap().path();
+            let account: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            let pubkey = account["pubkey"]
+                .as_str()
+                .expect("dumped accounts should have a pubkey");
+            command.arg("--account").arg(pubkey).arg(&path);
+        }
+        let process = command
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("solana-test-validator should be in the PATH");
+        let validator = TestValidator { process, ledger };
+
+        let rpc_client = RpcClient::new(validator.json_rpc_url());
+        let started_at = Instant::now();
+        while rpc_client.get_health().await.is_err() {
+            assert!(
+                started_at.elapsed() < VALIDATOR_STARTUP_TIMEOUT,
+                "solana-test-validator did not start"
+            );
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        validator
+    }
+
+    fn json_rpc_url(&self) -> String {
+        format!("http://127.0.0.1:{RPC_PORT}")
+    }
+
+    fn websocket_url(&self) -> String {
+        format!("ws://127.0.0.1:{}", RPC_PORT + 1)
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.ledger);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 10)]
+#[ignore = "needs solana-test-validator and the mango fixtures"]
+async fn mini_benchmark_confirms_transactions() {
+    let fixtures = fixtures_dir();
+    let work_dir = std::env::temp_dir().join(format!("mango-simulation-{}", std::process::id()));
+    fs::create_dir_all(&work_dir).unwrap();
+    let validator = TestValidator::start(&fixtures, work_dir.join("ledger")).await;
+
+    let output = |name: &str| work_dir.join(name).to_string_lossy().to_string();
+    let authority = read_keypair_file(fixtures.join("authority.json")).unwrap();
+    let config = Config {
+        json_rpc_url: validator.json_rpc_url(),
+        websocket_url: validator.websocket_url(),
+        identity: read_keypair_file(fixtures.join("authority.json")).unwrap(),
+        keeper_authority: Some(authority),
+        account_keys: fixtures.join("accounts.json").to_string_lossy().to_string(),
+        mango_keys: fixtures.join("ids.json").to_string_lossy().to_string(),
+        transaction_save_file: output("transactions.csv"),
+        block_data_save_file: output("blocks.csv"),
+        summary_file: output("summary.json"),
+        run_manifest_file: output("run_manifest.json"),
+        run_state_file: output("run_state.json"),
+        timeseries_file: output("timeseries.csv"),
+        number_of_markers_per_mm: 1,
+        sender: SenderKind::Rpc,
+        commitment: CommitmentLevel::Confirmed,
+        oracle_feeder: Some(OracleFeederConfig::default()),
+        seed: Some(0),
+        ..Config::default()
+    };
+
+    let summary = BenchOrchestrator::new(config)
+        .run_for(RUN_DURATION)
+        .await
+        .expect("the run should succeed")
+        .expect("a run which is not a dry run has a summary");
+    assert!(summary.results.sent > 0, "no transaction was sent");
+    assert!(
+        summary.results.confirmed > 0,
+        "none of the {} sent transactions was confirmed",
+        summary.results.sent
+    );
+
+    let (schema_version, rows) = read_transactions(&output("transactions.csv"))
+        .await
+        .expect("the transactions csv should be valid");
+    assert_eq!(schema_version, TRANSACTION_SCHEMA_VERSION);
+    assert!(!rows.is_empty(), "the transactions csv is empty");
+    assert!(rows.iter().any(|r| r.confirmed_slot.is_some()));
+    assert!(rows.iter().all(|r| !r.signature.is_empty()));
+
+    drop(validator);
+    let _ = fs::remove_dir_all(&work_dir);
+}
@@ -0,0 +1,171 @@
+//! Sending path of the simulation into the confirmation records, with the mock sender instead of
+//! a cluster.
+
+use std::{sync::Arc, time::Instant};
+
+use chrono::Utc;
+use dashmap::DashMap;
+use mango_simulation::{
+    clock_skew::ClockSkew,
+    confirmation_strategies::process_blocks,
+    states::{BlockhashSlots, LiveParameters, TransactionSendRecord, TransactionType},
+    stats::MangoSimulationStats,
+    tpu_manager::TpuManager,
+    transaction_sender::{MockSender, SenderKind},
+};
+use solana_sdk::{
+    commitment_config::CommitmentLevel, hash::Hash, signature::Keypair, signer::Signer,
+    system_instruction, transaction::Transaction,
+};
+use solana_transaction_status::{
+    EncodedTransaction, EncodedTransactionWithStatusMeta, TransactionBinaryEncoding,
+    TransactionStatusMeta, UiConfirmedBlock,
+};
+use tokio::sync::{broadcast, mpsc::unbounded_channel};
+
+fn transaction() -> Transaction {
+    let payer = Keypair::new();
+    Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &Keypair::new().pubkey(),
+            1,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        Hash::new_unique(),
+    )
+}
+
+fn send_record(transaction: &Transaction) -> TransactionSendRecord {
+    let sent_at = Utc::now();
+    TransactionSendRecord {
+        signature: transaction.signatures[0],
+        sent_at,
+        sent_at_us: sent_at.timestamp_micros(),
+        sent_slot: 10,
+        market_maker: None,
+        market: None,
+        keeper_instruction: None,
+        tx_type: TransactionType::MarketMaker,
+        priority_fees: 0,
+        spoofing: false,
+        phase: None,
+        blockhash_slot: None,
+        blockhash_age_slots: None,
+        last_valid_block_height: None,
+        chaos: None,
+        send_delay_ms: None,
+        tx_size_bytes: 0,
+        num_instructions: 0,
+        order_type: None,
+        reduce_only: false,
+        client_order_id: None,
+        route: None,
+    }
+}
+
+// a block of the cluster holding the transactions, all successful
+fn block(transactions: &[Transaction]) -> UiConfirmedBlock {
+    UiConfirmedBlock {
+        previous_blockhash: Hash::new_unique().to_string(),
+        blockhash: Hash::new_unique().to_string(),
+        parent_slot: 11,
+        transactions: Some(
+            transactions
+                .iter()
+                .map(|tx| EncodedTransactionWithStatusMeta {
+                    transaction: EncodedTransaction::Binary(
+                        bs58::encode(bincode::serialize(tx).unwrap()).into_string(),
+                        TransactionBinaryEncoding::Base58,
+                    ),
+                    meta: Some(TransactionStatusMeta::default().into()),
+                    version: None,
+                })
+                .collect(),
+        ),
+        signatures: None,
+        rewards: None,
+        block_time: None,
+        block_height: None,
+    }
+}
+
+#[test]
+fn the_mock_sender_can_be_selected() {
+    assert_eq!("mock".parse::<SenderKind>(), Ok(SenderKind::Mock));
+}
+
+#[tokio::test]
+async fn mocked_transactions_reach_the_confirmation_records() {
+    let mock = MockSender::default();
+    let (tx_record_sx, mut tx_record_rx) = unbounded_channel();
+    let (send_errors_sx, _send_errors_rx) = unbounded_channel();
+    let tpu_manager = TpuManager::new(
+        vec![Arc::new(mock.clone())],
+        MangoSimulationStats::new(1, 1, 1, 1),
+        tx_record_sx,
+        None,
+        send_errors_sx,
+        Arc::new(LiveParameters::new(1, 0)),
+        BlockhashSlots::default(),
+        None,
+        None,
+        None,
+        Some(0),
+    )
+    .await
+    .unwrap();
+
+    let transactions = vec![transaction(), transaction()];
+    let batch = transactions
+        .iter()
+        .map(|tx| (tx.clone(), send_record(tx)))
+        .collect();
+    assert!(tpu_manager.send_transaction_batch(batch).await);
+    assert_eq!(tpu_manager.pending_sends(), 0);
+    assert_eq!(
+        mock.sent_signatures(),
+        transactions
+            .iter()
+            .map(|tx| tx.signatures[0])
+            .collect::<Vec<_>>()
+    );
+
+    // the send records are what the confirmation waits for
+    let transaction_map = Arc::new(DashMap::new());
+    while let Ok(record) = tx_record_rx.try_recv() {
+        assert!(record.tx_size_bytes > 0);
+        transaction_map.insert(record.signature, (record, Instant::now()));
+    }
+    assert_eq!(transaction_map.len(), 2);
+
+    let (confirm_sx, mut confirm_rx) = broadcast::channel(16);
+    let (block_sx, mut block_rx) = broadcast::channel(16);
+    process_blocks(
+        &block(&transactions[..1]),
+        confirm_sx,
+        block_sx,
+        transaction_map.clone(),
+        12,
+        CommitmentLevel::Confirmed,
+        None,
+        &ClockSkew::default(),
+    )
+    .await;
+
+    let confirmed = confirm_rx.try_recv().unwrap();
+    assert_eq!(
+        confirmed.signature,
+        transactions[0].signatures[0].to_string()
+    );
+    assert_eq!(confirmed.confirmed_slot, Some(12));
+    assert!(confirmed.successful);
+    assert_eq!(confirmed.tx_type, TransactionType::MarketMaker);
+    assert!(confirm_rx.try_recv().is_err());
+    // the transaction not in the block is still waited for
+    assert!(transaction_map.contains_key(&transactions[1].signatures[0]));
+    let block_data = block_rx.try_recv().unwrap();
+    assert_eq!(block_data.number_of_mango_simulation_txs, 1);
+    assert_eq!(block_data.missed_transactions, 1);
+}
@@ -0,0 +1,261 @@
+//! Counting of the sent and confirmed transactions by the stats, without any cluster.
+
+use chrono::Utc;
+use mango_simulation::{
+    chaos::ChaosKind,
+    states::{TransactionConfirmRecord, TransactionSendRecord, TransactionType},
+    stats::{LatencyHistogram, MangoSimulationStats, RateWindow},
+    timeseries::TimeSeries,
+};
+use solana_sdk::signature::Signature;
+
+fn send_record(tx_type: TransactionType) -> TransactionSendRecord {
+    let sent_at = Utc::now();
+    TransactionSendRecord {
+        signature: Signature::new_unique(),
+        sent_at,
+        sent_at_us: sent_at.timestamp_micros(),
+        sent_slot: 1,
+        market_maker: None,
+        market: None,
+        keeper_instruction: None,
+        tx_type,
+        priority_fees: 0,
+        spoofing: false,
+        phase: None,
+        blockhash_slot: None,
+        blockhash_age_slots: None,
+        last_valid_block_height: None,
+        chaos: None,
+        send_delay_ms: None,
+        tx_size_bytes: 200,
+        num_instructions: 2,
+        order_type: None,
+        reduce_only: false,
+        client_order_id: None,
+        route: None,
+    }
+}
+
+// outcome of a sent transaction, confirmed after the latency unless it timed out
+fn confirm_record(
+    sent: &TransactionSendRecord,
+    latency_ms: Option<i64>,
+    error: Option<&str>,
+) -> TransactionConfirmRecord {
+    let confirmed_at_us = latency_ms.map(|latency_ms| sent.sent_at_us + latency_ms * 1000);
+    TransactionConfirmRecord {
+        signature: sent.signature.to_string(),
+        sent_slot: sent.sent_slot,
+        sent_at: sent.sent_at.to_string(),
+        sent_at_us: sent.sent_at_us,
+        confirmed_slot: latency_ms.map(|_| 2),
+        confirmed_at: latency_ms.map(|_| Utc::now().to_string()),
+        confirmed_at_us,
+        successful: latency_ms.is_some() && error.is_none(),
+        slot_leader: None,
+        error: match latency_ms {
+            Some(_) => error.map(|e| e.to_string()),
+            None => Some("timeout".to_string()),
+        },
+        mango_error_code: None,
+        failed_instruction: None,
+        market_maker: None,
+        market: None,
+        block_hash: None,
+        slot_processed: None,
+        keeper_instruction: sent.keeper_instruction.clone(),
+        tx_type: sent.tx_type,
+        blockhash_slot: None,
+        blockhash_age_slots: None,
+        last_valid_block_height: None,
+        chaos: sent.chaos,
+        send_delay_ms: None,
+        tx_size_bytes: sent.tx_size_bytes,
+        num_instructions: sent.num_instructions,
+        order_type: None,
+        reduce_only: false,
+        client_order_id: None,
+        route: None,
+        expired: false,
+        timed_out: latency_ms.is_none(),
+        priority_fees: 0,
+        spoofing: false,
+        phase: None,
+        commitment_processed_at: None,
+        commitment_confirmed_at: None,
+        commitment_finalized_at: None,
+        inclusion_latency_ms: None,
+        clock_offset_ms: None,
+        corrected_inclusion_latency_ms: None,
+        run_id: String::new(),
+        cluster: None,
+        shard: None,
+    }
+}
+
+#[test]
+fn latency_histogram_percentiles() {
+    let mut histogram = LatencyHistogram::default();
+    assert_eq!(histogram.percentile(50.0), None);
+    for ms in 1..=100 {
+        histogram.record(ms);
+    }
+    assert_eq!(histogram.percentile(50.0), Some(50));
+    assert_eq!(histogram.percentile(95.0), Some(95));
+    assert_eq!(histogram.percentile(100.0), Some(100));
+
+    // above 1s the latencies are rounded down to 10ms
+    let mut histogram = LatencyHistogram::default();
+    histogram.record(1_234);
+    assert_eq!(histogram.percentile(50.0), Some(1_230));
+}
+
+#[test]
+fn rate_window_only_counts_the_last_seconds() {
+    let mut window = RateWindow::new(10);
+    for second in 0..10 {
+        for _ in 0..4 {
+            window.record_sent(second);
+        }
+        window.record_confirmed(second, true);
+        window.record_confirmed(second, false);
+    }
+    let rates = window.rates(9);
+    assert_eq!(rates.window_secs, 10);
+    assert_eq!(rates.sent_per_second, 4.0);
+    assert_eq!(rates.confirmed_per_second, 2.0);
+    assert_eq!(rates.successful_per_second, 1.0);
+    assert_eq!(rates.confirmation_rate, 50.0);
+
+    // the window is shorter during the first seconds
+    assert_eq!(RateWindow::new(10).rates(1).window_secs, 2);
+
+    window.record_sent(14);
+    let rates = window.rates(14);
+    // seconds 5 to 14
+    assert_eq!(rates.sent_per_second, 2.1);
+
+    assert_eq!(window.rates(100).sent_per_second, 0.0);
+}
+
+#[test]
+fn rate_window_keeps_the_seconds_of_longer_windows() {
+    let mut window = RateWindow::new(10);
+    window.keep(30);
+    for second in 0..30 {
+        window.record_sent(second);
+        window.record_confirmed(second, true);
+        window.record_latency(second, second * 100);
+    }
+    let rates = window.rates_over(29, 30);
+    assert_eq!(rates.window_secs, 30);
+    assert_eq!(rates.sent, 30);
+    assert_eq!(rates.confirmed, 30);
+    // 2.8s rounded down to 10ms
+    assert_eq!(rates.p95_latency_ms, Some(2_800));
+    // the recent rates keep their window
+    assert_eq!(window.rates(29).sent, 10);
+    assert_eq!(window.rates_over(29, 5).p95_latency_ms, Some(2_900));
+    // windows longer than the seconds kept are cut to them
+    assert_eq!(window.rates_over(29, 60).sent, 30);
+}
+
+// sent in the given second of the run, confirmed after the latency unless it timed out
+fn record_at(
+    second: i64,
+    latency_ms: Option<i64>,
+    error: Option<&str>,
+) -> TransactionConfirmRecord {
+    let mut sent = send_record(TransactionType::MarketMaker);
+    sent.sent_at_us = second * 1_000_000;
+    confirm_record(&sent, latency_ms, error)
+}
+
+#[test]
+fn time_series_counts_the_sends_and_confirmations_in_their_seconds() {
+    let mut timeseries = TimeSeries::default();
+    timeseries.record(&record_at(10, Some(400), None));
+    timeseries.record(&record_at(10, Some(1_600), Some("custom program error")));
+    timeseries.record(&record_at(12, None, None));
+
+    let rows = timeseries.rows();
+    assert_eq!(
+        rows.iter().map(|row| row.second).collect::<Vec<_>>(),
+        vec![10, 11, 12]
+    );
+    assert_eq!((rows[0].sent, rows[0].confirmed), (2, 1));
+    assert_eq!(rows[0].average_confirmation_ms, Some(400.0));
+    assert_eq!((rows[1].sent, rows[1].confirmed, rows[1].errors), (0, 1, 1));
+    assert_eq!(rows[1].average_confirmation_ms, Some(1_600.0));
+    assert_eq!((rows[2].sent, rows[2].timeouts), (1, 1));
+    assert_eq!(rows[2].average_confirmation_ms, None);
+    assert_eq!(rows[2].slot, Some(1));
+}
+
+#[tokio::test]
+async fn stats_count_the_confirmation_stream() {
+    let stats = MangoSimulationStats::new(1, 1, 1, 1);
+    let market_makers = (0..3)
+        .map(|_| send_record(TransactionType::MarketMaker))
+        .collect::<Vec<_>>();
+    let keeper = send_record(TransactionType::Keeper);
+    for record in market_makers.iter().chain([&keeper]) {
+        stats.inc_send(record);
+    }
+
+    stats
+        .record_confirmation(confirm_record(&market_makers[0], Some(400), None))
+        .await;
+    stats
+        .record_confirmation(confirm_record(
+            &market_makers[1],
+            Some(600),
+            Some("Error processing Instruction 1: custom program error: 0x1"),
+        ))
+        .await;
+    stats
+        .record_confirmation(confirm_record(&market_makers[2], None, None))
+        .await;
+    stats
+        .record_confirmation(confirm_record(&keeper, Some(800), None))
+        .await;
+
+    let summary = stats.summary().await;
+    assert_eq!(summary.sent, 4);
+    assert_eq!(summary.confirmed, 3);
+    assert_eq!(summary.successful, 2);
+    assert_eq!(summary.errors, 1);
+    assert_eq!(summary.timed_out, 1);
+    assert_eq!(summary.pending, 0);
+    assert_eq!(summary.market_maker_sent, 3);
+    assert_eq!(summary.market_maker_successful, 1);
+    assert_eq!(summary.keeper_sent, 1);
+    assert_eq!(summary.keeper_successful, 1);
+    assert_eq!(summary.confirmation_rate, 75.0);
+    assert_eq!(summary.bytes_sent, 800);
+    assert_eq!(summary.latency_ms.p50, Some(600));
+    assert_eq!(summary.latency_ms.p99, Some(800));
+    // the same error of any instruction is counted once
+    assert_eq!(summary.top_errors.len(), 1);
+    assert_eq!(summary.top_errors[0].error, "custom program error: 0x1");
+    assert_eq!(summary.recent.confirmation_rate, 75.0);
+}
+
+#[tokio::test]
+async fn chaos_transactions_are_counted_apart() {
+    let stats = MangoSimulationStats::new(1, 1, 1, 1);
+    let mut chaos = send_record(TransactionType::MarketMaker);
+    chaos.chaos = Some(ChaosKind::WrongOwner);
+    stats.inc_send(&chaos);
+    stats
+        .record_confirmation(confirm_record(&chaos, Some(400), Some("wrong owner")))
+        .await;
+
+    let summary = stats.summary().await;
+    assert_eq!(summary.sent, 0);
+    assert_eq!(summary.confirmed, 0);
+    assert_eq!(summary.chaos_sent, 1);
+    assert_eq!(summary.chaos_confirmed, 1);
+    assert!(summary.top_errors.is_empty());
+}